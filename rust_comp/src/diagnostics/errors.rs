@@ -0,0 +1,546 @@
+use super::diagnostic::{Diagnostic, ToDiagnostic};
+use crate::config::SOURCE_FILE_EXT;
+use crate::frontend::lexer::ScanError;
+use crate::frontend::parser::ParseError;
+use crate::runtime::budget::CallFrame;
+use crate::runtime::interpreter::EvalError;
+use crate::semantics::meta::meta_processor::MetaProcessError;
+use crate::semantics::types::type_error::TypeError;
+
+// Error codes are grouped by phase:
+//   E00xx scanning, E01xx parsing, E02xx metaprocessing,
+//   E03xx evaluation, E04xx type checking.
+
+impl ToDiagnostic for ScanError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            ScanError::UnterminatedString(span) => {
+                Diagnostic::error("E0001", "unterminated string literal")
+                    .with_span(*span)
+                    .with_label("string starts here")
+                    .with_help("add a closing `\"`")
+            }
+            ScanError::UnexpectedCharacter(c, span) => {
+                Diagnostic::error("E0002", format!("unexpected character `{c}`"))
+                    .with_span(*span)
+                    .with_label("not valid here")
+            }
+        }
+    }
+}
+
+impl ToDiagnostic for ParseError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            ParseError::UnexpectedToken {
+                found,
+                expected,
+                span,
+            } => Diagnostic::error("E0101", format!("expected {expected}, found {found}"))
+                .with_span(*span)
+                .with_label(format!("expected {expected}")),
+            ParseError::UnexpectedEOF { expected, span } => Diagnostic::error(
+                "E0102",
+                format!("unexpected end of file, expected {expected}"),
+            )
+            .with_span(*span),
+            ParseError::ExpectedExpression { found, span } => {
+                Diagnostic::error("E0104", format!("expected expression, found {found}"))
+                    .with_span(*span)
+                    .with_label("expected expression")
+            }
+            ParseError::InvalidAssignmentTarget(span) => {
+                Diagnostic::error("E0105", "invalid left-hand side of assignment")
+                    .with_span(*span)
+                    .with_label("cannot assign to this expression")
+                    .with_help("only variables, struct fields and list elements can be assigned")
+            }
+            ParseError::InvalidEmbedMode { found, span } => {
+                Diagnostic::error("E0106", format!("unknown embed mode `{found}`"))
+                    .with_span(*span)
+                    .with_label("expected `text` or `bytes`")
+            }
+            ParseError::ExpectedPattern { found, span } => {
+                Diagnostic::error("E0107", format!("expected pattern, found {found}"))
+                    .with_span(*span)
+                    .with_label("expected pattern")
+            }
+            ParseError::UnterminatedString(span) => {
+                Diagnostic::error("E0103", "unterminated string literal").with_span(*span)
+            }
+        }
+    }
+}
+
+impl ToDiagnostic for MetaProcessError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            MetaProcessError::EmbedFailed { path, error, span } => {
+                Diagnostic::error("E0201", format!("failed to embed `{path}`"))
+                    .with_span(*span)
+                    .with_label("embedded here")
+                    .with_note(error.clone())
+            }
+            MetaProcessError::UnknownType(name, span) => {
+                Diagnostic::error("E0202", format!("unknown type `{name}`"))
+                    .with_span(*span)
+                    .with_label("not declared")
+            }
+            MetaProcessError::ModuleNotFound(name, span) => {
+                Diagnostic::error("E0206", format!("cannot find module `{name}`"))
+                    .with_span(*span)
+                    .with_label("imported here")
+                    .with_help(format!(
+                "expected a file named `{name}.{SOURCE_FILE_EXT}` next to the importing file"
+            ))
+            }
+            MetaProcessError::NonConstantArgument { function, span } => Diagnostic::error(
+                "E0211",
+                format!("the call to `meta fn {function}` can't be folded"),
+            )
+            .with_span(*span)
+            .with_label("this argument isn't known until run time")
+            .with_note("a call to a `meta fn` is replaced by its result at compile time"),
+            MetaProcessError::ImpureMetaCall { function, span } => Diagnostic::error(
+                "E0212",
+                format!("the call to `meta fn {function}` can't be folded"),
+            )
+            .with_span(*span)
+            .with_label(format!("`{function}` isn't pure"))
+            .with_note(
+                "a folded function can't have side effects or read variables it didn't bind",
+            ),
+            MetaProcessError::ConflictingImpl {
+                trait_name,
+                type_name,
+                span,
+            } => Diagnostic::error(
+                "E0210",
+                format!("conflicting implementations of `{trait_name}` for `{type_name}`"),
+            )
+            .with_span(*span)
+            .with_label(format!("`{type_name}` already implements `{trait_name}`")),
+            MetaProcessError::DuplicateType { name, span } => {
+                Diagnostic::error("E0213", format!("`{name}` is declared twice"))
+                    .with_span(*span)
+                    .with_label(format!("a struct or enum called `{name}` already exists"))
+                    .with_help("each struct and enum in a file needs its own name")
+            }
+            MetaProcessError::ImportCycle { cycle, span } => {
+                Diagnostic::error("E0207", "import cycle detected")
+                    .with_span(*span)
+                    .with_label("this import closes the cycle")
+                    .with_note(format!("cycle: {}", cycle.join(" -> ")))
+            }
+            MetaProcessError::MetaDeclarationCycle { names, span } => {
+                Diagnostic::error("E0209", "`meta` blocks wait on each other's declarations")
+                    .with_span(*span)
+                    .with_label("this block can't run")
+                    .with_note(format!("waiting for: {}", names.join(", ")))
+            }
+            MetaProcessError::ModuleScan { name, error } => error
+                .to_diagnostic()
+                .with_note(format!("in module `{name}`")),
+            MetaProcessError::ModuleParse { name, errors } => {
+                let mut diag = errors
+                    .first()
+                    .map(|e| e.to_diagnostic())
+                    .unwrap_or_else(|| Diagnostic::error("E0208", "module failed to parse"))
+                    .with_note(format!("in module `{name}`"));
+                if errors.len() > 1 {
+                    diag = diag.with_note(format!(
+                        "{} more syntax errors in this module",
+                        errors.len() - 1
+                    ));
+                }
+                diag
+            }
+            MetaProcessError::Unimplemented(what) => {
+                Diagnostic::error("E0203", format!("not supported yet: {what}"))
+            }
+            MetaProcessError::ExprNotFound(id) | MetaProcessError::StmtNotFound(id) => {
+                Diagnostic::error("E0204", format!("internal error: missing AST node {id}"))
+                    .with_note("this is a compiler bug")
+            }
+            MetaProcessError::Eval(e) => e
+                .to_diagnostic()
+                .with_note("raised while running a `meta` block at compile time"),
+            MetaProcessError::PolicyViolation { error, block } => {
+                let diag = error.to_diagnostic();
+                match block {
+                    Some(block) => diag.with_note(format!(
+                        "in the `meta` block at line {}, column {}",
+                        block.line, block.col
+                    )),
+                    None => diag,
+                }
+            }
+        }
+    }
+}
+
+impl ToDiagnostic for EvalError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            EvalError::UndefinedVariable(name, span) => {
+                Diagnostic::error("E0301", format!("cannot find `{name}` in this scope"))
+                    .with_span(*span)
+                    .with_label("not found")
+            }
+            EvalError::TypeError(ty, span) => {
+                Diagnostic::error("E0302", format!("mismatched types, expected {ty}"))
+                    .with_span(*span)
+                    .with_label(format!("expected {ty}"))
+            }
+            EvalError::NonFunctionCall(span) => {
+                Diagnostic::error("E0303", "called value is not a function")
+                    .with_span(*span)
+                    .with_label("not callable")
+            }
+            EvalError::ArgumentMismatch(span) => {
+                Diagnostic::error("E0304", "wrong number of arguments")
+                    .with_span(*span)
+                    .with_label("in this call")
+            }
+            EvalError::UnknownStructType(name, span) => {
+                Diagnostic::error("E0305", format!("unknown struct `{name}`"))
+                    .with_span(*span)
+                    .with_label("not declared")
+            }
+            EvalError::MissingField {
+                struct_name,
+                field,
+                span,
+            } => Diagnostic::error(
+                "E0316",
+                format!("missing field `{field}` in `{struct_name}` literal"),
+            )
+            .with_span(*span)
+            .with_label(format!("`{field}` not given")),
+            EvalError::UnknownField {
+                struct_name,
+                field,
+                span,
+            } => Diagnostic::error(
+                "E0317",
+                format!("struct `{struct_name}` has no field named `{field}`"),
+            )
+            .with_span(*span)
+            .with_label("unknown field")
+            .with_help(format!("check the declaration of `{struct_name}`")),
+            EvalError::DuplicateField(field, span) => {
+                Diagnostic::error("E0318", format!("field `{field}` given more than once"))
+                    .with_span(*span)
+                    .with_label("duplicate field")
+            }
+            EvalError::UnknownVariant {
+                enum_name,
+                variant,
+                span,
+            } => Diagnostic::error("E0324", format!("unknown variant `{enum_name}::{variant}`"))
+                .with_span(*span)
+                .with_label("not declared"),
+            EvalError::PayloadMismatch { variant, span } => Diagnostic::error(
+                "E0325",
+                format!("`{variant}` is not constructed the way it was declared"),
+            )
+            .with_span(*span)
+            .with_label("wrong values for this variant"),
+            EvalError::NoMatch(span) => Diagnostic::error("E0326", "no arm matches the value")
+                .with_span(*span)
+                .with_label("every pattern failed to match")
+                .with_help("add an arm with a `_` pattern"),
+            EvalError::GenOutsideMeta(span) => {
+                Diagnostic::error("E0327", "`gen` outside of a `meta` block")
+                    .with_span(*span)
+                    .with_label("only compile-time code can emit statements")
+            }
+            EvalError::QuoteOutsideMeta(span) => {
+                Diagnostic::error("E0328", "`quote` outside of a `meta` block")
+                    .with_span(*span)
+                    .with_label("only compile-time code can build code values")
+            }
+            EvalError::QuoteInGen(span) => {
+                Diagnostic::error("E0333", "`gen` cannot emit a `quote`")
+                    .with_span(*span)
+                    .with_label("this would be a code value at run time")
+                    .with_note("code values only exist while `meta` blocks run")
+                    .with_help("to emit the quoted code, splice it in with `gen $code;`")
+            }
+            EvalError::CannotCompare { left, right, span } => {
+                Diagnostic::error("E0334", format!("cannot compare `{left}` with `{right}`"))
+                    .with_span(*span)
+                    .with_label("no equality for these operands")
+            }
+            EvalError::UnquoteOutsideQuote(span) => {
+                Diagnostic::error("E0329", "`$` outside of a `quote` or `gen`")
+                    .with_span(*span)
+                    .with_label("nothing to splice this into")
+            }
+            EvalError::InvalidSplice(what, span) => {
+                Diagnostic::error("E0330", format!("cannot splice {what} here"))
+                    .with_span(*span)
+                    .with_label("spliced here")
+            }
+            EvalError::NoSuchField(field, span) => {
+                Diagnostic::error("E0308", format!("no field `{field}` on this value"))
+                    .with_span(*span)
+                    .with_label("unknown field")
+            }
+            EvalError::IndexOutOfBounds { index, len, span } => Diagnostic::error(
+                "E0309",
+                format!("index out of bounds: the length is {len} but the index is {index}"),
+            )
+            .with_span(*span)
+            .with_label("out of bounds"),
+            EvalError::DivisionByZero(span) => {
+                Diagnostic::error("E0331", "attempt to divide by zero")
+                    .with_span(*span)
+                    .with_label("the divisor is zero")
+            }
+            EvalError::IntegerOverflow(span) => Diagnostic::error("E0332", "integer overflow")
+                .with_span(*span)
+                .with_label("the result doesn't fit in an `int`"),
+            EvalError::NotIndexable(span) => {
+                Diagnostic::error("E0310", "cannot index into this value")
+                    .with_span(*span)
+                    .with_label("not a list")
+            }
+            EvalError::UndeclaredAssignment(name, span) => Diagnostic::error(
+                "E0311",
+                format!("cannot assign to undeclared variable `{name}`"),
+            )
+            .with_span(*span)
+            .with_label("not declared")
+            .with_help(format!("declare it first with `var {name} = ...;`")),
+            EvalError::InvalidSlice {
+                start,
+                end,
+                len,
+                span,
+            } => Diagnostic::error(
+                "E0312",
+                format!("slice {start}:{end} is out of range for a list of length {len}"),
+            )
+            .with_span(*span)
+            .with_label("invalid slice"),
+            EvalError::EmptyList(span) => {
+                Diagnostic::error("E0313", "cannot pop from an empty list")
+                    .with_span(*span)
+                    .with_label("list is empty")
+            }
+            EvalError::NotIterable(span) => {
+                Diagnostic::error("E0314", "cannot iterate over this value")
+                    .with_span(*span)
+                    .with_label("not a list")
+            }
+            EvalError::UnknownMethod(method, span) => {
+                Diagnostic::error("E0315", format!("no method `{method}` on this value"))
+                    .with_span(*span)
+                    .with_label("unknown method")
+            }
+            EvalError::UnknownModule(name, span) => {
+                Diagnostic::error("E0319", format!("module `{name}` was never loaded"))
+                    .with_span(*span)
+                    .with_note("this is a compiler bug")
+            }
+            EvalError::CapabilityDenied { capability, span } => {
+                Diagnostic::error("E0320", format!("compile-time code may not {capability}"))
+                    .with_span(*span)
+                    .with_label("not allowed by the meta policy")
+            }
+            EvalError::OutOfFuel {
+                limit,
+                span,
+                call_stack,
+            } => with_call_stack(
+                Diagnostic::error("E0321", format!("ran out of fuel after {limit} steps"))
+                    .with_span(*span)
+                    .with_label("fuel ran out here")
+                    .with_help("check for a loop that never ends, or raise the fuel budget"),
+                call_stack,
+            ),
+            EvalError::StackOverflow {
+                limit,
+                span,
+                call_stack,
+            } => with_call_stack(
+                Diagnostic::error("E0323", format!("more than {limit} nested calls"))
+                    .with_span(*span)
+                    .with_label("this call went too deep")
+                    .with_help("check that the recursion reaches its base case"),
+                call_stack,
+            ),
+            EvalError::MemoryLimitExceeded { limit, span } => Diagnostic::error(
+                "E0322",
+                format!("compile-time code allocated more than {limit} bytes"),
+            )
+            .with_span(*span)
+            .with_label("memory budget ran out here"),
+            EvalError::Unimplemented(span) => {
+                Diagnostic::error("E0306", "this construct cannot be evaluated yet")
+                    .with_span(*span)
+            }
+            EvalError::ExprNotFound(id) | EvalError::StmtNotFound(id) => {
+                Diagnostic::error("E0307", format!("internal error: missing AST node {id}"))
+                    .with_note("this is a compiler bug")
+            }
+        }
+    }
+}
+
+// Calls listed before the rest are summarized.
+const MAX_SHOWN_FRAMES: usize = 8;
+
+// Adds one note per active call, innermost first. Runs of the same call,
+// as in a recursion, share a note.
+fn with_call_stack(mut diag: Diagnostic, call_stack: &[CallFrame]) -> Diagnostic {
+    let mut runs: Vec<(&CallFrame, usize)> = Vec::new();
+    for frame in call_stack.iter().rev() {
+        match runs.last_mut() {
+            Some((last, count)) if *last == frame => *count += 1,
+            _ => runs.push((frame, 1)),
+        }
+    }
+
+    for (frame, count) in runs.iter().take(MAX_SHOWN_FRAMES) {
+        let function = match &frame.function {
+            Some(name) => format!("`{name}`"),
+            None => "a function expression".to_string(),
+        };
+        let repeated = if *count > 1 {
+            format!(" ({count} times)")
+        } else {
+            String::new()
+        };
+        diag = diag.with_note(format!(
+            "in {function}, called at line {}, column {}{repeated}",
+            frame.span.line, frame.span.col
+        ));
+    }
+    if runs.len() > MAX_SHOWN_FRAMES {
+        let hidden: usize = runs[MAX_SHOWN_FRAMES..].iter().map(|(_, n)| n).sum();
+        diag = diag.with_note(format!("... and {hidden} more calls"));
+    }
+    diag
+}
+
+impl ToDiagnostic for TypeError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            TypeError::Located { span, error } => error.to_diagnostic().with_span(*span),
+            TypeError::TypeMismatch { expected, found } => {
+                Diagnostic::error("E0401", "mismatched types")
+                    .with_label(format!("expected {expected}, found {found}"))
+            }
+            TypeError::UnboundVar(name) => {
+                Diagnostic::error("E0402", format!("cannot find `{name}` in this scope"))
+                    .with_label("not found")
+            }
+            TypeError::NoMethod { ty, method } => {
+                Diagnostic::error("E0407", format!("no method `{method}` on type {ty}"))
+                    .with_label("unknown method")
+            }
+            TypeError::UnknownType(name) => {
+                Diagnostic::error("E0408", format!("unknown type `{name}`"))
+                    .with_label("not a builtin type or a declared struct")
+            }
+            TypeError::WrongTypeArgs {
+                name,
+                expected,
+                found,
+            } => Diagnostic::error(
+                "E0412",
+                format!(
+                    "`{name}` takes {expected} type argument{} but {found} {} given",
+                    if *expected == 1 { "" } else { "s" },
+                    if *found == 1 { "was" } else { "were" },
+                ),
+            )
+            .with_label("wrong number of type arguments"),
+            TypeError::NoField { ty, field } => {
+                Diagnostic::error("E0409", format!("no field `{field}` on type {ty}"))
+                    .with_label("unknown field")
+            }
+            TypeError::MissingField { struct_name, field } => Diagnostic::error(
+                "E0410",
+                format!("missing field `{field}` in `{struct_name}` literal"),
+            )
+            .with_label(format!("`{field}` is not given a value")),
+            TypeError::AmbiguousField { field, structs } => Diagnostic::error(
+                "E0411",
+                format!("cannot tell which struct's `{field}` this is"),
+            )
+            .with_label("type of this value is not known here")
+            .with_note(format!("declared by {}", structs.join(", "))),
+            TypeError::NoVariant { ty, variant } => {
+                Diagnostic::error("E0413", format!("no variant `{variant}` in enum {ty}"))
+                    .with_label("unknown variant")
+            }
+            TypeError::WrongPayload {
+                enum_name,
+                declared,
+            } => Diagnostic::error(
+                "E0414",
+                format!("wrong values for `{enum_name}::{}`", declared.name),
+            )
+            .with_label("does not match the declaration")
+            .with_note(format!("declared as `{declared}`")),
+            TypeError::NonExhaustive { missing } => {
+                Diagnostic::error("E0415", format!("`match` does not cover `{missing}`"))
+                    .with_label(format!("`{missing}` not matched"))
+                    .with_help("add an arm for it, or a `_` arm")
+            }
+            TypeError::UnreachableArm => Diagnostic::error("E0416", "unreachable `match` arm")
+                .with_label("earlier arms already match every value this does"),
+            TypeError::UnknownTrait(name) => {
+                Diagnostic::error("E0417", format!("unknown trait `{name}`"))
+                    .with_label("no trait with this name is declared")
+            }
+            TypeError::MissingImpl { trait_name, ty } => {
+                Diagnostic::error("E0418", format!("`{ty}` does not implement `{trait_name}`"))
+                    .with_label(format!("requires `{ty}: {trait_name}`"))
+                    .with_help(format!("add `impl {trait_name} for ...` for this type"))
+            }
+            TypeError::MissingBound { param, trait_name } => Diagnostic::error(
+                "E0419",
+                format!("type parameter `{param}` is used as `{trait_name}` without that bound"),
+            )
+            .with_label(format!("requires `{param}: {trait_name}`"))
+            .with_help(format!("declare it as `<{param}: {trait_name}>`")),
+            TypeError::MissingMethod { trait_name, method } => Diagnostic::error(
+                "E0420",
+                format!("impl of `{trait_name}` is missing method `{method}`"),
+            )
+            .with_label(format!("`{method}` not implemented")),
+            TypeError::NotInTrait { trait_name, method } => Diagnostic::error(
+                "E0421",
+                format!("method `{method}` is not declared by trait `{trait_name}`"),
+            )
+            .with_label("not a member of the trait"),
+            TypeError::AmbiguousMethod { method, traits } => Diagnostic::error(
+                "E0422",
+                format!("method `{method}` is declared by several traits"),
+            )
+            .with_label(format!("could be from any of {}", traits.join(", ")))
+            .with_help("annotate the receiver's type"),
+            TypeError::JumpOutOfDefer(keyword) => {
+                Diagnostic::error("E0423", format!("`{keyword}` cannot leave a `defer`"))
+                    .with_label("deferred statements run while their block is being left")
+            }
+            TypeError::InvalidReturn => {
+                Diagnostic::error("E0403", "`return` outside of a function")
+                    .with_label("not inside a function body")
+            }
+            TypeError::BreakOutsideLoop => Diagnostic::error("E0405", "`break` outside of a loop")
+                .with_label("cannot `break` outside of a loop"),
+            TypeError::ContinueOutsideLoop => {
+                Diagnostic::error("E0406", "`continue` outside of a loop")
+                    .with_label("cannot `continue` outside of a loop")
+            }
+            TypeError::Unsupported => {
+                Diagnostic::error("E0404", "this construct cannot be type checked yet")
+            }
+        }
+    }
+}
@@ -0,0 +1,4 @@
+pub mod diagnostic;
+pub mod errors;
+pub mod render;
+pub mod source_map;
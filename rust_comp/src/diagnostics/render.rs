@@ -0,0 +1,191 @@
+use super::diagnostic::{Diagnostic, Severity};
+use super::source_map::SourceMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderStyle {
+    Plain,
+    Ansi,
+}
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+
+struct Palette {
+    severity: &'static str,
+    gutter: &'static str,
+    bold: &'static str,
+    reset: &'static str,
+}
+
+impl Palette {
+    fn new(style: RenderStyle, severity: Severity) -> Self {
+        match style {
+            RenderStyle::Plain => Self {
+                severity: "",
+                gutter: "",
+                bold: "",
+                reset: "",
+            },
+            RenderStyle::Ansi => Self {
+                severity: match severity {
+                    Severity::Error => RED,
+                    Severity::Warning => YELLOW,
+                },
+                gutter: BLUE,
+                bold: BOLD,
+                reset: RESET,
+            },
+        }
+    }
+}
+
+/// Renders a diagnostic in the familiar rustc layout:
+///
+/// ```text
+/// error[E0101]: expected `)`, found `;`
+///  --> main.cx:1:8
+///   |
+/// 1 | print(1;
+///   |        ^ expected `)`
+///   = help: ...
+/// ```
+pub fn render(diag: &Diagnostic, sources: &SourceMap, style: RenderStyle) -> String {
+    let p = Palette::new(style, diag.severity);
+    let mut out = format!(
+        "{}{}[{}]{}{}: {}{}\n",
+        p.severity, diag.severity, diag.code, p.reset, p.bold, diag.message, p.reset
+    );
+
+    let located = diag
+        .span
+        .and_then(|span| sources.get(span.file).map(|file| (span, file)));
+
+    let gutter_width = match located {
+        Some((span, _)) => span.line.to_string().len(),
+        None => 1,
+    };
+    let pad = " ".repeat(gutter_width);
+
+    if let Some((span, file)) = located {
+        out += &format!(
+            "{pad}{}-->{} {}:{}:{}\n",
+            p.gutter, p.reset, file.name, span.line, span.col
+        );
+
+        if let Some(text) = file.line(span.line) {
+            let line_start = file.line_start(span.line).unwrap_or(0);
+            let col_offset = span.start.saturating_sub(line_start).min(text.len());
+            let underline_end = span
+                .end
+                .saturating_sub(line_start)
+                .clamp(col_offset, text.len());
+
+            // keep tabs so the caret lines up with the source line
+            let indent: String = text[..col_offset]
+                .chars()
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let width = text[col_offset..underline_end].chars().count().max(1);
+
+            out += &format!("{pad} {}|{}\n", p.gutter, p.reset);
+            out += &format!("{}{} |{} {}\n", p.gutter, span.line, p.reset, text);
+            out += &format!(
+                "{pad} {}|{} {}{}{}{}",
+                p.gutter,
+                p.reset,
+                indent,
+                p.severity,
+                "^".repeat(width),
+                p.reset
+            );
+            if let Some(label) = &diag.label {
+                out += &format!(" {}{}{}", p.severity, label, p.reset);
+            }
+            out += "\n";
+        }
+    }
+
+    for note in &diag.notes {
+        out += &format!(
+            "{pad} {}={} {}note{}: {}\n",
+            p.gutter, p.reset, p.bold, p.reset, note
+        );
+    }
+    if let Some(help) = &diag.help {
+        out += &format!(
+            "{pad} {}={} {}help{}: {}\n",
+            p.gutter, p.reset, p.bold, p.reset, help
+        );
+    }
+
+    out
+}
+
+/// Renders a diagnostic as a single-line JSON object for editors and tools.
+pub fn render_json(diag: &Diagnostic, sources: &SourceMap) -> String {
+    let mut fields = vec![
+        format!("\"severity\":{}", json_str(&diag.severity.to_string())),
+        format!("\"code\":{}", json_str(diag.code)),
+        format!("\"message\":{}", json_str(&diag.message)),
+    ];
+
+    match diag.span {
+        Some(span) => {
+            let file = sources
+                .get(span.file)
+                .map(|f| json_str(&f.name))
+                .unwrap_or_else(|| "null".to_string());
+            fields.push(format!(
+                "\"span\":{{\"file\":{},\"line\":{},\"column\":{},\"start\":{},\"end\":{}}}",
+                file, span.line, span.col, span.start, span.end
+            ));
+        }
+        None => fields.push("\"span\":null".to_string()),
+    }
+
+    fields.push(format!(
+        "\"label\":{}",
+        diag.label
+            .as_deref()
+            .map(json_str)
+            .unwrap_or_else(|| "null".to_string())
+    ));
+    fields.push(format!(
+        "\"notes\":[{}]",
+        diag.notes
+            .iter()
+            .map(|n| json_str(n))
+            .collect::<Vec<_>>()
+            .join(",")
+    ));
+    fields.push(format!(
+        "\"help\":{}",
+        diag.help
+            .as_deref()
+            .map(json_str)
+            .unwrap_or_else(|| "null".to_string())
+    ));
+
+    format!("{{{}}}", fields.join(","))
+}
+
+fn json_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,52 @@
+use crate::frontend::span::FileId;
+
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub name: String,
+    pub source: String,
+}
+
+impl SourceFile {
+    /// Text of the 1-based `line`, without its line ending.
+    pub fn line(&self, line: usize) -> Option<&str> {
+        self.source
+            .split('\n')
+            .nth(line.checked_sub(1)?)
+            .map(|l| l.trim_end_matches('\r'))
+    }
+
+    /// Byte offset at which the 1-based `line` starts.
+    pub fn line_start(&self, line: usize) -> Option<usize> {
+        if line == 1 {
+            return Some(0);
+        }
+        self.source
+            .match_indices('\n')
+            .nth(line.checked_sub(2)?)
+            .map(|(i, _)| i + 1)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self { files: Vec::new() }
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, source: impl Into<String>) -> FileId {
+        let id = FileId(self.files.len());
+        self.files.push(SourceFile {
+            name: name.into(),
+            source: source.into(),
+        });
+        id
+    }
+
+    pub fn get(&self, file: FileId) -> Option<&SourceFile> {
+        self.files.get(file.0)
+    }
+}
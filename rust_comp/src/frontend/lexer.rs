@@ -1,9 +1,19 @@
+use super::span::*;
 use super::token::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ScanError {
-    UnterminatedString,
-    UnexpectedCharacter(char),
+    UnterminatedString(Span),
+    UnexpectedCharacter(char, Span),
+}
+
+impl ScanError {
+    pub fn span(&self) -> Span {
+        match self {
+            ScanError::UnterminatedString(span) => *span,
+            ScanError::UnexpectedCharacter(_, span) => *span,
+        }
+    }
 }
 
 fn is_digit(c: char) -> bool {
@@ -40,19 +50,54 @@ fn lex_identifier(chars: &[char], mut i: usize) -> (String, usize) {
     (acc, i)
 }
 
-pub fn tokenize(s: &str) -> Result<Vec<Token>, ScanError> {
+struct SourcePos<'a> {
+    file: FileId,
+    offsets: &'a [usize],
+    line: usize,
+    line_start: usize,
+}
+
+impl SourcePos<'_> {
+    // `start` and `end` are char indices; spans store byte offsets
+    fn span(&self, start: usize, end: usize) -> Span {
+        Span::new(
+            self.file,
+            self.offsets[start],
+            self.offsets[end],
+            self.line,
+            start - self.line_start + 1,
+        )
+    }
+
+    fn newline(&mut self, next_char: usize) {
+        self.line += 1;
+        self.line_start = next_char;
+    }
+}
+
+pub fn tokenize(s: &str, file: FileId) -> Result<Vec<Token>, ScanError> {
     let chars: Vec<char> = s.chars().collect();
+    let offsets: Vec<usize> = s
+        .char_indices()
+        .map(|(offset, _)| offset)
+        .chain(std::iter::once(s.len()))
+        .collect();
     let len = chars.len();
     let mut tokens = Vec::new();
 
     let mut i = 0;
-    let mut line_number: usize = 1;
+    let mut src = SourcePos {
+        file,
+        offsets: &offsets,
+        line: 1,
+        line_start: 0,
+    };
     while i < len {
         let c = chars[i];
 
         match c {
             '\n' => {
-                line_number += 1;
+                src.newline(i + 1);
                 i += 1;
             }
             ' ' | '\t' => {
@@ -62,7 +107,7 @@ pub fn tokenize(s: &str) -> Result<Vec<Token>, ScanError> {
             '(' => {
                 tokens.push(Token {
                     token_type: TokenType::LeftParen,
-                    line_number: line_number,
+                    span: src.span(i, i + 1),
                     metadata: None,
                 });
                 i += 1;
@@ -70,7 +115,7 @@ pub fn tokenize(s: &str) -> Result<Vec<Token>, ScanError> {
             ')' => {
                 tokens.push(Token {
                     token_type: TokenType::RightParen,
-                    line_number: line_number,
+                    span: src.span(i, i + 1),
                     metadata: None,
                 });
                 i += 1;
@@ -79,7 +124,7 @@ pub fn tokenize(s: &str) -> Result<Vec<Token>, ScanError> {
             '{' => {
                 tokens.push(Token {
                     token_type: TokenType::LeftBrace,
-                    line_number: line_number,
+                    span: src.span(i, i + 1),
                     metadata: None,
                 });
                 i += 1;
@@ -88,7 +133,7 @@ pub fn tokenize(s: &str) -> Result<Vec<Token>, ScanError> {
             '}' => {
                 tokens.push(Token {
                     token_type: TokenType::RightBrace,
-                    line_number: line_number,
+                    span: src.span(i, i + 1),
                     metadata: None,
                 });
                 i += 1;
@@ -97,7 +142,7 @@ pub fn tokenize(s: &str) -> Result<Vec<Token>, ScanError> {
             '[' => {
                 tokens.push(Token {
                     token_type: TokenType::LeftBracket,
-                    line_number: line_number,
+                    span: src.span(i, i + 1),
                     metadata: None,
                 });
                 i += 1;
@@ -106,7 +151,7 @@ pub fn tokenize(s: &str) -> Result<Vec<Token>, ScanError> {
             ']' => {
                 tokens.push(Token {
                     token_type: TokenType::RightBracket,
-                    line_number: line_number,
+                    span: src.span(i, i + 1),
                     metadata: None,
                 });
                 i += 1;
@@ -115,7 +160,7 @@ pub fn tokenize(s: &str) -> Result<Vec<Token>, ScanError> {
             ',' => {
                 tokens.push(Token {
                     token_type: TokenType::Comma,
-                    line_number: line_number,
+                    span: src.span(i, i + 1),
                     metadata: None,
                 });
                 i += 1;
@@ -124,78 +169,139 @@ pub fn tokenize(s: &str) -> Result<Vec<Token>, ScanError> {
             '.' => {
                 tokens.push(Token {
                     token_type: TokenType::Dot,
-                    line_number: line_number,
+                    span: src.span(i, i + 1),
                     metadata: None,
                 });
                 i += 1;
             }
 
-            '-' => {
+            '$' => {
                 tokens.push(Token {
-                    token_type: TokenType::Minus,
-                    line_number: line_number,
+                    token_type: TokenType::Dollar,
+                    span: src.span(i, i + 1),
                     metadata: None,
                 });
                 i += 1;
             }
 
+            '-' => {
+                if i + 1 < len && chars[i + 1] == '=' {
+                    tokens.push(Token {
+                        token_type: TokenType::MinusEqual,
+                        span: src.span(i, i + 2),
+                        metadata: None,
+                    });
+                    i += 2;
+                } else if i + 1 < len && chars[i + 1] == '>' {
+                    tokens.push(Token {
+                        token_type: TokenType::Arrow,
+                        span: src.span(i, i + 2),
+                        metadata: None,
+                    });
+                    i += 2;
+                } else {
+                    tokens.push(Token {
+                        token_type: TokenType::Minus,
+                        span: src.span(i, i + 1),
+                        metadata: None,
+                    });
+                    i += 1;
+                }
+            }
+
             '+' => {
-                tokens.push(Token {
-                    token_type: TokenType::Plus,
-                    line_number: line_number,
-                    metadata: None,
-                });
-                i += 1;
+                if i + 1 < len && chars[i + 1] == '=' {
+                    tokens.push(Token {
+                        token_type: TokenType::PlusEqual,
+                        span: src.span(i, i + 2),
+                        metadata: None,
+                    });
+                    i += 2;
+                } else {
+                    tokens.push(Token {
+                        token_type: TokenType::Plus,
+                        span: src.span(i, i + 1),
+                        metadata: None,
+                    });
+                    i += 1;
+                }
             }
 
             ';' => {
                 tokens.push(Token {
                     token_type: TokenType::Semicolon,
-                    line_number: line_number,
+                    span: src.span(i, i + 1),
                     metadata: None,
                 });
                 i += 1;
             }
 
             ':' => {
-                tokens.push(Token {
-                    token_type: TokenType::Colon,
-                    line_number: line_number,
-                    metadata: None,
-                });
-                i += 1;
+                if i + 1 < len && chars[i + 1] == ':' {
+                    tokens.push(Token {
+                        token_type: TokenType::ColonColon,
+                        span: src.span(i, i + 2),
+                        metadata: None,
+                    });
+                    i += 2;
+                } else {
+                    tokens.push(Token {
+                        token_type: TokenType::Colon,
+                        span: src.span(i, i + 1),
+                        metadata: None,
+                    });
+                    i += 1;
+                }
             }
 
             '/' => {
-                tokens.push(Token {
-                    token_type: TokenType::Slash,
-                    line_number: line_number,
-                    metadata: None,
-                });
-                i += 1;
+                if i + 1 < len && chars[i + 1] == '=' {
+                    tokens.push(Token {
+                        token_type: TokenType::SlashEqual,
+                        span: src.span(i, i + 2),
+                        metadata: None,
+                    });
+                    i += 2;
+                } else {
+                    tokens.push(Token {
+                        token_type: TokenType::Slash,
+                        span: src.span(i, i + 1),
+                        metadata: None,
+                    });
+                    i += 1;
+                }
             }
 
             '*' => {
-                tokens.push(Token {
-                    token_type: TokenType::Star,
-                    line_number: line_number,
-                    metadata: None,
-                });
-                i += 1;
+                if i + 1 < len && chars[i + 1] == '=' {
+                    tokens.push(Token {
+                        token_type: TokenType::StarEqual,
+                        span: src.span(i, i + 2),
+                        metadata: None,
+                    });
+                    i += 2;
+                } else {
+                    tokens.push(Token {
+                        token_type: TokenType::Star,
+                        span: src.span(i, i + 1),
+                        metadata: None,
+                    });
+                    i += 1;
+                }
             }
 
             '!' => {
                 if i + 1 < len && chars[i + 1] == '=' {
                     tokens.push(Token {
                         token_type: TokenType::BangEqual,
-                        line_number: line_number,
+                        span: src.span(i, i + 2),
                         metadata: None,
                     });
                     i += 2;
                 } else {
                     tokens.push(Token {
                         token_type: TokenType::Bang,
-                        line_number: line_number,
+                        span: src.span(i, i + 1),
                         metadata: None,
                     });
                     i += 1;
@@ -206,14 +312,21 @@ pub fn tokenize(s: &str) -> Result<Vec<Token>, ScanError> {
                 if i + 1 < len && chars[i + 1] == '=' {
                     tokens.push(Token {
                         token_type: TokenType::EqualEqual,
-                        line_number: line_number,
+                        span: src.span(i, i + 2),
+                        metadata: None,
+                    });
+                    i += 2;
+                } else if i + 1 < len && chars[i + 1] == '>' {
+                    tokens.push(Token {
+                        token_type: TokenType::FatArrow,
+                        span: src.span(i, i + 2),
                         metadata: None,
                     });
                     i += 2;
                 } else {
                     tokens.push(Token {
                         token_type: TokenType::Equal,
-                        line_number: line_number,
+                        span: src.span(i, i + 1),
                         metadata: None,
                     });
                     i += 1;
@@ -224,14 +337,14 @@ pub fn tokenize(s: &str) -> Result<Vec<Token>, ScanError> {
                 if i + 1 < len && chars[i + 1] == '=' {
                     tokens.push(Token {
                         token_type: TokenType::GreaterEqual,
-                        line_number: line_number,
+                        span: src.span(i, i + 2),
                         metadata: None,
                     });
                     i += 2;
                 } else {
                     tokens.push(Token {
                         token_type: TokenType::Greater,
-                        line_number: line_number,
+                        span: src.span(i, i + 1),
                         metadata: None,
                     });
                     i += 1;
@@ -242,14 +355,14 @@ pub fn tokenize(s: &str) -> Result<Vec<Token>, ScanError> {
                 if i + 1 < len && chars[i + 1] == '=' {
                     tokens.push(Token {
                         token_type: TokenType::LessEqual,
-                        line_number: line_number,
+                        span: src.span(i, i + 2),
                         metadata: None,
                     });
                     i += 2;
                 } else {
                     tokens.push(Token {
                         token_type: TokenType::Less,
-                        line_number: line_number,
+                        span: src.span(i, i + 1),
                         metadata: None,
                     });
                     i += 1;
@@ -260,7 +373,7 @@ pub fn tokenize(s: &str) -> Result<Vec<Token>, ScanError> {
                 let (num, j) = lex_number(&chars, i);
                 tokens.push(Token {
                     token_type: TokenType::Number,
-                    line_number: line_number,
+                    span: src.span(i, j),
                     metadata: Some(TokenMetadata::Int(num)),
                 });
                 i = j;
@@ -272,20 +385,29 @@ pub fn tokenize(s: &str) -> Result<Vec<Token>, ScanError> {
                 // Keywords
                 let tok_type = match name.as_str() {
                     "and" => TokenType::And,
+                    "break" => TokenType::Break,
+                    "continue" => TokenType::Continue,
+                    "defer" => TokenType::Defer,
                     "else" => TokenType::Else,
                     "embed" => TokenType::Embed,
+                    "enum" => TokenType::Enum,
                     "false" => TokenType::False,
                     "fn" => TokenType::Func,
                     "for" => TokenType::For,
                     "gen" => TokenType::Gen,
                     "if" => TokenType::If,
+                    "impl" => TokenType::Impl,
                     "import" => TokenType::Import,
                     "in" => TokenType::In,
+                    "match" => TokenType::Match,
                     "meta" => TokenType::Meta,
+                    "none" => TokenType::None,
                     "or" => TokenType::Or,
                     "print" => TokenType::Print,
+                    "quote" => TokenType::Quote,
                     "return" => TokenType::Return,
                     "struct" => TokenType::Struct,
+                    "trait" => TokenType::Trait,
                     "true" => TokenType::True,
                     "typeof" => TokenType::Typeof,
                     "var" => TokenType::Var,
@@ -296,13 +418,13 @@ pub fn tokenize(s: &str) -> Result<Vec<Token>, ScanError> {
                 if tok_type == TokenType::Identifier {
                     tokens.push(Token {
                         token_type: tok_type,
-                        line_number: line_number,
+                        span: src.span(i, j),
                         metadata: Some(TokenMetadata::String(name)),
                     });
                 } else {
                     tokens.push(Token {
                         token_type: tok_type,
-                        line_number: line_number,
+                        span: src.span(i, j),
                         metadata: None,
                     });
                 }
@@ -319,9 +441,14 @@ pub fn tokenize(s: &str) -> Result<Vec<Token>, ScanError> {
                         '"' => {
                             tokens.push(Token {
                                 token_type: TokenType::String,
-                                line_number: line_number,
+                                span: src.span(i, j + 1),
                                 metadata: Some(TokenMetadata::String(acc)),
                             });
+                            for (k, ch) in chars.iter().enumerate().take(j).skip(i + 1) {
+                                if *ch == '\n' {
+                                    src.newline(k + 1);
+                                }
+                            }
                             i = j + 1;
                             break;
                         }
@@ -333,17 +460,17 @@ pub fn tokenize(s: &str) -> Result<Vec<Token>, ScanError> {
                 }
 
                 if j >= len {
-                    return Err(ScanError::UnterminatedString);
+                    return Err(ScanError::UnterminatedString(src.span(i, len)));
                 }
             }
 
-            _ => return Err(ScanError::UnexpectedCharacter(c)),
+            _ => return Err(ScanError::UnexpectedCharacter(c, src.span(i, i + 1))),
         }
     }
 
     tokens.push(Token {
         token_type: TokenType::EOF,
-        line_number: line_number,
+        span: src.span(len, len),
         metadata: None,
     });
     Ok(tokens)
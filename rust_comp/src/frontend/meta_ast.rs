@@ -1,12 +1,15 @@
 use super::id_provider::*;
+use super::span::Span;
 use crate::util::formatters::tree_formatter::*;
 use std::collections::HashMap;
+use std::fmt;
 
 #[derive(Debug, Clone)]
 pub struct MetaAst {
     pub sem_root_stmts: Vec<AstId>,
     exprs: HashMap<AstId, MetaExpr>,
     stmts: HashMap<AstId, MetaStmt>,
+    spans: HashMap<AstId, Span>,
 }
 
 #[derive(Debug)]
@@ -21,18 +24,31 @@ impl MetaAst {
             sem_root_stmts: vec![],
             exprs: HashMap::new(),
             stmts: HashMap::new(),
+            spans: HashMap::new(),
         }
     }
 
-    pub fn insert_expr(&mut self, id_provider: &mut IdProvider, expr: MetaExpr) -> usize {
+    pub fn insert_expr(
+        &mut self,
+        id_provider: &mut IdProvider,
+        expr: MetaExpr,
+        span: Span,
+    ) -> usize {
         let id = id_provider.next();
         self.exprs.insert(id, expr);
+        self.spans.insert(id, span);
         id
     }
 
-    pub fn insert_stmt(&mut self, id_provider: &mut IdProvider, stmt: MetaStmt) -> usize {
+    pub fn insert_stmt(
+        &mut self,
+        id_provider: &mut IdProvider,
+        stmt: MetaStmt,
+        span: Span,
+    ) -> usize {
         let id = id_provider.next();
         self.stmts.insert(id, stmt);
+        self.spans.insert(id, span);
         id
     }
 
@@ -43,6 +59,10 @@ impl MetaAst {
     pub fn get_stmt(&self, id: AstId) -> Option<&MetaStmt> {
         self.stmts.get(&id)
     }
+
+    pub fn get_span(&self, id: AstId) -> Option<Span> {
+        self.spans.get(&id).copied()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -57,18 +77,72 @@ pub enum MetaExpr {
         fields: Vec<(String, AstId)>,
     },
 
+    // `Enum::Variant`, `Enum::Variant(args)` or `Enum::Variant { fields }`
+    Variant {
+        enum_name: String,
+        variant: String,
+        payload: Payload<AstId>,
+    },
+
     Variable(String),
 
     List(Vec<AstId>),
 
     Call {
-        callee: String,
+        callee: AstId,
         args: Vec<AstId>,
     },
 
+    Lambda {
+        params: Vec<Param>,
+        ret: Option<TypeExpr>,
+        body: AstId,
+    },
+
     Typeof(String),
 
-    Embed(String),
+    Embed {
+        path: String,
+        kind: EmbedKind,
+    },
+
+    // `quote { stmts }`, the statements as a code value
+    Quote(Vec<AstId>),
+
+    // `$name` or `$(expr)` in a `quote` or `gen`, spliced in from the level
+    // that emits the code
+    Unquote(AstId),
+
+    // ACCESS
+    FieldAccess {
+        target: AstId,
+        field: String,
+    },
+
+    Index {
+        target: AstId,
+        index: AstId,
+    },
+
+    // `target[start:end]`, either bound may be left out
+    Slice {
+        target: AstId,
+        start: Option<AstId>,
+        end: Option<AstId>,
+    },
+
+    MethodCall {
+        receiver: AstId,
+        method: String,
+        args: Vec<AstId>,
+    },
+
+    // `target` is a Variable, FieldAccess or Index expression
+    Assign {
+        target: AstId,
+        op: AssignOp,
+        value: AstId,
+    },
 
     // BINOPS
     Add(AstId, AstId),
@@ -76,6 +150,17 @@ pub enum MetaExpr {
     Mult(AstId, AstId),
     Div(AstId, AstId),
     Equals(AstId, AstId),
+    NotEquals(AstId, AstId),
+    Less(AstId, AstId),
+    LessEqual(AstId, AstId),
+    Greater(AstId, AstId),
+    GreaterEqual(AstId, AstId),
+    And(AstId, AstId),
+    Or(AstId, AstId),
+
+    // UNARY OPS
+    Not(AstId),
+    Negate(AstId),
 }
 
 #[derive(Debug, Clone)]
@@ -86,12 +171,15 @@ pub enum MetaStmt {
     // DECLARATION
     VarDecl {
         name: String,
+        ty: Option<TypeExpr>,
         expr: AstId,
     },
 
     FnDecl {
         name: String,
-        params: Vec<String>,
+        type_params: Vec<TypeParam>,
+        params: Vec<Param>,
+        ret: Option<TypeExpr>,
         body: AstId,
     },
 
@@ -100,6 +188,24 @@ pub enum MetaStmt {
         fields: Vec<MetaFieldDecl>,
     },
 
+    EnumDecl {
+        name: String,
+        type_params: Vec<String>,
+        variants: Vec<VariantDecl>,
+    },
+
+    TraitDecl {
+        name: String,
+        methods: Vec<MethodSig>,
+    },
+
+    // `methods` are `FnDecl`s; they are only reachable through the trait
+    ImplDecl {
+        trait_name: String,
+        type_name: String,
+        methods: Vec<AstId>,
+    },
+
     // CONTROL
     If {
         cond: AstId,
@@ -113,8 +219,24 @@ pub enum MetaStmt {
         body: AstId,
     },
 
+    While {
+        cond: AstId,
+        body: AstId,
+    },
+
+    Match {
+        scrutinee: AstId,
+        arms: Vec<MatchArm>,
+    },
+
+    Break,
+    Continue,
+
     Return(Option<AstId>),
 
+    // runs when the enclosing block is left
+    Defer(AstId),
+
     Block(Vec<AstId>),
 
     // UTIL
@@ -126,12 +248,342 @@ pub enum MetaStmt {
 
     // TEMPORARY
     Print(AstId),
+
+    // placeholder for a statement that failed to parse
+    Error,
+}
+
+/// How `embed(...)` hands the file contents to the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbedKind {
+    Text,
+    Bytes,
+}
+
+impl fmt::Display for EmbedKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmbedKind::Text => write!(f, "text"),
+            EmbedKind::Bytes => write!(f, "bytes"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssignOp {
+    Assign,
+    Add,
+    Sub,
+    Mult,
+    Div,
+}
+
+impl fmt::Display for AssignOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssignOp::Assign => write!(f, "="),
+            AssignOp::Add => write!(f, "+="),
+            AssignOp::Sub => write!(f, "-="),
+            AssignOp::Mult => write!(f, "*="),
+            AssignOp::Div => write!(f, "/="),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct MetaFieldDecl {
     pub field_name: String,
-    pub type_name: String,
+    pub ty: TypeExpr,
+}
+
+/// A type written in the source: `int`, `Point`, `List<int>` or
+/// `fn(int) -> bool`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeExpr {
+    Named {
+        name: String,
+        args: Vec<TypeExpr>,
+        span: Span,
+    },
+    Func {
+        params: Vec<TypeExpr>,
+        ret: Box<TypeExpr>,
+        span: Span,
+    },
+}
+
+impl TypeExpr {
+    pub fn span(&self) -> Span {
+        match self {
+            TypeExpr::Named { span, .. } | TypeExpr::Func { span, .. } => *span,
+        }
+    }
+}
+
+impl fmt::Display for TypeExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |types: &[TypeExpr]| {
+            types
+                .iter()
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            TypeExpr::Named { name, args, .. } if args.is_empty() => write!(f, "{name}"),
+            TypeExpr::Named { name, args, .. } => write!(f, "{name}<{}>", join(args)),
+            TypeExpr::Func { params, ret, .. } => write!(f, "fn({}) -> {ret}", join(params)),
+        }
+    }
+}
+
+/// What an enum variant carries: nothing, values in order, or named fields.
+/// Declarations hold the types, constructors the expressions and runtime
+/// values the values.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Payload<T> {
+    Unit,
+    Tuple(Vec<T>),
+    Struct(Vec<(String, T)>),
+}
+
+impl<T> Payload<T> {
+    pub fn len(&self) -> usize {
+        match self {
+            Payload::Unit => 0,
+            Payload::Tuple(items) => items.len(),
+            Payload::Struct(fields) => fields.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn map<U>(&self, mut f: impl FnMut(&T) -> U) -> Payload<U> {
+        match self {
+            Payload::Unit => Payload::Unit,
+            Payload::Tuple(items) => Payload::Tuple(items.iter().map(f).collect()),
+            Payload::Struct(fields) => Payload::Struct(
+                fields
+                    .iter()
+                    .map(|(name, item)| (name.clone(), f(item)))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Whether `other` has the same form, length and field names, in any
+    /// order.
+    pub fn same_shape<U>(&self, other: &Payload<U>) -> bool {
+        match (self, other) {
+            (Payload::Unit, Payload::Unit) => true,
+            (Payload::Tuple(a), Payload::Tuple(b)) => a.len() == b.len(),
+            (Payload::Struct(a), Payload::Struct(b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .all(|(name, _)| b.iter().any(|(other, _)| other == name))
+            }
+            _ => false,
+        }
+    }
+
+    /// Writes the payload after a variant name: `(a, b)`, ` {x: a}` or
+    /// nothing, the way struct values are written.
+    pub fn write_with(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        mut item: impl FnMut(&mut fmt::Formatter<'_>, &T) -> fmt::Result,
+    ) -> fmt::Result {
+        match self {
+            Payload::Unit => Ok(()),
+            Payload::Tuple(items) => {
+                write!(f, "(")?;
+                for (i, value) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    item(f, value)?;
+                }
+                write!(f, ")")
+            }
+            Payload::Struct(fields) => {
+                write!(f, " {{")?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{name}: ")?;
+                    item(f, value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// One variant of an `enum` declaration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantDecl {
+    pub name: String,
+    pub payload: Payload<TypeExpr>,
+}
+
+impl fmt::Display for VariantDecl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        self.payload.write_with(f, |f, ty| write!(f, "{ty}"))
+    }
+}
+
+/// A function parameter, with its type if one was written.
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub name: String,
+    pub ty: Option<TypeExpr>,
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.ty {
+            Some(ty) => write!(f, "{}: {ty}", self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// A pattern on the left of a `match` arm.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternKind {
+    // `_`
+    Wildcard,
+    // a name, bound to the whole value
+    Binding(String),
+    Int(i64),
+    String(String),
+    Bool(bool),
+    // `[a, b]`; with a `rest` (`..` or `..name`) longer lists match too and
+    // the rest pattern gets the remaining elements
+    List {
+        items: Vec<Pattern>,
+        rest: Option<Box<Pattern>>,
+    },
+    // `Point { x, y: 0 }`; fields left out match anything
+    Struct {
+        name: String,
+        fields: Vec<(String, Pattern)>,
+    },
+    Variant {
+        enum_name: String,
+        variant: String,
+        payload: Payload<Pattern>,
+    },
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            PatternKind::Wildcard => write!(f, "_"),
+            PatternKind::Binding(name) => write!(f, "{name}"),
+            PatternKind::Int(n) => write!(f, "{n}"),
+            PatternKind::String(s) => write!(f, "\"{s}\""),
+            PatternKind::Bool(b) => write!(f, "{b}"),
+            PatternKind::List { items, rest } => {
+                let mut parts: Vec<String> = items.iter().map(|p| p.to_string()).collect();
+                match rest.as_deref().map(|p| &p.kind) {
+                    Some(PatternKind::Binding(name)) => parts.push(format!("..{name}")),
+                    Some(_) => parts.push("..".into()),
+                    None => {}
+                }
+                write!(f, "[{}]", parts.join(", "))
+            }
+            PatternKind::Struct { name, fields } => {
+                let fields: Vec<String> = fields.iter().map(|(n, p)| format!("{n}: {p}")).collect();
+                write!(f, "{name} {{{}}}", fields.join(", "))
+            }
+            PatternKind::Variant {
+                enum_name,
+                variant,
+                payload,
+            } => {
+                write!(f, "{enum_name}::{variant}")?;
+                payload.write_with(f, |f, p| write!(f, "{p}"))
+            }
+        }
+    }
+}
+
+/// One `pattern if guard => body` arm of a `match`.
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<AstId>,
+    pub body: AstId,
+}
+
+/// A type parameter of a generic function, with the traits its type must
+/// implement: `T: Show + Eq`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeParam {
+    pub name: String,
+    pub bounds: Vec<String>,
+}
+
+impl fmt::Display for TypeParam {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.bounds.is_empty() {
+            write!(f, ": {}", self.bounds.join(" + "))?;
+        }
+        Ok(())
+    }
+}
+
+/// A method a `trait` declares: `fn show(self) -> string;`. A `self`
+/// parameter without a type stands for the implementing type.
+#[derive(Debug, Clone)]
+pub struct MethodSig {
+    pub name: String,
+    pub params: Vec<Param>,
+    pub ret: Option<TypeExpr>,
+}
+
+impl fmt::Display for MethodSig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "fn {}({})", self.name, param_list(&self.params))?;
+        match &self.ret {
+            Some(ret) => write!(f, " -> {ret}"),
+            None => Ok(()),
+        }
+    }
+}
+
+// Tree leaf for a generic function's type parameters, if it has any.
+pub(crate) fn type_params_leaf(type_params: &[TypeParam]) -> Option<TreeNode> {
+    (!type_params.is_empty()).then(|| {
+        let params: Vec<String> = type_params.iter().map(|p| p.to_string()).collect();
+        TreeNode::leaf(format!("TypeParams({})", params.join(", ")))
+    })
+}
+
+// Tree leaves for a declaration's optional annotations.
+pub(crate) fn annotation_leaf(label: &str, ty: &Option<TypeExpr>) -> Option<TreeNode> {
+    ty.as_ref()
+        .map(|ty| TreeNode::leaf(format!("{label}({ty})")))
+}
+
+pub(crate) fn param_list(params: &[Param]) -> String {
+    params
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 impl AsTree for MetaAst {
@@ -151,40 +603,92 @@ impl MetaAst {
         let (label, mut children): (String, Vec<TreeNode>) = match stmt {
             MetaStmt::ExprStmt(e) => ("ExprStmt".into(), vec![self.convert_expr(*e)]),
 
-            MetaStmt::VarDecl { name, expr } => (
+            MetaStmt::VarDecl { name, ty, expr } => (
                 "VarDecl".into(),
+                std::iter::once(TreeNode::leaf(format!("Name({name})")))
+                    .chain(annotation_leaf("Type", ty))
+                    .chain([self.convert_expr(*expr)])
+                    .collect(),
+            ),
+
+            MetaStmt::FnDecl {
+                name,
+                type_params,
+                params,
+                ret,
+                body,
+            } => (
+                "FnDecl".into(),
+                std::iter::once(TreeNode::leaf(format!("Name({name})")))
+                    .chain(type_params_leaf(type_params))
+                    .chain([TreeNode::node(
+                        "Params",
+                        params
+                            .iter()
+                            .map(|p| TreeNode::leaf(p.to_string()))
+                            .collect(),
+                    )])
+                    .chain(annotation_leaf("Returns", ret))
+                    .chain([self.convert_stmt(*body)])
+                    .collect(),
+            ),
+
+            MetaStmt::StructDecl { name, fields } => (
+                "StructDecl".into(),
                 vec![
                     TreeNode::leaf(format!("Name({name})")),
-                    self.convert_expr(*expr),
+                    TreeNode::node(
+                        "Fields",
+                        fields
+                            .iter()
+                            .map(|f| TreeNode::leaf(format!("{}: {}", f.field_name, f.ty)))
+                            .collect(),
+                    ),
                 ],
             ),
 
-            MetaStmt::FnDecl { name, params, body } => (
-                "FnDecl".into(),
+            MetaStmt::EnumDecl {
+                name,
+                type_params,
+                variants,
+            } => (
+                "EnumDecl".into(),
                 vec![
                     TreeNode::leaf(format!("Name({name})")),
+                    TreeNode::leaf(format!("TypeParams({})", type_params.join(", "))),
                     TreeNode::node(
-                        "Params",
-                        params.iter().map(|p| TreeNode::leaf(p.clone())).collect(),
+                        "Variants",
+                        variants
+                            .iter()
+                            .map(|v| TreeNode::leaf(v.to_string()))
+                            .collect(),
                     ),
-                    self.convert_stmt(*body),
                 ],
             ),
 
-            MetaStmt::StructDecl { name, fields } => (
-                "StructDecl".into(),
+            MetaStmt::TraitDecl { name, methods } => (
+                "TraitDecl".into(),
                 vec![
                     TreeNode::leaf(format!("Name({name})")),
                     TreeNode::node(
-                        "Fields",
-                        fields
+                        "Methods",
+                        methods
                             .iter()
-                            .map(|f| TreeNode::leaf(format!("{}: {}", f.field_name, f.type_name)))
+                            .map(|m| TreeNode::leaf(m.to_string()))
                             .collect(),
                     ),
                 ],
             ),
 
+            MetaStmt::ImplDecl {
+                trait_name,
+                type_name,
+                methods,
+            } => (
+                format!("ImplDecl({trait_name} for {type_name})"),
+                methods.iter().map(|m| self.convert_stmt(*m)).collect(),
+            ),
+
             MetaStmt::If {
                 cond,
                 body,
@@ -213,11 +717,42 @@ impl MetaAst {
                 ],
             ),
 
+            MetaStmt::While { cond, body } => (
+                "WhileStmt".into(),
+                vec![
+                    TreeNode::node("Cond", vec![self.convert_expr(*cond)]),
+                    TreeNode::node("Body", vec![self.convert_stmt(*body)]),
+                ],
+            ),
+
+            MetaStmt::Match { scrutinee, arms } => (
+                "MatchStmt".into(),
+                std::iter::once(TreeNode::node(
+                    "Scrutinee",
+                    vec![self.convert_expr(*scrutinee)],
+                ))
+                .chain(arms.iter().map(|arm| {
+                    let mut children = vec![TreeNode::leaf(format!("Pattern({})", arm.pattern))];
+                    if let Some(guard) = arm.guard {
+                        children.push(TreeNode::node("Guard", vec![self.convert_expr(guard)]));
+                    }
+                    children.push(self.convert_stmt(arm.body));
+                    TreeNode::node("Arm", children)
+                }))
+                .collect(),
+            ),
+
+            MetaStmt::Break => ("BreakStmt".into(), vec![]),
+
+            MetaStmt::Continue => ("ContinueStmt".into(), vec![]),
+
             MetaStmt::Return(e) => (
                 "ReturnStmt".into(),
                 e.map(|id| vec![self.convert_expr(id)]).unwrap_or_default(),
             ),
 
+            MetaStmt::Defer(s) => ("DeferStmt".into(), vec![self.convert_stmt(*s)]),
+
             MetaStmt::Block(stmts) => (
                 "Block".into(),
                 stmts.iter().map(|s| self.convert_stmt(*s)).collect(),
@@ -233,6 +768,8 @@ impl MetaAst {
             ),
 
             MetaStmt::Print(e) => ("PrintStmt".into(), vec![self.convert_expr(*e)]),
+
+            MetaStmt::Error => ("Error".into(), vec![]),
         };
 
         children.insert(0, TreeNode::leaf(format!("id: {id}")));
@@ -259,19 +796,93 @@ impl MetaAst {
                     .collect(),
             ),
 
+            MetaExpr::Variant {
+                enum_name,
+                variant,
+                payload,
+            } => (
+                format!("Variant({enum_name}::{variant})"),
+                match payload {
+                    Payload::Unit => vec![],
+                    Payload::Tuple(items) => items.iter().map(|e| self.convert_expr(*e)).collect(),
+                    Payload::Struct(fields) => fields
+                        .iter()
+                        .map(|(n, e)| TreeNode::node(n.clone(), vec![self.convert_expr(*e)]))
+                        .collect(),
+                },
+            ),
+
             MetaExpr::List(items) => (
                 "List".into(),
                 items.iter().map(|e| self.convert_expr(*e)).collect(),
             ),
 
             MetaExpr::Call { callee, args } => (
-                format!("Call({callee})"),
-                args.iter().map(|e| self.convert_expr(*e)).collect(),
+                "Call".into(),
+                vec![
+                    TreeNode::node("Callee", vec![self.convert_expr(*callee)]),
+                    TreeNode::node("Args", args.iter().map(|e| self.convert_expr(*e)).collect()),
+                ],
+            ),
+
+            MetaExpr::Lambda { params, ret, body } => (
+                "Lambda".into(),
+                std::iter::once(TreeNode::leaf(format!("Params({})", param_list(params))))
+                    .chain(annotation_leaf("Returns", ret))
+                    .chain([TreeNode::node("Body", vec![self.convert_stmt(*body)])])
+                    .collect(),
             ),
 
             MetaExpr::Typeof(name) => ("Typeof".into(), vec![TreeNode::leaf(name.clone())]),
 
-            MetaExpr::Embed(path) => ("Embed".into(), vec![TreeNode::leaf(path.clone())]),
+            MetaExpr::Embed { path, kind } => {
+                (format!("Embed({kind})"), vec![TreeNode::leaf(path.clone())])
+            }
+
+            MetaExpr::Quote(stmts) => (
+                "Quote".into(),
+                stmts.iter().map(|s| self.convert_stmt(*s)).collect(),
+            ),
+
+            MetaExpr::Unquote(e) => ("Unquote".into(), vec![self.convert_expr(*e)]),
+
+            MetaExpr::FieldAccess { target, field } => (
+                format!("FieldAccess({field})"),
+                vec![self.convert_expr(*target)],
+            ),
+
+            MetaExpr::Index { target, index } => (
+                "Index".into(),
+                vec![self.convert_expr(*target), self.convert_expr(*index)],
+            ),
+
+            MetaExpr::Slice { target, start, end } => {
+                let mut children = vec![self.convert_expr(*target)];
+                if let Some(start) = start {
+                    children.push(TreeNode::node("Start", vec![self.convert_expr(*start)]));
+                }
+                if let Some(end) = end {
+                    children.push(TreeNode::node("End", vec![self.convert_expr(*end)]));
+                }
+                ("Slice".into(), children)
+            }
+
+            MetaExpr::MethodCall {
+                receiver,
+                method,
+                args,
+            } => (
+                format!("MethodCall({method})"),
+                vec![
+                    TreeNode::node("Receiver", vec![self.convert_expr(*receiver)]),
+                    TreeNode::node("Args", args.iter().map(|e| self.convert_expr(*e)).collect()),
+                ],
+            ),
+
+            MetaExpr::Assign { target, op, value } => (
+                format!("Assign({op})"),
+                vec![self.convert_expr(*target), self.convert_expr(*value)],
+            ),
 
             MetaExpr::Add(a, b) => (
                 "Add".into(),
@@ -297,6 +908,45 @@ impl MetaAst {
                 "Equals".into(),
                 vec![self.convert_expr(*a), self.convert_expr(*b)],
             ),
+
+            MetaExpr::NotEquals(a, b) => (
+                "NotEquals".into(),
+                vec![self.convert_expr(*a), self.convert_expr(*b)],
+            ),
+
+            MetaExpr::Less(a, b) => (
+                "Less".into(),
+                vec![self.convert_expr(*a), self.convert_expr(*b)],
+            ),
+
+            MetaExpr::LessEqual(a, b) => (
+                "LessEqual".into(),
+                vec![self.convert_expr(*a), self.convert_expr(*b)],
+            ),
+
+            MetaExpr::Greater(a, b) => (
+                "Greater".into(),
+                vec![self.convert_expr(*a), self.convert_expr(*b)],
+            ),
+
+            MetaExpr::GreaterEqual(a, b) => (
+                "GreaterEqual".into(),
+                vec![self.convert_expr(*a), self.convert_expr(*b)],
+            ),
+
+            MetaExpr::And(a, b) => (
+                "And".into(),
+                vec![self.convert_expr(*a), self.convert_expr(*b)],
+            ),
+
+            MetaExpr::Or(a, b) => (
+                "Or".into(),
+                vec![self.convert_expr(*a), self.convert_expr(*b)],
+            ),
+
+            MetaExpr::Not(a) => ("Not".into(), vec![self.convert_expr(*a)]),
+
+            MetaExpr::Negate(a) => ("Negate".into(), vec![self.convert_expr(*a)]),
         };
 
         children.insert(0, TreeNode::leaf(format!("id: {id}")));
@@ -3,4 +3,5 @@ pub mod lexer;
 pub mod meta_ast;
 pub mod parser;
 pub mod source_discovery;
+pub mod span;
 pub mod token;
@@ -1,10 +1,14 @@
 use super::id_provider::*;
 use super::meta_ast::*;
+use super::span::Span;
 use super::token::*;
 
 pub struct ParseCtx {
     pub ast: MetaAst,
     pub id_provider: IdProvider,
+    pub errors: Vec<ParseError>,
+    // off while parsing a `match` scrutinee, where `name {` opens the arms
+    struct_literals: bool,
 }
 
 impl ParseCtx {
@@ -12,24 +16,53 @@ impl ParseCtx {
         Self {
             ast: MetaAst::new(),
             id_provider: IdProvider::new(),
+            errors: Vec::new(),
+            struct_literals: true,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ParseError {
-    UnterminatedString,
+    UnterminatedString(Span),
     UnexpectedToken {
         found: TokenType,
         expected: TokenType,
-        pos: usize,
+        span: Span,
     },
     UnexpectedEOF {
         expected: TokenType,
-        pos: usize,
+        span: Span,
+    },
+    ExpectedExpression {
+        found: TokenType,
+        span: Span,
+    },
+    InvalidAssignmentTarget(Span),
+    InvalidEmbedMode {
+        found: String,
+        span: Span,
+    },
+    ExpectedPattern {
+        found: TokenType,
+        span: Span,
     },
 }
 
+impl ParseError {
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnterminatedString(span) => *span,
+            ParseError::UnexpectedToken { span, .. } => *span,
+            ParseError::UnexpectedEOF { span, .. } => *span,
+            ParseError::ExpectedExpression { span, .. } => *span,
+            ParseError::InvalidAssignmentTarget(span) => *span,
+            ParseError::InvalidEmbedMode { span, .. } => *span,
+            ParseError::ExpectedPattern { span, .. } => *span,
+        }
+    }
+}
+
 fn peek(tokens: &[Token], pos: usize) -> Option<TokenType> {
     match tokens.get(pos) {
         None => None,
@@ -51,18 +84,82 @@ fn consume<'a>(
 ) -> Result<&'a Token, ParseError> {
     match tokens.get(*pos) {
         Some(t) if t.token_type == expected => Ok(consume_next(tokens, pos)),
+        Some(t) if t.token_type == TokenType::EOF => Err(ParseError::UnexpectedEOF {
+            expected,
+            span: t.span,
+        }),
         Some(t) => Err(ParseError::UnexpectedToken {
             found: t.token_type,
             expected,
-            pos: *pos,
+            span: t.span,
         }),
         None => Err(ParseError::UnexpectedEOF {
             expected,
-            pos: *pos,
+            span: eof_span(tokens),
         }),
     }
 }
 
+fn eof_span(tokens: &[Token]) -> Span {
+    tokens.last().map(|t| t.span).unwrap_or_default()
+}
+
+// Span from the token at `start` through the last consumed token.
+fn span_since(tokens: &[Token], start: usize, pos: usize) -> Span {
+    let first = match tokens.get(start) {
+        Some(t) => t.span,
+        None => return eof_span(tokens),
+    };
+    if pos <= start {
+        return first;
+    }
+    match tokens.get(pos - 1) {
+        Some(last) => first.to(last.span),
+        None => first,
+    }
+}
+
+fn expected_expression(tokens: &[Token], pos: usize) -> ParseError {
+    match tokens.get(pos) {
+        Some(t) => ParseError::ExpectedExpression {
+            found: t.token_type,
+            span: t.span,
+        },
+        None => ParseError::ExpectedExpression {
+            found: TokenType::EOF,
+            span: eof_span(tokens),
+        },
+    }
+}
+
+fn expected_pattern(tokens: &[Token], pos: usize) -> ParseError {
+    match tokens.get(pos) {
+        Some(t) => ParseError::ExpectedPattern {
+            found: t.token_type,
+            span: t.span,
+        },
+        None => ParseError::ExpectedPattern {
+            found: TokenType::EOF,
+            span: eof_span(tokens),
+        },
+    }
+}
+
+// Runs `parse` with struct literals allowed or not, then restores the
+// previous setting.
+fn with_struct_literals<T>(
+    tokens: &[Token],
+    pos: &mut usize,
+    ctx: &mut ParseCtx,
+    allowed: bool,
+    parse: impl FnOnce(&[Token], &mut usize, &mut ParseCtx) -> Result<T, ParseError>,
+) -> Result<T, ParseError> {
+    let saved = std::mem::replace(&mut ctx.struct_literals, allowed);
+    let result = parse(tokens, pos, ctx);
+    ctx.struct_literals = saved;
+    result
+}
+
 fn consume_next<'a>(tokens: &'a [Token], pos: &mut usize) -> &'a Token {
     let tok = tokens
         .get(*pos)
@@ -87,10 +184,16 @@ fn parse_separated<T>(
 
     loop {
         let before = *pos;
-        items.push(parse_item(tokens, pos, ctx)?);
+        items.push(with_struct_literals(
+            tokens,
+            pos,
+            ctx,
+            true,
+            &mut parse_item,
+        )?);
 
         if *pos == before {
-            panic!("parser made no progress in comma-separated list");
+            return Err(expected_expression(tokens, *pos));
         }
 
         if check(tokens, *pos, separator) {
@@ -103,48 +206,73 @@ fn parse_separated<T>(
     Ok(items)
 }
 
-fn parse_factor<'a>(
+fn parse_primary<'a>(
     tokens: &'a [Token],
     pos: &mut usize,
     ctx: &mut ParseCtx,
 ) -> Result<AstId, ParseError> {
+    let start = *pos;
     match tokens.get(*pos) {
         Some(tok) => match tok.token_type {
             TokenType::Number => {
                 consume_next(tokens, pos);
-                let id = ctx
-                    .ast
-                    .insert_expr(&mut ctx.id_provider, MetaExpr::Int(tok.expect_int()));
+                let id = ctx.ast.insert_expr(
+                    &mut ctx.id_provider,
+                    MetaExpr::Int(tok.expect_int()),
+                    span_since(tokens, start, *pos),
+                );
                 Ok(id)
             }
 
             TokenType::String => {
                 consume_next(tokens, pos);
-                let id = ctx
-                    .ast
-                    .insert_expr(&mut ctx.id_provider, MetaExpr::String(tok.expect_str()));
+                let id = ctx.ast.insert_expr(
+                    &mut ctx.id_provider,
+                    MetaExpr::String(tok.expect_str()),
+                    span_since(tokens, start, *pos),
+                );
                 Ok(id)
             }
 
             TokenType::True => {
                 consume_next(tokens, pos);
-                let id = ctx
-                    .ast
-                    .insert_expr(&mut ctx.id_provider, MetaExpr::Bool(true));
+                let id = ctx.ast.insert_expr(
+                    &mut ctx.id_provider,
+                    MetaExpr::Bool(true),
+                    span_since(tokens, start, *pos),
+                );
                 Ok(id)
             }
 
             TokenType::False => {
                 consume_next(tokens, pos);
-                let id = ctx
-                    .ast
-                    .insert_expr(&mut ctx.id_provider, MetaExpr::Bool(false));
+                let id = ctx.ast.insert_expr(
+                    &mut ctx.id_provider,
+                    MetaExpr::Bool(false),
+                    span_since(tokens, start, *pos),
+                );
+                Ok(id)
+            }
+
+            // `none` is the prelude's `Option::None`
+            TokenType::None => {
+                consume_next(tokens, pos);
+                let none = MetaExpr::Variant {
+                    enum_name: "Option".to_string(),
+                    variant: "None".to_string(),
+                    payload: Payload::Unit,
+                };
+                let id = ctx.ast.insert_expr(
+                    &mut ctx.id_provider,
+                    none,
+                    span_since(tokens, start, *pos),
+                );
                 Ok(id)
             }
 
             TokenType::LeftParen => {
                 consume(tokens, pos, TokenType::LeftParen)?;
-                let expr_id = parse_expr(tokens, pos, ctx)?;
+                let expr_id = with_struct_literals(tokens, pos, ctx, true, parse_expr)?;
                 consume(tokens, pos, TokenType::RightParen)?;
                 Ok(expr_id)
             }
@@ -154,9 +282,11 @@ fn parse_factor<'a>(
                 consume(tokens, pos, TokenType::LeftParen)?;
                 let ident = consume(tokens, pos, TokenType::Identifier)?.expect_str();
                 consume(tokens, pos, TokenType::RightParen)?;
-                let id = ctx
-                    .ast
-                    .insert_expr(&mut ctx.id_provider, MetaExpr::Typeof(ident));
+                let id = ctx.ast.insert_expr(
+                    &mut ctx.id_provider,
+                    MetaExpr::Typeof(ident),
+                    span_since(tokens, start, *pos),
+                );
                 Ok(id)
             }
 
@@ -164,66 +294,152 @@ fn parse_factor<'a>(
                 consume(tokens, pos, TokenType::Embed)?;
                 consume(tokens, pos, TokenType::LeftParen)?;
                 let file_path = consume(tokens, pos, TokenType::String)?.expect_str();
+                let kind = if check(tokens, *pos, TokenType::Comma) {
+                    consume(tokens, pos, TokenType::Comma)?;
+                    let mode = consume(tokens, pos, TokenType::Identifier)?;
+                    match mode.expect_str().as_str() {
+                        "text" => EmbedKind::Text,
+                        "bytes" => EmbedKind::Bytes,
+                        other => {
+                            return Err(ParseError::InvalidEmbedMode {
+                                found: other.to_string(),
+                                span: mode.span,
+                            })
+                        }
+                    }
+                } else {
+                    EmbedKind::Text
+                };
                 consume(tokens, pos, TokenType::RightParen)?;
-                let id = ctx
-                    .ast
-                    .insert_expr(&mut ctx.id_provider, MetaExpr::Embed(file_path));
+                let id = ctx.ast.insert_expr(
+                    &mut ctx.id_provider,
+                    MetaExpr::Embed {
+                        path: file_path,
+                        kind,
+                    },
+                    span_since(tokens, start, *pos),
+                );
                 Ok(id)
             }
 
-            TokenType::Identifier => {
-                let name = consume_next(tokens, pos).expect_str();
+            TokenType::Quote => {
+                consume(tokens, pos, TokenType::Quote)?;
+                consume(tokens, pos, TokenType::LeftBrace)?;
+                let saved = std::mem::replace(&mut ctx.struct_literals, true);
+                let mut stmts = Vec::new();
+                while !check(tokens, *pos, TokenType::RightBrace)
+                    && !check(tokens, *pos, TokenType::EOF)
+                    && *pos < tokens.len()
+                {
+                    stmts.push(parse_stmt_or_recover(tokens, pos, ctx));
+                }
+                ctx.struct_literals = saved;
+                consume(tokens, pos, TokenType::RightBrace)?;
+                let id = ctx.ast.insert_expr(
+                    &mut ctx.id_provider,
+                    MetaExpr::Quote(stmts),
+                    span_since(tokens, start, *pos),
+                );
+                Ok(id)
+            }
 
-                if check(tokens, *pos, TokenType::LeftParen) {
+            TokenType::Dollar => {
+                consume(tokens, pos, TokenType::Dollar)?;
+                let inner = if check(tokens, *pos, TokenType::LeftParen) {
                     consume(tokens, pos, TokenType::LeftParen)?;
-                    let args = parse_separated(
-                        tokens,
-                        pos,
-                        ctx,
-                        TokenType::Comma,
-                        TokenType::RightParen,
-                        parse_expr,
-                    )?;
+                    let expr_id = with_struct_literals(tokens, pos, ctx, true, parse_expr)?;
                     consume(tokens, pos, TokenType::RightParen)?;
+                    expr_id
+                } else {
+                    let name = consume(tokens, pos, TokenType::Identifier)?.expect_str();
+                    ctx.ast.insert_expr(
+                        &mut ctx.id_provider,
+                        MetaExpr::Variable(name),
+                        span_since(tokens, start, *pos),
+                    )
+                };
+                let id = ctx.ast.insert_expr(
+                    &mut ctx.id_provider,
+                    MetaExpr::Unquote(inner),
+                    span_since(tokens, start, *pos),
+                );
+                Ok(id)
+            }
 
-                    let id = ctx
-                        .ast
-                        .insert_expr(&mut ctx.id_provider, MetaExpr::Call { callee: name, args });
-                    Ok(id)
-                } else if check(tokens, *pos, TokenType::LeftBrace) {
-                    consume(tokens, pos, TokenType::LeftBrace)?;
+            TokenType::Identifier => {
+                let name = consume_next(tokens, pos).expect_str();
 
-                    let fields = parse_separated(
-                        tokens,
-                        pos,
-                        ctx,
-                        TokenType::Comma,
-                        TokenType::RightBrace,
-                        |tokens, pos, ctx| {
-                            let field_name =
-                                consume(tokens, pos, TokenType::Identifier)?.expect_str();
-                            consume(tokens, pos, TokenType::Colon)?;
-                            let expr_id = parse_expr(tokens, pos, ctx)?;
-                            Ok((field_name, expr_id))
-                        },
-                    )?;
+                if check(tokens, *pos, TokenType::ColonColon) {
+                    consume(tokens, pos, TokenType::ColonColon)?;
+                    let variant = consume(tokens, pos, TokenType::Identifier)?.expect_str();
+                    let payload = if check(tokens, *pos, TokenType::LeftParen) {
+                        consume(tokens, pos, TokenType::LeftParen)?;
+                        let args = parse_separated(
+                            tokens,
+                            pos,
+                            ctx,
+                            TokenType::Comma,
+                            TokenType::RightParen,
+                            parse_expr,
+                        )?;
+                        consume(tokens, pos, TokenType::RightParen)?;
+                        Payload::Tuple(args)
+                    } else if ctx.struct_literals && check(tokens, *pos, TokenType::LeftBrace) {
+                        Payload::Struct(parse_field_inits(tokens, pos, ctx)?)
+                    } else {
+                        Payload::Unit
+                    };
 
-                    consume(tokens, pos, TokenType::RightBrace)?;
+                    let variant = MetaExpr::Variant {
+                        enum_name: name,
+                        variant,
+                        payload,
+                    };
+                    let id = ctx.ast.insert_expr(
+                        &mut ctx.id_provider,
+                        variant,
+                        span_since(tokens, start, *pos),
+                    );
+                    Ok(id)
+                } else if ctx.struct_literals && check(tokens, *pos, TokenType::LeftBrace) {
+                    let fields = parse_field_inits(tokens, pos, ctx)?;
 
                     let struct_literal = MetaExpr::StructLiteral {
                         type_name: name,
                         fields,
                     };
-                    let id = ctx.ast.insert_expr(&mut ctx.id_provider, struct_literal);
+                    let id = ctx.ast.insert_expr(
+                        &mut ctx.id_provider,
+                        struct_literal,
+                        span_since(tokens, start, *pos),
+                    );
                     Ok(id)
                 } else {
-                    let id = ctx
-                        .ast
-                        .insert_expr(&mut ctx.id_provider, MetaExpr::Variable(name));
+                    let id = ctx.ast.insert_expr(
+                        &mut ctx.id_provider,
+                        MetaExpr::Variable(name),
+                        span_since(tokens, start, *pos),
+                    );
                     Ok(id)
                 }
             }
 
+            TokenType::Func => {
+                consume(tokens, pos, TokenType::Func)?;
+                let params = parse_params(tokens, pos, ctx)?;
+                let ret = parse_annotation(tokens, pos, ctx, TokenType::Arrow)?;
+                consume(tokens, pos, TokenType::LeftBrace)?;
+                let body = parse_block(tokens, pos, ctx)?;
+                consume(tokens, pos, TokenType::RightBrace)?;
+
+                let id = ctx.ast.insert_expr(
+                    &mut ctx.id_provider,
+                    MetaExpr::Lambda { params, ret, body },
+                    span_since(tokens, start, *pos),
+                );
+                Ok(id)
+            }
+
             TokenType::LeftBracket => {
                 consume(tokens, pos, TokenType::LeftBracket)?;
 
@@ -238,103 +454,700 @@ fn parse_factor<'a>(
 
                 consume(tokens, pos, TokenType::RightBracket)?;
 
-                let id = ctx
-                    .ast
-                    .insert_expr(&mut ctx.id_provider, MetaExpr::List(elems));
+                let id = ctx.ast.insert_expr(
+                    &mut ctx.id_provider,
+                    MetaExpr::List(elems),
+                    span_since(tokens, start, *pos),
+                );
                 Ok(id)
             }
 
-            _ => panic!("expected literal or '('"),
+            _ => Err(expected_expression(tokens, *pos)),
         },
-        None => panic!("Unexpected EOF"),
+        None => Err(expected_expression(tokens, *pos)),
     }
 }
 
-fn parse_term<'a>(
-    tokens: &'a [Token],
+fn parse_expr(
+    tokens: &[Token],
     pos: &mut usize,
     ctx: &mut ParseCtx,
 ) -> Result<AstId, ParseError> {
-    let mut left = parse_factor(tokens, pos, ctx)?;
+    parse_assignment(tokens, pos, ctx)
+}
 
-    loop {
-        match tokens.get(*pos) {
-            Some(tok) => match tok.token_type {
-                TokenType::Star => {
-                    *pos += 1;
-                    let right = parse_factor(tokens, pos, ctx)?;
-                    let node = MetaExpr::Mult(left, right);
-                    left = ctx.ast.insert_expr(&mut ctx.id_provider, node);
-                }
-                TokenType::Slash => {
-                    *pos += 1;
-                    let right = parse_factor(tokens, pos, ctx)?;
-                    let node = MetaExpr::Div(left, right);
-                    left = ctx.ast.insert_expr(&mut ctx.id_provider, node);
-                }
-                _ => return Ok(left),
-            },
-            _ => return Ok(left),
+fn parse_assignment(
+    tokens: &[Token],
+    pos: &mut usize,
+    ctx: &mut ParseCtx,
+) -> Result<AstId, ParseError> {
+    let start = *pos;
+    let target = parse_logic_or(tokens, pos, ctx)?;
+
+    let op = match peek(tokens, *pos) {
+        Some(TokenType::Equal) => AssignOp::Assign,
+        Some(TokenType::PlusEqual) => AssignOp::Add,
+        Some(TokenType::MinusEqual) => AssignOp::Sub,
+        Some(TokenType::StarEqual) => AssignOp::Mult,
+        Some(TokenType::SlashEqual) => AssignOp::Div,
+        _ => return Ok(target),
+    };
+
+    match ctx.ast.get_expr(target) {
+        Some(MetaExpr::Variable(_) | MetaExpr::FieldAccess { .. } | MetaExpr::Index { .. }) => {}
+        _ => {
+            let span = ctx.ast.get_span(target).unwrap_or_default();
+            return Err(ParseError::InvalidAssignmentTarget(span));
         }
     }
+    *pos += 1;
+
+    // right associative: `a = b = c` assigns `c` to both
+    let value = parse_assignment(tokens, pos, ctx)?;
+    let id = ctx.ast.insert_expr(
+        &mut ctx.id_provider,
+        MetaExpr::Assign { target, op, value },
+        span_since(tokens, start, *pos),
+    );
+    Ok(id)
 }
 
-fn parse_expr<'a>(
-    tokens: &'a [Token],
+type BinaryCtor = fn(AstId, AstId) -> MetaExpr;
+type ParseFn = fn(&[Token], &mut usize, &mut ParseCtx) -> Result<AstId, ParseError>;
+
+// Parses a left-associative chain of `operand (op operand)*`.
+fn parse_binary_level(
+    tokens: &[Token],
     pos: &mut usize,
     ctx: &mut ParseCtx,
+    ops: &[(TokenType, BinaryCtor)],
+    operand: ParseFn,
 ) -> Result<AstId, ParseError> {
-    let mut left = parse_term(tokens, pos, ctx)?;
+    let start = *pos;
+    let mut left = operand(tokens, pos, ctx)?;
 
-    loop {
-        match tokens.get(*pos) {
-            Some(tok) => match tok.token_type {
-                TokenType::Plus => {
-                    *pos += 1;
-                    let right = parse_term(tokens, pos, ctx)?;
-                    let node = MetaExpr::Add(left, right);
-                    left = ctx.ast.insert_expr(&mut ctx.id_provider, node);
-                }
+    while let Some(tok) = tokens.get(*pos) {
+        let Some((_, ctor)) = ops.iter().find(|(op, _)| *op == tok.token_type) else {
+            break;
+        };
+        *pos += 1;
+        let right = operand(tokens, pos, ctx)?;
+        left = ctx.ast.insert_expr(
+            &mut ctx.id_provider,
+            ctor(left, right),
+            span_since(tokens, start, *pos),
+        );
+    }
 
-                TokenType::Minus => {
-                    *pos += 1;
-                    let right = parse_term(tokens, pos, ctx)?;
-                    let node = MetaExpr::Sub(left, right);
-                    left = ctx.ast.insert_expr(&mut ctx.id_provider, node);
-                }
+    Ok(left)
+}
 
-                TokenType::EqualEqual => {
-                    *pos += 1;
-                    let right = parse_term(tokens, pos, ctx)?;
-                    let node = MetaExpr::Equals(left, right);
-                    left = ctx.ast.insert_expr(&mut ctx.id_provider, node);
-                }
+fn parse_logic_or(
+    tokens: &[Token],
+    pos: &mut usize,
+    ctx: &mut ParseCtx,
+) -> Result<AstId, ParseError> {
+    parse_binary_level(
+        tokens,
+        pos,
+        ctx,
+        &[(TokenType::Or, MetaExpr::Or)],
+        parse_logic_and,
+    )
+}
+
+fn parse_logic_and(
+    tokens: &[Token],
+    pos: &mut usize,
+    ctx: &mut ParseCtx,
+) -> Result<AstId, ParseError> {
+    parse_binary_level(
+        tokens,
+        pos,
+        ctx,
+        &[(TokenType::And, MetaExpr::And)],
+        parse_equality,
+    )
+}
+
+fn parse_equality(
+    tokens: &[Token],
+    pos: &mut usize,
+    ctx: &mut ParseCtx,
+) -> Result<AstId, ParseError> {
+    parse_binary_level(
+        tokens,
+        pos,
+        ctx,
+        &[
+            (TokenType::EqualEqual, MetaExpr::Equals),
+            (TokenType::BangEqual, MetaExpr::NotEquals),
+        ],
+        parse_comparison,
+    )
+}
+
+fn parse_comparison(
+    tokens: &[Token],
+    pos: &mut usize,
+    ctx: &mut ParseCtx,
+) -> Result<AstId, ParseError> {
+    parse_binary_level(
+        tokens,
+        pos,
+        ctx,
+        &[
+            (TokenType::Greater, MetaExpr::Greater),
+            (TokenType::GreaterEqual, MetaExpr::GreaterEqual),
+            (TokenType::Less, MetaExpr::Less),
+            (TokenType::LessEqual, MetaExpr::LessEqual),
+        ],
+        parse_term,
+    )
+}
+
+fn parse_term(tokens: &[Token], pos: &mut usize, ctx: &mut ParseCtx) -> Result<AstId, ParseError> {
+    parse_binary_level(
+        tokens,
+        pos,
+        ctx,
+        &[
+            (TokenType::Plus, MetaExpr::Add),
+            (TokenType::Minus, MetaExpr::Sub),
+        ],
+        parse_factor,
+    )
+}
+
+fn parse_factor(
+    tokens: &[Token],
+    pos: &mut usize,
+    ctx: &mut ParseCtx,
+) -> Result<AstId, ParseError> {
+    parse_binary_level(
+        tokens,
+        pos,
+        ctx,
+        &[
+            (TokenType::Star, MetaExpr::Mult),
+            (TokenType::Slash, MetaExpr::Div),
+        ],
+        parse_unary,
+    )
+}
+
+// `{ name: expr, ... }` after a struct or variant name
+fn parse_field_inits(
+    tokens: &[Token],
+    pos: &mut usize,
+    ctx: &mut ParseCtx,
+) -> Result<Vec<(String, AstId)>, ParseError> {
+    consume(tokens, pos, TokenType::LeftBrace)?;
+    let fields = parse_separated(
+        tokens,
+        pos,
+        ctx,
+        TokenType::Comma,
+        TokenType::RightBrace,
+        |tokens, pos, ctx| {
+            let field_name = consume(tokens, pos, TokenType::Identifier)?.expect_str();
+            consume(tokens, pos, TokenType::Colon)?;
+            let expr_id = parse_expr(tokens, pos, ctx)?;
+            Ok((field_name, expr_id))
+        },
+    )?;
+    consume(tokens, pos, TokenType::RightBrace)?;
+    Ok(fields)
+}
 
-                _ => return Ok(left),
+// `Name`, `Name(types)` or `Name { field: type, ... }` in an `enum`
+fn parse_variant_decl(
+    tokens: &[Token],
+    pos: &mut usize,
+    ctx: &mut ParseCtx,
+) -> Result<VariantDecl, ParseError> {
+    let name = consume(tokens, pos, TokenType::Identifier)?.expect_str();
+    let payload = if check(tokens, *pos, TokenType::LeftParen) {
+        consume(tokens, pos, TokenType::LeftParen)?;
+        let types = parse_separated(
+            tokens,
+            pos,
+            ctx,
+            TokenType::Comma,
+            TokenType::RightParen,
+            parse_type,
+        )?;
+        consume(tokens, pos, TokenType::RightParen)?;
+        Payload::Tuple(types)
+    } else if check(tokens, *pos, TokenType::LeftBrace) {
+        consume(tokens, pos, TokenType::LeftBrace)?;
+        let fields = parse_separated(
+            tokens,
+            pos,
+            ctx,
+            TokenType::Comma,
+            TokenType::RightBrace,
+            |tokens, pos, ctx| {
+                let field_name = consume(tokens, pos, TokenType::Identifier)?.expect_str();
+                consume(tokens, pos, TokenType::Colon)?;
+                Ok((field_name, parse_type(tokens, pos, ctx)?))
             },
-            _ => return Ok(left),
+        )?;
+        consume(tokens, pos, TokenType::RightBrace)?;
+        Payload::Struct(fields)
+    } else {
+        Payload::Unit
+    };
+    Ok(VariantDecl { name, payload })
+}
+
+// `pattern (if guard)? => stmt`, optionally followed by a comma
+fn parse_match_arm(
+    tokens: &[Token],
+    pos: &mut usize,
+    ctx: &mut ParseCtx,
+) -> Result<MatchArm, ParseError> {
+    let pattern = parse_pattern(tokens, pos, ctx)?;
+    let guard = if check(tokens, *pos, TokenType::If) {
+        consume(tokens, pos, TokenType::If)?;
+        Some(parse_expr(tokens, pos, ctx)?)
+    } else {
+        None
+    };
+    consume(tokens, pos, TokenType::FatArrow)?;
+    let body = parse_stmt(tokens, pos, ctx)?;
+    if check(tokens, *pos, TokenType::Comma) {
+        consume(tokens, pos, TokenType::Comma)?;
+    }
+    Ok(MatchArm {
+        pattern,
+        guard,
+        body,
+    })
+}
+
+fn parse_pattern(
+    tokens: &[Token],
+    pos: &mut usize,
+    ctx: &mut ParseCtx,
+) -> Result<Pattern, ParseError> {
+    let start = *pos;
+    let kind = match peek(tokens, *pos) {
+        Some(TokenType::Number) => PatternKind::Int(consume_next(tokens, pos).expect_int()),
+        Some(TokenType::Minus) => {
+            consume(tokens, pos, TokenType::Minus)?;
+            PatternKind::Int(-consume(tokens, pos, TokenType::Number)?.expect_int())
+        }
+        Some(TokenType::String) => PatternKind::String(consume_next(tokens, pos).expect_str()),
+        Some(TokenType::True) => {
+            consume_next(tokens, pos);
+            PatternKind::Bool(true)
+        }
+        Some(TokenType::False) => {
+            consume_next(tokens, pos);
+            PatternKind::Bool(false)
+        }
+        Some(TokenType::None) => {
+            consume_next(tokens, pos);
+            PatternKind::Variant {
+                enum_name: "Option".to_string(),
+                variant: "None".to_string(),
+                payload: Payload::Unit,
+            }
+        }
+        Some(TokenType::LeftBracket) => {
+            consume(tokens, pos, TokenType::LeftBracket)?;
+            let mut items = Vec::new();
+            let mut rest = None;
+            while !check(tokens, *pos, TokenType::RightBracket) {
+                if check(tokens, *pos, TokenType::Dot) {
+                    rest = Some(Box::new(parse_rest_pattern(tokens, pos)?));
+                    break;
+                }
+                items.push(parse_pattern(tokens, pos, ctx)?);
+                if !check(tokens, *pos, TokenType::Comma) {
+                    break;
+                }
+                consume(tokens, pos, TokenType::Comma)?;
+            }
+            consume(tokens, pos, TokenType::RightBracket)?;
+            PatternKind::List { items, rest }
+        }
+        Some(TokenType::Identifier) => {
+            let name = consume_next(tokens, pos).expect_str();
+            if check(tokens, *pos, TokenType::ColonColon) {
+                consume(tokens, pos, TokenType::ColonColon)?;
+                let variant = consume(tokens, pos, TokenType::Identifier)?.expect_str();
+                let payload = if check(tokens, *pos, TokenType::LeftParen) {
+                    consume(tokens, pos, TokenType::LeftParen)?;
+                    let items = parse_separated(
+                        tokens,
+                        pos,
+                        ctx,
+                        TokenType::Comma,
+                        TokenType::RightParen,
+                        parse_pattern,
+                    )?;
+                    consume(tokens, pos, TokenType::RightParen)?;
+                    Payload::Tuple(items)
+                } else if check(tokens, *pos, TokenType::LeftBrace) {
+                    Payload::Struct(parse_field_patterns(tokens, pos, ctx)?)
+                } else {
+                    Payload::Unit
+                };
+                PatternKind::Variant {
+                    enum_name: name,
+                    variant,
+                    payload,
+                }
+            } else if check(tokens, *pos, TokenType::LeftBrace) {
+                let fields = parse_field_patterns(tokens, pos, ctx)?;
+                PatternKind::Struct { name, fields }
+            } else if name == "_" {
+                PatternKind::Wildcard
+            } else {
+                PatternKind::Binding(name)
+            }
+        }
+        _ => return Err(expected_pattern(tokens, *pos)),
+    };
+    Ok(Pattern {
+        kind,
+        span: span_since(tokens, start, *pos),
+    })
+}
+
+// `..` or `..name` at the end of a list pattern
+fn parse_rest_pattern(tokens: &[Token], pos: &mut usize) -> Result<Pattern, ParseError> {
+    let start = *pos;
+    consume(tokens, pos, TokenType::Dot)?;
+    consume(tokens, pos, TokenType::Dot)?;
+    let kind = if check(tokens, *pos, TokenType::Identifier) {
+        PatternKind::Binding(consume_next(tokens, pos).expect_str())
+    } else {
+        PatternKind::Wildcard
+    };
+    Ok(Pattern {
+        kind,
+        span: span_since(tokens, start, *pos),
+    })
+}
+
+// `{ field: pattern, field, ... }`; a bare field name binds the field
+fn parse_field_patterns(
+    tokens: &[Token],
+    pos: &mut usize,
+    ctx: &mut ParseCtx,
+) -> Result<Vec<(String, Pattern)>, ParseError> {
+    consume(tokens, pos, TokenType::LeftBrace)?;
+    let fields = parse_separated(
+        tokens,
+        pos,
+        ctx,
+        TokenType::Comma,
+        TokenType::RightBrace,
+        |tokens, pos, ctx| {
+            let field = consume(tokens, pos, TokenType::Identifier)?;
+            let field_name = field.expect_str();
+            if check(tokens, *pos, TokenType::Colon) {
+                consume(tokens, pos, TokenType::Colon)?;
+                Ok((field_name, parse_pattern(tokens, pos, ctx)?))
+            } else {
+                let binding = Pattern {
+                    kind: PatternKind::Binding(field_name.clone()),
+                    span: field.span,
+                };
+                Ok((field_name, binding))
+            }
+        },
+    )?;
+    consume(tokens, pos, TokenType::RightBrace)?;
+    Ok(fields)
+}
+
+// `fn name<T: Bound>(params) -> ret { body }`
+fn parse_fn_decl(
+    tokens: &[Token],
+    pos: &mut usize,
+    ctx: &mut ParseCtx,
+) -> Result<AstId, ParseError> {
+    let start = *pos;
+    consume(tokens, pos, TokenType::Func)?;
+    let name = consume(tokens, pos, TokenType::Identifier)?.expect_str();
+    let type_params = if check(tokens, *pos, TokenType::Less) {
+        consume(tokens, pos, TokenType::Less)?;
+        let type_params = parse_separated(
+            tokens,
+            pos,
+            ctx,
+            TokenType::Comma,
+            TokenType::Greater,
+            parse_type_param,
+        )?;
+        consume(tokens, pos, TokenType::Greater)?;
+        type_params
+    } else {
+        vec![]
+    };
+    let params = parse_params(tokens, pos, ctx)?;
+    let ret = parse_annotation(tokens, pos, ctx, TokenType::Arrow)?;
+
+    consume(tokens, pos, TokenType::LeftBrace)?;
+    let body = parse_block(tokens, pos, ctx)?;
+    consume(tokens, pos, TokenType::RightBrace)?;
+
+    let fn_decl = MetaStmt::FnDecl {
+        name,
+        type_params,
+        params,
+        ret,
+        body,
+    };
+    let id = ctx.ast.insert_stmt(
+        &mut ctx.id_provider,
+        fn_decl,
+        span_since(tokens, start, *pos),
+    );
+    Ok(id)
+}
+
+// `T` or `T: Show + Eq`
+fn parse_type_param(
+    tokens: &[Token],
+    pos: &mut usize,
+    _ctx: &mut ParseCtx,
+) -> Result<TypeParam, ParseError> {
+    let name = consume(tokens, pos, TokenType::Identifier)?.expect_str();
+    let mut bounds = Vec::new();
+    if check(tokens, *pos, TokenType::Colon) {
+        consume(tokens, pos, TokenType::Colon)?;
+        bounds.push(consume(tokens, pos, TokenType::Identifier)?.expect_str());
+        while check(tokens, *pos, TokenType::Plus) {
+            consume(tokens, pos, TokenType::Plus)?;
+            bounds.push(consume(tokens, pos, TokenType::Identifier)?.expect_str());
         }
     }
+    Ok(TypeParam { name, bounds })
 }
 
-fn parse_expr_stmt<'a>(
-    tokens: &'a [Token],
+// `(a, b)` parameter list of a function declaration or expression.
+fn parse_params(
+    tokens: &[Token],
+    pos: &mut usize,
+    ctx: &mut ParseCtx,
+) -> Result<Vec<Param>, ParseError> {
+    consume(tokens, pos, TokenType::LeftParen)?;
+    let params = parse_separated(
+        tokens,
+        pos,
+        ctx,
+        TokenType::Comma,
+        TokenType::RightParen,
+        |tokens, pos, ctx| {
+            let name = consume(tokens, pos, TokenType::Identifier)?.expect_str();
+            let ty = parse_annotation(tokens, pos, ctx, TokenType::Colon)?;
+            Ok(Param { name, ty })
+        },
+    )?;
+    consume(tokens, pos, TokenType::RightParen)?;
+    Ok(params)
+}
+
+// An optional type annotation: `introducer` followed by a type.
+fn parse_annotation(
+    tokens: &[Token],
+    pos: &mut usize,
+    ctx: &mut ParseCtx,
+    introducer: TokenType,
+) -> Result<Option<TypeExpr>, ParseError> {
+    if !check(tokens, *pos, introducer) {
+        return Ok(None);
+    }
+    consume(tokens, pos, introducer)?;
+    Ok(Some(parse_type(tokens, pos, ctx)?))
+}
+
+// `int`, `List<int>` or `fn(int, int) -> bool`
+fn parse_type(
+    tokens: &[Token],
+    pos: &mut usize,
+    ctx: &mut ParseCtx,
+) -> Result<TypeExpr, ParseError> {
+    let start = *pos;
+    if check(tokens, *pos, TokenType::Func) {
+        consume(tokens, pos, TokenType::Func)?;
+        consume(tokens, pos, TokenType::LeftParen)?;
+        let params = parse_separated(
+            tokens,
+            pos,
+            ctx,
+            TokenType::Comma,
+            TokenType::RightParen,
+            parse_type,
+        )?;
+        consume(tokens, pos, TokenType::RightParen)?;
+        consume(tokens, pos, TokenType::Arrow)?;
+        let ret = parse_type(tokens, pos, ctx)?;
+        return Ok(TypeExpr::Func {
+            params,
+            ret: Box::new(ret),
+            span: span_since(tokens, start, *pos),
+        });
+    }
+
+    let name = consume(tokens, pos, TokenType::Identifier)?.expect_str();
+    let args = if check(tokens, *pos, TokenType::Less) {
+        consume(tokens, pos, TokenType::Less)?;
+        let args = parse_separated(
+            tokens,
+            pos,
+            ctx,
+            TokenType::Comma,
+            TokenType::Greater,
+            parse_type,
+        )?;
+        consume(tokens, pos, TokenType::Greater)?;
+        args
+    } else {
+        vec![]
+    };
+    Ok(TypeExpr::Named {
+        name,
+        args,
+        span: span_since(tokens, start, *pos),
+    })
+}
+
+// `(args)`, `.field`, `.method(args)`, `[index]` and `[start:end]` suffixes
+// on a primary expression.
+fn parse_postfix(
+    tokens: &[Token],
+    pos: &mut usize,
+    ctx: &mut ParseCtx,
+) -> Result<AstId, ParseError> {
+    let start = *pos;
+    let mut expr = parse_primary(tokens, pos, ctx)?;
+
+    loop {
+        let node = match peek(tokens, *pos) {
+            Some(TokenType::LeftParen) => {
+                consume(tokens, pos, TokenType::LeftParen)?;
+                let args = parse_separated(
+                    tokens,
+                    pos,
+                    ctx,
+                    TokenType::Comma,
+                    TokenType::RightParen,
+                    parse_expr,
+                )?;
+                consume(tokens, pos, TokenType::RightParen)?;
+                MetaExpr::Call { callee: expr, args }
+            }
+            Some(TokenType::Dot) => {
+                consume(tokens, pos, TokenType::Dot)?;
+                let field = consume(tokens, pos, TokenType::Identifier)?.expect_str();
+                if check(tokens, *pos, TokenType::LeftParen) {
+                    consume(tokens, pos, TokenType::LeftParen)?;
+                    let args = parse_separated(
+                        tokens,
+                        pos,
+                        ctx,
+                        TokenType::Comma,
+                        TokenType::RightParen,
+                        parse_expr,
+                    )?;
+                    consume(tokens, pos, TokenType::RightParen)?;
+                    MetaExpr::MethodCall {
+                        receiver: expr,
+                        method: field,
+                        args,
+                    }
+                } else {
+                    MetaExpr::FieldAccess {
+                        target: expr,
+                        field,
+                    }
+                }
+            }
+            Some(TokenType::LeftBracket) => {
+                consume(tokens, pos, TokenType::LeftBracket)?;
+                let index = if check(tokens, *pos, TokenType::Colon) {
+                    None
+                } else {
+                    Some(parse_expr(tokens, pos, ctx)?)
+                };
+
+                let node = if check(tokens, *pos, TokenType::Colon) {
+                    consume(tokens, pos, TokenType::Colon)?;
+                    let end = if check(tokens, *pos, TokenType::RightBracket) {
+                        None
+                    } else {
+                        Some(parse_expr(tokens, pos, ctx)?)
+                    };
+                    MetaExpr::Slice {
+                        target: expr,
+                        start: index,
+                        end,
+                    }
+                } else {
+                    match index {
+                        Some(index) => MetaExpr::Index {
+                            target: expr,
+                            index,
+                        },
+                        None => return Err(expected_expression(tokens, *pos)),
+                    }
+                };
+                consume(tokens, pos, TokenType::RightBracket)?;
+                node
+            }
+            _ => return Ok(expr),
+        };
+        expr = ctx
+            .ast
+            .insert_expr(&mut ctx.id_provider, node, span_since(tokens, start, *pos));
+    }
+}
+
+fn parse_unary(tokens: &[Token], pos: &mut usize, ctx: &mut ParseCtx) -> Result<AstId, ParseError> {
+    let start = *pos;
+    let ctor: fn(AstId) -> MetaExpr = match peek(tokens, *pos) {
+        Some(TokenType::Bang) => MetaExpr::Not,
+        Some(TokenType::Minus) => MetaExpr::Negate,
+        _ => return parse_postfix(tokens, pos, ctx),
+    };
+    *pos += 1;
+    let operand = parse_unary(tokens, pos, ctx)?;
+    let id = ctx.ast.insert_expr(
+        &mut ctx.id_provider,
+        ctor(operand),
+        span_since(tokens, start, *pos),
+    );
+    Ok(id)
+}
+
+fn parse_expr_stmt(
+    tokens: &[Token],
     pos: &mut usize,
     ctx: &mut ParseCtx,
 ) -> Result<AstId, ParseError> {
+    let start = *pos;
     let expr = parse_expr(tokens, pos, ctx)?;
     consume(tokens, pos, TokenType::Semicolon)?;
-    let id = ctx
-        .ast
-        .insert_stmt(&mut ctx.id_provider, MetaStmt::ExprStmt(expr));
+    let id = ctx.ast.insert_stmt(
+        &mut ctx.id_provider,
+        MetaStmt::ExprStmt(expr),
+        span_since(tokens, start, *pos),
+    );
     Ok(id)
 }
 
-fn parse_stmt<'a>(
-    tokens: &'a [Token],
+fn parse_stmt(
+    tokens: &[Token],
     pos: &mut usize,
     ctx: &mut ParseCtx,
 ) -> Result<AstId, ParseError> {
+    let start = *pos;
     match tokens.get(*pos) {
         Some(tok) => match tok.token_type {
             TokenType::Print => {
@@ -343,9 +1156,11 @@ fn parse_stmt<'a>(
                 let expr = parse_expr(tokens, pos, ctx)?;
                 consume(tokens, pos, TokenType::RightParen)?;
                 consume(tokens, pos, TokenType::Semicolon)?;
-                let id = ctx
-                    .ast
-                    .insert_stmt(&mut ctx.id_provider, MetaStmt::Print(expr));
+                let id = ctx.ast.insert_stmt(
+                    &mut ctx.id_provider,
+                    MetaStmt::Print(expr),
+                    span_since(tokens, start, *pos),
+                );
                 Ok(id)
             }
 
@@ -364,9 +1179,9 @@ fn parse_stmt<'a>(
                         Some(parse_stmt(tokens, pos, ctx)?)
                     } else {
                         consume(tokens, pos, TokenType::LeftBrace)?;
-                        let stmt = parse_stmt(tokens, pos, ctx)?;
+                        let block = parse_block(tokens, pos, ctx)?;
                         consume(tokens, pos, TokenType::RightBrace)?;
-                        Some(stmt)
+                        Some(block)
                     }
                 } else {
                     None
@@ -378,7 +1193,11 @@ fn parse_stmt<'a>(
                     else_branch: else_branch,
                 };
 
-                let id = ctx.ast.insert_stmt(&mut ctx.id_provider, if_stmt);
+                let id = ctx.ast.insert_stmt(
+                    &mut ctx.id_provider,
+                    if_stmt,
+                    span_since(tokens, start, *pos),
+                );
                 Ok(id)
             }
 
@@ -398,51 +1217,148 @@ fn parse_stmt<'a>(
                     body: inner,
                 };
 
-                let id = ctx.ast.insert_stmt(&mut ctx.id_provider, for_stmt);
+                let id = ctx.ast.insert_stmt(
+                    &mut ctx.id_provider,
+                    for_stmt,
+                    span_since(tokens, start, *pos),
+                );
+                Ok(id)
+            }
+
+            TokenType::While => {
+                consume(tokens, pos, TokenType::While)?;
+                consume(tokens, pos, TokenType::LeftParen)?;
+                let cond = parse_expr(tokens, pos, ctx)?;
+                consume(tokens, pos, TokenType::RightParen)?;
+                consume(tokens, pos, TokenType::LeftBrace)?;
+                let body = parse_block(tokens, pos, ctx)?;
+                consume(tokens, pos, TokenType::RightBrace)?;
+
+                let id = ctx.ast.insert_stmt(
+                    &mut ctx.id_provider,
+                    MetaStmt::While { cond, body },
+                    span_since(tokens, start, *pos),
+                );
+                Ok(id)
+            }
+
+            TokenType::Match => {
+                consume(tokens, pos, TokenType::Match)?;
+                let scrutinee = with_struct_literals(tokens, pos, ctx, false, parse_expr)?;
+                consume(tokens, pos, TokenType::LeftBrace)?;
+                let mut arms = Vec::new();
+                while !check(tokens, *pos, TokenType::RightBrace)
+                    && !check(tokens, *pos, TokenType::EOF)
+                    && *pos < tokens.len()
+                {
+                    arms.push(parse_match_arm(tokens, pos, ctx)?);
+                }
+                consume(tokens, pos, TokenType::RightBrace)?;
+
+                let id = ctx.ast.insert_stmt(
+                    &mut ctx.id_provider,
+                    MetaStmt::Match { scrutinee, arms },
+                    span_since(tokens, start, *pos),
+                );
+                Ok(id)
+            }
+
+            TokenType::Break => {
+                consume(tokens, pos, TokenType::Break)?;
+                consume(tokens, pos, TokenType::Semicolon)?;
+                let id = ctx.ast.insert_stmt(
+                    &mut ctx.id_provider,
+                    MetaStmt::Break,
+                    span_since(tokens, start, *pos),
+                );
+                Ok(id)
+            }
+
+            TokenType::Continue => {
+                consume(tokens, pos, TokenType::Continue)?;
+                consume(tokens, pos, TokenType::Semicolon)?;
+                let id = ctx.ast.insert_stmt(
+                    &mut ctx.id_provider,
+                    MetaStmt::Continue,
+                    span_since(tokens, start, *pos),
+                );
                 Ok(id)
             }
 
             TokenType::Var => {
                 consume(tokens, pos, TokenType::Var)?;
-                let ident = consume(tokens, pos, TokenType::Identifier)?;
+                let name = consume(tokens, pos, TokenType::Identifier)?.expect_str();
+                let ty = parse_annotation(tokens, pos, ctx, TokenType::Colon)?;
                 consume(tokens, pos, TokenType::Equal)?;
                 let expr = parse_expr(tokens, pos, ctx)?;
                 consume(tokens, pos, TokenType::Semicolon)?;
-                let var_decl = MetaStmt::VarDecl {
-                    name: ident.expect_str(),
-                    expr,
-                };
+                let var_decl = MetaStmt::VarDecl { name, ty, expr };
 
-                let id = ctx.ast.insert_stmt(&mut ctx.id_provider, var_decl);
+                let id = ctx.ast.insert_stmt(
+                    &mut ctx.id_provider,
+                    var_decl,
+                    span_since(tokens, start, *pos),
+                );
                 Ok(id)
             }
 
-            TokenType::Func => {
-                consume(tokens, pos, TokenType::Func)?;
+            // `fn (` starts an anonymous function expression
+            TokenType::Func if !check(tokens, *pos + 1, TokenType::LeftParen) => {
+                parse_fn_decl(tokens, pos, ctx)
+            }
+
+            TokenType::Trait => {
+                consume(tokens, pos, TokenType::Trait)?;
                 let name = consume(tokens, pos, TokenType::Identifier)?.expect_str();
+                consume(tokens, pos, TokenType::LeftBrace)?;
+                let mut methods = Vec::new();
+                while check(tokens, *pos, TokenType::Func) {
+                    consume(tokens, pos, TokenType::Func)?;
+                    let name = consume(tokens, pos, TokenType::Identifier)?.expect_str();
+                    let params = parse_params(tokens, pos, ctx)?;
+                    let ret = parse_annotation(tokens, pos, ctx, TokenType::Arrow)?;
+                    consume(tokens, pos, TokenType::Semicolon)?;
+                    methods.push(MethodSig { name, params, ret });
+                }
+                consume(tokens, pos, TokenType::RightBrace)?;
 
-                consume(tokens, pos, TokenType::LeftParen)?;
-                let params = parse_separated(
-                    tokens,
-                    pos,
-                    ctx,
-                    TokenType::Comma,
-                    TokenType::RightParen,
-                    |tokens, pos, _ctx| {
-                        Ok(consume(tokens, pos, TokenType::Identifier)?.expect_str())
-                    },
-                )?;
-                consume(tokens, pos, TokenType::RightParen)?;
+                let id = ctx.ast.insert_stmt(
+                    &mut ctx.id_provider,
+                    MetaStmt::TraitDecl { name, methods },
+                    span_since(tokens, start, *pos),
+                );
+                Ok(id)
+            }
 
+            TokenType::Impl => {
+                consume(tokens, pos, TokenType::Impl)?;
+                let trait_name = consume(tokens, pos, TokenType::Identifier)?.expect_str();
+                consume(tokens, pos, TokenType::For)?;
+                let type_name = consume(tokens, pos, TokenType::Identifier)?.expect_str();
                 consume(tokens, pos, TokenType::LeftBrace)?;
-                let body = parse_block(tokens, pos, ctx)?;
+                let mut methods = Vec::new();
+                while !check(tokens, *pos, TokenType::RightBrace) {
+                    if !check(tokens, *pos, TokenType::Func) {
+                        // reports what was found instead of a method
+                        consume(tokens, pos, TokenType::Func)?;
+                    }
+                    methods.push(parse_fn_decl(tokens, pos, ctx)?);
+                }
                 consume(tokens, pos, TokenType::RightBrace)?;
 
-                let fn_decl = MetaStmt::FnDecl { name, params, body };
-                let id = ctx.ast.insert_stmt(&mut ctx.id_provider, fn_decl);
+                let impl_decl = MetaStmt::ImplDecl {
+                    trait_name,
+                    type_name,
+                    methods,
+                };
+                let id = ctx.ast.insert_stmt(
+                    &mut ctx.id_provider,
+                    impl_decl,
+                    span_since(tokens, start, *pos),
+                );
                 Ok(id)
             }
-            //parse_fn_decl(tokens, pos, ctx, BlueprintFuncType::Normal),
+
             TokenType::Struct => {
                 consume(tokens, pos, TokenType::Struct)?;
                 let name = consume(tokens, pos, TokenType::Identifier)?.expect_str();
@@ -453,20 +1369,66 @@ fn parse_stmt<'a>(
                     ctx,
                     TokenType::Semicolon,
                     TokenType::RightBrace,
-                    |tokens, pos, _ctx| {
+                    |tokens, pos, ctx| {
                         let field_name = consume(tokens, pos, TokenType::Identifier)?.expect_str();
                         consume(tokens, pos, TokenType::Colon)?;
-                        let type_name = consume(tokens, pos, TokenType::Identifier)?.expect_str();
-                        Ok(MetaFieldDecl {
-                            field_name,
-                            type_name,
-                        })
+                        let ty = parse_type(tokens, pos, ctx)?;
+                        Ok(MetaFieldDecl { field_name, ty })
                     },
                 )?;
 
                 consume(tokens, pos, TokenType::RightBrace)?;
                 let struct_decl = MetaStmt::StructDecl { name, fields };
-                let id = ctx.ast.insert_stmt(&mut ctx.id_provider, struct_decl);
+                let id = ctx.ast.insert_stmt(
+                    &mut ctx.id_provider,
+                    struct_decl,
+                    span_since(tokens, start, *pos),
+                );
+                Ok(id)
+            }
+
+            TokenType::Enum => {
+                consume(tokens, pos, TokenType::Enum)?;
+                let name = consume(tokens, pos, TokenType::Identifier)?.expect_str();
+                let type_params = if check(tokens, *pos, TokenType::Less) {
+                    consume(tokens, pos, TokenType::Less)?;
+                    let params = parse_separated(
+                        tokens,
+                        pos,
+                        ctx,
+                        TokenType::Comma,
+                        TokenType::Greater,
+                        |tokens, pos, _ctx| {
+                            Ok(consume(tokens, pos, TokenType::Identifier)?.expect_str())
+                        },
+                    )?;
+                    consume(tokens, pos, TokenType::Greater)?;
+                    params
+                } else {
+                    vec![]
+                };
+
+                consume(tokens, pos, TokenType::LeftBrace)?;
+                let variants = parse_separated(
+                    tokens,
+                    pos,
+                    ctx,
+                    TokenType::Comma,
+                    TokenType::RightBrace,
+                    parse_variant_decl,
+                )?;
+                consume(tokens, pos, TokenType::RightBrace)?;
+
+                let enum_decl = MetaStmt::EnumDecl {
+                    name,
+                    type_params,
+                    variants,
+                };
+                let id = ctx.ast.insert_stmt(
+                    &mut ctx.id_provider,
+                    enum_decl,
+                    span_since(tokens, start, *pos),
+                );
                 Ok(id)
             }
 
@@ -480,7 +1442,23 @@ fn parse_stmt<'a>(
                 consume(tokens, pos, TokenType::Semicolon)?;
 
                 let return_stmt = MetaStmt::Return(opt_expr);
-                let id = ctx.ast.insert_stmt(&mut ctx.id_provider, return_stmt);
+                let id = ctx.ast.insert_stmt(
+                    &mut ctx.id_provider,
+                    return_stmt,
+                    span_since(tokens, start, *pos),
+                );
+                Ok(id)
+            }
+
+            TokenType::Defer => {
+                consume(tokens, pos, TokenType::Defer)?;
+                let stmt = parse_stmt(tokens, pos, ctx)?;
+
+                let id = ctx.ast.insert_stmt(
+                    &mut ctx.id_provider,
+                    MetaStmt::Defer(stmt),
+                    span_since(tokens, start, *pos),
+                );
                 Ok(id)
             }
 
@@ -488,7 +1466,9 @@ fn parse_stmt<'a>(
                 consume(tokens, pos, TokenType::Gen)?;
                 let stmt = parse_stmt(tokens, pos, ctx)?;
                 let gen = MetaStmt::Gen(vec![stmt]);
-                let id = ctx.ast.insert_stmt(&mut ctx.id_provider, gen);
+                let id =
+                    ctx.ast
+                        .insert_stmt(&mut ctx.id_provider, gen, span_since(tokens, start, *pos));
                 Ok(id)
             }
 
@@ -506,7 +1486,11 @@ fn parse_stmt<'a>(
                 let mod_name = consume(tokens, pos, TokenType::Identifier)?.expect_str();
                 consume(tokens, pos, TokenType::Semicolon)?;
                 let import = MetaStmt::Import(mod_name);
-                let id = ctx.ast.insert_stmt(&mut ctx.id_provider, import);
+                let id = ctx.ast.insert_stmt(
+                    &mut ctx.id_provider,
+                    import,
+                    span_since(tokens, start, *pos),
+                );
                 Ok(id)
             }
 
@@ -521,32 +1505,124 @@ fn parse_meta_stmt(
     pos: &mut usize,
     ctx: &mut ParseCtx,
 ) -> Result<AstId, ParseError> {
+    let start = *pos;
     consume(tokens, pos, TokenType::Meta)?;
     let stmt = parse_stmt(tokens, pos, ctx)?;
     let meta_stmt = MetaStmt::MetaBlock(stmt);
-    let id = ctx.ast.insert_stmt(&mut ctx.id_provider, meta_stmt);
+    let id = ctx.ast.insert_stmt(
+        &mut ctx.id_provider,
+        meta_stmt,
+        span_since(tokens, start, *pos),
+    );
     Ok(id)
 }
 
 fn parse_block(tokens: &[Token], pos: &mut usize, ctx: &mut ParseCtx) -> Result<AstId, ParseError> {
+    let start = *pos;
     let mut stmts = Vec::new();
+    let saved = std::mem::replace(&mut ctx.struct_literals, true);
 
-    while *pos < tokens.len() && tokens[*pos].token_type != TokenType::RightBrace {
-        stmts.push(parse_stmt(tokens, pos, ctx)?);
+    while !check(tokens, *pos, TokenType::RightBrace)
+        && !check(tokens, *pos, TokenType::EOF)
+        && *pos < tokens.len()
+    {
+        stmts.push(parse_stmt_or_recover(tokens, pos, ctx));
     }
+    ctx.struct_literals = saved;
 
     let block_stmt = MetaStmt::Block(stmts);
-    let id = ctx.ast.insert_stmt(&mut ctx.id_provider, block_stmt);
+    let id = ctx.ast.insert_stmt(
+        &mut ctx.id_provider,
+        block_stmt,
+        span_since(tokens, start, *pos),
+    );
     Ok(id)
 }
 
-pub fn parse(tokens: &[Token], ctx: &mut ParseCtx) -> Result<(), ParseError> {
+/// Parses a statement; on failure the error is recorded, the parser skips
+/// ahead to the next statement boundary and an `Error` node takes its place.
+fn parse_stmt_or_recover(tokens: &[Token], pos: &mut usize, ctx: &mut ParseCtx) -> AstId {
+    let start = *pos;
+    match parse_stmt(tokens, pos, ctx) {
+        Ok(id) => id,
+        Err(e) => {
+            ctx.errors.push(e);
+            synchronize(tokens, start, pos);
+            if *pos == start {
+                *pos += 1;
+            }
+            ctx.ast.insert_stmt(
+                &mut ctx.id_provider,
+                MetaStmt::Error,
+                span_since(tokens, start, *pos),
+            )
+        }
+    }
+}
+
+// Skips to just past a `;`, or up to a `}` or statement keyword, ignoring
+// anything nested inside braces. The braces the failed statement opened from
+// `start` on are skipped to their closing `}` too.
+fn synchronize(tokens: &[Token], start: usize, pos: &mut usize) {
+    let mut depth = 0usize;
+    for tok in &tokens[start..*pos] {
+        match tok.token_type {
+            TokenType::LeftBrace => depth += 1,
+            TokenType::RightBrace => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    while let Some(tok) = tokens.get(*pos) {
+        match tok.token_type {
+            TokenType::EOF => return,
+            TokenType::Semicolon if depth == 0 => {
+                *pos += 1;
+                return;
+            }
+            TokenType::LeftBrace => depth += 1,
+            TokenType::RightBrace if depth == 0 => return,
+            TokenType::RightBrace => {
+                depth -= 1;
+                if depth == 0 {
+                    *pos += 1;
+                    if check(tokens, *pos, TokenType::Semicolon) {
+                        *pos += 1;
+                    }
+                    return;
+                }
+            }
+            TokenType::Func
+            | TokenType::Var
+            | TokenType::Struct
+            | TokenType::Trait
+            | TokenType::Impl
+            | TokenType::If
+            | TokenType::For
+            | TokenType::While
+            | TokenType::Match
+            | TokenType::Defer
+            | TokenType::Meta
+                if depth == 0 =>
+            {
+                return
+            }
+            _ => {}
+        }
+        *pos += 1;
+    }
+}
+
+pub fn parse(tokens: &[Token], ctx: &mut ParseCtx) -> Result<(), Vec<ParseError>> {
     let mut pos: usize = 0;
 
     while pos < tokens.len() && tokens[pos].token_type != TokenType::EOF {
-        let id = parse_stmt(tokens, &mut pos, ctx)?;
+        let id = parse_stmt_or_recover(tokens, &mut pos, ctx);
         ctx.ast.sem_root_stmts.push(id);
     }
 
-    Ok(())
+    if ctx.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ctx.errors.clone())
+    }
 }
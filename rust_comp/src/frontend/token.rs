@@ -1,3 +1,6 @@
+use super::span::Span;
+use std::fmt;
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TokenType {
     // Single-character tokens
@@ -15,16 +18,24 @@ pub enum TokenType {
     Colon,
     Slash,
     Star,
+    Dollar,
 
     // One or two character tokens
+    Arrow,
     Bang,
     BangEqual,
+    ColonColon,
     Equal,
     EqualEqual,
+    FatArrow,
     Greater,
     GreaterEqual,
     Less,
     LessEqual,
+    MinusEqual,
+    PlusEqual,
+    SlashEqual,
+    StarEqual,
 
     // Literals
     Identifier,
@@ -33,20 +44,29 @@ pub enum TokenType {
 
     // Keywords
     And,
+    Break,
+    Continue,
+    Defer,
     Else,
     Embed,
+    Enum,
     False,
     Func,
     For,
     Gen,
     If,
+    Impl,
     Import,
     In,
+    Match,
     Meta,
+    None,
     Or,
     Print,
+    Quote,
     Return,
     Struct,
+    Trait,
     True,
     Typeof,
     Var,
@@ -56,6 +76,76 @@ pub enum TokenType {
     EOF,
 }
 
+impl fmt::Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            TokenType::LeftParen => "`(`",
+            TokenType::RightParen => "`)`",
+            TokenType::LeftBrace => "`{`",
+            TokenType::RightBrace => "`}`",
+            TokenType::LeftBracket => "`[`",
+            TokenType::RightBracket => "`]`",
+            TokenType::Comma => "`,`",
+            TokenType::Dot => "`.`",
+            TokenType::Minus => "`-`",
+            TokenType::Plus => "`+`",
+            TokenType::Semicolon => "`;`",
+            TokenType::Colon => "`:`",
+            TokenType::Slash => "`/`",
+            TokenType::Star => "`*`",
+            TokenType::Dollar => "`$`",
+            TokenType::Arrow => "`->`",
+            TokenType::Bang => "`!`",
+            TokenType::BangEqual => "`!=`",
+            TokenType::ColonColon => "`::`",
+            TokenType::Equal => "`=`",
+            TokenType::EqualEqual => "`==`",
+            TokenType::FatArrow => "`=>`",
+            TokenType::Greater => "`>`",
+            TokenType::GreaterEqual => "`>=`",
+            TokenType::Less => "`<`",
+            TokenType::LessEqual => "`<=`",
+            TokenType::MinusEqual => "`-=`",
+            TokenType::PlusEqual => "`+=`",
+            TokenType::SlashEqual => "`/=`",
+            TokenType::StarEqual => "`*=`",
+            TokenType::Identifier => "identifier",
+            TokenType::String => "string literal",
+            TokenType::Number => "number",
+            TokenType::And => "`and`",
+            TokenType::Break => "`break`",
+            TokenType::Continue => "`continue`",
+            TokenType::Defer => "`defer`",
+            TokenType::Else => "`else`",
+            TokenType::Embed => "`embed`",
+            TokenType::Enum => "`enum`",
+            TokenType::False => "`false`",
+            TokenType::Func => "`fn`",
+            TokenType::For => "`for`",
+            TokenType::Gen => "`gen`",
+            TokenType::If => "`if`",
+            TokenType::Impl => "`impl`",
+            TokenType::Import => "`import`",
+            TokenType::In => "`in`",
+            TokenType::Match => "`match`",
+            TokenType::Meta => "`meta`",
+            TokenType::None => "`none`",
+            TokenType::Or => "`or`",
+            TokenType::Print => "`print`",
+            TokenType::Quote => "`quote`",
+            TokenType::Return => "`return`",
+            TokenType::Struct => "`struct`",
+            TokenType::Trait => "`trait`",
+            TokenType::True => "`true`",
+            TokenType::Typeof => "`typeof`",
+            TokenType::Var => "`var`",
+            TokenType::While => "`while`",
+            TokenType::EOF => "end of file",
+        };
+        write!(f, "{text}")
+    }
+}
+
 #[derive(PartialEq, Debug)]
 pub enum TokenMetadata {
     Int(i64),
@@ -83,6 +173,6 @@ impl Token {
 #[derive(PartialEq, Debug)]
 pub struct Token {
     pub token_type: TokenType,
-    pub line_number: usize,
+    pub span: Span,
     pub metadata: Option<TokenMetadata>,
 }
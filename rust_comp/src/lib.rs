@@ -1,4 +1,5 @@
 pub mod config;
+pub mod diagnostics;
 pub mod frontend;
 pub mod runtime;
 pub mod semantics;
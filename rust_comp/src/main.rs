@@ -1,32 +1,67 @@
+use cronyx::diagnostics::diagnostic::*;
+use cronyx::diagnostics::render::*;
+use cronyx::diagnostics::source_map::SourceMap;
 use cronyx::frontend::lexer::*;
 use cronyx::frontend::parser::*;
+use cronyx::runtime::budget::Budget;
 use cronyx::runtime::environment::*;
 use cronyx::runtime::interpreter::*;
+use cronyx::semantics::meta::meta_policy::MetaPolicy;
 use cronyx::semantics::meta::meta_processor::*;
+use cronyx::semantics::meta::module_loader::ModuleLoader;
+use cronyx::semantics::types::runtime_checker::check_program;
+use cronyx::util::external_resolver::DefaultResolver;
 use cronyx::util::formatters::tree_formatter::*;
 use std::fmt::Debug;
 use std::fs::{create_dir_all, read_to_string, File};
-use std::io::{self, Write};
-use std::path::PathBuf;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::thread;
 
-fn main() {
-    fn run_pipeline(root_path: &PathBuf, out_dir: &PathBuf) {
-        let buf = read_to_string(root_path).unwrap();
-        create_dir_all(&out_dir).unwrap();
+enum ErrorFormat {
+    Human(RenderStyle),
+    Json,
+}
+
+fn main() -> ExitCode {
+    fn run_pipeline(
+        root_path: &PathBuf,
+        out_dir: &PathBuf,
+        sources: &mut SourceMap,
+        policy: &MetaPolicy,
+        budget: Budget,
+        typecheck: bool,
+    ) -> Result<(), Vec<Diagnostic>> {
+        let buf = read_to_string(root_path).map_err(|e| {
+            vec![Diagnostic::error(
+                "E0000",
+                format!("couldn't read `{}`: {e}", root_path.display()),
+            )]
+        })?;
+        let file_id = sources.add(root_path.display().to_string(), buf.clone());
+        create_dir_all(out_dir).unwrap();
 
         // TOKENIZE
 
-        let tokens = tokenize(&buf).unwrap();
+        let tokens = tokenize(&buf, file_id).map_err(|e| vec![e.to_diagnostic()])?;
         let mut tok_file = to_file(out_dir, "tokens.txt");
         dump(&tokens, &mut tok_file);
 
         // PARSE
         let mut parse_ctx = ParseCtx::new();
-        let _ = parse(&tokens, &mut parse_ctx).unwrap();
+        // statements that fail to parse are left as `Error` nodes, so the
+        // phases below still report what's wrong with the rest
+        let mut diagnostics: Vec<Diagnostic> = parse(&tokens, &mut parse_ctx)
+            .err()
+            .unwrap_or_default()
+            .iter()
+            .map(|e| e.to_diagnostic())
+            .collect();
         let meta_ast = &(parse_ctx.ast);
 
         let mut meta_ast_graph_file = to_file(out_dir, "meta_ast_graph.txt");
-        writeln!(meta_ast_graph_file, "{:?}", meta_ast);
+        writeln!(meta_ast_graph_file, "{:?}", meta_ast).unwrap();
 
         let mut meta_ast_file = to_file(out_dir, "meta_ast.txt");
         meta_ast.format_tree(&mut meta_ast_file);
@@ -35,29 +70,137 @@ fn main() {
 
         // METAPROCESSING
 
-        let runtime_ast = process(&meta_ast, &mut io::stdout()).unwrap();
+        let resolver = DefaultResolver {};
+        let root_dir = root_path.parent().unwrap_or(Path::new("."));
+        let mut modules = ModuleLoader::new(&resolver, root_dir, sources);
+        let runtime_ast =
+            match process_with_modules(meta_ast, &mut io::stdout(), &mut modules, policy) {
+                Ok(runtime_ast) => runtime_ast,
+                Err(e) => {
+                    diagnostics.push(e.to_diagnostic());
+                    return Err(diagnostics);
+                }
+            };
+
+        let mut deps_file = to_file(out_dir, "dependencies.txt");
+        for dep in modules.dependencies() {
+            writeln!(deps_file, "{}", dep.display()).unwrap();
+        }
 
         let mut runtime_ast_file = to_file(out_dir, "runtime_ast.txt");
         runtime_ast.format_tree(&mut runtime_ast_file);
 
         let mut runtime_ast_graph_file = to_file(out_dir, "runtime_ast_graph.txt");
-        writeln!(runtime_ast_graph_file, "{:?}", runtime_ast);
+        writeln!(runtime_ast_graph_file, "{:?}", runtime_ast).unwrap();
+
+        let mut stages_file = to_file(out_dir, "stages.txt");
+        write!(stages_file, "{}", runtime_ast.stages).unwrap();
+
+        // TYPE CHECKING
+
+        if typecheck {
+            match check_program(&runtime_ast) {
+                Ok(types) => {
+                    let mut types_file = to_file(out_dir, "types.txt");
+                    for (id, ty) in types.entries() {
+                        writeln!(types_file, "{id}: {ty}").unwrap();
+                    }
+                }
+                Err(e) => diagnostics.push(e.to_diagnostic()),
+            }
+        }
+
+        // a program with errors isn't run
+        if !diagnostics.is_empty() {
+            return Err(diagnostics);
+        }
+
         // EVALUATION
 
-        eval(
+        eval_with_budget(
             &runtime_ast,
             &runtime_ast.sem_root_stmts,
             Environment::new(),
             &mut None,
             &mut io::stdout(),
+            budget,
         )
-        .unwrap();
+        .map_err(|e| vec![e.to_diagnostic()])?;
+
+        Ok(())
+    }
+
+    let mut input = None;
+    let mut policy = MetaPolicy::default();
+    let mut budget = Budget::default();
+    let mut typecheck = true;
+    let mut format = if io::stderr().is_terminal() {
+        ErrorFormat::Human(RenderStyle::Ansi)
+    } else {
+        ErrorFormat::Human(RenderStyle::Plain)
+    };
+    for arg in std::env::args().skip(1) {
+        if let Some((flag, value)) = arg.split_once('=') {
+            let parsed = match flag {
+                "--fuel" => value.parse().map(|n| budget.fuel = Some(n)),
+                "--max-depth" => value.parse().map(|n| budget.max_depth = Some(n)),
+                _ => {
+                    input = Some(arg);
+                    continue;
+                }
+            };
+            if parsed.is_err() {
+                eprintln!("error: `{flag}` expects a number, found `{value}`");
+                return ExitCode::FAILURE;
+            }
+            continue;
+        }
+        match arg.as_str() {
+            "--json" => format = ErrorFormat::Json,
+            "--no-color" => format = ErrorFormat::Human(RenderStyle::Plain),
+            "--no-typecheck" => typecheck = false,
+            "--meta-allow-env" => policy.env = true,
+            "--meta-allow-clock" => policy.clock = true,
+            _ => input = Some(arg),
+        }
     }
 
-    let input = std::env::args().nth(1);
     let root_path = PathBuf::from(input.expect("source file path required"));
     let out_path = PathBuf::from("../out");
-    run_pipeline(&root_path, &out_path);
+    let mut sources = SourceMap::new();
+
+    // deep recursion in the evaluated program needs a deep native stack
+    let result = thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(Budget::STACK_SIZE)
+            .spawn_scoped(scope, || {
+                run_pipeline(
+                    &root_path,
+                    &out_path,
+                    &mut sources,
+                    &policy,
+                    budget,
+                    typecheck,
+                )
+            })
+            .expect("couldn't start the compiler thread")
+            .join()
+            .expect("the compiler thread panicked")
+    });
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(diags) => {
+            for diag in &diags {
+                let report = match format {
+                    ErrorFormat::Human(style) => render(diag, &sources, style),
+                    ErrorFormat::Json => render_json(diag, &sources) + "\n",
+                };
+                eprint!("{report}");
+            }
+            ExitCode::FAILURE
+        }
+    }
 }
 
 pub fn to_file(out_dir: &PathBuf, file_name: &str) -> File {
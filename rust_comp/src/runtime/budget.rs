@@ -0,0 +1,40 @@
+use crate::frontend::span::Span;
+
+/// How much work an evaluation may do before it is stopped.
+///
+/// Both the program itself and compile-time code run under a budget; the
+/// metaprocessor takes its budget from the `MetaPolicy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    /// Statements and expressions that may be evaluated; `None` for no limit.
+    pub fuel: Option<u64>,
+    /// Function calls that may be active at once; `None` for no limit, in
+    /// which case deep recursion overflows the interpreter's own stack.
+    pub max_depth: Option<usize>,
+}
+
+impl Budget {
+    pub const DEFAULT_MAX_DEPTH: usize = 1_000;
+
+    /// Stack a thread needs to reach `DEFAULT_MAX_DEPTH`; unoptimized builds
+    /// use tens of kilobytes per call, far more than a default thread has.
+    pub const STACK_SIZE: usize = 256 * 1024 * 1024;
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self {
+            fuel: None,
+            max_depth: Some(Self::DEFAULT_MAX_DEPTH),
+        }
+    }
+}
+
+/// A function call that was active when evaluation stopped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallFrame {
+    /// The function's name, or `None` for a function expression.
+    pub function: Option<String>,
+    /// Where it was called from.
+    pub span: Span,
+}
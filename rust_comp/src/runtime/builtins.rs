@@ -0,0 +1,78 @@
+use super::interpreter::{EvalCtx, EvalError};
+use super::value::Value;
+use crate::frontend::span::Span;
+use crate::semantics::meta::meta_policy::Capability;
+use crate::semantics::types::types::{self, Type};
+use std::fmt;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Functions provided by the interpreter rather than written in the language.
+/// They are found after every user scope, so user code may shadow them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    /// `env(name)`: the environment variable's value, or `""` when unset.
+    Env,
+    /// `clock()`: milliseconds since the Unix epoch.
+    Clock,
+}
+
+impl fmt::Display for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Builtin::Env => write!(f, "env"),
+            Builtin::Clock => write!(f, "clock"),
+        }
+    }
+}
+
+impl Builtin {
+    pub const ALL: [Builtin; 2] = [Builtin::Env, Builtin::Clock];
+
+    pub fn ty(&self) -> Type {
+        let (params, ret) = match self {
+            // an unset variable reads as the empty string
+            Builtin::Env => (vec![types::string_type()], types::string_type()),
+            Builtin::Clock => (vec![], types::int_type()),
+        };
+        Type::Func {
+            params,
+            ret: Box::new(ret),
+        }
+    }
+}
+
+pub fn lookup(name: &str) -> Option<Builtin> {
+    match name {
+        "env" => Some(Builtin::Env),
+        "clock" => Some(Builtin::Clock),
+        _ => None,
+    }
+}
+
+pub fn call_builtin<W: Write>(
+    builtin: Builtin,
+    args: Vec<Value>,
+    span: Span,
+    ctx: &mut EvalCtx<W>,
+) -> Result<Value, EvalError> {
+    match (builtin, args.as_slice()) {
+        (Builtin::Env, [name]) => {
+            ctx.require(Capability::Env, span)?;
+            let Value::String(name) = name else {
+                return Err(EvalError::TypeError(types::string_type(), span));
+            };
+            Ok(Value::String(std::env::var(name).unwrap_or_default()))
+        }
+
+        (Builtin::Clock, []) => {
+            ctx.require(Capability::Clock, span)?;
+            let millis = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as i64);
+            Ok(Value::Int(millis))
+        }
+
+        _ => Err(EvalError::ArgumentMismatch(span)),
+    }
+}
@@ -20,6 +20,10 @@ impl EnvHandler {
         Self { env }
     }
 
+    pub fn current(&self) -> EnvRef {
+        Rc::clone(&self.env)
+    }
+
     pub fn push_scope(&mut self) {
         let new_env = Environment::new_child(self.env.clone());
         self.env = new_env;
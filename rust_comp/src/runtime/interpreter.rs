@@ -1,28 +1,144 @@
+use super::budget::{Budget, CallFrame};
+use super::builtins::{self, call_builtin};
 use super::environment::{EnvHandler, EnvRef, Environment};
+use super::list_methods::call_list_method;
 use super::result::ExecResult;
-use super::value::{Function, Value};
+use super::value::{Function, Module, Value};
 use crate::frontend::id_provider::*;
+use crate::frontend::meta_ast::{AssignOp, MatchArm, Pattern, PatternKind, Payload};
+use crate::frontend::span::Span;
+use crate::semantics::meta::meta_policy::{Capability, MetaPolicy};
 use crate::semantics::meta::meta_processor::MetaContext;
 use crate::semantics::meta::runtime_ast::*;
+use crate::semantics::meta::substitution::{quote_stmts, splices, subst_stmts};
 use crate::semantics::types::types::{self, Type};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::io::Write;
+use std::rc::Rc;
 
 #[derive(Debug)]
 pub enum EvalError {
     ExprNotFound(AstId),
     StmtNotFound(AstId),
-    UnknownStructType(String),
-    UndefinedVariable(String),
-    TypeError(Type),
-    NonFunctionCall,
-    ArgumentMismatch,
-    Unimplemented,
+    UnknownStructType(String, Span),
+    MissingField {
+        struct_name: String,
+        field: String,
+        span: Span,
+    },
+    UnknownField {
+        struct_name: String,
+        field: String,
+        span: Span,
+    },
+    DuplicateField(String, Span),
+    UnknownVariant {
+        enum_name: String,
+        variant: String,
+        span: Span,
+    },
+    PayloadMismatch {
+        variant: String,
+        span: Span,
+    },
+    NoMatch(Span),
+    UndefinedVariable(String, Span),
+    TypeError(Type, Span),
+    CannotCompare {
+        left: String,
+        right: String,
+        span: Span,
+    },
+    NonFunctionCall(Span),
+    ArgumentMismatch(Span),
+    UndeclaredAssignment(String, Span),
+    NoSuchField(String, Span),
+    NotIndexable(Span),
+    IndexOutOfBounds {
+        index: i64,
+        len: usize,
+        span: Span,
+    },
+    DivisionByZero(Span),
+    IntegerOverflow(Span),
+    InvalidSlice {
+        start: i64,
+        end: i64,
+        len: usize,
+        span: Span,
+    },
+    EmptyList(Span),
+    NotIterable(Span),
+    UnknownMethod(String, Span),
+    UnknownModule(String, Span),
+    CapabilityDenied {
+        capability: Capability,
+        span: Span,
+    },
+    OutOfFuel {
+        limit: u64,
+        span: Span,
+        call_stack: Vec<CallFrame>,
+    },
+    StackOverflow {
+        limit: usize,
+        span: Span,
+        call_stack: Vec<CallFrame>,
+    },
+    MemoryLimitExceeded {
+        limit: usize,
+        span: Span,
+    },
+    GenOutsideMeta(Span),
+    QuoteOutsideMeta(Span),
+    // a `gen` emitting a `quote` into code that runs after compile time
+    QuoteInGen(Span),
+    UnquoteOutsideQuote(Span),
+    // the value of a `$` has no code form, or statements were spliced in
+    // where an expression goes
+    InvalidSplice(String, Span),
+    Unimplemented(Span),
 }
 
-// TODO this is not the correct way to do this
-impl From<String> for EvalError {
-    fn from(name: String) -> Self {
-        EvalError::UndefinedVariable(name)
+impl EvalError {
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            EvalError::ExprNotFound(_) | EvalError::StmtNotFound(_) => None,
+            EvalError::UnknownStructType(_, span)
+            | EvalError::MissingField { span, .. }
+            | EvalError::UnknownField { span, .. }
+            | EvalError::DuplicateField(_, span)
+            | EvalError::UnknownVariant { span, .. }
+            | EvalError::PayloadMismatch { span, .. }
+            | EvalError::NoMatch(span)
+            | EvalError::GenOutsideMeta(span)
+            | EvalError::QuoteOutsideMeta(span)
+            | EvalError::QuoteInGen(span)
+            | EvalError::UnquoteOutsideQuote(span)
+            | EvalError::InvalidSplice(_, span)
+            | EvalError::UndefinedVariable(_, span)
+            | EvalError::TypeError(_, span)
+            | EvalError::CannotCompare { span, .. }
+            | EvalError::NonFunctionCall(span)
+            | EvalError::ArgumentMismatch(span)
+            | EvalError::UndeclaredAssignment(_, span)
+            | EvalError::NoSuchField(_, span)
+            | EvalError::NotIndexable(span)
+            | EvalError::IndexOutOfBounds { span, .. }
+            | EvalError::DivisionByZero(span)
+            | EvalError::IntegerOverflow(span)
+            | EvalError::InvalidSlice { span, .. }
+            | EvalError::EmptyList(span)
+            | EvalError::NotIterable(span)
+            | EvalError::UnknownMethod(_, span)
+            | EvalError::UnknownModule(_, span)
+            | EvalError::CapabilityDenied { span, .. }
+            | EvalError::OutOfFuel { span, .. }
+            | EvalError::StackOverflow { span, .. }
+            | EvalError::MemoryLimitExceeded { span, .. }
+            | EvalError::Unimplemented(span) => Some(*span),
+        }
     }
 }
 
@@ -31,127 +147,961 @@ pub struct EvalCtx<'a, W> {
     pub env: &'a mut EnvHandler,
     pub meta_ctx: &'a mut Option<MetaContext>,
     pub ast: &'a RuntimeAst,
+    // modules already imported during this run, by name
+    pub modules: HashMap<String, Value>,
+    // impl methods whose `impl` has run, by the id of their `FnDecl`
+    pub methods: HashMap<AstId, Rc<Function>>,
+    // statements deferred by each block being run, innermost last
+    pub deferred: Vec<Vec<AstId>>,
+    // restrictions on compile-time code; `None` when running the program
+    pub policy: Option<&'a MetaPolicy>,
+    pub budget: Budget,
+    // fuel left; `None` when the budget doesn't limit it
+    pub fuel: Option<u64>,
+    // innermost call last
+    pub call_stack: Vec<CallFrame>,
+    pub memory: usize,
+}
+
+impl<W> EvalCtx<'_, W> {
+    fn span(&self, id: AstId) -> Span {
+        self.ast.get_span(id).unwrap_or_default()
+    }
+
+    /// Fails unless the policy grants `capability`.
+    pub fn require(&self, capability: Capability, span: Span) -> Result<(), EvalError> {
+        match self.policy {
+            Some(policy) if !policy.allows(capability) => {
+                Err(EvalError::CapabilityDenied { capability, span })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Burns one unit of fuel for evaluating `id`.
+    fn tick(&mut self, id: AstId) -> Result<(), EvalError> {
+        match &mut self.fuel {
+            Some(0) => Err(EvalError::OutOfFuel {
+                limit: self.budget.fuel.unwrap_or_default(),
+                span: self.span(id),
+                call_stack: self.call_stack.clone(),
+            }),
+            Some(fuel) => {
+                *fuel -= 1;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Counts `bytes` of newly allocated values against the memory budget.
+    pub fn charge(&mut self, bytes: usize, span: Span) -> Result<(), EvalError> {
+        self.memory = self.memory.saturating_add(bytes);
+        match self.policy.and_then(|p| p.max_memory) {
+            Some(limit) if self.memory > limit => {
+                Err(EvalError::MemoryLimitExceeded { limit, span })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Charges for `count` new list elements or struct fields.
+    pub fn charge_values(&mut self, count: usize, span: Span) -> Result<(), EvalError> {
+        self.charge(count.saturating_mul(std::mem::size_of::<Value>()), span)
+    }
 }
 
+// Each level of recursion in the evaluated program takes an `eval_expr` and
+// an `eval_stmt` frame, and a frame holds room for all of its cases, so the
+// larger cases live in helpers that are never inlined.
 pub fn eval_expr<W: Write>(expr_id: AstId, ctx: &mut EvalCtx<W>) -> Result<Value, EvalError> {
-    match ctx
+    ctx.tick(expr_id)?;
+    let expr = ctx
         .ast
         .get_expr(expr_id)
-        .ok_or(EvalError::ExprNotFound(expr_id))?
-    {
+        .ok_or(EvalError::ExprNotFound(expr_id))?;
+    match expr {
         RuntimeExpr::Int(n) => Ok(Value::Int(*n)),
         RuntimeExpr::String(s) => Ok(Value::String(s.clone())),
         RuntimeExpr::Bool(b) => Ok(Value::Bool(*b)),
+        RuntimeExpr::Bytes(bytes) => {
+            ctx.charge_values(bytes.len(), ctx.span(expr_id))?;
+            let elems = bytes.iter().map(|b| Value::Int(*b as i64)).collect();
+            Ok(Value::List(Rc::new(RefCell::new(elems))))
+        }
 
         RuntimeExpr::StructLiteral { type_name, fields } => {
-            //let _struct_def = decls
-            //    .get_struct(type_name)
-            //    .ok_or_else(|| EvalError::UnknownStructType(type_name.clone()))?;
-
-            let mut fs = vec![];
-
-            for (field_name, expr) in fields {
-                let value = eval_expr(*expr, ctx)?;
-                fs.push((field_name.clone(), value));
-            }
-
-            //Ok(Value::Struct {
-            //    type_name: type_name.clone(),
-            //    fields: Rc::new(RefCell::new(fs)),
-            //})
-            Err(EvalError::Unimplemented)
+            eval_struct_literal(expr_id, type_name, fields, ctx)
         }
 
-        RuntimeExpr::Variable(name) => {
-            let var = ctx.env.get(name)?;
-            Ok(var)
-        }
+        RuntimeExpr::Variant {
+            enum_name,
+            variant,
+            payload,
+        } => eval_variant(expr_id, enum_name, variant, payload, ctx),
+
+        RuntimeExpr::Variable(name) => match ctx.env.get(name) {
+            Ok(var) => Ok(var),
+            Err(_) => builtins::lookup(name)
+                .map(Value::Builtin)
+                .ok_or_else(|| EvalError::UndefinedVariable(name.clone(), ctx.span(expr_id))),
+        },
 
         RuntimeExpr::List(exprs) => {
             let mut values = Vec::new();
             for e in exprs {
                 values.push(eval_expr(*e, ctx)?);
             }
+            ctx.charge_values(values.len(), ctx.span(expr_id))?;
 
-            //Ok(Value::List(Rc::new(RefCell::new(values))))
-            Err(EvalError::Unimplemented)
+            Ok(Value::List(Rc::new(RefCell::new(values))))
         }
 
-        RuntimeExpr::Add(a, b) => match (eval_expr(*a, ctx)?, eval_expr(*b, ctx)?) {
-            (Value::Int(x), Value::Int(y)) => Ok(Value::Int(x + y)),
-            (Value::String(x), Value::String(y)) => Ok(Value::String(x + &y)),
-            _ => Err(EvalError::TypeError(types::int_type())),
-        },
+        RuntimeExpr::FieldAccess { target, field } => {
+            let value = eval_expr(*target, ctx)?;
+            match &value {
+                Value::Module(module) => module
+                    .env
+                    .borrow()
+                    .get(field)
+                    .map_err(|_| EvalError::NoSuchField(field.clone(), ctx.span(expr_id))),
+                Value::Struct { fields, .. } => fields
+                    .borrow()
+                    .iter()
+                    .find(|(name, _)| name == field)
+                    .map(|(_, v)| v.clone())
+                    .ok_or_else(|| EvalError::NoSuchField(field.clone(), ctx.span(expr_id))),
+                _ => Err(EvalError::NoSuchField(field.clone(), ctx.span(expr_id))),
+            }
+        }
+
+        RuntimeExpr::Index { target, index } => {
+            let list = eval_list(*target, ctx)?;
+            let i = eval_index(*index, &list.borrow(), ctx)?;
+            let elem = list.borrow()[i].clone();
+            Ok(elem)
+        }
 
-        RuntimeExpr::Sub(a, b) => match (eval_expr(*a, ctx)?, eval_expr(*b, ctx)?) {
-            (Value::Int(x), Value::Int(y)) => Ok(Value::Int(x - y)),
-            _ => Err(EvalError::TypeError(types::int_type())),
+        RuntimeExpr::Slice { target, start, end } => {
+            eval_slice(expr_id, *target, *start, *end, ctx)
+        }
+
+        RuntimeExpr::Assign { target, op, value } => eval_assign(*target, *op, *value, ctx),
+
+        RuntimeExpr::Add(..)
+        | RuntimeExpr::Sub(..)
+        | RuntimeExpr::Mult(..)
+        | RuntimeExpr::Div(..)
+        | RuntimeExpr::Equals(..)
+        | RuntimeExpr::NotEquals(..)
+        | RuntimeExpr::Less(..)
+        | RuntimeExpr::LessEqual(..)
+        | RuntimeExpr::Greater(..)
+        | RuntimeExpr::GreaterEqual(..) => eval_binary(expr_id, expr, ctx),
+
+        // `and`/`or` only evaluate the right operand when the left one
+        // doesn't already decide the result
+        RuntimeExpr::And(a, b) => match eval_expr(*a, ctx)? {
+            Value::Bool(false) => Ok(Value::Bool(false)),
+            Value::Bool(true) => match eval_expr(*b, ctx)? {
+                Value::Bool(y) => Ok(Value::Bool(y)),
+                _ => Err(EvalError::TypeError(types::bool_type(), ctx.span(*b))),
+            },
+            _ => Err(EvalError::TypeError(types::bool_type(), ctx.span(*a))),
         },
 
-        RuntimeExpr::Mult(a, b) => match (eval_expr(*a, ctx)?, eval_expr(*b, ctx)?) {
-            (Value::Int(x), Value::Int(y)) => Ok(Value::Int(x * y)),
-            _ => Err(EvalError::TypeError(types::int_type())),
+        RuntimeExpr::Or(a, b) => match eval_expr(*a, ctx)? {
+            Value::Bool(true) => Ok(Value::Bool(true)),
+            Value::Bool(false) => match eval_expr(*b, ctx)? {
+                Value::Bool(y) => Ok(Value::Bool(y)),
+                _ => Err(EvalError::TypeError(types::bool_type(), ctx.span(*b))),
+            },
+            _ => Err(EvalError::TypeError(types::bool_type(), ctx.span(*a))),
         },
 
-        RuntimeExpr::Div(a, b) => match (eval_expr(*a, ctx)?, eval_expr(*b, ctx)?) {
-            (Value::Int(x), Value::Int(y)) => Ok(Value::Int(x / y)),
-            _ => Err(EvalError::TypeError(types::int_type())),
+        RuntimeExpr::Not(a) => match eval_expr(*a, ctx)? {
+            Value::Bool(x) => Ok(Value::Bool(!x)),
+            _ => Err(EvalError::TypeError(types::bool_type(), ctx.span(expr_id))),
         },
 
-        RuntimeExpr::Equals(a, b) => match (eval_expr(*a, ctx)?, eval_expr(*b, ctx)?) {
-            (Value::Int(x), Value::Int(y)) => Ok(Value::Bool(x == y)),
-            (Value::String(x), Value::String(y)) => Ok(Value::Bool(x == y)),
-            (Value::Bool(x), Value::Bool(y)) => Ok(Value::Bool(x == y)),
-            _ => Err(EvalError::TypeError(types::unit_type())),
+        RuntimeExpr::Negate(a) => match eval_expr(*a, ctx)? {
+            Value::Int(x) => x
+                .checked_neg()
+                .map(Value::Int)
+                .ok_or(EvalError::IntegerOverflow(ctx.span(expr_id))),
+            _ => Err(EvalError::TypeError(types::int_type(), ctx.span(expr_id))),
         },
 
+        RuntimeExpr::Lambda { params, body, .. } => Ok(Value::Function(Rc::new(Function {
+            name: None,
+            params: params.iter().map(|p| p.name.clone()).collect(),
+            body: *body,
+            env: ctx.env.current(),
+        }))),
+
+        RuntimeExpr::Quote(stmts) => {
+            if ctx.meta_ctx.is_none() {
+                return Err(EvalError::QuoteOutsideMeta(ctx.span(expr_id)));
+            }
+            let values = eval_splices(stmts, ctx)?;
+            let meta = ctx.meta_ctx.as_mut().expect("checked above");
+            let copies = quote_stmts(stmts, ctx.ast, &values, meta)?;
+            Ok(Value::Ast(Rc::new(copies)))
+        }
+
+        RuntimeExpr::Unquote(_) => Err(EvalError::UnquoteOutsideQuote(ctx.span(expr_id))),
+
         RuntimeExpr::Call { callee, args } => {
-            let func = match ctx.env.get(callee)? {
+            let func = match eval_expr(*callee, ctx)? {
                 Value::Function(f) => f,
-                _ => return Err(EvalError::NonFunctionCall),
+                Value::Builtin(builtin) => {
+                    let arg_vals = eval_args(args, ctx)?;
+                    return call_builtin(builtin, arg_vals, ctx.span(expr_id), ctx);
+                }
+                _ => return Err(EvalError::NonFunctionCall(ctx.span(*callee))),
             };
 
             if func.params.len() != args.len() {
-                return Err(EvalError::ArgumentMismatch);
+                return Err(EvalError::ArgumentMismatch(ctx.span(expr_id)));
             }
 
-            let arg_vals =
-                args.iter()
-                    .try_fold(Vec::new(), |mut v, a| -> Result<Vec<Value>, EvalError> {
-                        v.push(eval_expr(*a, ctx)?);
-                        Ok(v)
-                    })?;
+            let arg_vals = eval_args(args, ctx)?;
+            call_function(&func, arg_vals, ctx.span(expr_id), ctx)
+        }
 
-            //let callee_env = Env::new_child(Rc::clone(&func.env));
-            let callee_env = Environment::new();
+        RuntimeExpr::MethodCall {
+            receiver,
+            method,
+            args,
+        } => eval_method_call(expr_id, *receiver, method, args, ctx),
+    }
+}
 
-            {
-                let mut e = callee_env.borrow_mut();
-                for (param, value) in func.params.iter().zip(arg_vals) {
-                    e.define(param.clone(), value);
+// The arithmetic and comparison operators, which evaluate both operands
+// before looking at either.
+#[inline(never)]
+fn eval_binary<W: Write>(
+    expr_id: AstId,
+    expr: &RuntimeExpr,
+    ctx: &mut EvalCtx<W>,
+) -> Result<Value, EvalError> {
+    let (a, b) = match expr {
+        RuntimeExpr::Add(a, b)
+        | RuntimeExpr::Sub(a, b)
+        | RuntimeExpr::Mult(a, b)
+        | RuntimeExpr::Div(a, b)
+        | RuntimeExpr::Equals(a, b)
+        | RuntimeExpr::NotEquals(a, b)
+        | RuntimeExpr::Less(a, b)
+        | RuntimeExpr::LessEqual(a, b)
+        | RuntimeExpr::Greater(a, b)
+        | RuntimeExpr::GreaterEqual(a, b) => (*a, *b),
+        _ => unreachable!("not a binary operator"),
+    };
+    let (x, y) = (eval_expr(a, ctx)?, eval_expr(b, ctx)?);
+    let span = ctx.span(expr_id);
+    match (expr, x, y) {
+        (RuntimeExpr::Add(..), Value::Int(x), Value::Int(y)) => int_op(AssignOp::Add, x, y, span),
+        (RuntimeExpr::Add(..), Value::String(x), Value::String(y)) => {
+            ctx.charge(x.len() + y.len(), span)?;
+            Ok(Value::String(x + &y))
+        }
+        (RuntimeExpr::Sub(..), Value::Int(x), Value::Int(y)) => int_op(AssignOp::Sub, x, y, span),
+        (RuntimeExpr::Mult(..), Value::Int(x), Value::Int(y)) => int_op(AssignOp::Mult, x, y, span),
+        (RuntimeExpr::Div(..), Value::Int(x), Value::Int(y)) => int_op(AssignOp::Div, x, y, span),
+        (RuntimeExpr::Equals(..), x, y) => equal(&x, &y, span).map(Value::Bool),
+        (RuntimeExpr::NotEquals(..), x, y) => equal(&x, &y, span).map(|eq| Value::Bool(!eq)),
+        (RuntimeExpr::Less(..), Value::Int(x), Value::Int(y)) => Ok(Value::Bool(x < y)),
+        (RuntimeExpr::LessEqual(..), Value::Int(x), Value::Int(y)) => Ok(Value::Bool(x <= y)),
+        (RuntimeExpr::Greater(..), Value::Int(x), Value::Int(y)) => Ok(Value::Bool(x > y)),
+        (RuntimeExpr::GreaterEqual(..), Value::Int(x), Value::Int(y)) => Ok(Value::Bool(x >= y)),
+        _ => Err(EvalError::TypeError(types::int_type(), span)),
+    }
+}
+
+// Whether two values of the same kind are equal, comparing lists, structs and
+// variants element by element. Functions, modules and code values have no
+// equality.
+fn equal(x: &Value, y: &Value, span: Span) -> Result<bool, EvalError> {
+    let comparable = |v: &Value| {
+        !matches!(
+            v,
+            Value::Function(_) | Value::Builtin(_) | Value::Module(_) | Value::Ast(_)
+        )
+    };
+    if comparable(x) && std::mem::discriminant(x) == std::mem::discriminant(y) {
+        return Ok(x == y);
+    }
+    Err(EvalError::CannotCompare {
+        left: kind_name(x).to_string(),
+        right: kind_name(y).to_string(),
+        span,
+    })
+}
+
+// The name of the type of `value` for diagnostics.
+fn kind_name(value: &Value) -> &str {
+    match value {
+        Value::Unit => "unit",
+        Value::Function(_) | Value::Builtin(_) => "function",
+        Value::Module(_) => "module",
+        Value::Ast(_) => "code",
+        _ => value.type_name().unwrap_or("value"),
+    }
+}
+
+#[inline(never)]
+fn eval_struct_literal<W: Write>(
+    expr_id: AstId,
+    type_name: &str,
+    fields: &[(String, AstId)],
+    ctx: &mut EvalCtx<W>,
+) -> Result<Value, EvalError> {
+    let span = ctx.span(expr_id);
+    let def = ctx
+        .ast
+        .decls
+        .get_struct(type_name)
+        .ok_or_else(|| EvalError::UnknownStructType(type_name.to_string(), span))?;
+
+    for (i, (field_name, expr)) in fields.iter().enumerate() {
+        if !def.has_field(field_name) {
+            return Err(EvalError::UnknownField {
+                struct_name: type_name.to_string(),
+                field: field_name.clone(),
+                span: ctx.span(*expr),
+            });
+        }
+        if fields[..i].iter().any(|(f, _)| f == field_name) {
+            return Err(EvalError::DuplicateField(
+                field_name.clone(),
+                ctx.span(*expr),
+            ));
+        }
+    }
+
+    if let Some((missing, _)) = def
+        .fields
+        .iter()
+        .find(|(f, _)| !fields.iter().any(|(name, _)| name == f))
+    {
+        return Err(EvalError::MissingField {
+            struct_name: type_name.to_string(),
+            field: missing.clone(),
+            span,
+        });
+    }
+
+    let mut values = vec![];
+    for (field_name, expr) in fields {
+        values.push((field_name, eval_expr(*expr, ctx)?));
+    }
+    ctx.charge_values(values.len(), span)?;
+
+    // fields are stored in declaration order, whatever order the
+    // literal lists them in
+    let fs = def
+        .fields
+        .iter()
+        .filter_map(|(f, _)| {
+            let i = values.iter().position(|(name, _)| *name == f)?;
+            let (name, value) = values.swap_remove(i);
+            Some((name.clone(), value))
+        })
+        .collect();
+
+    Ok(Value::Struct {
+        type_name: type_name.to_string(),
+        fields: Rc::new(RefCell::new(fs)),
+    })
+}
+
+#[inline(never)]
+fn eval_variant<W: Write>(
+    expr_id: AstId,
+    enum_name: &str,
+    variant: &str,
+    payload: &Payload<AstId>,
+    ctx: &mut EvalCtx<W>,
+) -> Result<Value, EvalError> {
+    let span = ctx.span(expr_id);
+    let unknown = || EvalError::UnknownVariant {
+        enum_name: enum_name.to_string(),
+        variant: variant.to_string(),
+        span,
+    };
+    let def = ctx.ast.decls.get_enum(enum_name).ok_or_else(unknown)?;
+    let decl = def.variant(variant).ok_or_else(unknown)?;
+    if !decl.payload.same_shape(payload) {
+        return Err(EvalError::PayloadMismatch {
+            variant: variant.to_string(),
+            span,
+        });
+    }
+
+    let payload = match (&decl.payload, payload) {
+        (Payload::Tuple(_), Payload::Tuple(args)) => {
+            let mut values = Vec::with_capacity(args.len());
+            for arg in args {
+                values.push(eval_expr(*arg, ctx)?);
+            }
+            Payload::Tuple(values)
+        }
+        (Payload::Struct(declared), Payload::Struct(fields)) => {
+            let mut values = vec![];
+            for (name, expr) in fields {
+                values.push((name, eval_expr(*expr, ctx)?));
+            }
+            let fields = declared
+                .iter()
+                .filter_map(|(f, _)| {
+                    let i = values.iter().position(|(name, _)| *name == f)?;
+                    let (name, value) = values.swap_remove(i);
+                    Some((name.clone(), value))
+                })
+                .collect();
+            Payload::Struct(fields)
+        }
+        _ => Payload::Unit,
+    };
+    ctx.charge_values(payload.len(), span)?;
+
+    Ok(Value::Variant {
+        enum_name: enum_name.to_string(),
+        variant: variant.to_string(),
+        payload: Rc::new(payload),
+    })
+}
+
+#[inline(never)]
+fn eval_slice<W: Write>(
+    expr_id: AstId,
+    target: AstId,
+    start: Option<AstId>,
+    end: Option<AstId>,
+    ctx: &mut EvalCtx<W>,
+) -> Result<Value, EvalError> {
+    let list = eval_list(target, ctx)?;
+    let len = list.borrow().len();
+    let from = match start {
+        Some(e) => eval_int(e, ctx)?,
+        None => 0,
+    };
+    let to = match end {
+        Some(e) => eval_int(e, ctx)?,
+        None => len as i64,
+    };
+    if from < 0 || to < from || to as usize > len {
+        return Err(EvalError::InvalidSlice {
+            start: from,
+            end: to,
+            len,
+            span: ctx.span(expr_id),
+        });
+    }
+    ctx.charge_values((to - from) as usize, ctx.span(expr_id))?;
+    let elems = list.borrow()[from as usize..to as usize].to_vec();
+    Ok(Value::List(Rc::new(RefCell::new(elems))))
+}
+
+#[inline(never)]
+fn eval_method_call<W: Write>(
+    expr_id: AstId,
+    receiver: AstId,
+    method: &str,
+    args: &[AstId],
+    ctx: &mut EvalCtx<W>,
+) -> Result<Value, EvalError> {
+    let span = ctx.span(expr_id);
+    let receiver_val = eval_expr(receiver, ctx)?;
+    let arg_vals = eval_args(args, ctx)?;
+
+    // methods from an impl come first, with the receiver as `self`
+    if let Some(func) = impl_method(&receiver_val, method, ctx) {
+        let args: Vec<Value> = std::iter::once(receiver_val).chain(arg_vals).collect();
+        if func.params.len() != args.len() {
+            return Err(EvalError::ArgumentMismatch(span));
+        }
+        return call_function(&func, args, span, ctx);
+    }
+
+    match receiver_val {
+        Value::List(list) => call_list_method(&list, method, arg_vals, span, ctx),
+        Value::String(s) if method == "len" && arg_vals.is_empty() => {
+            Ok(Value::Int(s.chars().count() as i64))
+        }
+        // a struct field holding a function
+        Value::Struct { fields, .. } => {
+            let field = fields
+                .borrow()
+                .iter()
+                .find(|(name, _)| name == method)
+                .map(|(_, v)| v.clone());
+            match field {
+                Some(Value::Function(func)) => {
+                    if func.params.len() != arg_vals.len() {
+                        return Err(EvalError::ArgumentMismatch(span));
+                    }
+                    call_function(&func, arg_vals, span, ctx)
                 }
+                _ => Err(EvalError::UnknownMethod(method.to_string(), span)),
             }
+        }
+        Value::Module(module) => {
+            let member = module.env.borrow().get(method);
+            match member {
+                Ok(Value::Function(func)) => {
+                    if func.params.len() != arg_vals.len() {
+                        return Err(EvalError::ArgumentMismatch(span));
+                    }
+                    call_function(&func, arg_vals, span, ctx)
+                }
+                Ok(_) => Err(EvalError::NonFunctionCall(span)),
+                Err(_) => Err(EvalError::UnknownMethod(method.to_string(), span)),
+            }
+        }
+        _ => Err(EvalError::UnknownMethod(method.to_string(), span)),
+    }
+}
+
+// The method an impl for the receiver's type provides, once that impl has run.
+fn impl_method<W>(receiver: &Value, method: &str, ctx: &EvalCtx<W>) -> Option<Rc<Function>> {
+    let type_name = receiver.type_name()?;
+    let (_, id) = ctx.ast.decls.find_method(type_name, method)?;
+    ctx.methods.get(&id).cloned()
+}
+
+// Runs a module's top-level statements in a fresh environment.
+fn eval_module<W: Write>(
+    name: &str,
+    stmt_id: AstId,
+    ctx: &mut EvalCtx<W>,
+) -> Result<Value, EvalError> {
+    let stmts = ctx
+        .ast
+        .get_module(name)
+        .ok_or_else(|| EvalError::UnknownModule(name.to_string(), ctx.span(stmt_id)))?;
+
+    let module_env = Environment::new();
+    let importer_env = std::mem::replace(ctx.env, EnvHandler::from(Rc::clone(&module_env)));
+    let result = eval_scope(stmts, ctx);
+    *ctx.env = importer_env;
+    result?;
+
+    Ok(Value::Module(Rc::new(Module {
+        name: name.to_string(),
+        env: module_env,
+    })))
+}
+
+fn eval_args<W: Write>(args: &[AstId], ctx: &mut EvalCtx<W>) -> Result<Vec<Value>, EvalError> {
+    args.iter().map(|a| eval_expr(*a, ctx)).collect()
+}
+
+// The values of the `$`s in the statements of a `gen` or `quote`, computed
+// where the statements are emitted or quoted.
+fn eval_splices<W: Write>(
+    stmts: &[AstId],
+    ctx: &mut EvalCtx<W>,
+) -> Result<HashMap<AstId, Value>, EvalError> {
+    splices(stmts, ctx.ast)
+        .into_iter()
+        .map(|(splice, expr)| Ok((splice, eval_expr(expr, ctx)?)))
+        .collect()
+}
 
-            let result = match eval_stmt(func.body, ctx)? {
-                ExecResult::Return(v) => v,
-                ExecResult::Continue => Value::Unit,
+/// Runs `func` with already evaluated arguments; the caller checks the arity.
+/// `span` is the call site, recorded on the call stack.
+pub fn call_function<W: Write>(
+    func: &Function,
+    args: Vec<Value>,
+    span: Span,
+    ctx: &mut EvalCtx<W>,
+) -> Result<Value, EvalError> {
+    if let Some(limit) = ctx.budget.max_depth {
+        if ctx.call_stack.len() >= limit {
+            return Err(EvalError::StackOverflow {
+                limit,
+                span,
+                call_stack: ctx.call_stack.clone(),
+            });
+        }
+    }
+
+    // the body runs in a child of the environment the function was
+    // defined in, not the caller's
+    let callee_env = Environment::new_child(Rc::clone(&func.env));
+
+    {
+        let mut e = callee_env.borrow_mut();
+        for (param, value) in func.params.iter().zip(args) {
+            e.define(param.clone(), value);
+        }
+    }
+
+    ctx.call_stack.push(CallFrame {
+        function: func.name.clone(),
+        span,
+    });
+    let caller_env = std::mem::replace(ctx.env, EnvHandler::from(callee_env));
+    let body_result = eval_stmt(func.body, ctx);
+    *ctx.env = caller_env;
+    ctx.call_stack.pop();
+
+    let result = match body_result? {
+        ExecResult::Return(v) => v,
+        // a stray `break`/`continue` is rejected by the type checker
+        ExecResult::Normal | ExecResult::Break | ExecResult::Continue => Value::Unit,
+    };
+
+    Ok(result)
+}
+
+fn eval_list<W: Write>(
+    expr_id: AstId,
+    ctx: &mut EvalCtx<W>,
+) -> Result<Rc<RefCell<Vec<Value>>>, EvalError> {
+    match eval_expr(expr_id, ctx)? {
+        Value::List(list) => Ok(list),
+        _ => Err(EvalError::NotIndexable(ctx.span(expr_id))),
+    }
+}
+
+fn eval_int<W: Write>(expr_id: AstId, ctx: &mut EvalCtx<W>) -> Result<i64, EvalError> {
+    match eval_expr(expr_id, ctx)? {
+        Value::Int(i) => Ok(i),
+        _ => Err(EvalError::TypeError(types::int_type(), ctx.span(expr_id))),
+    }
+}
+
+// Evaluates `expr_id` as a position in `list`, failing when it is out of range.
+fn eval_index<W: Write>(
+    expr_id: AstId,
+    list: &[Value],
+    ctx: &mut EvalCtx<W>,
+) -> Result<usize, EvalError> {
+    let index = eval_int(expr_id, ctx)?;
+    if index < 0 || index as usize >= list.len() {
+        return Err(EvalError::IndexOutOfBounds {
+            index,
+            len: list.len(),
+            span: ctx.span(expr_id),
+        });
+    }
+    Ok(index as usize)
+}
+
+fn eval_assign<W: Write>(
+    target: AstId,
+    op: AssignOp,
+    value: AstId,
+    ctx: &mut EvalCtx<W>,
+) -> Result<Value, EvalError> {
+    let span = ctx.span(target);
+    match ctx
+        .ast
+        .get_expr(target)
+        .ok_or(EvalError::ExprNotFound(target))?
+    {
+        RuntimeExpr::Variable(name) => {
+            let current = ctx
+                .env
+                .get(name)
+                .map_err(|_| EvalError::UndeclaredAssignment(name.clone(), span))?;
+            let rhs = eval_expr(value, ctx)?;
+            let new = apply_assign_op(op, current, rhs, ctx.span(value), ctx)?;
+            ctx.env
+                .assign(name, new.clone())
+                .map_err(|_| EvalError::UndeclaredAssignment(name.clone(), span))?;
+            Ok(new)
+        }
+
+        RuntimeExpr::FieldAccess {
+            target: object,
+            field,
+        } => {
+            let fields = match eval_expr(*object, ctx)? {
+                Value::Struct { fields, .. } => fields,
+                _ => return Err(EvalError::NoSuchField(field.clone(), span)),
             };
+            let current = fields
+                .borrow()
+                .iter()
+                .find(|(name, _)| name == field)
+                .map(|(_, v)| v.clone())
+                .ok_or_else(|| EvalError::NoSuchField(field.clone(), span))?;
+            let rhs = eval_expr(value, ctx)?;
+            let new = apply_assign_op(op, current, rhs, ctx.span(value), ctx)?;
+            if let Some(slot) = fields
+                .borrow_mut()
+                .iter_mut()
+                .find(|(name, _)| name == field)
+            {
+                slot.1 = new.clone();
+            }
+            Ok(new)
+        }
 
-            Ok(result)
+        RuntimeExpr::Index {
+            target: list_expr,
+            index,
+        } => {
+            let list = eval_list(*list_expr, ctx)?;
+            let i = eval_index(*index, &list.borrow(), ctx)?;
+            let current = list.borrow()[i].clone();
+            let rhs = eval_expr(value, ctx)?;
+            let new = apply_assign_op(op, current, rhs, ctx.span(value), ctx)?;
+            // the right-hand side may have shrunk the list
+            match list.borrow_mut().get_mut(i) {
+                Some(slot) => *slot = new.clone(),
+                None => {
+                    return Err(EvalError::IndexOutOfBounds {
+                        index: i as i64,
+                        len: list.borrow().len(),
+                        span,
+                    })
+                }
+            }
+            Ok(new)
         }
+
+        // rejected by the parser
+        _ => Err(EvalError::Unimplemented(span)),
+    }
+}
+
+fn apply_assign_op<W: Write>(
+    op: AssignOp,
+    current: Value,
+    rhs: Value,
+    span: Span,
+    ctx: &mut EvalCtx<W>,
+) -> Result<Value, EvalError> {
+    match (op, current, rhs) {
+        (AssignOp::Assign, _, rhs) => Ok(rhs),
+        (AssignOp::Add, Value::String(x), Value::String(y)) => {
+            ctx.charge(x.len() + y.len(), span)?;
+            Ok(Value::String(x + &y))
+        }
+        (op, Value::Int(x), Value::Int(y)) => int_op(op, x, y, span),
+        _ => Err(EvalError::TypeError(types::int_type(), span)),
+    }
+}
+
+// Integer arithmetic, failing instead of wrapping around or dividing by zero.
+fn int_op(op: AssignOp, x: i64, y: i64, span: Span) -> Result<Value, EvalError> {
+    let result = match op {
+        AssignOp::Assign => Some(y),
+        AssignOp::Add => x.checked_add(y),
+        AssignOp::Sub => x.checked_sub(y),
+        AssignOp::Mult => x.checked_mul(y),
+        AssignOp::Div if y == 0 => return Err(EvalError::DivisionByZero(span)),
+        AssignOp::Div => x.checked_div(y),
+    };
+    result
+        .map(Value::Int)
+        .ok_or(EvalError::IntegerOverflow(span))
+}
+
+#[inline(never)]
+fn eval_for_each<W: Write>(
+    var: &str,
+    iterable: AstId,
+    body: AstId,
+    ctx: &mut EvalCtx<W>,
+) -> Result<ExecResult, EvalError> {
+    let value = eval_expr(iterable, ctx)?;
+    // iterate over a snapshot so the body may modify the list
+    let elems = value
+        .enumerate()
+        .ok_or_else(|| EvalError::NotIterable(ctx.span(iterable)))?
+        .clone();
+
+    for elem in elems {
+        ctx.env.push_scope();
+        ctx.env.define(var.to_string(), elem);
+        let res = eval_stmt(body, ctx)?;
+        ctx.env.pop_scope();
+
+        match res {
+            ExecResult::Return(v) => return Ok(ExecResult::Return(v)),
+            ExecResult::Break => break,
+            ExecResult::Normal | ExecResult::Continue => {}
+        }
+    }
+
+    Ok(ExecResult::Normal)
+}
+
+#[inline(never)]
+fn eval_while<W: Write>(
+    cond: AstId,
+    body: AstId,
+    ctx: &mut EvalCtx<W>,
+) -> Result<ExecResult, EvalError> {
+    loop {
+        match eval_expr(cond, ctx)? {
+            Value::Bool(true) => {}
+            Value::Bool(false) => break,
+            _ => return Err(EvalError::TypeError(types::bool_type(), ctx.span(cond))),
+        }
+
+        match eval_stmt(body, ctx)? {
+            ExecResult::Return(v) => return Ok(ExecResult::Return(v)),
+            ExecResult::Break => break,
+            ExecResult::Normal | ExecResult::Continue => {}
+        }
+    }
+
+    Ok(ExecResult::Normal)
+}
+
+// Runs the first arm that matches the value of `scrutinee`.
+#[inline(never)]
+fn eval_match<W: Write>(
+    stmt_id: AstId,
+    scrutinee: AstId,
+    arms: &[MatchArm],
+    ctx: &mut EvalCtx<W>,
+) -> Result<ExecResult, EvalError> {
+    let value = eval_expr(scrutinee, ctx)?;
+    for arm in arms {
+        if let Some(res) = eval_arm(arm, &value, ctx)? {
+            return Ok(res);
+        }
+    }
+    Err(EvalError::NoMatch(ctx.span(stmt_id)))
+}
+
+// Runs `arm` if its pattern matches `value` and its guard holds, with the
+// pattern's bindings in a scope of their own; `None` if the arm was skipped.
+fn eval_arm<W: Write>(
+    arm: &MatchArm,
+    value: &Value,
+    ctx: &mut EvalCtx<W>,
+) -> Result<Option<ExecResult>, EvalError> {
+    let mut bindings = Vec::new();
+    if !match_pattern(&arm.pattern, value, &mut bindings) {
+        return Ok(None);
+    }
+
+    // an error in the guard or the body may leave inner scopes pushed, so
+    // the scope the arm started in is restored however it is left
+    let scope = ctx.env.current();
+    ctx.env.push_scope();
+    for (name, value) in bindings {
+        ctx.env.define(name, value);
+    }
+    let res = eval_guarded_body(arm, ctx);
+    *ctx.env = EnvHandler::from(scope);
+    res
+}
+
+// Runs the body of `arm` unless its guard is false; `None` if it is.
+fn eval_guarded_body<W: Write>(
+    arm: &MatchArm,
+    ctx: &mut EvalCtx<W>,
+) -> Result<Option<ExecResult>, EvalError> {
+    if let Some(guard) = arm.guard {
+        match eval_expr(guard, ctx)? {
+            Value::Bool(true) => {}
+            Value::Bool(false) => return Ok(None),
+            _ => return Err(EvalError::TypeError(types::bool_type(), ctx.span(guard))),
+        }
+    }
+    eval_stmt(arm.body, ctx).map(Some)
+}
+
+// Whether `value` has the shape of `pattern`, collecting the values its
+// names bind.
+fn match_pattern(pattern: &Pattern, value: &Value, bindings: &mut Vec<(String, Value)>) -> bool {
+    match (&pattern.kind, value) {
+        (PatternKind::Wildcard, _) => true,
+        (PatternKind::Binding(name), _) => {
+            bindings.push((name.clone(), value.clone()));
+            true
+        }
+        (PatternKind::Int(n), Value::Int(v)) => n == v,
+        (PatternKind::String(s), Value::String(v)) => s == v,
+        (PatternKind::Bool(b), Value::Bool(v)) => b == v,
+        (PatternKind::List { items, rest }, Value::List(list)) => {
+            let elems = list.borrow();
+            let fits = match rest {
+                Some(_) => elems.len() >= items.len(),
+                None => elems.len() == items.len(),
+            };
+            fits && items
+                .iter()
+                .zip(elems.iter())
+                .all(|(p, v)| match_pattern(p, v, bindings))
+                && rest.as_ref().is_none_or(|rest| {
+                    let tail = elems[items.len()..].to_vec();
+                    match_pattern(rest, &Value::List(Rc::new(RefCell::new(tail))), bindings)
+                })
+        }
+        (
+            PatternKind::Struct { name, fields },
+            Value::Struct {
+                type_name,
+                fields: values,
+            },
+        ) => {
+            let values = values.borrow();
+            name == type_name
+                && fields.iter().all(|(field, p)| {
+                    values
+                        .iter()
+                        .find(|(n, _)| n == field)
+                        .is_some_and(|(_, v)| match_pattern(p, v, bindings))
+                })
+        }
+        (
+            PatternKind::Variant {
+                enum_name,
+                variant,
+                payload,
+            },
+            Value::Variant {
+                enum_name: value_enum,
+                variant: value_variant,
+                payload: values,
+            },
+        ) => {
+            enum_name == value_enum
+                && variant == value_variant
+                && match (payload, values.as_ref()) {
+                    (Payload::Unit, Payload::Unit) => true,
+                    (Payload::Tuple(ps), Payload::Tuple(vs)) => {
+                        ps.len() == vs.len()
+                            && ps
+                                .iter()
+                                .zip(vs)
+                                .all(|(p, v)| match_pattern(p, v, bindings))
+                    }
+                    (Payload::Struct(ps), Payload::Struct(vs)) => ps.iter().all(|(field, p)| {
+                        vs.iter()
+                            .find(|(n, _)| n == field)
+                            .is_some_and(|(_, v)| match_pattern(p, v, bindings))
+                    }),
+                    _ => false,
+                }
+        }
+        _ => false,
     }
 }
 
 pub fn eval_stmt<W: Write>(stmt_id: AstId, ctx: &mut EvalCtx<W>) -> Result<ExecResult, EvalError> {
+    ctx.tick(stmt_id)?;
     match ctx
         .ast
         .get_stmt(stmt_id)
         .ok_or(EvalError::StmtNotFound(stmt_id))?
     {
         RuntimeStmt::Print(expr) => {
+            ctx.require(Capability::Print, ctx.span(stmt_id))?;
             let value = eval_expr(*expr, ctx)?;
             writeln!(ctx.out, "{}", value).unwrap();
-            Ok(ExecResult::Continue)
+            Ok(ExecResult::Normal)
         }
 
         RuntimeStmt::If {
@@ -162,64 +1112,94 @@ pub fn eval_stmt<W: Write>(stmt_id: AstId, ctx: &mut EvalCtx<W>) -> Result<ExecR
             Value::Bool(true) => eval_stmt(*body, ctx),
             Value::Bool(false) => match else_branch {
                 Some(else_stmt) => eval_stmt(*else_stmt, ctx),
-                None => Ok(ExecResult::Continue),
+                None => Ok(ExecResult::Normal),
             },
-            _ => Err(EvalError::TypeError(types::bool_type())),
+            _ => Err(EvalError::TypeError(types::bool_type(), ctx.span(*cond))),
         },
 
         RuntimeStmt::ForEach {
             var,
             iterable,
             body,
-        } => {
-            let value = eval_expr(*iterable, ctx);
+        } => eval_for_each(var, *iterable, *body, ctx),
 
-            for elem in value?.enumerate().iter() {
-                ctx.env.push_scope();
-                ctx.env.define(var.clone(), elem.clone());
+        RuntimeStmt::While { cond, body } => eval_while(*cond, *body, ctx),
 
-                match eval_stmt(*body, ctx)? {
-                    ExecResult::Return(v) => {
-                        ctx.env.pop_scope();
-                        return Ok(ExecResult::Return(v));
-                    }
-                    ExecResult::Continue => {}
-                }
+        RuntimeStmt::Match { scrutinee, arms } => eval_match(stmt_id, *scrutinee, arms, ctx),
 
-                ctx.env.pop_scope();
-            }
+        RuntimeStmt::Break => Ok(ExecResult::Break),
 
-            Ok(ExecResult::Continue)
-        }
+        RuntimeStmt::Continue => Ok(ExecResult::Continue),
 
         RuntimeStmt::ExprStmt(expr) => {
             eval_expr(*expr, ctx)?;
-            Ok(ExecResult::Continue)
+            Ok(ExecResult::Normal)
         }
 
-        RuntimeStmt::VarDecl { name, expr } => {
+        RuntimeStmt::VarDecl { name, expr, .. } => {
             let value = eval_expr(*expr, ctx)?;
             ctx.env.define(name.clone(), value);
-            Ok(ExecResult::Continue)
+            Ok(ExecResult::Normal)
         }
 
         RuntimeStmt::Block(stmts) => {
             ctx.env.push_scope();
-            let res = eval_stmts(stmts, ctx);
+            let res = eval_scope(stmts, ctx);
             ctx.env.pop_scope();
             res
         }
 
-        RuntimeStmt::FnDecl { name, params, body } => {
-            //let func = Rc::new(Function {
-            //    params: params.clone(),
-            //    body: body.clone(),
-            //    env: Rc::clone(&env),
-            //});
+        RuntimeStmt::FnDecl {
+            name, params, body, ..
+        } => {
+            // capturing the scope the function is defined in also makes the
+            // function visible to itself for recursion
+            let func = Rc::new(Function {
+                name: Some(name.clone()),
+                params: params.iter().map(|p| p.name.clone()).collect(),
+                body: *body,
+                env: ctx.env.current(),
+            });
+
+            ctx.env.define(name.clone(), Value::Function(func));
 
-            //ctx.env.define(name.clone(), Value::Function(func));
+            Ok(ExecResult::Normal)
+        }
 
-            Ok(ExecResult::Continue)
+        RuntimeStmt::ImplDecl { methods, .. } => {
+            for method_id in methods {
+                if let Some(RuntimeStmt::FnDecl {
+                    name, params, body, ..
+                }) = ctx.ast.get_stmt(*method_id)
+                {
+                    let func = Rc::new(Function {
+                        name: Some(name.clone()),
+                        params: params.iter().map(|p| p.name.clone()).collect(),
+                        body: *body,
+                        env: ctx.env.current(),
+                    });
+                    ctx.methods.insert(*method_id, func);
+                }
+            }
+            Ok(ExecResult::Normal)
+        }
+
+        // registered with the declaration registry during metaprocessing
+        RuntimeStmt::StructDecl { .. }
+        | RuntimeStmt::EnumDecl { .. }
+        | RuntimeStmt::TraitDecl { .. } => Ok(ExecResult::Normal),
+
+        RuntimeStmt::Import(name) => {
+            let module = match ctx.modules.get(name) {
+                Some(module) => module.clone(),
+                None => {
+                    let module = eval_module(name, stmt_id, ctx)?;
+                    ctx.modules.insert(name.clone(), module.clone());
+                    module
+                }
+            };
+            ctx.env.define(name.clone(), module);
+            Ok(ExecResult::Normal)
         }
 
         RuntimeStmt::Return(opt_expr) => {
@@ -230,16 +1210,27 @@ pub fn eval_stmt<W: Write>(stmt_id: AstId, ctx: &mut EvalCtx<W>) -> Result<ExecR
             Ok(ExecResult::Return(val))
         }
 
+        RuntimeStmt::Defer(stmt) => {
+            // every block, module and program runs through `eval_scope`
+            if let Some(frame) = ctx.deferred.last_mut() {
+                frame.push(*stmt);
+            }
+            Ok(ExecResult::Normal)
+        }
+
         RuntimeStmt::Gen(stmts) => {
-            //let meta = ctx.meta_ctx.as_deref_mut().expect("gen outside meta");
-            //let substituted = subst_stmts(stmts, &env);
-            //for stmt in substituted {
-            //    meta.emitted.push(stmt.clone());
-            //}
-            Ok(ExecResult::Continue)
+            if ctx.meta_ctx.is_none() {
+                return Err(EvalError::GenOutsideMeta(ctx.span(stmt_id)));
+            }
+            let values = eval_splices(stmts, ctx)?;
+            let meta = ctx.meta_ctx.as_mut().expect("checked above");
+            let emitted = subst_stmts(stmts, ctx.ast, ctx.env, &values, meta)?;
+            meta.emitted.extend(emitted);
+            Ok(ExecResult::Normal)
         }
 
-        _ => Err(EvalError::Unimplemented),
+        // an emitted `meta` block runs, and is replaced, when it is staged
+        RuntimeStmt::MetaBlock(_) => Ok(ExecResult::Normal),
     }
 }
 
@@ -249,13 +1240,32 @@ pub fn eval_stmts<W: Write>(
 ) -> Result<ExecResult, EvalError> {
     for stmt in stmts {
         match eval_stmt(*stmt, ctx)? {
-            ExecResult::Continue => {}
-            ExecResult::Return(v) => {
-                return Ok(ExecResult::Return(v));
+            ExecResult::Normal => {}
+            other => return Ok(other),
+        }
+    }
+    Ok(ExecResult::Normal)
+}
+
+// Runs `stmts` as one block, then the statements they deferred, last first.
+// Those run however the block is left, an error included, in the scope the
+// block had; the first error is the one reported.
+fn eval_scope<W: Write>(stmts: &Vec<AstId>, ctx: &mut EvalCtx<W>) -> Result<ExecResult, EvalError> {
+    let scope = ctx.env.current();
+    ctx.deferred.push(Vec::new());
+    let mut result = eval_stmts(stmts, ctx);
+    let deferred = ctx.deferred.pop().unwrap_or_default();
+
+    // an error may leave inner scopes pushed
+    *ctx.env = EnvHandler::from(scope);
+    for stmt in deferred.into_iter().rev() {
+        if let Err(error) = eval_stmt(stmt, ctx) {
+            if result.is_ok() {
+                result = Err(error);
             }
         }
     }
-    Ok(ExecResult::Continue)
+    result
 }
 
 pub fn eval<W: Write>(
@@ -265,11 +1275,85 @@ pub fn eval<W: Write>(
     meta_ctx: &mut Option<MetaContext>,
     out: &mut W,
 ) -> Result<ExecResult, EvalError> {
+    eval_with_budget(ast, root_stmts, env, meta_ctx, out, Budget::default())
+}
+
+/// Like `eval`, but stops once `budget` is used up.
+pub fn eval_with_budget<W: Write>(
+    ast: &RuntimeAst,
+    root_stmts: &Vec<AstId>,
+    env: EnvRef,
+    meta_ctx: &mut Option<MetaContext>,
+    out: &mut W,
+    budget: Budget,
+) -> Result<ExecResult, EvalError> {
+    run(ast, env, meta_ctx, out, None, budget, |ctx| {
+        eval_scope(root_stmts, ctx)
+    })
+}
+
+/// Like `eval`, but holds compile-time code to `policy` and its budget.
+pub fn eval_with_policy<W: Write>(
+    ast: &RuntimeAst,
+    root_stmts: &Vec<AstId>,
+    env: EnvRef,
+    meta_ctx: &mut Option<MetaContext>,
+    out: &mut W,
+    policy: &MetaPolicy,
+) -> Result<ExecResult, EvalError> {
+    run(
+        ast,
+        env,
+        meta_ctx,
+        out,
+        Some(policy),
+        policy.budget,
+        |ctx| eval_scope(root_stmts, ctx),
+    )
+}
+
+/// Evaluates the expression `expr` against `env`, holding it to `policy`
+/// like compile-time code; the metaprocessor folds calls to `meta fn`s with it.
+pub fn eval_expr_with_policy<W: Write>(
+    ast: &RuntimeAst,
+    expr: AstId,
+    env: EnvRef,
+    out: &mut W,
+    policy: &MetaPolicy,
+) -> Result<Value, EvalError> {
+    run(
+        ast,
+        env,
+        &mut None,
+        out,
+        Some(policy),
+        policy.budget,
+        |ctx| eval_expr(expr, ctx),
+    )
+}
+
+fn run<W: Write, T>(
+    ast: &RuntimeAst,
+    env: EnvRef,
+    meta_ctx: &mut Option<MetaContext>,
+    out: &mut W,
+    policy: Option<&MetaPolicy>,
+    budget: Budget,
+    body: impl FnOnce(&mut EvalCtx<&mut W>) -> Result<T, EvalError>,
+) -> Result<T, EvalError> {
     let mut ctx = EvalCtx {
         ast,
         env: &mut EnvHandler::from(env),
         meta_ctx,
         out,
+        modules: HashMap::new(),
+        methods: HashMap::new(),
+        deferred: Vec::new(),
+        policy,
+        budget,
+        fuel: budget.fuel,
+        call_stack: Vec::new(),
+        memory: 0,
     };
-    eval_stmts(&root_stmts, &mut ctx)
+    body(&mut ctx)
 }
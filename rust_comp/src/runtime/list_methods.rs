@@ -0,0 +1,114 @@
+use super::interpreter::{call_function, EvalCtx, EvalError};
+use super::value::{Function, Value};
+use crate::frontend::span::Span;
+use crate::semantics::types::types;
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+type ListRef = Rc<RefCell<Vec<Value>>>;
+
+/// Dispatches `list.method(args)` for the builtin list methods.
+pub fn call_list_method<W: Write>(
+    list: &ListRef,
+    method: &str,
+    args: Vec<Value>,
+    span: Span,
+    ctx: &mut EvalCtx<W>,
+) -> Result<Value, EvalError> {
+    match (method, args.as_slice()) {
+        ("len", []) => Ok(Value::Int(list.borrow().len() as i64)),
+
+        ("push", [value]) => {
+            ctx.charge_values(1, span)?;
+            list.borrow_mut().push(value.clone());
+            Ok(Value::Unit)
+        }
+
+        ("pop", []) => list.borrow_mut().pop().ok_or(EvalError::EmptyList(span)),
+
+        ("insert", [index, value]) => {
+            let len = list.borrow().len();
+            // inserting at `len` appends
+            let i = position(index, len, true, span)?;
+            ctx.charge_values(1, span)?;
+            list.borrow_mut().insert(i, value.clone());
+            Ok(Value::Unit)
+        }
+
+        ("remove", [index]) => {
+            let len = list.borrow().len();
+            let i = position(index, len, false, span)?;
+            Ok(list.borrow_mut().remove(i))
+        }
+
+        ("contains", [value]) => Ok(Value::Bool(list.borrow().contains(value))),
+
+        ("map", [f]) => {
+            let f = as_function(f, 1, span)?;
+            let mut out = Vec::new();
+            for elem in snapshot(list) {
+                out.push(call_function(&f, vec![elem], span, ctx)?);
+            }
+            ctx.charge_values(out.len(), span)?;
+            Ok(Value::List(Rc::new(RefCell::new(out))))
+        }
+
+        ("filter", [f]) => {
+            let f = as_function(f, 1, span)?;
+            let mut out = Vec::new();
+            for elem in snapshot(list) {
+                match call_function(&f, vec![elem.clone()], span, ctx)? {
+                    Value::Bool(true) => out.push(elem),
+                    Value::Bool(false) => {}
+                    _ => return Err(EvalError::TypeError(types::bool_type(), span)),
+                }
+            }
+            ctx.charge_values(out.len(), span)?;
+            Ok(Value::List(Rc::new(RefCell::new(out))))
+        }
+
+        ("fold", [init, f]) => {
+            let f = as_function(f, 2, span)?;
+            let mut acc = init.clone();
+            for elem in snapshot(list) {
+                acc = call_function(&f, vec![acc, elem], span, ctx)?;
+            }
+            Ok(acc)
+        }
+
+        (
+            "len" | "push" | "pop" | "insert" | "remove" | "contains" | "map" | "filter" | "fold",
+            _,
+        ) => Err(EvalError::ArgumentMismatch(span)),
+
+        _ => Err(EvalError::UnknownMethod(method.to_string(), span)),
+    }
+}
+
+// Callbacks may modify the list, so they iterate over a copy.
+fn snapshot(list: &ListRef) -> Vec<Value> {
+    list.borrow().clone()
+}
+
+// Checks that `index` is an int in `0..len`, or `0..=len` when `allow_end` is set.
+fn position(index: &Value, len: usize, allow_end: bool, span: Span) -> Result<usize, EvalError> {
+    let end = if allow_end { len + 1 } else { len };
+    match index {
+        Value::Int(i) if *i >= 0 && (*i as usize) < end => Ok(*i as usize),
+        Value::Int(i) => Err(EvalError::IndexOutOfBounds {
+            index: *i,
+            len,
+            span,
+        }),
+        _ => Err(EvalError::TypeError(types::int_type(), span)),
+    }
+}
+
+fn as_function(value: &Value, arity: usize, span: Span) -> Result<Rc<Function>, EvalError> {
+    match value {
+        Value::Function(f) if f.params.len() == arity => Ok(Rc::clone(f)),
+        Value::Function(_) => Err(EvalError::ArgumentMismatch(span)),
+        _ => Err(EvalError::NonFunctionCall(span)),
+    }
+}
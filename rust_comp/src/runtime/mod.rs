@@ -1,4 +1,7 @@
+pub mod budget;
+pub mod builtins;
 pub mod environment;
 pub mod interpreter;
+pub mod list_methods;
 pub mod result;
 pub mod value;
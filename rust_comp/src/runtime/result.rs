@@ -1,6 +1,9 @@
 use crate::runtime::value::Value;
 
+/// How control leaves a statement.
 pub enum ExecResult {
-    Continue,
+    Normal,
     Return(Value),
+    Break,
+    Continue,
 }
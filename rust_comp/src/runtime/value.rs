@@ -1,4 +1,6 @@
 use crate::frontend::id_provider::*;
+use crate::frontend::meta_ast::Payload;
+use crate::runtime::builtins::Builtin;
 use crate::runtime::environment::*;
 use std::cell::RefCell;
 use std::fmt;
@@ -17,23 +19,122 @@ pub enum Value {
 
     List(Rc<RefCell<Vec<Value>>>),
 
+    // a value of an `enum`; struct payloads keep declaration order
+    Variant {
+        enum_name: String,
+        variant: String,
+        payload: Rc<Payload<Value>>,
+    },
+
     Function(Rc<Function>),
 
+    Module(Rc<Module>),
+
+    Builtin(Builtin),
+
+    // the code a `quote` evaluated to: copies of its statements, with fresh
+    // ids, that a `gen` or another `quote` can splice in
+    Ast(Rc<Vec<AstId>>),
+
     Unit,
 }
 
-#[derive(Debug, Clone)]
+/// An imported module; its members are the top-level names of its environment.
+pub struct Module {
+    pub name: String,
+    pub env: Rc<RefCell<Environment>>,
+}
+
+impl fmt::Debug for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Module")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Clone)]
 pub struct Function {
+    // `None` for a function expression
+    pub name: Option<String>,
     pub params: Vec<String>,
     pub body: AstId,
     pub env: Rc<RefCell<Environment>>,
 }
 
+// The captured environment is left out: it usually contains the function
+// itself, which would recurse forever.
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("name", &self.name)
+            .field("params", &self.params)
+            .field("body", &self.body)
+            .finish_non_exhaustive()
+    }
+}
+
 impl Value {
-    pub fn enumerate(&self) -> std::cell::Ref<'_, Vec<Value>> {
+    /// The name impls for this value's type are declared under: `int`,
+    /// `string`, `bool`, `List`, or the struct or enum's name.
+    pub fn type_name(&self) -> Option<&str> {
         match self {
-            Value::List(list) => list.borrow(),
-            _ => panic!("iterable expeced"),
+            Value::Int(_) => Some("int"),
+            Value::String(_) => Some("string"),
+            Value::Bool(_) => Some("bool"),
+            Value::List(_) => Some("List"),
+            Value::Struct { type_name, .. } => Some(type_name),
+            Value::Variant { enum_name, .. } => Some(enum_name),
+            _ => None,
+        }
+    }
+
+    /// The elements a `for` loop visits, if this value can be iterated.
+    pub fn enumerate(&self) -> Option<std::cell::Ref<'_, Vec<Value>>> {
+        match self {
+            Value::List(list) => Some(list.borrow()),
+            _ => None,
+        }
+    }
+}
+
+// Structural equality; functions and code values are only equal to
+// themselves.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Unit, Value::Unit) => true,
+            (Value::List(a), Value::List(b)) => Rc::ptr_eq(a, b) || *a.borrow() == *b.borrow(),
+            (
+                Value::Struct {
+                    type_name: t1,
+                    fields: f1,
+                },
+                Value::Struct {
+                    type_name: t2,
+                    fields: f2,
+                },
+            ) => t1 == t2 && (Rc::ptr_eq(f1, f2) || *f1.borrow() == *f2.borrow()),
+            (
+                Value::Variant {
+                    enum_name: e1,
+                    variant: v1,
+                    payload: p1,
+                },
+                Value::Variant {
+                    enum_name: e2,
+                    variant: v2,
+                    payload: p2,
+                },
+            ) => e1 == e2 && v1 == v2 && p1 == p2,
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            (Value::Module(a), Value::Module(b)) => Rc::ptr_eq(a, b),
+            (Value::Builtin(a), Value::Builtin(b)) => a == b,
+            (Value::Ast(a), Value::Ast(b)) => Rc::ptr_eq(a, b),
+            _ => false,
         }
     }
 }
@@ -67,6 +168,15 @@ impl fmt::Display for Value {
                 }
                 write!(f, "}}")
             }
+            Value::Variant {
+                variant, payload, ..
+            } => {
+                write!(f, "{variant}")?;
+                payload.write_with(f, |f, v| write!(f, "{v}"))
+            }
+            Value::Module(module) => write!(f, "<module {}>", module.name),
+            Value::Builtin(builtin) => write!(f, "<builtin {builtin}>"),
+            Value::Ast(_) => write!(f, "<code>"),
 
             _ => write!(f, ""),
         }
@@ -0,0 +1,298 @@
+use crate::frontend::id_provider::AstId;
+use crate::frontend::meta_ast::{MetaAst, MetaExpr, MetaStmt, Payload};
+use crate::runtime::environment::{EnvRef, Environment};
+use crate::semantics::meta::runtime_ast::{RuntimeAst, RuntimeStmt};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// The compile-time environment of one module. Every `meta` block the module
+/// runs at a level shares that level's environment, so a `meta var` or
+/// `meta fn` stays defined for the rest of the module.
+///
+/// The names the module's blocks declare are known before any of them runs,
+/// so a block that uses a name another block declares can wait for it, even
+/// when the declaration comes later in the source. Otherwise blocks that
+/// share a name run in source order, so what one assigns or mutates is seen
+/// by the blocks after it.
+pub struct CompileTimeEnv {
+    // the environment of each level, starting at level 1
+    levels: Vec<EnvRef>,
+    // what the module's `meta` blocks declare, by level
+    declared: HashSet<(usize, String)>,
+    // what the blocks that ran so far declared
+    defined: HashSet<(usize, String)>,
+    // the last block scheduled at a level to use or declare each name
+    last_use: HashMap<(usize, String), AstId>,
+    // the blocks that ran so far
+    ran: HashSet<AstId>,
+}
+
+impl CompileTimeEnv {
+    pub fn new(meta_ast: &MetaAst, root_stmts: &[AstId]) -> Self {
+        let mut env = Self {
+            levels: Vec::new(),
+            declared: HashSet::new(),
+            defined: HashSet::new(),
+            last_use: HashMap::new(),
+            ran: HashSet::new(),
+        };
+        for stmt in root_stmts {
+            env.scan(meta_ast, *stmt, 0);
+        }
+        env
+    }
+
+    pub fn level(&mut self, level: usize) -> EnvRef {
+        if self.levels.len() < level {
+            self.levels.resize_with(level, Environment::new);
+        }
+        Rc::clone(&self.levels[level - 1])
+    }
+
+    // Records the declarations of the `meta` blocks in `id`, which is at
+    // `level`. Those under a `gen` are only declared once they are emitted.
+    fn scan(&mut self, meta_ast: &MetaAst, id: AstId, level: usize) {
+        let children = match meta_ast.get_stmt(id) {
+            Some(MetaStmt::MetaBlock(body)) => {
+                for name in meta_declarations(meta_ast, *body) {
+                    self.declared.insert((level + 1, name));
+                }
+                self.scan(meta_ast, *body, level + 1);
+                return;
+            }
+            Some(MetaStmt::Block(stmts)) => stmts.clone(),
+            Some(MetaStmt::If {
+                body, else_branch, ..
+            }) => [*body].into_iter().chain(*else_branch).collect(),
+            Some(
+                MetaStmt::While { body, .. }
+                | MetaStmt::ForEach { body, .. }
+                | MetaStmt::FnDecl { body, .. }
+                | MetaStmt::Defer(body),
+            ) => vec![*body],
+            Some(MetaStmt::ImplDecl { methods, .. }) => methods.clone(),
+            Some(MetaStmt::Match { arms, .. }) => arms.iter().map(|arm| arm.body).collect(),
+            _ => return,
+        };
+        for child in children {
+            self.scan(meta_ast, child, level);
+        }
+    }
+
+    /// Whether a `meta` block of the module declares `name` at `level`.
+    pub fn declares(&self, level: usize, name: &str) -> bool {
+        self.declared.contains(&(level, name.to_string()))
+    }
+
+    /// Whether a block that declares `name` at `level` has run.
+    pub fn defines(&self, level: usize, name: &str) -> bool {
+        self.defined.contains(&(level, name.to_string()))
+    }
+
+    /// The names declared by other blocks at `level`, and not yet defined,
+    /// that the `meta` block with body `body` has to wait for.
+    pub fn waits_for(&self, meta_ast: &MetaAst, body: AstId, level: usize) -> Vec<String> {
+        let own = meta_declarations(meta_ast, body);
+        let mut names = Vec::new();
+        uses_in_stmt(meta_ast, body, &mut names);
+        let mut waits: Vec<String> = Vec::new();
+        for name in names {
+            let key = (level, name);
+            if self.declared.contains(&key)
+                && !self.defined.contains(&key)
+                && !own.contains(&key.1)
+                && !waits.contains(&key.1)
+            {
+                waits.push(key.1);
+            }
+        }
+        waits
+    }
+
+    /// The blocks scheduled earlier at `level` that the `meta` block `block`,
+    /// with body `body`, runs after: for each name it uses, the last one to
+    /// use or declare it, unless that one has run. Names the block declares
+    /// itself are left out, as earlier blocks using them wait for it.
+    pub fn follows(
+        &mut self,
+        meta_ast: &MetaAst,
+        block: AstId,
+        body: AstId,
+        level: usize,
+    ) -> Vec<AstId> {
+        let own = meta_declarations(meta_ast, body);
+        let mut names = Vec::new();
+        uses_in_stmt(meta_ast, body, &mut names);
+        let mut follows = Vec::new();
+        for name in &names {
+            if own.contains(name) {
+                continue;
+            }
+            if let Some(earlier) = self.last_use.get(&(level, name.clone())) {
+                if !self.ran.contains(earlier) && !follows.contains(earlier) {
+                    follows.push(*earlier);
+                }
+            }
+        }
+        for name in names.into_iter().chain(own) {
+            self.last_use.insert((level, name), block);
+        }
+        follows
+    }
+
+    /// Marks the `meta` block `block` as run and what its body `body`
+    /// declares at `level` as defined, returning the names.
+    pub fn define(
+        &mut self,
+        runtime_ast: &RuntimeAst,
+        block: AstId,
+        body: AstId,
+        level: usize,
+    ) -> Vec<String> {
+        self.ran.insert(block);
+        let stmts = match runtime_ast.get_stmt(body) {
+            Some(RuntimeStmt::Block(stmts)) => stmts.clone(),
+            _ => vec![body],
+        };
+        let names: Vec<String> = stmts
+            .iter()
+            .filter_map(|stmt| match runtime_ast.get_stmt(*stmt) {
+                Some(RuntimeStmt::VarDecl { name, .. } | RuntimeStmt::FnDecl { name, .. }) => {
+                    Some(name.clone())
+                }
+                _ => None,
+            })
+            .collect();
+        for name in &names {
+            self.defined.insert((level, name.clone()));
+        }
+        names
+    }
+}
+
+// The variables and functions the body of a `meta` block declares for the
+// rest of its level.
+fn meta_declarations(meta_ast: &MetaAst, body: AstId) -> Vec<String> {
+    let stmts = match meta_ast.get_stmt(body) {
+        Some(MetaStmt::Block(stmts)) => stmts.clone(),
+        _ => vec![body],
+    };
+    stmts
+        .iter()
+        .filter_map(|stmt| match meta_ast.get_stmt(*stmt) {
+            Some(MetaStmt::VarDecl { name, .. } | MetaStmt::FnDecl { name, .. }) => {
+                Some(name.clone())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+// Every name `id` reads or calls, including in the statements of its `gen`s
+// and `quote`s, which are filled in from the same level. Nested `meta` blocks
+// run at another level and are left out.
+fn uses_in_stmt(meta_ast: &MetaAst, id: AstId, names: &mut Vec<String>) {
+    let Some(stmt) = meta_ast.get_stmt(id) else {
+        return;
+    };
+    let (exprs, stmts): (Vec<AstId>, Vec<AstId>) = match stmt {
+        MetaStmt::ExprStmt(e) | MetaStmt::Print(e) | MetaStmt::VarDecl { expr: e, .. } => {
+            (vec![*e], vec![])
+        }
+        MetaStmt::Return(e) => (e.iter().copied().collect(), vec![]),
+        MetaStmt::FnDecl { body, .. } | MetaStmt::Defer(body) => (vec![], vec![*body]),
+        MetaStmt::ImplDecl { methods, .. } => (vec![], methods.clone()),
+        MetaStmt::If {
+            cond,
+            body,
+            else_branch,
+        } => (
+            vec![*cond],
+            [*body].into_iter().chain(*else_branch).collect(),
+        ),
+        MetaStmt::ForEach { iterable, body, .. } => (vec![*iterable], vec![*body]),
+        MetaStmt::While { cond, body } => (vec![*cond], vec![*body]),
+        MetaStmt::Match { scrutinee, arms } => (
+            [*scrutinee]
+                .into_iter()
+                .chain(arms.iter().filter_map(|arm| arm.guard))
+                .collect(),
+            arms.iter().map(|arm| arm.body).collect(),
+        ),
+        MetaStmt::Block(stmts) | MetaStmt::Gen(stmts) => (vec![], stmts.clone()),
+        MetaStmt::MetaBlock(_)
+        | MetaStmt::StructDecl { .. }
+        | MetaStmt::EnumDecl { .. }
+        | MetaStmt::TraitDecl { .. }
+        | MetaStmt::Break
+        | MetaStmt::Continue
+        | MetaStmt::Import(_)
+        | MetaStmt::Error => (vec![], vec![]),
+    };
+    for expr in exprs {
+        uses_in_expr(meta_ast, expr, names);
+    }
+    for stmt in stmts {
+        uses_in_stmt(meta_ast, stmt, names);
+    }
+}
+
+fn uses_in_expr(meta_ast: &MetaAst, id: AstId, names: &mut Vec<String>) {
+    let Some(expr) = meta_ast.get_expr(id) else {
+        return;
+    };
+    let children: Vec<AstId> = match expr {
+        MetaExpr::Variable(name) => {
+            names.push(name.clone());
+            return;
+        }
+        MetaExpr::Lambda { body, .. } => {
+            uses_in_stmt(meta_ast, *body, names);
+            return;
+        }
+        MetaExpr::Quote(stmts) => {
+            for stmt in stmts {
+                uses_in_stmt(meta_ast, *stmt, names);
+            }
+            return;
+        }
+        MetaExpr::Int(_)
+        | MetaExpr::String(_)
+        | MetaExpr::Bool(_)
+        | MetaExpr::Typeof(_)
+        | MetaExpr::Embed { .. } => vec![],
+        MetaExpr::StructLiteral { fields, .. } => fields.iter().map(|(_, e)| *e).collect(),
+        MetaExpr::Variant { payload, .. } => match payload {
+            Payload::Unit => vec![],
+            Payload::Tuple(items) => items.clone(),
+            Payload::Struct(fields) => fields.iter().map(|(_, e)| *e).collect(),
+        },
+        MetaExpr::List(items) => items.clone(),
+        MetaExpr::Call { callee, args } => [*callee].into_iter().chain(args.clone()).collect(),
+        MetaExpr::MethodCall { receiver, args, .. } => {
+            [*receiver].into_iter().chain(args.clone()).collect()
+        }
+        MetaExpr::FieldAccess { target, .. } => vec![*target],
+        MetaExpr::Index { target, index } => vec![*target, *index],
+        MetaExpr::Slice { target, start, end } => {
+            [*target].into_iter().chain(*start).chain(*end).collect()
+        }
+        MetaExpr::Assign { target, value, .. } => vec![*target, *value],
+        MetaExpr::Add(a, b)
+        | MetaExpr::Sub(a, b)
+        | MetaExpr::Mult(a, b)
+        | MetaExpr::Div(a, b)
+        | MetaExpr::Equals(a, b)
+        | MetaExpr::NotEquals(a, b)
+        | MetaExpr::Less(a, b)
+        | MetaExpr::LessEqual(a, b)
+        | MetaExpr::Greater(a, b)
+        | MetaExpr::GreaterEqual(a, b)
+        | MetaExpr::And(a, b)
+        | MetaExpr::Or(a, b) => vec![*a, *b],
+        MetaExpr::Not(a) | MetaExpr::Negate(a) | MetaExpr::Unquote(a) => vec![*a],
+    };
+    for child in children {
+        uses_in_expr(meta_ast, child, names);
+    }
+}
@@ -16,6 +16,8 @@ where
 {
     dependency_graph: HashMap<Dependency, Vec<TaskId>>,
     task_graph: HashMap<TaskId, Task<Event>>,
+    // events of tasks that were added with nothing to wait for
+    ready: Vec<Event>,
     next_id: TaskId,
 }
 
@@ -27,6 +29,7 @@ where
         Self {
             dependency_graph: HashMap::new(),
             task_graph: HashMap::new(),
+            ready: Vec::new(),
             next_id: 0,
         }
     }
@@ -35,6 +38,11 @@ where
         let id = self.next_id;
         self.next_id += 1;
 
+        if deps.is_empty() {
+            self.ready.push(event);
+            return id;
+        }
+
         self.task_graph.insert(
             id,
             Task {
@@ -53,6 +61,21 @@ where
         id
     }
 
+    /// Takes the events of tasks that had no dependencies to begin with.
+    pub fn take_ready(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.ready)
+    }
+
+    /// The events of tasks that are still waiting.
+    pub fn waiting(&self) -> impl Iterator<Item = &Event> {
+        self.task_graph.values().map(|task| &task.event)
+    }
+
+    /// The dependencies tasks are still waiting on.
+    pub fn unresolved(&self) -> impl Iterator<Item = &Dependency> {
+        self.dependency_graph.keys()
+    }
+
     pub fn resolve_dependency(&mut self, dep: Dependency) -> Vec<Event> {
         let mut ready = Vec::new();
 
@@ -18,6 +18,15 @@ pub enum ExpandedExpr {
     Mult(Box<ExpandedExpr>, Box<ExpandedExpr>),
     Div(Box<ExpandedExpr>, Box<ExpandedExpr>),
     Equals(Box<ExpandedExpr>, Box<ExpandedExpr>),
+    NotEquals(Box<ExpandedExpr>, Box<ExpandedExpr>),
+    Less(Box<ExpandedExpr>, Box<ExpandedExpr>),
+    LessEqual(Box<ExpandedExpr>, Box<ExpandedExpr>),
+    Greater(Box<ExpandedExpr>, Box<ExpandedExpr>),
+    GreaterEqual(Box<ExpandedExpr>, Box<ExpandedExpr>),
+    And(Box<ExpandedExpr>, Box<ExpandedExpr>),
+    Or(Box<ExpandedExpr>, Box<ExpandedExpr>),
+    Not(Box<ExpandedExpr>),
+    Negate(Box<ExpandedExpr>),
 
     Call {
         callee: String,
@@ -48,6 +57,14 @@ pub enum ExpandedStmt {
         body: Box<ExpandedStmt>,
     },
 
+    While {
+        cond: Box<ExpandedExpr>,
+        body: Box<ExpandedStmt>,
+    },
+
+    Break,
+    Continue,
+
     Block(Vec<ExpandedStmt>),
 
     FnDecl {
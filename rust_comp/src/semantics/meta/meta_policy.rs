@@ -0,0 +1,78 @@
+use crate::runtime::budget::Budget;
+use std::fmt;
+
+/// Something compile-time code may only do when the policy allows it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    ReadFiles,
+    Print,
+    Env,
+    Clock,
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Capability::ReadFiles => write!(f, "read files"),
+            Capability::Print => write!(f, "print to the build log"),
+            Capability::Env => write!(f, "read environment variables"),
+            Capability::Clock => write!(f, "read the clock"),
+        }
+    }
+}
+
+/// What code run by the metaprocessor (`meta` blocks and `embed`) may do.
+///
+/// The default is hermetic: files may be read and the build log written, but
+/// the environment and the clock are off so builds stay reproducible.
+#[derive(Debug, Clone)]
+pub struct MetaPolicy {
+    pub read_files: bool,
+    pub print: bool,
+    pub env: bool,
+    pub clock: bool,
+    /// Fuel and call depth a single `meta` block may use.
+    pub budget: Budget,
+    /// Bytes a single `meta` block may allocate for strings, lists and structs.
+    /// Counted cumulatively, so freed values still count.
+    pub max_memory: Option<usize>,
+}
+
+impl MetaPolicy {
+    /// Everything allowed and the same budget as ordinary runtime code.
+    pub fn unrestricted() -> Self {
+        Self {
+            read_files: true,
+            print: true,
+            env: true,
+            clock: true,
+            budget: Budget::default(),
+            max_memory: None,
+        }
+    }
+
+    pub fn allows(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::ReadFiles => self.read_files,
+            Capability::Print => self.print,
+            Capability::Env => self.env,
+            Capability::Clock => self.clock,
+        }
+    }
+}
+
+impl Default for MetaPolicy {
+    fn default() -> Self {
+        Self {
+            read_files: true,
+            print: true,
+            env: false,
+            clock: false,
+            budget: Budget {
+                fuel: Some(10_000_000),
+                ..Budget::default()
+            },
+            max_memory: Some(256 * 1024 * 1024),
+        }
+    }
+}
@@ -1,21 +1,111 @@
+use super::compile_env::CompileTimeEnv;
 use super::dependency_scheduler::*;
+use super::meta_policy::{Capability, MetaPolicy};
+use super::module_loader::ModuleLoader;
+use super::prelude::{prelude_decls, PRELUDE};
+use super::purity::is_pure;
+use super::stage::{Stage, StageEvent};
+use crate::diagnostics::source_map::SourceMap;
 use crate::frontend::id_provider::*;
+use crate::frontend::lexer::ScanError;
 use crate::frontend::meta_ast::*;
+use crate::frontend::parser::ParseError;
+use crate::frontend::span::Span;
 use crate::runtime::environment::*;
 use crate::runtime::interpreter::*;
 use crate::runtime::value::Value;
 use crate::semantics::meta::runtime_ast::*;
-use std::collections::VecDeque;
-use std::io::Write;
+use crate::util::decl_registry::{DeclRegistry, EnumDef, ImplDef, StructDef, TraitDef};
+use crate::util::external_resolver::MapResolver;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Write};
+use std::path::Path;
+use std::rc::Rc;
 
 #[derive(Debug)]
 pub enum MetaProcessError {
     ExprNotFound(AstId),
     StmtNotFound(AstId),
-    EmbedFailed { path: String, error: String },
-    UnknownType(String),
+    EmbedFailed {
+        path: String,
+        error: String,
+        span: Span,
+    },
+    UnknownType(String, Span),
+    ModuleNotFound(String, Span),
+    /// A call to the `meta fn` `function` with an argument, at `span`, that
+    /// isn't known until run time.
+    NonConstantArgument {
+        function: String,
+        span: Span,
+    },
+    /// A call to the `meta fn` `function`, whose body has side effects or
+    /// reads variables a `meta` block may change.
+    ImpureMetaCall {
+        function: String,
+        span: Span,
+    },
+    /// A second `impl` of `trait_name` for `type_name`.
+    ConflictingImpl {
+        trait_name: String,
+        type_name: String,
+        span: Span,
+    },
+    /// A second struct or enum called `name` in one module.
+    DuplicateType {
+        name: String,
+        span: Span,
+    },
+    ImportCycle {
+        cycle: Vec<String>,
+        span: Span,
+    },
+    /// `meta` blocks wait on each other for the names they declare; `span`
+    /// is one of the blocks.
+    MetaDeclarationCycle {
+        names: Vec<String>,
+        span: Span,
+    },
+    ModuleScan {
+        name: String,
+        error: ScanError,
+    },
+    ModuleParse {
+        name: String,
+        errors: Vec<ParseError>,
+    },
     Unimplemented(String),
     Eval(EvalError),
+    /// Compile-time code broke the `MetaPolicy`; `block` is the `meta` block
+    /// it ran in, if any.
+    PolicyViolation {
+        error: Box<EvalError>,
+        block: Option<Span>,
+    },
+}
+
+impl MetaProcessError {
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            MetaProcessError::EmbedFailed { span, .. } => Some(*span),
+            MetaProcessError::UnknownType(_, span) => Some(*span),
+            MetaProcessError::ModuleNotFound(_, span) => Some(*span),
+            MetaProcessError::NonConstantArgument { span, .. } => Some(*span),
+            MetaProcessError::ImpureMetaCall { span, .. } => Some(*span),
+            MetaProcessError::ConflictingImpl { span, .. } => Some(*span),
+            MetaProcessError::DuplicateType { span, .. } => Some(*span),
+            MetaProcessError::ImportCycle { span, .. } => Some(*span),
+            MetaProcessError::MetaDeclarationCycle { span, .. } => Some(*span),
+            MetaProcessError::ModuleScan { error, .. } => Some(error.span()),
+            MetaProcessError::ModuleParse { errors, .. } => errors.first().map(|e| e.span()),
+            MetaProcessError::Eval(e) => e.span(),
+            MetaProcessError::PolicyViolation { error, .. } => error.span(),
+            MetaProcessError::ExprNotFound(_)
+            | MetaProcessError::StmtNotFound(_)
+            | MetaProcessError::Unimplemented(_) => None,
+        }
+    }
 }
 
 impl From<EvalError> for MetaProcessError {
@@ -24,35 +114,79 @@ impl From<EvalError> for MetaProcessError {
     }
 }
 
+/// What the `gen` statements of a running `meta` block emit.
 pub struct MetaContext {
-    pub emitted: Vec<RuntimeStmt>,
+    /// the emitted statements, spliced in where the `meta` block was
+    pub emitted: Vec<AstId>,
+    /// every node of the emitted statements, with the span of the node it copies
+    pub nodes: Vec<(AstId, RuntimeNode, Option<Span>)>,
+    pub ids: IdProvider,
+}
+
+impl MetaContext {
+    pub fn new(ids: IdProvider) -> Self {
+        Self {
+            emitted: Vec::new(),
+            nodes: Vec::new(),
+            ids,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum Event {
     DependencyChain(Dependency),
-    MetaExec(AstId),
+    // the block's body, the block itself for error reporting, and the level
+    // the body runs at
+    MetaExec {
+        body: AstId,
+        block: AstId,
+        level: usize,
+    },
+    // a call to a `meta fn` at `level` whose arguments are lowered
+    Fold {
+        call: AstId,
+        level: usize,
+    },
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum Dependency {
     NodeDone(AstId),
+    // a `meta` block at `level` that declares `name` has run
+    Declared { level: usize, name: String },
 }
 
 #[derive(Debug)]
 pub enum WorkItem {
-    LowerExpr { meta_id: AstId, runtime_id: AstId },
-    LowerStmt { meta_id: AstId, runtime_id: AstId },
+    LowerExpr {
+        meta_id: AstId,
+        runtime_id: AstId,
+        stage: Stage,
+    },
+    LowerStmt {
+        meta_id: AstId,
+        runtime_id: AstId,
+        stage: Stage,
+    },
+    // an emitted `meta` block, to run once the blocks nested in it have
+    StageMeta {
+        block: AstId,
+        level: usize,
+    },
 }
 
 pub struct WorkQueue {
     queue: VecDeque<WorkItem>,
+    // the stage of the item being lowered, which its children share
+    stage: Stage,
 }
 
 impl WorkQueue {
     pub fn new() -> Self {
         Self {
             queue: VecDeque::new(),
+            stage: Stage::default(),
         }
     }
 
@@ -60,28 +194,60 @@ impl WorkQueue {
         self.queue.push_back(item);
     }
 
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
     pub fn queue_expr(&mut self, id_provider: &mut IdProvider, meta_id: AstId) -> AstId {
+        self.queue_expr_at(id_provider, meta_id, self.stage)
+    }
+
+    pub fn queue_expr_at(
+        &mut self,
+        id_provider: &mut IdProvider,
+        meta_id: AstId,
+        stage: Stage,
+    ) -> AstId {
         let runtime_id = id_provider.next();
         let item = WorkItem::LowerExpr {
             meta_id,
             runtime_id,
+            stage,
         };
         self.queue(item);
         runtime_id
     }
 
     pub fn queue_stmt(&mut self, id_provider: &mut IdProvider, meta_id: AstId) -> AstId {
+        self.queue_stmt_at(id_provider, meta_id, self.stage)
+    }
+
+    pub fn queue_stmt_at(
+        &mut self,
+        id_provider: &mut IdProvider,
+        meta_id: AstId,
+        stage: Stage,
+    ) -> AstId {
         let runtime_id = id_provider.next();
         let item = WorkItem::LowerStmt {
             meta_id,
             runtime_id,
+            stage,
         };
         self.queue(item);
         runtime_id
     }
 
+    pub fn queue_staged(&mut self, block: AstId, level: usize) {
+        self.queue(WorkItem::StageMeta { block, level });
+    }
+
     pub fn next(&mut self) -> Option<WorkItem> {
-        self.queue.pop_front()
+        let item = self.queue.pop_front()?;
+        if let WorkItem::LowerExpr { stage, .. } | WorkItem::LowerStmt { stage, .. } = &item {
+            self.stage = *stage;
+        }
+        Some(item)
     }
 }
 
@@ -127,10 +293,16 @@ pub fn process_expr(
     meta_ast: &MetaAst,
     runtime_ast: &mut RuntimeAst,
     id_provider: &mut IdProvider,
+    modules: &mut ModuleLoader,
+    policy: &MetaPolicy,
+    compile_env: &CompileTimeEnv,
 ) -> Result<(), MetaProcessError> {
     let meta_expr = meta_ast
         .get_expr(meta_expr_id)
         .ok_or(MetaProcessError::ExprNotFound(meta_expr_id))?;
+    if let Some(span) = meta_ast.get_span(meta_expr_id) {
+        runtime_ast.insert_span(runtime_expr_id, span);
+    }
     match meta_expr {
         MetaExpr::Int(i) => {
             let expr = RuntimeExpr::Int(*i);
@@ -168,15 +340,53 @@ pub fn process_expr(
                 out_fields.push((name.clone(), field_expr_id));
             }
 
+            let children = out_fields.iter().map(|(_, id)| *id).collect();
             let expr = RuntimeExpr::StructLiteral {
-                type_name: type_name.clone(),
+                type_name: modules.type_name(type_name),
                 fields: out_fields,
             };
 
-            insert_leaf(
+            insert_node(
                 runtime_expr_id,
                 RuntimeNode::Expr(expr),
-                completion_queue,
+                children,
+                dependency_scheduler,
+                runtime_ast,
+            );
+        }
+
+        MetaExpr::Variant {
+            enum_name,
+            variant,
+            payload,
+        } => {
+            let mut children = vec![];
+            let mut queue = |meta_expr_id: &AstId| {
+                let id = work_queue.queue_expr(id_provider, *meta_expr_id);
+                children.push(id);
+                id
+            };
+            let payload = match payload {
+                Payload::Unit => Payload::Unit,
+                Payload::Tuple(items) => Payload::Tuple(items.iter().map(&mut queue).collect()),
+                Payload::Struct(fields) => Payload::Struct(
+                    fields
+                        .iter()
+                        .map(|(name, e)| (name.clone(), queue(e)))
+                        .collect(),
+                ),
+            };
+
+            let expr = RuntimeExpr::Variant {
+                enum_name: modules.type_name(enum_name),
+                variant: variant.clone(),
+                payload,
+            };
+            insert_node(
+                runtime_expr_id,
+                RuntimeNode::Expr(expr),
+                children,
+                dependency_scheduler,
                 runtime_ast,
             );
         }
@@ -278,7 +488,221 @@ pub fn process_expr(
             );
         }
 
+        MetaExpr::NotEquals(a, b) => {
+            let a_id = work_queue.queue_expr(id_provider, *a);
+            let b_id = work_queue.queue_expr(id_provider, *b);
+            let expr = RuntimeExpr::NotEquals(a_id, b_id);
+            insert_node(
+                runtime_expr_id,
+                RuntimeNode::Expr(expr),
+                vec![a_id, b_id],
+                dependency_scheduler,
+                runtime_ast,
+            );
+        }
+
+        MetaExpr::Less(a, b) => {
+            let a_id = work_queue.queue_expr(id_provider, *a);
+            let b_id = work_queue.queue_expr(id_provider, *b);
+            let expr = RuntimeExpr::Less(a_id, b_id);
+            insert_node(
+                runtime_expr_id,
+                RuntimeNode::Expr(expr),
+                vec![a_id, b_id],
+                dependency_scheduler,
+                runtime_ast,
+            );
+        }
+
+        MetaExpr::LessEqual(a, b) => {
+            let a_id = work_queue.queue_expr(id_provider, *a);
+            let b_id = work_queue.queue_expr(id_provider, *b);
+            let expr = RuntimeExpr::LessEqual(a_id, b_id);
+            insert_node(
+                runtime_expr_id,
+                RuntimeNode::Expr(expr),
+                vec![a_id, b_id],
+                dependency_scheduler,
+                runtime_ast,
+            );
+        }
+
+        MetaExpr::Greater(a, b) => {
+            let a_id = work_queue.queue_expr(id_provider, *a);
+            let b_id = work_queue.queue_expr(id_provider, *b);
+            let expr = RuntimeExpr::Greater(a_id, b_id);
+            insert_node(
+                runtime_expr_id,
+                RuntimeNode::Expr(expr),
+                vec![a_id, b_id],
+                dependency_scheduler,
+                runtime_ast,
+            );
+        }
+
+        MetaExpr::GreaterEqual(a, b) => {
+            let a_id = work_queue.queue_expr(id_provider, *a);
+            let b_id = work_queue.queue_expr(id_provider, *b);
+            let expr = RuntimeExpr::GreaterEqual(a_id, b_id);
+            insert_node(
+                runtime_expr_id,
+                RuntimeNode::Expr(expr),
+                vec![a_id, b_id],
+                dependency_scheduler,
+                runtime_ast,
+            );
+        }
+
+        MetaExpr::And(a, b) => {
+            let a_id = work_queue.queue_expr(id_provider, *a);
+            let b_id = work_queue.queue_expr(id_provider, *b);
+            let expr = RuntimeExpr::And(a_id, b_id);
+            insert_node(
+                runtime_expr_id,
+                RuntimeNode::Expr(expr),
+                vec![a_id, b_id],
+                dependency_scheduler,
+                runtime_ast,
+            );
+        }
+
+        MetaExpr::Or(a, b) => {
+            let a_id = work_queue.queue_expr(id_provider, *a);
+            let b_id = work_queue.queue_expr(id_provider, *b);
+            let expr = RuntimeExpr::Or(a_id, b_id);
+            insert_node(
+                runtime_expr_id,
+                RuntimeNode::Expr(expr),
+                vec![a_id, b_id],
+                dependency_scheduler,
+                runtime_ast,
+            );
+        }
+
+        MetaExpr::Not(a) => {
+            let a_id = work_queue.queue_expr(id_provider, *a);
+            let expr = RuntimeExpr::Not(a_id);
+            insert_node(
+                runtime_expr_id,
+                RuntimeNode::Expr(expr),
+                vec![a_id],
+                dependency_scheduler,
+                runtime_ast,
+            );
+        }
+
+        MetaExpr::Negate(a) => {
+            let a_id = work_queue.queue_expr(id_provider, *a);
+            let expr = RuntimeExpr::Negate(a_id);
+            insert_node(
+                runtime_expr_id,
+                RuntimeNode::Expr(expr),
+                vec![a_id],
+                dependency_scheduler,
+                runtime_ast,
+            );
+        }
+
+        MetaExpr::FieldAccess { target, field } => {
+            let target_id = work_queue.queue_expr(id_provider, *target);
+            let expr = RuntimeExpr::FieldAccess {
+                target: target_id,
+                field: field.clone(),
+            };
+            insert_node(
+                runtime_expr_id,
+                RuntimeNode::Expr(expr),
+                vec![target_id],
+                dependency_scheduler,
+                runtime_ast,
+            );
+        }
+
+        MetaExpr::Index { target, index } => {
+            let target_id = work_queue.queue_expr(id_provider, *target);
+            let index_id = work_queue.queue_expr(id_provider, *index);
+            let expr = RuntimeExpr::Index {
+                target: target_id,
+                index: index_id,
+            };
+            insert_node(
+                runtime_expr_id,
+                RuntimeNode::Expr(expr),
+                vec![target_id, index_id],
+                dependency_scheduler,
+                runtime_ast,
+            );
+        }
+
+        MetaExpr::Slice { target, start, end } => {
+            let target_id = work_queue.queue_expr(id_provider, *target);
+            let start_id = start.map(|e| work_queue.queue_expr(id_provider, e));
+            let end_id = end.map(|e| work_queue.queue_expr(id_provider, e));
+            let expr = RuntimeExpr::Slice {
+                target: target_id,
+                start: start_id,
+                end: end_id,
+            };
+
+            let mut children = vec![target_id];
+            children.extend(start_id);
+            children.extend(end_id);
+            insert_node(
+                runtime_expr_id,
+                RuntimeNode::Expr(expr),
+                children,
+                dependency_scheduler,
+                runtime_ast,
+            );
+        }
+
+        MetaExpr::MethodCall {
+            receiver,
+            method,
+            args,
+        } => {
+            let receiver_id = work_queue.queue_expr(id_provider, *receiver);
+            let mut out_args = Vec::with_capacity(args.len());
+            for meta_arg in args {
+                out_args.push(work_queue.queue_expr(id_provider, *meta_arg));
+            }
+
+            let expr = RuntimeExpr::MethodCall {
+                receiver: receiver_id,
+                method: method.clone(),
+                args: out_args.clone(),
+            };
+
+            let mut children = vec![receiver_id];
+            children.extend(out_args);
+            insert_node(
+                runtime_expr_id,
+                RuntimeNode::Expr(expr),
+                children,
+                dependency_scheduler,
+                runtime_ast,
+            );
+        }
+
+        MetaExpr::Assign { target, op, value } => {
+            let target_id = work_queue.queue_expr(id_provider, *target);
+            let value_id = work_queue.queue_expr(id_provider, *value);
+            let expr = RuntimeExpr::Assign {
+                target: target_id,
+                op: *op,
+                value: value_id,
+            };
+            insert_node(
+                runtime_expr_id,
+                RuntimeNode::Expr(expr),
+                vec![target_id, value_id],
+                dependency_scheduler,
+                runtime_ast,
+            );
+        }
+
         MetaExpr::Call { callee, args } => {
+            let callee_id = work_queue.queue_expr(id_provider, *callee);
             let mut out_args = Vec::with_capacity(args.len());
 
             for meta_arg in args {
@@ -287,32 +711,99 @@ pub fn process_expr(
             }
 
             let expr = RuntimeExpr::Call {
-                callee: callee.clone(),
+                callee: callee_id,
                 args: out_args.clone(),
             };
 
+            let mut children = vec![callee_id];
+            children.extend(out_args);
+
+            // a call to a `meta fn` is folded into its result once the
+            // function is defined
+            let stage = work_queue.stage();
+            let level = stage.level + 1;
+            let meta_fn = match meta_ast.get_expr(*callee) {
+                Some(MetaExpr::Variable(name))
+                    if !stage.quoted && compile_env.declares(level, name) =>
+                {
+                    Some(name)
+                }
+                _ => None,
+            };
+            match meta_fn {
+                Some(name) => {
+                    runtime_ast.insert_expr(runtime_expr_id, expr);
+                    let mut deps: Vec<Dependency> =
+                        children.into_iter().map(Dependency::NodeDone).collect();
+                    if !compile_env.defines(level, name) {
+                        deps.push(Dependency::Declared {
+                            level,
+                            name: name.clone(),
+                        });
+                    }
+                    dependency_scheduler.add_task(
+                        &deps,
+                        Event::Fold {
+                            call: runtime_expr_id,
+                            level,
+                        },
+                    );
+                }
+                None => insert_node(
+                    runtime_expr_id,
+                    RuntimeNode::Expr(expr),
+                    children,
+                    dependency_scheduler,
+                    runtime_ast,
+                ),
+            }
+        }
+
+        MetaExpr::Lambda { params, ret, body } => {
+            let body_id = work_queue.queue_stmt(id_provider, *body);
+            let expr = RuntimeExpr::Lambda {
+                params: modules.params(params),
+                ret: ret.as_ref().map(|ty| modules.type_expr(ty)),
+                body: body_id,
+            };
             insert_node(
                 runtime_expr_id,
                 RuntimeNode::Expr(expr),
-                out_args,
+                vec![body_id],
+                dependency_scheduler,
+                runtime_ast,
+            );
+        }
+
+        // the quoted statements are code for the level below, like those of
+        // a `gen`, and what they splice in is computed at this level again
+        MetaExpr::Quote(stmts) => {
+            let stage = work_queue.stage().gen();
+            let children: Vec<AstId> = stmts
+                .iter()
+                .map(|s| work_queue.queue_stmt_at(id_provider, *s, stage))
+                .collect();
+            insert_node(
+                runtime_expr_id,
+                RuntimeNode::Expr(RuntimeExpr::Quote(children.clone())),
+                children,
+                dependency_scheduler,
+                runtime_ast,
+            );
+        }
+
+        MetaExpr::Unquote(inner) => {
+            let stage = work_queue.stage().meta();
+            let inner_id = work_queue.queue_expr_at(id_provider, *inner, stage);
+            insert_node(
+                runtime_expr_id,
+                RuntimeNode::Expr(RuntimeExpr::Unquote(inner_id)),
+                vec![inner_id],
                 dependency_scheduler,
                 runtime_ast,
             );
         }
 
-        //match ctx.env.borrow().get(&callee) {
-        //    Some(_) => {
-        //        let val = interpreter::eval_expr(
-        //            &call_expr,
-        //            ctx.env.clone(),
-        //            ctx.decls,
-        //            &mut None,
-        //            ctx.out,
-        //        )?;
-        //        value_to_literal(val)
-        //    }
-        //    None => Ok(call_expr),
-        //}
         MetaExpr::Typeof(ident) => {
             //let def = ctx
             //    .decls
@@ -328,8 +819,18 @@ pub fn process_expr(
             );
         }
 
-        MetaExpr::Embed(file_path) => {
-            let expr = RuntimeExpr::String(file_path.clone());
+        MetaExpr::Embed { path, kind } => {
+            let span = meta_ast.get_span(meta_expr_id).unwrap_or_default();
+            if !policy.allows(Capability::ReadFiles) {
+                return Err(MetaProcessError::PolicyViolation {
+                    error: Box::new(EvalError::CapabilityDenied {
+                        capability: Capability::ReadFiles,
+                        span,
+                    }),
+                    block: None,
+                });
+            }
+            let expr = modules.embed(path, *kind, span)?;
             insert_leaf(
                 runtime_expr_id,
                 RuntimeNode::Expr(expr),
@@ -350,10 +851,15 @@ pub fn process_stmt(
     meta_ast: &MetaAst,
     runtime_ast: &mut RuntimeAst,
     id_provider: &mut IdProvider,
+    modules: &mut ModuleLoader,
+    compile_env: &mut CompileTimeEnv,
 ) -> Result<(), MetaProcessError> {
     let meta_stmt = meta_ast
         .get_stmt(meta_stmt_id)
         .ok_or(MetaProcessError::StmtNotFound(meta_stmt_id))?;
+    if let Some(span) = meta_ast.get_span(meta_stmt_id) {
+        runtime_ast.insert_span(runtime_stmt_id, span);
+    }
     match meta_stmt {
         MetaStmt::ExprStmt(expr) => {
             let expr_id = work_queue.queue_expr(id_provider, *expr);
@@ -367,10 +873,11 @@ pub fn process_stmt(
             );
         }
 
-        MetaStmt::VarDecl { name, expr } => {
+        MetaStmt::VarDecl { name, ty, expr } => {
             let expr_id = work_queue.queue_expr(id_provider, *expr);
             let stmt = RuntimeStmt::VarDecl {
                 name: name.clone(),
+                ty: ty.as_ref().map(|ty| modules.type_expr(ty)),
                 expr: expr_id,
             };
             insert_node(
@@ -449,6 +956,85 @@ pub fn process_stmt(
             );
         }
 
+        MetaStmt::While { cond, body } => {
+            let cond_id = work_queue.queue_expr(id_provider, *cond);
+            let body_id = work_queue.queue_stmt(id_provider, *body);
+
+            let stmt = RuntimeStmt::While {
+                cond: cond_id,
+                body: body_id,
+            };
+
+            insert_node(
+                runtime_stmt_id,
+                RuntimeNode::Stmt(stmt),
+                vec![cond_id, body_id],
+                dependency_scheduler,
+                runtime_ast,
+            );
+        }
+
+        MetaStmt::Match { scrutinee, arms } => {
+            let scrutinee_id = work_queue.queue_expr(id_provider, *scrutinee);
+            let mut children = vec![scrutinee_id];
+            let arms = arms
+                .iter()
+                .map(|arm| {
+                    let guard = arm.guard.map(|g| work_queue.queue_expr(id_provider, g));
+                    let body = work_queue.queue_stmt(id_provider, arm.body);
+                    children.extend(guard);
+                    children.push(body);
+                    MatchArm {
+                        pattern: modules.pattern(&arm.pattern),
+                        guard,
+                        body,
+                    }
+                })
+                .collect();
+
+            let stmt = RuntimeStmt::Match {
+                scrutinee: scrutinee_id,
+                arms,
+            };
+
+            insert_node(
+                runtime_stmt_id,
+                RuntimeNode::Stmt(stmt),
+                children,
+                dependency_scheduler,
+                runtime_ast,
+            );
+        }
+
+        MetaStmt::Break => {
+            insert_leaf(
+                runtime_stmt_id,
+                RuntimeNode::Stmt(RuntimeStmt::Break),
+                completion_queue,
+                runtime_ast,
+            );
+        }
+
+        MetaStmt::Continue => {
+            insert_leaf(
+                runtime_stmt_id,
+                RuntimeNode::Stmt(RuntimeStmt::Continue),
+                completion_queue,
+                runtime_ast,
+            );
+        }
+
+        MetaStmt::Defer(stmt) => {
+            let stmt_id = work_queue.queue_stmt(id_provider, *stmt);
+            insert_node(
+                runtime_stmt_id,
+                RuntimeNode::Stmt(RuntimeStmt::Defer(stmt_id)),
+                vec![stmt_id],
+                dependency_scheduler,
+                runtime_ast,
+            );
+        }
+
         MetaStmt::Block(stmts) => {
             let mut children = Vec::with_capacity(stmts.len());
 
@@ -468,12 +1054,20 @@ pub fn process_stmt(
             );
         }
 
-        MetaStmt::FnDecl { name, params, body } => {
+        MetaStmt::FnDecl {
+            name,
+            type_params,
+            params,
+            ret,
+            body,
+        } => {
             let body_id = work_queue.queue_stmt(id_provider, *body);
 
             let stmt = RuntimeStmt::FnDecl {
                 name: name.clone(),
-                params: params.clone(),
+                type_params: type_params.clone(),
+                params: modules.params(params),
+                ret: ret.as_ref().map(|ty| modules.type_expr(ty)),
                 body: body_id,
             };
 
@@ -487,12 +1081,125 @@ pub fn process_stmt(
         }
 
         MetaStmt::StructDecl { name, fields } => {
-            //ctx.decls.define_struct(
-            //    name.clone(),
-            //    StructDef {
-            //        fields: fields.clone(),
-            //    },
-            //);
+            let fields: Vec<RuntimeFieldDecl> = fields
+                .iter()
+                .map(|f| RuntimeFieldDecl {
+                    field_name: f.field_name.clone(),
+                    ty: modules.type_expr(&f.ty),
+                })
+                .collect();
+            let def = StructDef {
+                fields: fields
+                    .iter()
+                    .map(|f| (f.field_name.clone(), f.ty.clone()))
+                    .collect(),
+            };
+            runtime_ast
+                .decls
+                .define_struct(modules.type_name(name), def)
+                .map_err(|_| duplicate_type(name, meta_ast.get_span(meta_stmt_id)))?;
+            let stmt = RuntimeStmt::StructDecl {
+                name: modules.type_name(name),
+                fields,
+            };
+            insert_leaf(
+                runtime_stmt_id,
+                RuntimeNode::Stmt(stmt),
+                completion_queue,
+                runtime_ast,
+            );
+        }
+
+        MetaStmt::EnumDecl {
+            name,
+            type_params,
+            variants,
+        } => {
+            let variants = modules.variants(variants);
+            let def = EnumDef {
+                type_params: type_params.clone(),
+                variants: variants.clone(),
+            };
+            runtime_ast
+                .decls
+                .define_enum(modules.type_name(name), def)
+                .map_err(|_| duplicate_type(name, meta_ast.get_span(meta_stmt_id)))?;
+            let stmt = RuntimeStmt::EnumDecl {
+                name: modules.type_name(name),
+                type_params: type_params.clone(),
+                variants,
+            };
+            insert_leaf(
+                runtime_stmt_id,
+                RuntimeNode::Stmt(stmt),
+                completion_queue,
+                runtime_ast,
+            );
+        }
+
+        MetaStmt::TraitDecl { name, methods } => {
+            let methods: Vec<MethodSig> = methods
+                .iter()
+                .map(|m| MethodSig {
+                    name: m.name.clone(),
+                    params: modules.params(&m.params),
+                    ret: m.ret.as_ref().map(|ty| modules.type_expr(ty)),
+                })
+                .collect();
+            runtime_ast.decls.define_trait(
+                name.clone(),
+                TraitDef {
+                    methods: methods.clone(),
+                },
+            );
+            let stmt = RuntimeStmt::TraitDecl {
+                name: name.clone(),
+                methods,
+            };
+            insert_leaf(
+                runtime_stmt_id,
+                RuntimeNode::Stmt(stmt),
+                completion_queue,
+                runtime_ast,
+            );
+        }
+
+        MetaStmt::ImplDecl {
+            trait_name,
+            type_name,
+            methods,
+        } => {
+            let mut lowered = Vec::with_capacity(methods.len());
+            let mut named = Vec::with_capacity(methods.len());
+            for method in methods {
+                let method_id = work_queue.queue_stmt(id_provider, *method);
+                if let Some(MetaStmt::FnDecl { name, .. }) = meta_ast.get_stmt(*method) {
+                    named.push((name.clone(), method_id));
+                }
+                lowered.push(method_id);
+            }
+
+            let def = ImplDef {
+                trait_name: trait_name.clone(),
+                type_name: modules.type_name(type_name),
+                methods: named,
+            };
+            runtime_ast.decls.define_impl(def).map_err(|def| {
+                let span = meta_ast.get_span(meta_stmt_id).unwrap_or_default();
+                conflicting_impl(def, span)
+            })?;
+            let stmt = RuntimeStmt::ImplDecl {
+                trait_name: trait_name.clone(),
+                type_name: modules.type_name(type_name),
+                methods: lowered.clone(),
+            };
+            insert_node(
+                runtime_stmt_id,
+                RuntimeNode::Stmt(stmt),
+                lowered,
+                dependency_scheduler,
+                runtime_ast,
+            );
         }
 
         MetaStmt::Return(expr) => {
@@ -515,9 +1222,10 @@ pub fn process_stmt(
         }
 
         MetaStmt::Gen(stmts) => {
+            let stage = work_queue.stage().gen();
             let children: Vec<_> = stmts
                 .iter()
-                .map(|s| work_queue.queue_stmt(id_provider, *s))
+                .map(|s| work_queue.queue_stmt_at(id_provider, *s, stage))
                 .collect();
 
             let stmt = RuntimeStmt::Gen(children.clone());
@@ -532,68 +1240,251 @@ pub fn process_stmt(
         }
 
         MetaStmt::MetaBlock(parsed_stmt) => {
-            let body_id = work_queue.queue_stmt(id_provider, *parsed_stmt);
+            let stage = work_queue.stage();
+            let body_id = work_queue.queue_stmt_at(id_provider, *parsed_stmt, stage.meta());
+
+            if stage.quoted {
+                // it runs once a `gen` has emitted it
+                insert_node(
+                    runtime_stmt_id,
+                    RuntimeNode::Stmt(RuntimeStmt::MetaBlock(body_id)),
+                    vec![body_id],
+                    dependency_scheduler,
+                    runtime_ast,
+                );
+            } else {
+                // it runs once its body is lowered, the other blocks at its
+                // level have declared the names it uses, and the blocks
+                // before it using those names have run
+                let level = stage.level + 1;
+                let deps: Vec<Dependency> = [Dependency::NodeDone(body_id)]
+                    .into_iter()
+                    .chain(
+                        compile_env
+                            .waits_for(meta_ast, *parsed_stmt, level)
+                            .into_iter()
+                            .map(|name| Dependency::Declared { level, name }),
+                    )
+                    .chain(
+                        compile_env
+                            .follows(meta_ast, runtime_stmt_id, *parsed_stmt, level)
+                            .into_iter()
+                            .map(Dependency::NodeDone),
+                    )
+                    .collect();
+                dependency_scheduler.add_task(
+                    &deps,
+                    Event::MetaExec {
+                        body: body_id,
+                        block: runtime_stmt_id,
+                        level,
+                    },
+                );
+            }
+        }
 
-            dependency_scheduler
-                .add_task(&[Dependency::NodeDone(body_id)], Event::MetaExec(body_id));
+        MetaStmt::Import(mod_name) => {
+            let span = meta_ast.get_span(meta_stmt_id).unwrap_or_default();
+            modules.request(mod_name.clone(), span);
+            insert_leaf(
+                runtime_stmt_id,
+                RuntimeNode::Stmt(RuntimeStmt::Import(mod_name.clone())),
+                completion_queue,
+                runtime_ast,
+            );
         }
 
-        MetaStmt::Import(_mod_name) => {}
+        // the parser already reported it; lowering it to nothing lets the
+        // rest of the program be checked too
+        MetaStmt::Error => {
+            insert_leaf(
+                runtime_stmt_id,
+                RuntimeNode::Stmt(RuntimeStmt::Block(vec![])),
+                completion_queue,
+                runtime_ast,
+            );
+        }
     };
     Ok(())
 }
 
-pub fn value_to_literal<W: Write>(
+/// Puts the literal for `val` at `runtime_expr_id`. The elements of a list,
+/// struct or variant get literals of their own, with fresh ids.
+pub fn value_to_literal(
     val: Value,
     runtime_expr_id: AstId,
     runtime_ast: &mut RuntimeAst,
+    id_provider: &mut IdProvider,
 ) -> Result<(), MetaProcessError> {
-    match val {
-        Value::Int(n) => {
-            let expr = RuntimeExpr::Int(n);
-            runtime_ast.insert_expr(runtime_expr_id, expr);
-            Ok(())
-        }
-        Value::String(s) => {
-            let expr = RuntimeExpr::String(s);
-            runtime_ast.insert_expr(runtime_expr_id, expr);
-            Ok(())
-        }
-        Value::Bool(b) => {
-            let expr = RuntimeExpr::Bool(b);
-            runtime_ast.insert_expr(runtime_expr_id, expr);
-            Ok(())
-        }
-        Value::Unit => Err(MetaProcessError::Unimplemented(
-            "Unit has no literal representation".to_string(),
-        )),
-        _ => Err(MetaProcessError::Unimplemented(
-            "non-primitive value not supported yet".to_string(),
-        )),
+    let mut element = |val: Value, runtime_ast: &mut RuntimeAst| {
+        let id = id_provider.next();
+        if let Some(span) = runtime_ast.get_span(runtime_expr_id) {
+            runtime_ast.insert_span(id, span);
+        }
+        value_to_literal(val, id, runtime_ast, id_provider).map(|()| id)
+    };
+    let expr = match val {
+        Value::Int(n) => RuntimeExpr::Int(n),
+        Value::String(s) => RuntimeExpr::String(s),
+        Value::Bool(b) => RuntimeExpr::Bool(b),
+        Value::List(items) => RuntimeExpr::List(
+            items
+                .borrow()
+                .iter()
+                .map(|item| element(item.clone(), runtime_ast))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Struct { type_name, fields } => RuntimeExpr::StructLiteral {
+            type_name,
+            fields: fields
+                .borrow()
+                .iter()
+                .map(|(name, value)| Ok((name.clone(), element(value.clone(), runtime_ast)?)))
+                .collect::<Result<_, MetaProcessError>>()?,
+        },
+        Value::Variant {
+            enum_name,
+            variant,
+            payload,
+        } => {
+            let payload = match payload.as_ref() {
+                Payload::Unit => Payload::Unit,
+                Payload::Tuple(items) => Payload::Tuple(
+                    items
+                        .iter()
+                        .map(|item| element(item.clone(), runtime_ast))
+                        .collect::<Result<_, _>>()?,
+                ),
+                Payload::Struct(fields) => Payload::Struct(
+                    fields
+                        .iter()
+                        .map(|(name, value)| {
+                            Ok((name.clone(), element(value.clone(), runtime_ast)?))
+                        })
+                        .collect::<Result<_, MetaProcessError>>()?,
+                ),
+            };
+            RuntimeExpr::Variant {
+                enum_name,
+                variant,
+                payload,
+            }
+        }
+        Value::Unit => {
+            return Err(MetaProcessError::Unimplemented(
+                "Unit has no literal representation".to_string(),
+            ))
+        }
+        Value::Function(_) | Value::Module(_) | Value::Builtin(_) => {
+            return Err(MetaProcessError::Unimplemented(
+                "functions and modules have no literal representation".to_string(),
+            ))
+        }
+        Value::Ast(_) => {
+            return Err(MetaProcessError::Unimplemented(
+                "code values have no literal representation".to_string(),
+            ))
+        }
+    };
+    runtime_ast.insert_expr(runtime_expr_id, expr);
+    Ok(())
+}
+
+// Whether `id` is a literal, so a call with it as an argument can be folded.
+fn is_constant(id: AstId, runtime_ast: &RuntimeAst) -> bool {
+    let elements: Vec<AstId> = match runtime_ast.get_expr(id) {
+        Some(
+            RuntimeExpr::Int(_)
+            | RuntimeExpr::String(_)
+            | RuntimeExpr::Bool(_)
+            | RuntimeExpr::Bytes(_),
+        ) => vec![],
+        Some(RuntimeExpr::Negate(e)) => vec![*e],
+        Some(RuntimeExpr::List(items)) => items.clone(),
+        Some(RuntimeExpr::StructLiteral { fields, .. }) => fields.iter().map(|(_, e)| *e).collect(),
+        Some(RuntimeExpr::Variant { payload, .. }) => match payload {
+            Payload::Unit => vec![],
+            Payload::Tuple(items) => items.clone(),
+            Payload::Struct(fields) => fields.iter().map(|(_, e)| *e).collect(),
+        },
+        _ => return false,
+    };
+    elements.into_iter().all(|e| is_constant(e, runtime_ast))
+}
+
+// Replaces a call to a `meta fn` with constant arguments by its result. The
+// function doesn't exist at run time, so a call that can't be folded, for
+// an argument that isn't constant or a body that isn't pure, is an error.
+fn fold_call(
+    call: AstId,
+    env: EnvRef,
+    runtime_ast: &mut RuntimeAst,
+    id_provider: &mut IdProvider,
+    policy: &MetaPolicy,
+) -> Result<(), MetaProcessError> {
+    let Some(RuntimeExpr::Call { callee, args }) = runtime_ast.get_expr(call) else {
+        return Ok(());
+    };
+    let Some(RuntimeExpr::Variable(name)) = runtime_ast.get_expr(*callee) else {
+        return Ok(());
+    };
+    if let Some(arg) = args.iter().find(|arg| !is_constant(**arg, runtime_ast)) {
+        return Err(MetaProcessError::NonConstantArgument {
+            function: name.clone(),
+            span: runtime_ast.get_span(*arg).unwrap_or_default(),
+        });
+    }
+    let func = env.borrow().get(name);
+    let Ok(Value::Function(func)) = func else {
+        return Ok(());
+    };
+    if !is_pure(&func, runtime_ast) {
+        return Err(MetaProcessError::ImpureMetaCall {
+            function: name.clone(),
+            span: runtime_ast.get_span(call).unwrap_or_default(),
+        });
     }
+
+    let value = eval_expr_with_policy(runtime_ast, call, env, &mut io::sink(), policy)
+        .map_err(|e| meta_block_error(e, None))?;
+    value_to_literal(value, call, runtime_ast, id_provider)
 }
 
+/// Lowers `root_stmts` into `runtime_ast`, returning the runtime ids of the roots.
+///
+/// Each `meta` level has one environment for all of `root_stmts`, so what a
+/// block defines is seen by the blocks at its level that run after it, and a
+/// block using a name another one declares runs after that one. The `meta`
+/// blocks a `gen` emits are fed back and run at their own level once they are
+/// in place.
 pub fn process_root<W: Write>(
     meta_ast: &MetaAst,
-    root_stmts: &Vec<AstId>,
+    root_stmts: &[AstId],
     runtime_ast: &mut RuntimeAst,
     out: &mut W,
     id_provider: &mut IdProvider,
-    dependency_scheduler: &mut DependencyScheduler<Dependency, Event>,
-    completion_queue: &mut VecDeque<Dependency>,
-    work_queue: &mut WorkQueue,
-) -> Result<(), MetaProcessError> {
-    for stmt in root_stmts {
-        let runtime_id = work_queue.queue_stmt(id_provider, *stmt);
-        runtime_ast.sem_root_stmts.push(runtime_id);
-    }
+    modules: &mut ModuleLoader,
+    policy: &MetaPolicy,
+) -> Result<Vec<AstId>, MetaProcessError> {
+    let mut dependency_scheduler = DependencyScheduler::new();
+    let mut completion_queue = VecDeque::new();
+    let mut work_queue = WorkQueue::new();
+    let dependency_scheduler = &mut dependency_scheduler;
+    let completion_queue = &mut completion_queue;
+    let work_queue = &mut work_queue;
+    let mut compile_env = CompileTimeEnv::new(meta_ast, root_stmts);
+
+    let mut roots: Vec<AstId> = root_stmts
+        .iter()
+        .map(|stmt| work_queue.queue_stmt(id_provider, *stmt))
+        .collect();
 
     while let Some(work_item) = work_queue.next() {
-        println!("{:?}", work_item);
         match work_item {
             WorkItem::LowerExpr {
                 runtime_id,
                 meta_id,
+                ..
             } => {
                 process_expr(
                     meta_id,
@@ -604,12 +1495,16 @@ pub fn process_root<W: Write>(
                     meta_ast,
                     runtime_ast,
                     id_provider,
+                    modules,
+                    policy,
+                    &compile_env,
                 )?;
             }
 
             WorkItem::LowerStmt {
                 runtime_id,
                 meta_id,
+                ..
             } => {
                 process_stmt(
                     meta_id,
@@ -620,51 +1515,295 @@ pub fn process_root<W: Write>(
                     meta_ast,
                     runtime_ast,
                     id_provider,
+                    modules,
+                    &mut compile_env,
                 )?;
             }
+
+            WorkItem::StageMeta { block, level } => {
+                let Some(RuntimeStmt::MetaBlock(body)) = runtime_ast.get_stmt(block) else {
+                    return Err(MetaProcessError::StmtNotFound(block));
+                };
+                let body = *body;
+                runtime_ast.stages.record(StageEvent::Staged {
+                    level,
+                    block: runtime_ast.get_span(block),
+                });
+                // the blocks nested in it run first, one level up
+                let nested = staged_meta_blocks(&[body], runtime_ast);
+                for inner in &nested {
+                    work_queue.queue_staged(*inner, level + 1);
+                }
+                let deps: Vec<Dependency> = nested.into_iter().map(Dependency::NodeDone).collect();
+                dependency_scheduler.add_task(&deps, Event::MetaExec { body, block, level });
+            }
         }
 
-        println!("{:?}", dependency_scheduler);
+        modules.load_pending(runtime_ast, id_provider, out, policy)?;
 
-        while let Some(dep) = completion_queue.pop_front() {
-            println!("dependency completed: {:?}", dep);
-            let events = dependency_scheduler.resolve_dependency(dep);
-            for event in events {
-                println!("event emitted: {:?}", event);
+        // nodes without children (an empty block or list) and staged blocks
+        // with nothing nested are ready at once
+        let mut events: VecDeque<Event> = dependency_scheduler.take_ready().into();
+        loop {
+            while let Some(event) = events.pop_front() {
                 match event {
-                    Event::DependencyChain(dependency) => {
-                        completion_queue.push_back(dependency);
+                    Event::DependencyChain(dependency) => completion_queue.push_back(dependency),
+
+                    Event::MetaExec { body, block, level } => {
+                        let env = compile_env.level(level);
+                        runtime_ast.stages.record(StageEvent::Run {
+                            level,
+                            block: runtime_ast.get_span(block),
+                        });
+                        let emitted = run_meta_block(
+                            body,
+                            block,
+                            env,
+                            runtime_ast,
+                            out,
+                            id_provider,
+                            policy,
+                        )?;
+                        runtime_ast.stages.record(StageEvent::Emit {
+                            level,
+                            count: emitted.len(),
+                        });
+                        for name in compile_env.define(runtime_ast, block, body, level) {
+                            completion_queue.push_back(Dependency::Declared { level, name });
+                        }
+                        let staged = staged_meta_blocks(&emitted, runtime_ast);
+                        match roots.iter().position(|root| *root == block) {
+                            Some(at) => {
+                                roots.splice(at..=at, emitted);
+                            }
+                            None => runtime_ast.splice(block, emitted),
+                        }
+
+                        // what contains the block can only be done once it is
+                        // replaced, and the blocks it emitted have run
+                        if staged.is_empty() {
+                            completion_queue.push_back(Dependency::NodeDone(block));
+                        } else {
+                            for inner in &staged {
+                                work_queue.queue_staged(*inner, level);
+                            }
+                            let deps: Vec<Dependency> =
+                                staged.into_iter().map(Dependency::NodeDone).collect();
+                            dependency_scheduler.add_task(
+                                &deps,
+                                Event::DependencyChain(Dependency::NodeDone(block)),
+                            );
+                        }
                     }
 
-                    Event::MetaExec(ast_id) => {
-                        let stmts = vec![ast_id];
-                        eval(&runtime_ast, &stmts, Environment::new(), &mut None, out)?;
+                    Event::Fold { call, level } => {
+                        let env = compile_env.level(level);
+                        fold_call(call, env, runtime_ast, id_provider, policy)?;
+                        completion_queue.push_back(Dependency::NodeDone(call));
                     }
                 }
             }
+
+            let Some(dep) = completion_queue.pop_front() else {
+                break;
+            };
+            events.extend(dependency_scheduler.resolve_dependency(dep));
         }
     }
 
-    Ok(())
+    // blocks still waiting wait on each other
+    let blocked = dependency_scheduler
+        .waiting()
+        .filter_map(|event| match event {
+            Event::MetaExec { block, .. } => runtime_ast.get_span(*block),
+            _ => None,
+        })
+        .min_by_key(|span| span.start);
+    if let Some(span) = blocked {
+        let mut names: Vec<String> = dependency_scheduler
+            .unresolved()
+            .filter_map(|dep| match dep {
+                Dependency::Declared { name, .. } => Some(name.clone()),
+                Dependency::NodeDone(_) => None,
+            })
+            .collect();
+        names.sort();
+        names.dedup();
+        return Err(MetaProcessError::MetaDeclarationCycle { names, span });
+    }
+
+    Ok(roots)
+}
+
+// The emitted `meta` blocks among `stmts` and the statements nested in them,
+// not counting those in the statements of a `gen` or the body of another
+// `meta` block, which are emitted or staged later.
+fn staged_meta_blocks(stmts: &[AstId], runtime_ast: &RuntimeAst) -> Vec<AstId> {
+    let mut found = Vec::new();
+    let mut pending: Vec<AstId> = stmts.iter().rev().copied().collect();
+    while let Some(id) = pending.pop() {
+        let children: Vec<AstId> = match runtime_ast.get_stmt(id) {
+            Some(RuntimeStmt::MetaBlock(_)) => {
+                found.push(id);
+                continue;
+            }
+            Some(RuntimeStmt::Block(stmts)) => stmts.clone(),
+            Some(RuntimeStmt::If {
+                body, else_branch, ..
+            }) => [*body].into_iter().chain(*else_branch).collect(),
+            Some(
+                RuntimeStmt::While { body, .. }
+                | RuntimeStmt::ForEach { body, .. }
+                | RuntimeStmt::FnDecl { body, .. }
+                | RuntimeStmt::Defer(body),
+            ) => vec![*body],
+            Some(RuntimeStmt::ImplDecl { methods, .. }) => methods.clone(),
+            Some(RuntimeStmt::Match { arms, .. }) => arms.iter().map(|arm| arm.body).collect(),
+            _ => continue,
+        };
+        pending.extend(children.into_iter().rev());
+    }
+    found
+}
+
+// Runs the body of a `meta` block in the `env` of its level, adding
+// the statements its `gen`s emit to `runtime_ast` and returning them.
+fn run_meta_block<W: Write>(
+    body: AstId,
+    block: AstId,
+    env: EnvRef,
+    runtime_ast: &mut RuntimeAst,
+    out: &mut W,
+    id_provider: &mut IdProvider,
+    policy: &MetaPolicy,
+) -> Result<Vec<AstId>, MetaProcessError> {
+    // the statements of a block body are run in the level's environment
+    // itself, so what they define outlives the block
+    let stmts = match runtime_ast.get_stmt(body) {
+        Some(RuntimeStmt::Block(stmts)) => stmts.clone(),
+        _ => vec![body],
+    };
+
+    // emitted nodes take their ids from the same provider as lowered ones
+    let ids = std::mem::replace(id_provider, IdProvider::new());
+    let mut meta_ctx = Some(MetaContext::new(ids));
+    let result = eval_with_policy(runtime_ast, &stmts, env, &mut meta_ctx, out, policy);
+    let meta = meta_ctx.expect("the meta context outlives the block");
+    *id_provider = meta.ids;
+    result.map_err(|e| meta_block_error(e, runtime_ast.get_span(block)))?;
+
+    for (id, node, span) in meta.nodes {
+        match node {
+            RuntimeNode::Expr(expr) => runtime_ast.insert_expr(id, expr),
+            RuntimeNode::Stmt(stmt) => runtime_ast.insert_stmt(id, stmt),
+        }
+        if let Some(span) = span {
+            runtime_ast.insert_span(id, span);
+        }
+    }
+    for id in &meta.emitted {
+        define_emitted_impl(*id, block, runtime_ast)?;
+    }
+    Ok(meta.emitted)
+}
+
+// An emitted `impl` dispatches to its own copies of the methods; one
+// without a span of its own is reported at the `meta` block emitting it.
+fn define_emitted_impl(
+    id: AstId,
+    block: AstId,
+    runtime_ast: &mut RuntimeAst,
+) -> Result<(), MetaProcessError> {
+    let Some(RuntimeStmt::ImplDecl {
+        trait_name,
+        type_name,
+        methods,
+    }) = runtime_ast.get_stmt(id)
+    else {
+        return Ok(());
+    };
+    let named = methods
+        .iter()
+        .filter_map(|method| match runtime_ast.get_stmt(*method) {
+            Some(RuntimeStmt::FnDecl { name, .. }) => Some((name.clone(), *method)),
+            _ => None,
+        })
+        .collect();
+    let def = ImplDef {
+        trait_name: trait_name.clone(),
+        type_name: type_name.clone(),
+        methods: named,
+    };
+    runtime_ast.decls.define_impl(def).map_err(|def| {
+        let span = runtime_ast
+            .get_span(id)
+            .or_else(|| runtime_ast.get_span(block));
+        conflicting_impl(def, span.unwrap_or_default())
+    })
+}
+
+fn duplicate_type(name: &str, span: Option<Span>) -> MetaProcessError {
+    MetaProcessError::DuplicateType {
+        name: name.to_string(),
+        span: span.unwrap_or_default(),
+    }
+}
+
+fn conflicting_impl(def: ImplDef, span: Span) -> MetaProcessError {
+    MetaProcessError::ConflictingImpl {
+        trait_name: def.trait_name,
+        type_name: def.type_name,
+        span,
+    }
+}
+
+// Policy violations point at the offending `meta` block; other errors are
+// reported where they happened.
+fn meta_block_error(error: EvalError, block: Option<Span>) -> MetaProcessError {
+    match error {
+        EvalError::CapabilityDenied { .. }
+        | EvalError::OutOfFuel { .. }
+        | EvalError::StackOverflow { .. }
+        | EvalError::MemoryLimitExceeded { .. } => MetaProcessError::PolicyViolation {
+            error: Box::new(error),
+            block,
+        },
+        _ => MetaProcessError::Eval(error),
+    }
 }
 
+/// Lowers a program that doesn't import anything under the default
+/// `MetaPolicy`; any `import` fails.
 pub fn process<W: Write>(meta_ast: &MetaAst, out: &mut W) -> Result<RuntimeAst, MetaProcessError> {
+    let resolver = MapResolver {
+        map: HashMap::new(),
+    };
+    let mut sources = SourceMap::new();
+    let mut modules = ModuleLoader::new(&resolver, Path::new("."), &mut sources);
+    process_with_modules(meta_ast, out, &mut modules, &MetaPolicy::default())
+}
+
+/// Lowers a program, loading the modules it imports through `modules` and
+/// holding compile-time code to `policy`.
+pub fn process_with_modules<W: Write>(
+    meta_ast: &MetaAst,
+    out: &mut W,
+    modules: &mut ModuleLoader,
+    policy: &MetaPolicy,
+) -> Result<RuntimeAst, MetaProcessError> {
     let mut runtime_ast = RuntimeAst::new();
+    let prelude = modules.add_source("<prelude>", PRELUDE);
+    runtime_ast.decls = DeclRegistry::child(Rc::new(RefCell::new(prelude_decls(prelude))));
     let mut id_provider = IdProvider::new();
-    let mut dependency_scheduler = DependencyScheduler::new();
-    let mut completion_queue = VecDeque::new();
-
-    let mut work_queue = WorkQueue::new();
 
-    process_root(
+    runtime_ast.sem_root_stmts = process_root(
         meta_ast,
         &meta_ast.sem_root_stmts,
         &mut runtime_ast,
         out,
         &mut id_provider,
-        &mut dependency_scheduler,
-        &mut completion_queue,
-        &mut work_queue,
+        modules,
+        policy,
     )?;
 
     Ok(runtime_ast)
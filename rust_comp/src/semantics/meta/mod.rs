@@ -1,5 +1,11 @@
+pub mod compile_env;
 pub mod dependency_scheduler;
 pub mod expanded_ast;
+pub mod meta_policy;
 pub mod meta_processor;
+pub mod module_loader;
+pub mod prelude;
+pub mod purity;
 pub mod runtime_ast;
-//pub mod substitution;
+pub mod stage;
+pub mod substitution;
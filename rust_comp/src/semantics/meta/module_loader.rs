@@ -0,0 +1,254 @@
+use super::meta_policy::MetaPolicy;
+use super::meta_processor::{process_root, MetaProcessError};
+use crate::config::SOURCE_FILE_EXT;
+use crate::diagnostics::source_map::SourceMap;
+use crate::frontend::id_provider::IdProvider;
+use crate::frontend::lexer::tokenize;
+use crate::frontend::meta_ast::{
+    EmbedKind, MetaStmt, Param, Pattern, PatternKind, TypeExpr, VariantDecl,
+};
+use crate::frontend::parser::{parse, ParseCtx};
+use crate::frontend::span::{FileId, Span};
+use crate::semantics::meta::runtime_ast::{RuntimeAst, RuntimeExpr};
+use crate::util::external_resolver::ExternalResolver;
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Finds, parses and lowers the modules named by `import` statements, and
+/// reads the files named by `embed(...)`.
+///
+/// Every module is lowered into the importing `RuntimeAst` once, no matter
+/// how often it is imported; its root statements are recorded under its name.
+/// The structs and enums a module declares are registered as `module.Name`,
+/// so they can't collide with its importer's.
+/// Every file read along the way is recorded as a build dependency.
+pub struct ModuleLoader<'a> {
+    resolver: &'a dyn ExternalResolver,
+    dir: PathBuf,
+    sources: &'a mut SourceMap,
+    // modules currently being lowered, innermost last
+    loading: Vec<String>,
+    // the type names each module in `loading` declares
+    declared: Vec<HashSet<String>>,
+    // imports met while lowering, waiting to be loaded
+    pending: Vec<(String, Span)>,
+    dependencies: Vec<PathBuf>,
+}
+
+impl<'a> ModuleLoader<'a> {
+    pub fn new(resolver: &'a dyn ExternalResolver, dir: &Path, sources: &'a mut SourceMap) -> Self {
+        Self {
+            resolver,
+            dir: dir.to_path_buf(),
+            sources,
+            loading: Vec::new(),
+            declared: Vec::new(),
+            pending: Vec::new(),
+            dependencies: Vec::new(),
+        }
+    }
+
+    /// Adds a source that isn't read through the resolver, such as the
+    /// prelude, so diagnostics can point into it.
+    pub fn add_source(&mut self, name: &str, source: &str) -> FileId {
+        self.sources.add(name, source)
+    }
+
+    /// Files the program was built from, other than the root file.
+    pub fn dependencies(&self) -> &[PathBuf] {
+        &self.dependencies
+    }
+
+    /// Reads an embedded file into the literal that replaces the `embed`.
+    ///
+    /// Imported modules sit next to their importer, so paths are resolved
+    /// relative to the embedding module.
+    pub fn embed(
+        &mut self,
+        path: &str,
+        kind: EmbedKind,
+        span: Span,
+    ) -> Result<RuntimeExpr, MetaProcessError> {
+        let failed = |error| MetaProcessError::EmbedFailed {
+            path: path.to_string(),
+            error,
+            span,
+        };
+        let expr = match kind {
+            EmbedKind::Text => {
+                RuntimeExpr::String(self.resolver.read_file(&self.dir, path).map_err(failed)?)
+            }
+            EmbedKind::Bytes => {
+                RuntimeExpr::Bytes(self.resolver.read_bytes(&self.dir, path).map_err(failed)?)
+            }
+        };
+        self.record(self.dir.join(path));
+        Ok(expr)
+    }
+
+    fn record(&mut self, path: PathBuf) {
+        if !self.dependencies.contains(&path) {
+            self.dependencies.push(path);
+        }
+    }
+
+    /// The name the type `name` goes by in the `DeclRegistry`, from the
+    /// module being lowered.
+    pub fn type_name(&self, name: &str) -> String {
+        match (self.loading.last(), self.declared.last()) {
+            (Some(module), Some(declared)) if declared.contains(name) => {
+                format!("{module}.{name}")
+            }
+            _ => name.to_string(),
+        }
+    }
+
+    pub fn type_expr(&self, ty: &TypeExpr) -> TypeExpr {
+        match ty {
+            TypeExpr::Named { name, args, span } => TypeExpr::Named {
+                name: self.type_name(name),
+                args: args.iter().map(|arg| self.type_expr(arg)).collect(),
+                span: *span,
+            },
+            TypeExpr::Func { params, ret, span } => TypeExpr::Func {
+                params: params.iter().map(|param| self.type_expr(param)).collect(),
+                ret: Box::new(self.type_expr(ret)),
+                span: *span,
+            },
+        }
+    }
+
+    pub fn params(&self, params: &[Param]) -> Vec<Param> {
+        params
+            .iter()
+            .map(|param| Param {
+                name: param.name.clone(),
+                ty: param.ty.as_ref().map(|ty| self.type_expr(ty)),
+            })
+            .collect()
+    }
+
+    pub fn variants(&self, variants: &[VariantDecl]) -> Vec<VariantDecl> {
+        variants
+            .iter()
+            .map(|variant| VariantDecl {
+                name: variant.name.clone(),
+                payload: variant.payload.map(|ty| self.type_expr(ty)),
+            })
+            .collect()
+    }
+
+    pub fn pattern(&self, pattern: &Pattern) -> Pattern {
+        let kind = match &pattern.kind {
+            PatternKind::List { items, rest } => PatternKind::List {
+                items: items.iter().map(|item| self.pattern(item)).collect(),
+                rest: rest.as_ref().map(|rest| Box::new(self.pattern(rest))),
+            },
+            PatternKind::Struct { name, fields } => PatternKind::Struct {
+                name: self.type_name(name),
+                fields: fields
+                    .iter()
+                    .map(|(field, p)| (field.clone(), self.pattern(p)))
+                    .collect(),
+            },
+            PatternKind::Variant {
+                enum_name,
+                variant,
+                payload,
+            } => PatternKind::Variant {
+                enum_name: self.type_name(enum_name),
+                variant: variant.clone(),
+                payload: payload.map(|p| self.pattern(p)),
+            },
+            kind => kind.clone(),
+        };
+        Pattern {
+            kind,
+            span: pattern.span,
+        }
+    }
+
+    /// Remembers an `import` so it's loaded once the current work item is done.
+    pub fn request(&mut self, name: String, span: Span) {
+        self.pending.push((name, span));
+    }
+
+    pub fn load_pending<W: Write>(
+        &mut self,
+        runtime_ast: &mut RuntimeAst,
+        id_provider: &mut IdProvider,
+        out: &mut W,
+        policy: &MetaPolicy,
+    ) -> Result<(), MetaProcessError> {
+        for (name, span) in std::mem::take(&mut self.pending) {
+            self.load(&name, span, runtime_ast, id_provider, out, policy)?;
+        }
+        Ok(())
+    }
+
+    fn load<W: Write>(
+        &mut self,
+        name: &str,
+        span: Span,
+        runtime_ast: &mut RuntimeAst,
+        id_provider: &mut IdProvider,
+        out: &mut W,
+        policy: &MetaPolicy,
+    ) -> Result<(), MetaProcessError> {
+        if let Some(start) = self.loading.iter().position(|m| m == name) {
+            let mut cycle = self.loading[start..].to_vec();
+            cycle.push(name.to_string());
+            return Err(MetaProcessError::ImportCycle { cycle, span });
+        }
+        if runtime_ast.get_module(name).is_some() {
+            return Ok(());
+        }
+
+        let source = self
+            .resolver
+            .read_mod(&self.dir, name)
+            .ok_or_else(|| MetaProcessError::ModuleNotFound(name.to_string(), span))?;
+        let path = self.dir.join(format!("{name}.{SOURCE_FILE_EXT}"));
+        let file_id = self.sources.add(path.display().to_string(), source.clone());
+        self.record(path);
+
+        let tokens = tokenize(&source, file_id).map_err(|error| MetaProcessError::ModuleScan {
+            name: name.to_string(),
+            error,
+        })?;
+        let mut parse_ctx = ParseCtx::new();
+        parse(&tokens, &mut parse_ctx).map_err(|errors| MetaProcessError::ModuleParse {
+            name: name.to_string(),
+            errors,
+        })?;
+
+        let declared = parse_ctx
+            .ast
+            .sem_root_stmts
+            .iter()
+            .filter_map(|id| match parse_ctx.ast.get_stmt(*id) {
+                Some(MetaStmt::StructDecl { name, .. } | MetaStmt::EnumDecl { name, .. }) => {
+                    Some(name.clone())
+                }
+                _ => None,
+            })
+            .collect();
+        self.loading.push(name.to_string());
+        self.declared.push(declared);
+        let roots = process_root(
+            &parse_ctx.ast,
+            &parse_ctx.ast.sem_root_stmts,
+            runtime_ast,
+            out,
+            id_provider,
+            self,
+            policy,
+        );
+        self.loading.pop();
+        self.declared.pop();
+
+        runtime_ast.insert_module(name.to_string(), roots?);
+        Ok(())
+    }
+}
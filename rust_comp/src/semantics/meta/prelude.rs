@@ -0,0 +1,40 @@
+use crate::frontend::lexer::tokenize;
+use crate::frontend::meta_ast::MetaStmt;
+use crate::frontend::parser::{parse, ParseCtx};
+use crate::frontend::span::FileId;
+use crate::util::decl_registry::{DeclRegistry, EnumDef};
+
+/// Types every program can use without declaring them. `none` is
+/// `Option::None`.
+pub const PRELUDE: &str = "\
+enum Option<T> { Some(T), None }
+enum Result<T, E> { Ok(T), Err(E) }
+";
+
+/// The prelude's types, as the parent of a program's own declarations so a
+/// program may redeclare them. `file` is the prelude's entry in the
+/// `SourceMap`.
+pub fn prelude_decls(file: FileId) -> DeclRegistry {
+    let mut decls = DeclRegistry::new();
+    let tokens = tokenize(PRELUDE, file).expect("the prelude should lex");
+    let mut ctx = ParseCtx::new();
+    parse(&tokens, &mut ctx).expect("the prelude should parse");
+
+    for id in &ctx.ast.sem_root_stmts {
+        if let Some(MetaStmt::EnumDecl {
+            name,
+            type_params,
+            variants,
+        }) = ctx.ast.get_stmt(*id)
+        {
+            let def = EnumDef {
+                type_params: type_params.clone(),
+                variants: variants.clone(),
+            };
+            decls
+                .define_enum(name.clone(), def)
+                .expect("the prelude should declare each type once");
+        }
+    }
+    decls
+}
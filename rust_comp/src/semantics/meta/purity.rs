@@ -0,0 +1,244 @@
+use crate::frontend::id_provider::AstId;
+use crate::frontend::meta_ast::{Pattern, PatternKind, Payload};
+use crate::runtime::builtins;
+use crate::runtime::environment::EnvRef;
+use crate::runtime::value::{Function, Value};
+use crate::semantics::meta::runtime_ast::*;
+use std::collections::HashSet;
+
+/// Whether a call to `func` only computes its result, so it can be replaced
+/// by that result at compile time.
+///
+/// A pure function doesn't print, emit code, import, read the clock or the
+/// environment, call a trait method, or read or change a variable or
+/// container it didn't bind itself, and the functions it refers to are pure
+/// too.
+pub fn is_pure(func: &Function, ast: &RuntimeAst) -> bool {
+    let mut purity = Purity {
+        ast,
+        seen: HashSet::new(),
+        env: func.env.clone(),
+        bound: Vec::new(),
+    };
+    purity.function(func)
+}
+
+struct Purity<'a> {
+    ast: &'a RuntimeAst,
+    // bodies already checked or being checked, so recursion terminates
+    seen: HashSet<AstId>,
+    // where the function being checked looks up the names it doesn't bind
+    env: EnvRef,
+    // names the function binds so far, innermost scope last
+    bound: Vec<String>,
+}
+
+impl Purity<'_> {
+    fn function(&mut self, func: &Function) -> bool {
+        if !self.seen.insert(func.body) {
+            return true;
+        }
+        let env = std::mem::replace(&mut self.env, func.env.clone());
+        let bound = std::mem::replace(&mut self.bound, func.params.clone());
+        let pure = self.stmt(func.body);
+        self.env = env;
+        self.bound = bound;
+        pure
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.bound.iter().any(|b| b == name)
+    }
+
+    // Checks with the names `check` binds dropped again afterwards.
+    fn scoped(&mut self, check: impl FnOnce(&mut Self) -> bool) -> bool {
+        let depth = self.bound.len();
+        let pure = check(self);
+        self.bound.truncate(depth);
+        pure
+    }
+
+    fn bind_pattern(&mut self, pattern: &Pattern) {
+        match &pattern.kind {
+            PatternKind::Binding(name) => self.bound.push(name.clone()),
+            PatternKind::List { items, rest } => {
+                for item in items.iter().chain(rest.as_deref()) {
+                    self.bind_pattern(item);
+                }
+            }
+            PatternKind::Struct { fields, .. }
+            | PatternKind::Variant {
+                payload: Payload::Struct(fields),
+                ..
+            } => {
+                for (_, field) in fields {
+                    self.bind_pattern(field);
+                }
+            }
+            PatternKind::Variant {
+                payload: Payload::Tuple(items),
+                ..
+            } => {
+                for item in items {
+                    self.bind_pattern(item);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // A name the function reads: builtins are impure, and so are variables
+    // from outside, which a `meta` block may change before the call runs;
+    // functions from outside are checked in turn.
+    fn variable(&mut self, name: &str) -> bool {
+        if self.is_local(name) {
+            return true;
+        }
+        let value = self.env.borrow().get(name);
+        match value {
+            Ok(Value::Function(func)) => self.function(&func),
+            Ok(_) => false,
+            Err(_) => builtins::lookup(name).is_none(),
+        }
+    }
+
+    // Whether changing what `id` names only changes the function's own data.
+    fn place(&mut self, id: AstId) -> bool {
+        match self.ast.get_expr(id) {
+            Some(RuntimeExpr::Variable(name)) => self.is_local(name),
+            Some(RuntimeExpr::FieldAccess { target, .. }) => self.place(*target),
+            Some(RuntimeExpr::Index { target, index }) => self.place(*target) && self.expr(*index),
+            _ => self.expr(id),
+        }
+    }
+
+    fn exprs(&mut self, ids: &[AstId]) -> bool {
+        ids.iter().all(|id| self.expr(*id))
+    }
+
+    fn expr(&mut self, id: AstId) -> bool {
+        let Some(expr) = self.ast.get_expr(id) else {
+            return false;
+        };
+        match expr {
+            RuntimeExpr::Variable(name) => self.variable(name),
+            RuntimeExpr::Int(_)
+            | RuntimeExpr::String(_)
+            | RuntimeExpr::Bool(_)
+            | RuntimeExpr::Bytes(_) => true,
+            RuntimeExpr::StructLiteral { fields, .. } => {
+                fields.iter().all(|(_, field)| self.expr(*field))
+            }
+            RuntimeExpr::Variant { payload, .. } => match payload {
+                Payload::Unit => true,
+                Payload::Tuple(items) => self.exprs(items),
+                Payload::Struct(fields) => fields.iter().all(|(_, field)| self.expr(*field)),
+            },
+            RuntimeExpr::List(items) => self.exprs(items),
+            RuntimeExpr::Call { callee, args } => self.expr(*callee) && self.exprs(args),
+            RuntimeExpr::Lambda { params, body, .. } => self.scoped(|s| {
+                s.bound
+                    .extend(params.iter().map(|param| param.name.clone()));
+                s.stmt(*body)
+            }),
+            RuntimeExpr::FieldAccess { target, .. } => self.expr(*target),
+            RuntimeExpr::Index { target, index } => self.expr(*target) && self.expr(*index),
+            RuntimeExpr::Slice { target, start, end } => {
+                self.expr(*target) && start.iter().chain(end).all(|e| self.expr(*e))
+            }
+            // list methods may change the list they are called on
+            RuntimeExpr::MethodCall {
+                receiver,
+                method,
+                args,
+            } => {
+                self.ast.decls.traits_with_method(method).is_empty()
+                    && self.place(*receiver)
+                    && self.exprs(args)
+            }
+            RuntimeExpr::Assign { target, value, .. } => self.place(*target) && self.expr(*value),
+            RuntimeExpr::Add(a, b)
+            | RuntimeExpr::Sub(a, b)
+            | RuntimeExpr::Mult(a, b)
+            | RuntimeExpr::Div(a, b)
+            | RuntimeExpr::Equals(a, b)
+            | RuntimeExpr::NotEquals(a, b)
+            | RuntimeExpr::Less(a, b)
+            | RuntimeExpr::LessEqual(a, b)
+            | RuntimeExpr::Greater(a, b)
+            | RuntimeExpr::GreaterEqual(a, b)
+            | RuntimeExpr::And(a, b)
+            | RuntimeExpr::Or(a, b) => self.expr(*a) && self.expr(*b),
+            RuntimeExpr::Not(a) | RuntimeExpr::Negate(a) => self.expr(*a),
+            // code values only exist while a `meta` block runs
+            RuntimeExpr::Quote(_) | RuntimeExpr::Unquote(_) => false,
+        }
+    }
+
+    fn stmt(&mut self, id: AstId) -> bool {
+        let Some(stmt) = self.ast.get_stmt(id) else {
+            return false;
+        };
+        match stmt {
+            RuntimeStmt::ExprStmt(e) => self.expr(*e),
+            RuntimeStmt::VarDecl { name, expr, .. } => {
+                let pure = self.expr(*expr);
+                self.bound.push(name.clone());
+                pure
+            }
+            RuntimeStmt::FnDecl {
+                name, params, body, ..
+            } => {
+                self.bound.push(name.clone());
+                self.scoped(|s| {
+                    s.bound
+                        .extend(params.iter().map(|param| param.name.clone()));
+                    s.stmt(*body)
+                })
+            }
+            RuntimeStmt::StructDecl { .. }
+            | RuntimeStmt::EnumDecl { .. }
+            | RuntimeStmt::TraitDecl { .. }
+            | RuntimeStmt::Break
+            | RuntimeStmt::Continue => true,
+            RuntimeStmt::ImplDecl { methods, .. } => methods.iter().all(|m| self.stmt(*m)),
+            RuntimeStmt::If {
+                cond,
+                body,
+                else_branch,
+            } => {
+                self.expr(*cond)
+                    && self.scoped(|s| s.stmt(*body))
+                    && self.scoped(|s| else_branch.iter().all(|e| s.stmt(*e)))
+            }
+            RuntimeStmt::ForEach {
+                var,
+                iterable,
+                body,
+            } => {
+                self.expr(*iterable)
+                    && self.scoped(|s| {
+                        s.bound.push(var.clone());
+                        s.stmt(*body)
+                    })
+            }
+            RuntimeStmt::While { cond, body } => self.expr(*cond) && self.scoped(|s| s.stmt(*body)),
+            RuntimeStmt::Match { scrutinee, arms } => {
+                self.expr(*scrutinee)
+                    && arms.iter().all(|arm| {
+                        self.scoped(|s| {
+                            s.bind_pattern(&arm.pattern);
+                            arm.guard.iter().all(|g| s.expr(*g)) && s.stmt(arm.body)
+                        })
+                    })
+            }
+            RuntimeStmt::Return(e) => e.iter().all(|e| self.expr(*e)),
+            RuntimeStmt::Defer(s) => self.stmt(*s),
+            RuntimeStmt::Block(stmts) => self.scoped(|s| stmts.iter().all(|stmt| s.stmt(*stmt))),
+            RuntimeStmt::Print(_)
+            | RuntimeStmt::Import(_)
+            | RuntimeStmt::Gen(_)
+            | RuntimeStmt::MetaBlock(_) => false,
+        }
+    }
+}
@@ -1,4 +1,11 @@
 use crate::frontend::id_provider::*;
+use crate::frontend::meta_ast::{
+    annotation_leaf, param_list, type_params_leaf, AssignOp, MatchArm, MethodSig, Param, Payload,
+    TypeExpr, TypeParam, VariantDecl,
+};
+use crate::frontend::span::Span;
+use crate::semantics::meta::stage::StageTrace;
+use crate::util::decl_registry::DeclRegistry;
 use crate::util::formatters::tree_formatter::*;
 use std::collections::HashMap;
 
@@ -7,6 +14,12 @@ pub struct RuntimeAst {
     pub sem_root_stmts: Vec<AstId>,
     exprs: HashMap<AstId, RuntimeExpr>,
     stmts: HashMap<AstId, RuntimeStmt>,
+    spans: HashMap<AstId, Span>,
+    pub decls: DeclRegistry,
+    // root statements of each imported module, by module name
+    modules: HashMap<String, Vec<AstId>>,
+    /// what each stage of compile-time code did while this was lowered
+    pub stages: StageTrace,
 }
 
 impl RuntimeAst {
@@ -15,9 +28,21 @@ impl RuntimeAst {
             sem_root_stmts: vec![],
             exprs: HashMap::new(),
             stmts: HashMap::new(),
+            spans: HashMap::new(),
+            decls: DeclRegistry::new(),
+            modules: HashMap::new(),
+            stages: StageTrace::default(),
         }
     }
 
+    pub fn insert_module(&mut self, name: String, root_stmts: Vec<AstId>) {
+        self.modules.insert(name, root_stmts);
+    }
+
+    pub fn get_module(&self, name: &str) -> Option<&Vec<AstId>> {
+        self.modules.get(name)
+    }
+
     pub fn insert_expr(&mut self, id: AstId, expr: RuntimeExpr) {
         self.exprs.insert(id, expr);
     }
@@ -33,6 +58,28 @@ impl RuntimeAst {
     pub fn get_stmt(&self, id: AstId) -> Option<&RuntimeStmt> {
         self.stmts.get(&id)
     }
+
+    pub fn insert_span(&mut self, id: AstId, span: Span) {
+        self.spans.insert(id, span);
+    }
+
+    pub fn get_span(&self, id: AstId) -> Option<Span> {
+        self.spans.get(&id).copied()
+    }
+
+    /// Replaces statement `id` with `stmts` in the block that lists it. Where
+    /// no block does, `id` becomes a block of `stmts` instead.
+    pub fn splice(&mut self, id: AstId, stmts: Vec<AstId>) {
+        for stmt in self.stmts.values_mut() {
+            if let RuntimeStmt::Block(list) = stmt {
+                if let Some(at) = list.iter().position(|s| *s == id) {
+                    list.splice(at..=at, stmts);
+                    return;
+                }
+            }
+        }
+        self.stmts.insert(id, RuntimeStmt::Block(stmts));
+    }
 }
 
 // For util purposes
@@ -49,27 +96,89 @@ pub enum RuntimeExpr {
     Int(i64),
     String(String),
     Bool(bool),
+    // contents of an `embed(..., bytes)`, one int per byte at runtime
+    Bytes(Vec<u8>),
 
     StructLiteral {
         type_name: String,
         fields: Vec<(String, AstId)>,
     },
 
+    Variant {
+        enum_name: String,
+        variant: String,
+        payload: Payload<AstId>,
+    },
+
     Variable(String),
 
     List(Vec<AstId>),
 
     Call {
-        callee: String,
+        callee: AstId,
         args: Vec<AstId>,
     },
 
+    Lambda {
+        params: Vec<Param>,
+        ret: Option<TypeExpr>,
+        body: AstId,
+    },
+
+    // `quote { stmts }`, evaluated at compile time to a copy of the statements
+    Quote(Vec<AstId>),
+
+    // `$expr`, replaced by the value of `expr` when the code around it is
+    // quoted or emitted
+    Unquote(AstId),
+
+    // ACCESS
+    FieldAccess {
+        target: AstId,
+        field: String,
+    },
+
+    Index {
+        target: AstId,
+        index: AstId,
+    },
+
+    // `target[start:end]`, either bound may be left out
+    Slice {
+        target: AstId,
+        start: Option<AstId>,
+        end: Option<AstId>,
+    },
+
+    MethodCall {
+        receiver: AstId,
+        method: String,
+        args: Vec<AstId>,
+    },
+
+    Assign {
+        target: AstId,
+        op: AssignOp,
+        value: AstId,
+    },
+
     // BINOPS
     Add(AstId, AstId),
     Sub(AstId, AstId),
     Mult(AstId, AstId),
     Div(AstId, AstId),
     Equals(AstId, AstId),
+    NotEquals(AstId, AstId),
+    Less(AstId, AstId),
+    LessEqual(AstId, AstId),
+    Greater(AstId, AstId),
+    GreaterEqual(AstId, AstId),
+    And(AstId, AstId),
+    Or(AstId, AstId),
+
+    // UNARY OPS
+    Not(AstId),
+    Negate(AstId),
 }
 
 #[derive(Debug, Clone)]
@@ -80,12 +189,15 @@ pub enum RuntimeStmt {
     // DECLARATION
     VarDecl {
         name: String,
+        ty: Option<TypeExpr>,
         expr: AstId,
     },
 
     FnDecl {
         name: String,
-        params: Vec<String>,
+        type_params: Vec<TypeParam>,
+        params: Vec<Param>,
+        ret: Option<TypeExpr>,
         body: AstId,
     },
 
@@ -94,6 +206,24 @@ pub enum RuntimeStmt {
         fields: Vec<RuntimeFieldDecl>,
     },
 
+    EnumDecl {
+        name: String,
+        type_params: Vec<String>,
+        variants: Vec<VariantDecl>,
+    },
+
+    TraitDecl {
+        name: String,
+        methods: Vec<MethodSig>,
+    },
+
+    // `methods` are `FnDecl`s; they are only reachable through the trait
+    ImplDecl {
+        trait_name: String,
+        type_name: String,
+        methods: Vec<AstId>,
+    },
+
     // CONTROL
     If {
         cond: AstId,
@@ -107,8 +237,24 @@ pub enum RuntimeStmt {
         body: AstId,
     },
 
+    While {
+        cond: AstId,
+        body: AstId,
+    },
+
+    Match {
+        scrutinee: AstId,
+        arms: Vec<MatchArm>,
+    },
+
+    Break,
+    Continue,
+
     Return(Option<AstId>),
 
+    // runs when the enclosing block is left
+    Defer(AstId),
+
     Block(Vec<AstId>),
 
     // UTIL
@@ -117,6 +263,9 @@ pub enum RuntimeStmt {
     // META
     Gen(Vec<AstId>),
 
+    // a `meta` block a `gen` emitted, left in place until it is staged
+    MetaBlock(AstId),
+
     // TEMPORARY
     Print(AstId),
 }
@@ -124,7 +273,7 @@ pub enum RuntimeStmt {
 #[derive(Debug, Clone)]
 pub struct RuntimeFieldDecl {
     pub field_name: String,
-    pub type_name: String,
+    pub ty: TypeExpr,
 }
 
 impl AsTree for RuntimeAst {
@@ -146,40 +295,92 @@ impl RuntimeAst {
         let (label, mut children): (String, Vec<TreeNode>) = match stmt {
             RuntimeStmt::ExprStmt(e) => ("ExprStmt".into(), vec![self.convert_expr(*e)]),
 
-            RuntimeStmt::VarDecl { name, expr } => (
+            RuntimeStmt::VarDecl { name, ty, expr } => (
                 "VarDecl".into(),
+                std::iter::once(TreeNode::leaf(format!("Name({name})")))
+                    .chain(annotation_leaf("Type", ty))
+                    .chain([self.convert_expr(*expr)])
+                    .collect(),
+            ),
+
+            RuntimeStmt::FnDecl {
+                name,
+                type_params,
+                params,
+                ret,
+                body,
+            } => (
+                "FnDecl".into(),
+                std::iter::once(TreeNode::leaf(format!("Name({name})")))
+                    .chain(type_params_leaf(type_params))
+                    .chain([TreeNode::node(
+                        "Params",
+                        params
+                            .iter()
+                            .map(|p| TreeNode::leaf(p.to_string()))
+                            .collect(),
+                    )])
+                    .chain(annotation_leaf("Returns", ret))
+                    .chain([self.convert_stmt(*body)])
+                    .collect(),
+            ),
+
+            RuntimeStmt::StructDecl { name, fields } => (
+                "StructDecl".into(),
                 vec![
                     TreeNode::leaf(format!("Name({name})")),
-                    self.convert_expr(*expr),
+                    TreeNode::node(
+                        "Fields",
+                        fields
+                            .iter()
+                            .map(|f| TreeNode::leaf(format!("{}: {}", f.field_name, f.ty)))
+                            .collect(),
+                    ),
                 ],
             ),
 
-            RuntimeStmt::FnDecl { name, params, body } => (
-                "FnDecl".into(),
+            RuntimeStmt::EnumDecl {
+                name,
+                type_params,
+                variants,
+            } => (
+                "EnumDecl".into(),
                 vec![
                     TreeNode::leaf(format!("Name({name})")),
+                    TreeNode::leaf(format!("TypeParams({})", type_params.join(", "))),
                     TreeNode::node(
-                        "Params",
-                        params.iter().map(|p| TreeNode::leaf(p.clone())).collect(),
+                        "Variants",
+                        variants
+                            .iter()
+                            .map(|v| TreeNode::leaf(v.to_string()))
+                            .collect(),
                     ),
-                    self.convert_stmt(*body),
                 ],
             ),
 
-            RuntimeStmt::StructDecl { name, fields } => (
-                "StructDecl".into(),
+            RuntimeStmt::TraitDecl { name, methods } => (
+                "TraitDecl".into(),
                 vec![
                     TreeNode::leaf(format!("Name({name})")),
                     TreeNode::node(
-                        "Fields",
-                        fields
+                        "Methods",
+                        methods
                             .iter()
-                            .map(|f| TreeNode::leaf(format!("{}: {}", f.field_name, f.type_name)))
+                            .map(|m| TreeNode::leaf(m.to_string()))
                             .collect(),
                     ),
                 ],
             ),
 
+            RuntimeStmt::ImplDecl {
+                trait_name,
+                type_name,
+                methods,
+            } => (
+                format!("ImplDecl({trait_name} for {type_name})"),
+                methods.iter().map(|m| self.convert_stmt(*m)).collect(),
+            ),
+
             RuntimeStmt::If {
                 cond,
                 body,
@@ -208,11 +409,42 @@ impl RuntimeAst {
                 ],
             ),
 
+            RuntimeStmt::While { cond, body } => (
+                "WhileStmt".into(),
+                vec![
+                    TreeNode::node("Cond", vec![self.convert_expr(*cond)]),
+                    TreeNode::node("Body", vec![self.convert_stmt(*body)]),
+                ],
+            ),
+
+            RuntimeStmt::Match { scrutinee, arms } => (
+                "MatchStmt".into(),
+                std::iter::once(TreeNode::node(
+                    "Scrutinee",
+                    vec![self.convert_expr(*scrutinee)],
+                ))
+                .chain(arms.iter().map(|arm| {
+                    let mut children = vec![TreeNode::leaf(format!("Pattern({})", arm.pattern))];
+                    if let Some(guard) = arm.guard {
+                        children.push(TreeNode::node("Guard", vec![self.convert_expr(guard)]));
+                    }
+                    children.push(self.convert_stmt(arm.body));
+                    TreeNode::node("Arm", children)
+                }))
+                .collect(),
+            ),
+
+            RuntimeStmt::Break => ("BreakStmt".into(), vec![]),
+
+            RuntimeStmt::Continue => ("ContinueStmt".into(), vec![]),
+
             RuntimeStmt::Return(e) => (
                 "ReturnStmt".into(),
                 e.map(|id| vec![self.convert_expr(id)]).unwrap_or_default(),
             ),
 
+            RuntimeStmt::Defer(s) => ("DeferStmt".into(), vec![self.convert_stmt(*s)]),
+
             RuntimeStmt::Block(stmts) => (
                 "Block".into(),
                 stmts.iter().map(|s| self.convert_stmt(*s)).collect(),
@@ -220,6 +452,8 @@ impl RuntimeAst {
 
             RuntimeStmt::Import(path) => ("Import".into(), vec![TreeNode::leaf(path.clone())]),
 
+            RuntimeStmt::MetaBlock(s) => ("MetaBlock".into(), vec![self.convert_stmt(*s)]),
+
             RuntimeStmt::Gen(stmts) => (
                 "Gen".into(),
                 stmts.iter().map(|s| self.convert_stmt(*s)).collect(),
@@ -242,6 +476,11 @@ impl RuntimeAst {
 
             RuntimeExpr::Bool(b) => ("Bool".into(), vec![TreeNode::leaf(b.to_string())]),
 
+            RuntimeExpr::Bytes(bytes) => (
+                "Bytes".into(),
+                vec![TreeNode::leaf(format!("{} bytes", bytes.len()))],
+            ),
+
             RuntimeExpr::Variable(name) => ("Var".into(), vec![TreeNode::leaf(name.clone())]),
 
             RuntimeExpr::StructLiteral { type_name, fields } => (
@@ -252,14 +491,86 @@ impl RuntimeAst {
                     .collect(),
             ),
 
+            RuntimeExpr::Variant {
+                enum_name,
+                variant,
+                payload,
+            } => (
+                format!("Variant({enum_name}::{variant})"),
+                match payload {
+                    Payload::Unit => vec![],
+                    Payload::Tuple(items) => items.iter().map(|e| self.convert_expr(*e)).collect(),
+                    Payload::Struct(fields) => fields
+                        .iter()
+                        .map(|(n, e)| TreeNode::node(n.clone(), vec![self.convert_expr(*e)]))
+                        .collect(),
+                },
+            ),
+
             RuntimeExpr::List(items) => (
                 "List".into(),
                 items.iter().map(|e| self.convert_expr(*e)).collect(),
             ),
 
             RuntimeExpr::Call { callee, args } => (
-                format!("Call({callee})"),
-                args.iter().map(|e| self.convert_expr(*e)).collect(),
+                "Call".into(),
+                vec![
+                    TreeNode::node("Callee", vec![self.convert_expr(*callee)]),
+                    TreeNode::node("Args", args.iter().map(|e| self.convert_expr(*e)).collect()),
+                ],
+            ),
+
+            RuntimeExpr::Lambda { params, ret, body } => (
+                "Lambda".into(),
+                std::iter::once(TreeNode::leaf(format!("Params({})", param_list(params))))
+                    .chain(annotation_leaf("Returns", ret))
+                    .chain([TreeNode::node("Body", vec![self.convert_stmt(*body)])])
+                    .collect(),
+            ),
+
+            RuntimeExpr::Quote(stmts) => (
+                "Quote".into(),
+                stmts.iter().map(|s| self.convert_stmt(*s)).collect(),
+            ),
+
+            RuntimeExpr::Unquote(e) => ("Unquote".into(), vec![self.convert_expr(*e)]),
+
+            RuntimeExpr::FieldAccess { target, field } => (
+                format!("FieldAccess({field})"),
+                vec![self.convert_expr(*target)],
+            ),
+
+            RuntimeExpr::Index { target, index } => (
+                "Index".into(),
+                vec![self.convert_expr(*target), self.convert_expr(*index)],
+            ),
+
+            RuntimeExpr::Slice { target, start, end } => {
+                let mut children = vec![self.convert_expr(*target)];
+                if let Some(start) = start {
+                    children.push(TreeNode::node("Start", vec![self.convert_expr(*start)]));
+                }
+                if let Some(end) = end {
+                    children.push(TreeNode::node("End", vec![self.convert_expr(*end)]));
+                }
+                ("Slice".into(), children)
+            }
+
+            RuntimeExpr::MethodCall {
+                receiver,
+                method,
+                args,
+            } => (
+                format!("MethodCall({method})"),
+                vec![
+                    TreeNode::node("Receiver", vec![self.convert_expr(*receiver)]),
+                    TreeNode::node("Args", args.iter().map(|e| self.convert_expr(*e)).collect()),
+                ],
+            ),
+
+            RuntimeExpr::Assign { target, op, value } => (
+                format!("Assign({op})"),
+                vec![self.convert_expr(*target), self.convert_expr(*value)],
             ),
 
             RuntimeExpr::Add(a, b) => (
@@ -286,6 +597,45 @@ impl RuntimeAst {
                 "Equals".into(),
                 vec![self.convert_expr(*a), self.convert_expr(*b)],
             ),
+
+            RuntimeExpr::NotEquals(a, b) => (
+                "NotEquals".into(),
+                vec![self.convert_expr(*a), self.convert_expr(*b)],
+            ),
+
+            RuntimeExpr::Less(a, b) => (
+                "Less".into(),
+                vec![self.convert_expr(*a), self.convert_expr(*b)],
+            ),
+
+            RuntimeExpr::LessEqual(a, b) => (
+                "LessEqual".into(),
+                vec![self.convert_expr(*a), self.convert_expr(*b)],
+            ),
+
+            RuntimeExpr::Greater(a, b) => (
+                "Greater".into(),
+                vec![self.convert_expr(*a), self.convert_expr(*b)],
+            ),
+
+            RuntimeExpr::GreaterEqual(a, b) => (
+                "GreaterEqual".into(),
+                vec![self.convert_expr(*a), self.convert_expr(*b)],
+            ),
+
+            RuntimeExpr::And(a, b) => (
+                "And".into(),
+                vec![self.convert_expr(*a), self.convert_expr(*b)],
+            ),
+
+            RuntimeExpr::Or(a, b) => (
+                "Or".into(),
+                vec![self.convert_expr(*a), self.convert_expr(*b)],
+            ),
+
+            RuntimeExpr::Not(a) => ("Not".into(), vec![self.convert_expr(*a)]),
+
+            RuntimeExpr::Negate(a) => ("Negate".into(), vec![self.convert_expr(*a)]),
         };
 
         children.insert(0, TreeNode::leaf(format!("id: {id}")));
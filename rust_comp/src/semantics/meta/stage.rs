@@ -0,0 +1,88 @@
+use crate::frontend::span::Span;
+use std::fmt;
+
+/// Where lowered code runs. Level 0 is the program; the body of a `meta`
+/// block is one level above the code around it, and the statements of a
+/// `gen` one level below. `quoted` code is under a `gen` that hasn't emitted
+/// it yet, so the `meta` blocks in it wait until it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stage {
+    pub level: usize,
+    pub quoted: bool,
+}
+
+impl Stage {
+    /// The stage of the body of a `meta` block in code at this stage.
+    pub fn meta(self) -> Self {
+        Self {
+            level: self.level + 1,
+            ..self
+        }
+    }
+
+    /// The stage of the statements of a `gen` in code at this stage.
+    pub fn gen(self) -> Self {
+        Self {
+            level: self.level.saturating_sub(1),
+            quoted: true,
+        }
+    }
+}
+
+/// Something the metaprocessor did at one stage.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StageEvent {
+    /// A `meta` block whose body is at `level` ran.
+    Run { level: usize, block: Option<Span> },
+    /// It emitted `count` statements into the level below.
+    Emit { level: usize, count: usize },
+    /// A `meta` block emitted at the level below was fed back to run.
+    Staged { level: usize, block: Option<Span> },
+}
+
+/// The stage events of a compilation in the order they happened, for
+/// debugging multi-stage programs.
+#[derive(Debug, Clone, Default)]
+pub struct StageTrace {
+    pub events: Vec<StageEvent>,
+}
+
+impl StageTrace {
+    pub fn record(&mut self, event: StageEvent) {
+        self.events.push(event);
+    }
+}
+
+fn at(block: &Option<Span>) -> String {
+    match block {
+        Some(span) => format!(" at {}:{}", span.line, span.col),
+        None => String::new(),
+    }
+}
+
+impl fmt::Display for StageEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StageEvent::Run { level, block } => {
+                write!(f, "stage {level}: ran meta block{}", at(block))
+            }
+            StageEvent::Emit { level, count } => write!(
+                f,
+                "stage {level}: emitted {count} statement(s) into stage {}",
+                level - 1
+            ),
+            StageEvent::Staged { level, block } => {
+                write!(f, "stage {level}: staged emitted meta block{}", at(block))
+            }
+        }
+    }
+}
+
+impl fmt::Display for StageTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for event in &self.events {
+            writeln!(f, "{event}")?;
+        }
+        Ok(())
+    }
+}
@@ -1,115 +1,752 @@
-use crate::runtime::environment::*;
+use crate::frontend::id_provider::AstId;
+use crate::frontend::meta_ast::{MatchArm, Pattern, PatternKind, Payload};
+use crate::frontend::span::Span;
+use crate::runtime::environment::EnvHandler;
+use crate::runtime::interpreter::EvalError;
 use crate::runtime::value::Value;
+use crate::semantics::meta::meta_processor::MetaContext;
 use crate::semantics::meta::runtime_ast::*;
+use std::collections::HashMap;
+use std::rc::Rc;
 
-fn subst_str(name: &str, env: &EnvRef) -> String {
-    match env.borrow().get(name) {
-        Some(Value::String(s)) => s.clone(),
-        _ => name.to_string(),
-    }
+/// Copies `stmts` for a `gen`, giving every node a fresh id in `meta`.
+///
+/// Variables the compile-time `env` binds to a value with a literal form are
+/// replaced by that literal, and a string value renames a declared function
+/// or variable, or the function being called. Names the copied code binds
+/// itself shadow compile-time ones. The bodies of `meta` blocks are copied as
+/// they are, since they run against their own level's variables.
+///
+/// A `$` is replaced by its value in `splices`, and a code value, spliced in
+/// or bound to a variable, by a copy of its code.
+pub fn subst_stmts(
+    stmts: &[AstId],
+    ast: &RuntimeAst,
+    env: &EnvHandler,
+    splices: &HashMap<AstId, Value>,
+    meta: &mut MetaContext,
+) -> Result<Vec<AstId>, EvalError> {
+    let mut subst = Substitution {
+        ast,
+        env: Some(env),
+        splices,
+        meta,
+        bound: Vec::new(),
+        verbatim: false,
+    };
+    subst.stmts(stmts)
 }
 
-fn subst_expr(expr: &RuntimeExpr, env: &EnvRef) -> RuntimeExpr {
-    match expr {
-        RuntimeExpr::Int(n) => RuntimeExpr::Int(*n),
+/// Copies the statements of a `quote` into `meta` with fresh ids. Unlike a
+/// `gen`, only its `$`s are filled in, from `splices`; every other name is
+/// left for the code the statements end up in.
+pub fn quote_stmts(
+    stmts: &[AstId],
+    ast: &RuntimeAst,
+    splices: &HashMap<AstId, Value>,
+    meta: &mut MetaContext,
+) -> Result<Vec<AstId>, EvalError> {
+    let mut subst = Substitution {
+        ast,
+        env: None,
+        splices,
+        meta,
+        bound: Vec::new(),
+        verbatim: false,
+    };
+    subst.stmts(stmts)
+}
 
-        RuntimeExpr::String(s) => RuntimeExpr::String(s.clone()),
+/// The `$`s a `gen` or `quote` with statements `stmts` fills in, with the
+/// expression each splices in. Those in a nested `quote`, `gen` or `meta`
+/// block are filled in at another level and are left out.
+pub fn splices(stmts: &[AstId], ast: &RuntimeAst) -> Vec<(AstId, AstId)> {
+    let mut found = Vec::new();
+    for stmt in stmts {
+        splices_in_stmt(*stmt, ast, &mut found);
+    }
+    found
+}
 
-        RuntimeExpr::Bool(b) => RuntimeExpr::Bool(*b),
+fn splices_in_stmt(id: AstId, ast: &RuntimeAst, found: &mut Vec<(AstId, AstId)>) {
+    let Some(stmt) = ast.get_stmt(id) else {
+        return;
+    };
+    let (exprs, stmts): (Vec<AstId>, Vec<AstId>) = match stmt {
+        RuntimeStmt::ExprStmt(e) | RuntimeStmt::Print(e) | RuntimeStmt::VarDecl { expr: e, .. } => {
+            (vec![*e], vec![])
+        }
+        RuntimeStmt::Return(e) => (e.iter().copied().collect(), vec![]),
+        RuntimeStmt::FnDecl { body, .. } | RuntimeStmt::Defer(body) => (vec![], vec![*body]),
+        RuntimeStmt::ImplDecl { methods, .. } => (vec![], methods.clone()),
+        RuntimeStmt::If {
+            cond,
+            body,
+            else_branch,
+        } => (
+            vec![*cond],
+            [*body].into_iter().chain(*else_branch).collect(),
+        ),
+        RuntimeStmt::ForEach { iterable, body, .. } => (vec![*iterable], vec![*body]),
+        RuntimeStmt::While { cond, body } => (vec![*cond], vec![*body]),
+        RuntimeStmt::Match { scrutinee, arms } => (
+            [*scrutinee]
+                .into_iter()
+                .chain(arms.iter().filter_map(|arm| arm.guard))
+                .collect(),
+            arms.iter().map(|arm| arm.body).collect(),
+        ),
+        RuntimeStmt::Block(stmts) => (vec![], stmts.clone()),
+        RuntimeStmt::Gen(_)
+        | RuntimeStmt::MetaBlock(_)
+        | RuntimeStmt::StructDecl { .. }
+        | RuntimeStmt::EnumDecl { .. }
+        | RuntimeStmt::TraitDecl { .. }
+        | RuntimeStmt::Break
+        | RuntimeStmt::Continue
+        | RuntimeStmt::Import(_) => (vec![], vec![]),
+    };
+    for expr in exprs {
+        splices_in_expr(expr, ast, found);
+    }
+    for stmt in stmts {
+        splices_in_stmt(stmt, ast, found);
+    }
+}
 
-        RuntimeExpr::StructLiteral { type_name, fields } => RuntimeExpr::StructLiteral {
-            type_name: subst_str(type_name, env),
-            fields: fields.clone(),
+fn splices_in_expr(id: AstId, ast: &RuntimeAst, found: &mut Vec<(AstId, AstId)>) {
+    let Some(expr) = ast.get_expr(id) else {
+        return;
+    };
+    let children: Vec<AstId> = match expr {
+        RuntimeExpr::Unquote(inner) => {
+            found.push((id, *inner));
+            return;
+        }
+        RuntimeExpr::Lambda { body, .. } => {
+            splices_in_stmt(*body, ast, found);
+            return;
+        }
+        RuntimeExpr::Quote(_)
+        | RuntimeExpr::Variable(_)
+        | RuntimeExpr::Int(_)
+        | RuntimeExpr::String(_)
+        | RuntimeExpr::Bool(_)
+        | RuntimeExpr::Bytes(_) => vec![],
+        RuntimeExpr::StructLiteral { fields, .. } => fields.iter().map(|(_, e)| *e).collect(),
+        RuntimeExpr::Variant { payload, .. } => match payload {
+            Payload::Unit => vec![],
+            Payload::Tuple(items) => items.clone(),
+            Payload::Struct(fields) => fields.iter().map(|(_, e)| *e).collect(),
         },
+        RuntimeExpr::List(items) => items.clone(),
+        RuntimeExpr::Call { callee, args } => [*callee].into_iter().chain(args.clone()).collect(),
+        RuntimeExpr::MethodCall { receiver, args, .. } => {
+            [*receiver].into_iter().chain(args.clone()).collect()
+        }
+        RuntimeExpr::FieldAccess { target, .. } => vec![*target],
+        RuntimeExpr::Index { target, index } => vec![*target, *index],
+        RuntimeExpr::Slice { target, start, end } => {
+            [*target].into_iter().chain(*start).chain(*end).collect()
+        }
+        RuntimeExpr::Assign { target, value, .. } => vec![*target, *value],
+        RuntimeExpr::Add(a, b)
+        | RuntimeExpr::Sub(a, b)
+        | RuntimeExpr::Mult(a, b)
+        | RuntimeExpr::Div(a, b)
+        | RuntimeExpr::Equals(a, b)
+        | RuntimeExpr::NotEquals(a, b)
+        | RuntimeExpr::Less(a, b)
+        | RuntimeExpr::LessEqual(a, b)
+        | RuntimeExpr::Greater(a, b)
+        | RuntimeExpr::GreaterEqual(a, b)
+        | RuntimeExpr::And(a, b)
+        | RuntimeExpr::Or(a, b) => vec![*a, *b],
+        RuntimeExpr::Not(a) | RuntimeExpr::Negate(a) => vec![*a],
+    };
+    for child in children {
+        splices_in_expr(child, ast, found);
+    }
+}
 
-        RuntimeExpr::Variable(name) => match env.borrow().get(name) {
-            Some(Value::Int(n)) => RuntimeExpr::Int(n),
-            Some(Value::String(s)) => RuntimeExpr::String(s.clone()),
-            Some(Value::Bool(b)) => RuntimeExpr::Bool(b),
+struct Substitution<'a> {
+    ast: &'a RuntimeAst,
+    // the compile-time variables filled in, `None` for a `quote`
+    env: Option<&'a EnvHandler>,
+    // the values of the `$`s being filled in
+    splices: &'a HashMap<AstId, Value>,
+    meta: &'a mut MetaContext,
+    // names bound by the copied code so far, innermost scope last
+    bound: Vec<String>,
+    // inside an emitted `meta` block, which sees its own level's variables
+    verbatim: bool,
+}
 
-            _ => RuntimeExpr::Variable(name.clone()),
-        },
+impl Substitution<'_> {
+    // Adds a copy of the node at `original` to the emitted nodes.
+    fn emit(&mut self, original: AstId, node: RuntimeNode) -> AstId {
+        let id = self.meta.ids.next();
+        let span = self.span(original);
+        self.meta.nodes.push((id, node, span));
+        id
+    }
 
-        RuntimeExpr::List(exprs) => RuntimeExpr::List(exprs.clone()),
+    // A node being copied is lowered, or, when it belongs to a code value
+    // the running block built, among the nodes it emitted so far.
+    fn emitted(&self, id: AstId) -> Option<&(AstId, RuntimeNode, Option<Span>)> {
+        self.meta.nodes.iter().rev().find(|(node, ..)| *node == id)
+    }
 
-        RuntimeExpr::Add(a, b) => {
-            RuntimeExpr::Add(Box::new(subst_expr(a, env)), Box::new(subst_expr(b, env)))
+    fn get_expr(&self, id: AstId) -> Result<RuntimeExpr, EvalError> {
+        if let Some(expr) = self.ast.get_expr(id) {
+            return Ok(expr.clone());
         }
+        match self.emitted(id) {
+            Some((_, RuntimeNode::Expr(expr), _)) => Ok(expr.clone()),
+            _ => Err(EvalError::ExprNotFound(id)),
+        }
+    }
 
-        RuntimeExpr::Sub(a, b) => {
-            RuntimeExpr::Sub(Box::new(subst_expr(a, env)), Box::new(subst_expr(b, env)))
+    fn get_stmt(&self, id: AstId) -> Result<RuntimeStmt, EvalError> {
+        if let Some(stmt) = self.ast.get_stmt(id) {
+            return Ok(stmt.clone());
+        }
+        match self.emitted(id) {
+            Some((_, RuntimeNode::Stmt(stmt), _)) => Ok(stmt.clone()),
+            _ => Err(EvalError::StmtNotFound(id)),
         }
+    }
+
+    fn span(&self, id: AstId) -> Option<Span> {
+        self.ast
+            .get_span(id)
+            .or_else(|| self.emitted(id).and_then(|(_, _, span)| *span))
+    }
 
-        RuntimeExpr::Mult(a, b) => {
-            RuntimeExpr::Mult(Box::new(subst_expr(a, env)), Box::new(subst_expr(b, env)))
+    fn compile_time(&self, name: &str) -> Option<Value> {
+        if self.verbatim || self.bound.iter().any(|b| b == name) {
+            return None;
         }
+        self.env?.get(name).ok()
+    }
+
+    // Copies the code of a code value, which has nothing left to fill in at
+    // this level.
+    fn fragment<T>(
+        &mut self,
+        copy: impl FnOnce(&mut Self) -> Result<T, EvalError>,
+    ) -> Result<T, EvalError> {
+        let env = self.env.take();
+        let result = copy(self);
+        self.env = env;
+        result
+    }
+
+    // The code for `value` where an expression goes: the expression a code
+    // value holds, or the literal for any other value that has one.
+    fn splice(&mut self, original: AstId, value: &Value) -> Result<Option<AstId>, EvalError> {
+        let Value::Ast(stmts) = value else {
+            return Ok(self.literal(original, value));
+        };
+        let expr = match stmts.as_slice() {
+            [stmt] => match self.get_stmt(*stmt)? {
+                RuntimeStmt::ExprStmt(e) => Some(e),
+                _ => None,
+            },
+            _ => None,
+        };
+        let Some(expr) = expr else {
+            let span = self.span(original).unwrap_or_default();
+            return Err(EvalError::InvalidSplice("statements".to_string(), span));
+        };
+        self.fragment(|s| s.expr(expr)).map(Some)
+    }
 
-        RuntimeExpr::Div(a, b) => {
-            RuntimeExpr::Div(Box::new(subst_expr(a, env)), Box::new(subst_expr(b, env)))
+    // The statements of the code value an expression statement stands for,
+    // which are spliced in in its place.
+    fn spliced_stmts(&self, id: AstId) -> Result<Option<Rc<Vec<AstId>>>, EvalError> {
+        let RuntimeStmt::ExprStmt(e) = self.get_stmt(id)? else {
+            return Ok(None);
+        };
+        let value = match self.get_expr(e)? {
+            RuntimeExpr::Unquote(_) => self.splices.get(&e).cloned(),
+            RuntimeExpr::Variable(name) => self.compile_time(&name),
+            _ => None,
+        };
+        Ok(match value {
+            Some(Value::Ast(stmts)) => Some(stmts),
+            _ => None,
+        })
+    }
+
+    fn name(&self, name: &str) -> String {
+        match self.compile_time(name) {
+            Some(Value::String(s)) => s,
+            _ => name.to_string(),
         }
+    }
 
-        RuntimeExpr::Equals(a, b) => {
-            RuntimeExpr::Equals(Box::new(subst_expr(a, env)), Box::new(subst_expr(b, env)))
+    fn bind(&mut self, name: String) {
+        self.bound.push(name);
+    }
+
+    // Copies with the names `copy` binds dropped again afterwards.
+    fn scoped<T>(
+        &mut self,
+        copy: impl FnOnce(&mut Self) -> Result<T, EvalError>,
+    ) -> Result<T, EvalError> {
+        let depth = self.bound.len();
+        let result = copy(self);
+        self.bound.truncate(depth);
+        result
+    }
+
+    fn bind_pattern(&mut self, pattern: &Pattern) {
+        match &pattern.kind {
+            PatternKind::Binding(name) => self.bind(name.clone()),
+            PatternKind::List { items, rest } => {
+                for item in items.iter().chain(rest.as_deref()) {
+                    self.bind_pattern(item);
+                }
+            }
+            PatternKind::Struct { fields, .. } => {
+                for (_, field) in fields {
+                    self.bind_pattern(field);
+                }
+            }
+            PatternKind::Variant { payload, .. } => match payload {
+                Payload::Unit => {}
+                Payload::Tuple(items) => {
+                    for item in items {
+                        self.bind_pattern(item);
+                    }
+                }
+                Payload::Struct(fields) => {
+                    for (_, field) in fields {
+                        self.bind_pattern(field);
+                    }
+                }
+            },
+            PatternKind::Wildcard
+            | PatternKind::Int(_)
+            | PatternKind::String(_)
+            | PatternKind::Bool(_) => {}
         }
+    }
 
-        RuntimeExpr::Call { callee, args } => RuntimeExpr::Call {
-            callee: subst_str(callee, env),
-            args: args.iter().map(|a| subst_expr(a, env)).collect(),
-        },
+    // The literal for a compile-time value, if it has one.
+    fn literal(&mut self, original: AstId, value: &Value) -> Option<AstId> {
+        let expr = match value {
+            Value::Int(n) => RuntimeExpr::Int(*n),
+            Value::String(s) => RuntimeExpr::String(s.clone()),
+            Value::Bool(b) => RuntimeExpr::Bool(*b),
+            Value::List(items) => {
+                let items = items
+                    .borrow()
+                    .iter()
+                    .map(|item| self.literal(original, item))
+                    .collect::<Option<_>>()?;
+                RuntimeExpr::List(items)
+            }
+            Value::Struct { type_name, fields } => {
+                let fields = fields
+                    .borrow()
+                    .iter()
+                    .map(|(name, value)| Some((name.clone(), self.literal(original, value)?)))
+                    .collect::<Option<_>>()?;
+                RuntimeExpr::StructLiteral {
+                    type_name: type_name.clone(),
+                    fields,
+                }
+            }
+            Value::Variant {
+                enum_name,
+                variant,
+                payload,
+            } => {
+                let payload = match payload.as_ref() {
+                    Payload::Unit => Payload::Unit,
+                    Payload::Tuple(items) => Payload::Tuple(
+                        items
+                            .iter()
+                            .map(|item| self.literal(original, item))
+                            .collect::<Option<_>>()?,
+                    ),
+                    Payload::Struct(fields) => Payload::Struct(
+                        fields
+                            .iter()
+                            .map(|(name, value)| {
+                                Some((name.clone(), self.literal(original, value)?))
+                            })
+                            .collect::<Option<_>>()?,
+                    ),
+                };
+                RuntimeExpr::Variant {
+                    enum_name: enum_name.clone(),
+                    variant: variant.clone(),
+                    payload,
+                }
+            }
+            Value::Function(_)
+            | Value::Module(_)
+            | Value::Builtin(_)
+            | Value::Ast(_)
+            | Value::Unit => return None,
+        };
+        Some(self.emit(original, RuntimeNode::Expr(expr)))
     }
-}
 
-fn subst_stmt(stmt: &RuntimeStmt, env: &EnvRef) -> RuntimeStmt {
-    match stmt {
-        RuntimeStmt::ExprStmt(e) => RuntimeStmt::ExprStmt(Box::new(subst_expr(e, env))),
+    fn exprs(&mut self, ids: &[AstId]) -> Result<Vec<AstId>, EvalError> {
+        ids.iter().map(|id| self.expr(*id)).collect()
+    }
 
-        RuntimeStmt::Assignment { name, expr } => RuntimeStmt::Assignment {
-            name: subst_str(name, env),
-            expr: Box::new(subst_expr(expr, env)),
-        },
+    fn opt_expr(&mut self, id: Option<AstId>) -> Result<Option<AstId>, EvalError> {
+        id.map(|id| self.expr(id)).transpose()
+    }
 
-        RuntimeStmt::Print(e) => RuntimeStmt::Print(Box::new(subst_expr(e, env))),
+    // Copies the target of an assignment, which names a variable rather than
+    // reading it.
+    fn place(&mut self, id: AstId) -> Result<AstId, EvalError> {
+        let expr = match &self.get_expr(id)? {
+            RuntimeExpr::Variable(name) => RuntimeExpr::Variable(name.clone()),
+            RuntimeExpr::FieldAccess { target, field } => RuntimeExpr::FieldAccess {
+                target: self.place(*target)?,
+                field: field.clone(),
+            },
+            RuntimeExpr::Index { target, index } => RuntimeExpr::Index {
+                target: self.place(*target)?,
+                index: self.expr(*index)?,
+            },
+            _ => return self.expr(id),
+        };
+        Ok(self.emit(id, RuntimeNode::Expr(expr)))
+    }
 
-        RuntimeStmt::If {
-            cond,
-            body,
-            else_branch,
-        } => RuntimeStmt::If {
-            cond: Box::new(subst_expr(cond, env)),
-            body: Box::new(subst_stmt(body, env)),
-            else_branch: else_branch.as_ref().map(|b| Box::new(subst_stmt(b, env))),
-        },
+    fn expr(&mut self, id: AstId) -> Result<AstId, EvalError> {
+        let binary = |s: &mut Self, a: &AstId, b: &AstId| Ok((s.expr(*a)?, s.expr(*b)?));
+        let expr = match &self.get_expr(id)? {
+            RuntimeExpr::Variable(name) => {
+                if let Some(value) = self.compile_time(name) {
+                    if let Some(code) = self.splice(id, &value)? {
+                        return Ok(code);
+                    }
+                }
+                RuntimeExpr::Variable(name.clone())
+            }
 
-        RuntimeStmt::ForEach {
-            var,
-            iterable,
-            body,
-        } => RuntimeStmt::ForEach {
-            var: subst_str(var, env),
-            iterable: Box::new(subst_expr(iterable, env)),
-            body: Box::new(subst_stmt(body, env)),
-        },
+            RuntimeExpr::Unquote(inner) => match self.splices.get(&id) {
+                Some(value) => {
+                    let value = value.clone();
+                    return self.splice(id, &value)?.ok_or_else(|| {
+                        let what = match value {
+                            Value::Function(_) | Value::Builtin(_) => "a function",
+                            Value::Module(_) => "a module",
+                            _ => "a value without a literal form",
+                        };
+                        EvalError::InvalidSplice(
+                            what.to_string(),
+                            self.span(id).unwrap_or_default(),
+                        )
+                    });
+                }
+                // it is filled in by the level that emits this code
+                None => RuntimeExpr::Unquote(self.expr(*inner)?),
+            },
 
-        RuntimeStmt::Block(stmts) => {
-            RuntimeStmt::Block(stmts.iter().map(|s| subst_stmt(s, env)).collect())
-        }
+            // a `gen` emits run-time code, unless it emits a `meta` block
+            RuntimeExpr::Quote(_) if self.env.is_some() && !self.verbatim => {
+                return Err(EvalError::QuoteInGen(self.span(id).unwrap_or_default()));
+            }
+            RuntimeExpr::Quote(stmts) => RuntimeExpr::Quote(self.scoped(|s| s.stmts(stmts))?),
 
-        RuntimeStmt::FnDecl { name, params, body } => RuntimeStmt::FnDecl {
-            name: subst_str(name, env),
-            params: params.clone(),
-            body: Box::new(subst_stmt(body, env)),
-        },
+            expr @ (RuntimeExpr::Int(_)
+            | RuntimeExpr::String(_)
+            | RuntimeExpr::Bool(_)
+            | RuntimeExpr::Bytes(_)) => expr.clone(),
 
-        RuntimeStmt::Return(expr) => {
-            RuntimeStmt::Return(expr.as_ref().map(|e| Box::new(subst_expr(e, env))))
-        }
+            RuntimeExpr::StructLiteral { type_name, fields } => RuntimeExpr::StructLiteral {
+                type_name: type_name.clone(),
+                fields: fields
+                    .iter()
+                    .map(|(name, e)| Ok((name.clone(), self.expr(*e)?)))
+                    .collect::<Result<_, EvalError>>()?,
+            },
+
+            RuntimeExpr::Variant {
+                enum_name,
+                variant,
+                payload,
+            } => RuntimeExpr::Variant {
+                enum_name: enum_name.clone(),
+                variant: variant.clone(),
+                payload: match payload {
+                    Payload::Unit => Payload::Unit,
+                    Payload::Tuple(items) => Payload::Tuple(self.exprs(items)?),
+                    Payload::Struct(fields) => Payload::Struct(
+                        fields
+                            .iter()
+                            .map(|(name, e)| Ok((name.clone(), self.expr(*e)?)))
+                            .collect::<Result<_, EvalError>>()?,
+                    ),
+                },
+            },
+
+            RuntimeExpr::List(items) => RuntimeExpr::List(self.exprs(items)?),
+
+            RuntimeExpr::Call { callee, args } => {
+                let callee = match self.get_expr(*callee)? {
+                    RuntimeExpr::Variable(name) => {
+                        let renamed = RuntimeExpr::Variable(self.name(&name));
+                        self.emit(*callee, RuntimeNode::Expr(renamed))
+                    }
+                    _ => self.expr(*callee)?,
+                };
+                RuntimeExpr::Call {
+                    callee,
+                    args: self.exprs(args)?,
+                }
+            }
+
+            RuntimeExpr::Lambda { params, ret, body } => {
+                let body = self.scoped(|s| {
+                    for param in params {
+                        s.bind(param.name.clone());
+                    }
+                    s.stmt(*body)
+                })?;
+                RuntimeExpr::Lambda {
+                    params: params.clone(),
+                    ret: ret.clone(),
+                    body,
+                }
+            }
+
+            RuntimeExpr::FieldAccess { target, field } => RuntimeExpr::FieldAccess {
+                target: self.expr(*target)?,
+                field: field.clone(),
+            },
+
+            RuntimeExpr::Index { target, index } => RuntimeExpr::Index {
+                target: self.expr(*target)?,
+                index: self.expr(*index)?,
+            },
+
+            RuntimeExpr::Slice { target, start, end } => RuntimeExpr::Slice {
+                target: self.expr(*target)?,
+                start: self.opt_expr(*start)?,
+                end: self.opt_expr(*end)?,
+            },
+
+            RuntimeExpr::MethodCall {
+                receiver,
+                method,
+                args,
+            } => RuntimeExpr::MethodCall {
+                receiver: self.expr(*receiver)?,
+                method: method.clone(),
+                args: self.exprs(args)?,
+            },
+
+            RuntimeExpr::Assign { target, op, value } => RuntimeExpr::Assign {
+                target: self.place(*target)?,
+                op: *op,
+                value: self.expr(*value)?,
+            },
 
-        RuntimeStmt::Gen(stmts) => {
-            RuntimeStmt::Gen(stmts.iter().map(|s| subst_stmt(s, env)).collect())
+            RuntimeExpr::Add(a, b) => {
+                let (a, b) = binary(self, a, b)?;
+                RuntimeExpr::Add(a, b)
+            }
+            RuntimeExpr::Sub(a, b) => {
+                let (a, b) = binary(self, a, b)?;
+                RuntimeExpr::Sub(a, b)
+            }
+            RuntimeExpr::Mult(a, b) => {
+                let (a, b) = binary(self, a, b)?;
+                RuntimeExpr::Mult(a, b)
+            }
+            RuntimeExpr::Div(a, b) => {
+                let (a, b) = binary(self, a, b)?;
+                RuntimeExpr::Div(a, b)
+            }
+            RuntimeExpr::Equals(a, b) => {
+                let (a, b) = binary(self, a, b)?;
+                RuntimeExpr::Equals(a, b)
+            }
+            RuntimeExpr::NotEquals(a, b) => {
+                let (a, b) = binary(self, a, b)?;
+                RuntimeExpr::NotEquals(a, b)
+            }
+            RuntimeExpr::Less(a, b) => {
+                let (a, b) = binary(self, a, b)?;
+                RuntimeExpr::Less(a, b)
+            }
+            RuntimeExpr::LessEqual(a, b) => {
+                let (a, b) = binary(self, a, b)?;
+                RuntimeExpr::LessEqual(a, b)
+            }
+            RuntimeExpr::Greater(a, b) => {
+                let (a, b) = binary(self, a, b)?;
+                RuntimeExpr::Greater(a, b)
+            }
+            RuntimeExpr::GreaterEqual(a, b) => {
+                let (a, b) = binary(self, a, b)?;
+                RuntimeExpr::GreaterEqual(a, b)
+            }
+            RuntimeExpr::And(a, b) => {
+                let (a, b) = binary(self, a, b)?;
+                RuntimeExpr::And(a, b)
+            }
+            RuntimeExpr::Or(a, b) => {
+                let (a, b) = binary(self, a, b)?;
+                RuntimeExpr::Or(a, b)
+            }
+
+            RuntimeExpr::Not(a) => RuntimeExpr::Not(self.expr(*a)?),
+            RuntimeExpr::Negate(a) => RuntimeExpr::Negate(self.expr(*a)?),
+        };
+        Ok(self.emit(id, RuntimeNode::Expr(expr)))
+    }
+
+    fn stmts(&mut self, ids: &[AstId]) -> Result<Vec<AstId>, EvalError> {
+        let mut copies = Vec::with_capacity(ids.len());
+        for id in ids {
+            match self.spliced_stmts(*id)? {
+                Some(stmts) => copies.extend(self.fragment(|s| s.stmts(&stmts))?),
+                None => copies.push(self.stmt(*id)?),
+            }
         }
+        Ok(copies)
     }
-}
 
-pub fn subst_stmts(stmts: &[RuntimeStmt], env: &EnvRef) -> Vec<RuntimeStmt> {
-    stmts.iter().map(|s| subst_stmt(s, env)).collect()
+    fn arm(&mut self, arm: &MatchArm) -> Result<MatchArm, EvalError> {
+        self.scoped(|s| {
+            s.bind_pattern(&arm.pattern);
+            Ok(MatchArm {
+                pattern: arm.pattern.clone(),
+                guard: s.opt_expr(arm.guard)?,
+                body: s.stmt(arm.body)?,
+            })
+        })
+    }
+
+    fn stmt(&mut self, id: AstId) -> Result<AstId, EvalError> {
+        if let Some(stmts) = self.spliced_stmts(id)? {
+            let block = RuntimeStmt::Block(self.fragment(|s| s.stmts(&stmts))?);
+            return Ok(self.emit(id, RuntimeNode::Stmt(block)));
+        }
+        let stmt = match &self.get_stmt(id)? {
+            RuntimeStmt::ExprStmt(e) => RuntimeStmt::ExprStmt(self.expr(*e)?),
+            RuntimeStmt::Print(e) => RuntimeStmt::Print(self.expr(*e)?),
+
+            RuntimeStmt::VarDecl { name, ty, expr } => {
+                // the initializer can't see the variable yet
+                let expr = self.expr(*expr)?;
+                let name = self.name(name);
+                self.bind(name.clone());
+                RuntimeStmt::VarDecl {
+                    name,
+                    ty: ty.clone(),
+                    expr,
+                }
+            }
+
+            RuntimeStmt::FnDecl {
+                name,
+                type_params,
+                params,
+                ret,
+                body,
+            } => {
+                let name = self.name(name);
+                self.bind(name.clone());
+                let body = self.scoped(|s| {
+                    for param in params {
+                        s.bind(param.name.clone());
+                    }
+                    s.stmt(*body)
+                })?;
+                RuntimeStmt::FnDecl {
+                    name,
+                    type_params: type_params.clone(),
+                    params: params.clone(),
+                    ret: ret.clone(),
+                    body,
+                }
+            }
+
+            stmt @ (RuntimeStmt::StructDecl { .. }
+            | RuntimeStmt::EnumDecl { .. }
+            | RuntimeStmt::TraitDecl { .. }
+            | RuntimeStmt::Import(_)
+            | RuntimeStmt::Break
+            | RuntimeStmt::Continue) => stmt.clone(),
+
+            RuntimeStmt::ImplDecl {
+                trait_name,
+                type_name,
+                methods,
+            } => RuntimeStmt::ImplDecl {
+                trait_name: trait_name.clone(),
+                type_name: type_name.clone(),
+                methods: self.stmts(methods)?,
+            },
+
+            RuntimeStmt::If {
+                cond,
+                body,
+                else_branch,
+            } => RuntimeStmt::If {
+                cond: self.expr(*cond)?,
+                body: self.scoped(|s| s.stmt(*body))?,
+                else_branch: self.scoped(|s| else_branch.map(|e| s.stmt(e)).transpose())?,
+            },
+
+            RuntimeStmt::ForEach {
+                var,
+                iterable,
+                body,
+            } => {
+                let iterable = self.expr(*iterable)?;
+                let body = self.scoped(|s| {
+                    s.bind(var.clone());
+                    s.stmt(*body)
+                })?;
+                RuntimeStmt::ForEach {
+                    var: var.clone(),
+                    iterable,
+                    body,
+                }
+            }
+
+            RuntimeStmt::While { cond, body } => RuntimeStmt::While {
+                cond: self.expr(*cond)?,
+                body: self.scoped(|s| s.stmt(*body))?,
+            },
+
+            RuntimeStmt::Match { scrutinee, arms } => RuntimeStmt::Match {
+                scrutinee: self.expr(*scrutinee)?,
+                arms: arms
+                    .iter()
+                    .map(|arm| self.arm(arm))
+                    .collect::<Result<_, _>>()?,
+            },
+
+            RuntimeStmt::Return(e) => RuntimeStmt::Return(self.opt_expr(*e)?),
+
+            RuntimeStmt::Defer(s) => RuntimeStmt::Defer(self.stmt(*s)?),
+
+            RuntimeStmt::Block(stmts) => RuntimeStmt::Block(self.scoped(|s| s.stmts(stmts))?),
+
+            RuntimeStmt::Gen(stmts) => RuntimeStmt::Gen(self.stmts(stmts)?),
+
+            RuntimeStmt::MetaBlock(body) => {
+                let verbatim = std::mem::replace(&mut self.verbatim, true);
+                let body = self.stmt(*body);
+                self.verbatim = verbatim;
+                RuntimeStmt::MetaBlock(body?)
+            }
+        };
+        Ok(self.emit(id, RuntimeNode::Stmt(stmt)))
+    }
 }
@@ -0,0 +1,330 @@
+use super::type_error::TypeError;
+use crate::frontend::meta_ast::{MatchArm, Pattern, PatternKind, Payload};
+use crate::util::decl_registry::DeclRegistry;
+
+/// Reports the first arm of a type-correct `match` that can never run, then
+/// a value none of the arms match. Guarded arms may always fall through, so
+/// they cover nothing for the arms after them.
+///
+/// This is the usefulness check from Maranget's "Warnings for pattern
+/// matching": an arm is reachable if its pattern matches some value no
+/// earlier arm does, and the match is exhaustive if `_` would not be.
+pub fn check_match(arms: &[MatchArm], decls: &DeclRegistry) -> Result<(), TypeError> {
+    let mut matrix: Vec<Vec<Pat>> = Vec::new();
+    for arm in arms {
+        let row = vec![Pat::lower(&arm.pattern, decls)];
+        if !useful(&matrix, &row, decls) {
+            return Err(TypeError::UnreachableArm.at(arm.pattern.span));
+        }
+        if arm.guard.is_none() {
+            matrix.push(row);
+        }
+    }
+
+    match witness(&matrix, 1, decls) {
+        Some(missing) => Err(TypeError::NonExhaustive {
+            missing: missing[0].describe(decls),
+        }),
+        None => Ok(()),
+    }
+}
+
+// A pattern reduced to what it matches: constructors applied to sub-patterns.
+// Struct fields and struct payloads are in declaration order, with `Wild`
+// for the ones left out.
+#[derive(Debug, Clone)]
+enum Pat {
+    Wild,
+    Ctor(Ctor, Vec<Pat>),
+    // a list of at least as many elements as the prefix
+    ListRest(Vec<Pat>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Ctor {
+    Bool(bool),
+    Int(i64),
+    String(String),
+    // a list of exactly this many elements
+    List(usize),
+    Struct(String),
+    Variant { enum_name: String, variant: String },
+}
+
+impl Pat {
+    fn lower(pattern: &Pattern, decls: &DeclRegistry) -> Pat {
+        let lower_all =
+            |patterns: &[Pattern]| patterns.iter().map(|p| Pat::lower(p, decls)).collect();
+        match &pattern.kind {
+            PatternKind::Wildcard | PatternKind::Binding(_) => Pat::Wild,
+            PatternKind::Int(n) => Pat::Ctor(Ctor::Int(*n), vec![]),
+            PatternKind::String(s) => Pat::Ctor(Ctor::String(s.clone()), vec![]),
+            PatternKind::Bool(b) => Pat::Ctor(Ctor::Bool(*b), vec![]),
+            PatternKind::List { items, rest: None } => {
+                Pat::Ctor(Ctor::List(items.len()), lower_all(items))
+            }
+            PatternKind::List { items, .. } => Pat::ListRest(lower_all(items)),
+            PatternKind::Struct { name, fields } => {
+                let declared: Vec<String> = decls
+                    .get_struct(name)
+                    .map(|def| def.fields.into_iter().map(|(field, _)| field).collect())
+                    .unwrap_or_default();
+                Pat::Ctor(
+                    Ctor::Struct(name.clone()),
+                    Pat::lower_fields(&declared, fields, decls),
+                )
+            }
+            PatternKind::Variant {
+                enum_name,
+                variant,
+                payload,
+            } => {
+                let args = match payload {
+                    Payload::Unit => vec![],
+                    Payload::Tuple(items) => lower_all(items),
+                    Payload::Struct(fields) => {
+                        let declared: Vec<String> = decls
+                            .get_enum(enum_name)
+                            .and_then(|def| def.variant(variant).cloned())
+                            .map(|decl| match decl.payload {
+                                Payload::Struct(declared) => {
+                                    declared.into_iter().map(|(field, _)| field).collect()
+                                }
+                                _ => vec![],
+                            })
+                            .unwrap_or_default();
+                        Pat::lower_fields(&declared, fields, decls)
+                    }
+                };
+                let ctor = Ctor::Variant {
+                    enum_name: enum_name.clone(),
+                    variant: variant.clone(),
+                };
+                Pat::Ctor(ctor, args)
+            }
+        }
+    }
+
+    fn lower_fields(
+        declared: &[String],
+        fields: &[(String, Pattern)],
+        decls: &DeclRegistry,
+    ) -> Vec<Pat> {
+        declared
+            .iter()
+            .map(
+                |name| match fields.iter().find(|(field, _)| field == name) {
+                    Some((_, pattern)) => Pat::lower(pattern, decls),
+                    None => Pat::Wild,
+                },
+            )
+            .collect()
+    }
+
+    // Written the way the pattern would be in the source.
+    fn describe(&self, decls: &DeclRegistry) -> String {
+        let join = |pats: &[Pat]| {
+            pats.iter()
+                .map(|p| p.describe(decls))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let with_fields = |declared: Vec<String>, args: &[Pat]| {
+            declared
+                .iter()
+                .zip(args)
+                .map(|(field, p)| format!("{field}: {}", p.describe(decls)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            Pat::Wild => "_".to_string(),
+            Pat::ListRest(prefix) if prefix.is_empty() => "[..]".to_string(),
+            Pat::ListRest(prefix) => format!("[{}, ..]", join(prefix)),
+            Pat::Ctor(ctor, args) => match ctor {
+                Ctor::Bool(b) => b.to_string(),
+                Ctor::Int(n) => n.to_string(),
+                Ctor::String(s) => format!("\"{s}\""),
+                Ctor::List(_) => format!("[{}]", join(args)),
+                Ctor::Struct(name) => {
+                    let declared = decls
+                        .get_struct(name)
+                        .map(|def| def.fields.into_iter().map(|(field, _)| field).collect())
+                        .unwrap_or_default();
+                    format!("{name} {{{}}}", with_fields(declared, args))
+                }
+                Ctor::Variant { enum_name, variant } => {
+                    let decl = decls
+                        .get_enum(enum_name)
+                        .and_then(|def| def.variant(variant).cloned());
+                    match decl.map(|decl| decl.payload) {
+                        Some(Payload::Tuple(_)) => {
+                            format!("{enum_name}::{variant}({})", join(args))
+                        }
+                        Some(Payload::Struct(fields)) => {
+                            let declared = fields.into_iter().map(|(field, _)| field).collect();
+                            format!("{enum_name}::{variant} {{{}}}", with_fields(declared, args))
+                        }
+                        _ => format!("{enum_name}::{variant}"),
+                    }
+                }
+            },
+        }
+    }
+}
+
+// Every constructor of the type the patterns in `heads` match, with its
+// arity, if that is a finite list. Lists only have one when some pattern
+// matches any longer list: lengths past the longest pattern all behave the
+// same, so one more than that stands for them.
+fn constructors(heads: &[&Pat], decls: &DeclRegistry) -> Option<Vec<(Ctor, usize)>> {
+    let mut longest = None;
+    let mut open = false;
+    for head in heads {
+        match head {
+            Pat::Wild => {}
+            Pat::ListRest(prefix) => {
+                open = true;
+                longest = longest.max(Some(prefix.len()));
+            }
+            Pat::Ctor(Ctor::List(len), _) => longest = longest.max(Some(*len)),
+            Pat::Ctor(Ctor::Bool(_), _) => {
+                return Some(vec![(Ctor::Bool(true), 0), (Ctor::Bool(false), 0)])
+            }
+            Pat::Ctor(Ctor::Struct(name), args) => {
+                return Some(vec![(Ctor::Struct(name.clone()), args.len())])
+            }
+            Pat::Ctor(Ctor::Variant { enum_name, .. }, _) => {
+                let def = decls.get_enum(enum_name)?;
+                let ctors = def
+                    .variants
+                    .iter()
+                    .map(|decl| {
+                        let ctor = Ctor::Variant {
+                            enum_name: enum_name.clone(),
+                            variant: decl.name.clone(),
+                        };
+                        (ctor, decl.payload.len())
+                    })
+                    .collect();
+                return Some(ctors);
+            }
+            Pat::Ctor(Ctor::Int(_) | Ctor::String(_), _) => return None,
+        }
+    }
+    match longest {
+        Some(longest) if open => Some(
+            (0..=longest + 1)
+                .map(|len| (Ctor::List(len), len))
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+fn covers(head: &Pat, ctor: &Ctor) -> bool {
+    match (head, ctor) {
+        (Pat::Ctor(c, _), ctor) => c == ctor,
+        (Pat::ListRest(prefix), Ctor::List(len)) => prefix.len() <= *len,
+        _ => false,
+    }
+}
+
+fn wilds(n: usize) -> Vec<Pat> {
+    vec![Pat::Wild; n]
+}
+
+// The rows that match values built with `ctor`, with the first column
+// replaced by that constructor's `arity` sub-patterns.
+fn specialize(matrix: &[Vec<Pat>], ctor: &Ctor, arity: usize) -> Vec<Vec<Pat>> {
+    matrix
+        .iter()
+        .filter_map(|row| {
+            let mut args = match &row[0] {
+                Pat::Wild => wilds(arity),
+                Pat::Ctor(c, args) if c == ctor => args.clone(),
+                Pat::ListRest(prefix) if covers(&row[0], ctor) => {
+                    let mut args = prefix.clone();
+                    args.extend(wilds(arity - prefix.len()));
+                    args
+                }
+                _ => return None,
+            };
+            args.extend_from_slice(&row[1..]);
+            Some(args)
+        })
+        .collect()
+}
+
+// The rows whose first column matches anything, without that column.
+fn default_rows(matrix: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+    matrix
+        .iter()
+        .filter(|row| matches!(row[0], Pat::Wild))
+        .map(|row| row[1..].to_vec())
+        .collect()
+}
+
+// Whether some value matched by `row` is matched by no row of `matrix`.
+fn useful(matrix: &[Vec<Pat>], row: &[Pat], decls: &DeclRegistry) -> bool {
+    let Some((head, rest)) = row.split_first() else {
+        return matrix.is_empty();
+    };
+    let heads: Vec<&Pat> = matrix.iter().map(|r| &r[0]).chain([head]).collect();
+
+    let ctors: Vec<(Ctor, usize)> = match head {
+        Pat::Ctor(ctor, args) => vec![(ctor.clone(), args.len())],
+        Pat::ListRest(_) => constructors(&heads, decls)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(ctor, _)| covers(head, ctor))
+            .collect(),
+        Pat::Wild => match complete(&heads[..matrix.len()], decls) {
+            Some(ctors) => ctors,
+            None => return useful(&default_rows(matrix), rest, decls),
+        },
+    };
+    ctors.into_iter().any(|(ctor, arity)| {
+        let row = specialize(&[row.to_vec()], &ctor, arity).remove(0);
+        useful(&specialize(matrix, &ctor, arity), &row, decls)
+    })
+}
+
+// The constructors of the first column, if the rows use every one of them.
+fn complete(heads: &[&Pat], decls: &DeclRegistry) -> Option<Vec<(Ctor, usize)>> {
+    let ctors = constructors(heads, decls)?;
+    ctors
+        .iter()
+        .all(|(ctor, _)| heads.iter().any(|head| covers(head, ctor)))
+        .then_some(ctors)
+}
+
+// Values, one per column of `n`, that no row of `matrix` matches.
+fn witness(matrix: &[Vec<Pat>], n: usize, decls: &DeclRegistry) -> Option<Vec<Pat>> {
+    if n == 0 {
+        return matrix.is_empty().then(Vec::new);
+    }
+    let heads: Vec<&Pat> = matrix.iter().map(|r| &r[0]).collect();
+
+    if let Some(ctors) = complete(&heads, decls) {
+        return ctors.into_iter().find_map(|(ctor, arity)| {
+            let mut found = witness(&specialize(matrix, &ctor, arity), arity + n - 1, decls)?;
+            let rest = found.split_off(arity);
+            let mut values = vec![Pat::Ctor(ctor, found)];
+            values.extend(rest);
+            Some(values)
+        });
+    }
+
+    let mut values = witness(&default_rows(matrix), n - 1, decls)?;
+    // name a constructor the rows leave out when there is one
+    let missing = constructors(&heads, decls)
+        .and_then(|ctors| {
+            ctors
+                .into_iter()
+                .find(|(ctor, _)| !heads.iter().any(|head| covers(head, ctor)))
+        })
+        .map_or(Pat::Wild, |(ctor, arity)| Pat::Ctor(ctor, wilds(arity)));
+    values.insert(0, missing);
+    Some(values)
+}
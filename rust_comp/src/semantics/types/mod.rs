@@ -1,3 +1,5 @@
+pub mod exhaustiveness;
+pub mod runtime_checker;
 pub mod type_checker;
 pub mod type_env;
 pub mod type_error;
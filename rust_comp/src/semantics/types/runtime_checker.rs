@@ -0,0 +1,1186 @@
+use super::exhaustiveness::check_match;
+use super::type_checker::TypeCheckCtx;
+use super::type_env::TypeEnv;
+use super::type_error::TypeError;
+use super::type_subst::{unify, ApplySubst, TypeSubst};
+use super::type_utils::{generalize_with, instantiate};
+use super::types::*;
+use crate::frontend::id_provider::AstId;
+use crate::frontend::meta_ast::{
+    AssignOp, MatchArm, MethodSig, Param, Pattern, PatternKind, Payload, TypeExpr,
+};
+use crate::runtime::builtins::Builtin;
+use crate::semantics::meta::runtime_ast::{RuntimeAst, RuntimeExpr, RuntimeStmt};
+use std::collections::HashMap;
+
+/// The inferred type of every expression in a `RuntimeAst`.
+#[derive(Debug, Default)]
+pub struct TypeTable {
+    types: HashMap<AstId, Type>,
+}
+
+impl TypeTable {
+    pub fn get(&self, id: AstId) -> Option<&Type> {
+        self.types.get(&id)
+    }
+
+    /// Entries ordered by id.
+    pub fn entries(&self) -> Vec<(AstId, &Type)> {
+        let mut entries: Vec<_> = self.types.iter().map(|(id, ty)| (*id, ty)).collect();
+        entries.sort_by_key(|(id, _)| *id);
+        entries
+    }
+}
+
+/// Infers types for a lowered program, after meta expansion and before it
+/// runs. Imported modules are checked on their own, the first time they're
+/// imported.
+pub fn check_program(ast: &RuntimeAst) -> Result<TypeTable, TypeError> {
+    let mut checker = Checker {
+        ast,
+        env: TypeEnv::new(),
+        subst: TypeSubst::new(),
+        ctx: TypeCheckCtx::new(),
+        types: HashMap::new(),
+        modules: HashMap::new(),
+        preds: Vec::new(),
+        type_params: HashMap::new(),
+    };
+    checker.bind_builtins();
+    checker.check_stmts(&ast.sem_root_stmts)?;
+    // what is still on a variable holds for whatever type that turns out to be
+    checker.settle_preds(&[])?;
+
+    let types = checker
+        .types
+        .iter()
+        .map(|(id, ty)| (*id, ty.apply(&checker.subst)))
+        .collect();
+    Ok(TypeTable { types })
+}
+
+struct Checker<'a> {
+    ast: &'a RuntimeAst,
+    env: TypeEnv,
+    subst: TypeSubst,
+    ctx: TypeCheckCtx,
+    types: HashMap<AstId, Type>,
+    // top-level bindings of each module checked so far
+    modules: HashMap<String, HashMap<String, TypeScheme>>,
+    // trait predicates still to be checked, with the expression each came from
+    preds: Vec<(Predicate, AstId)>,
+    // type parameters of the generic functions being checked
+    type_params: HashMap<String, Type>,
+}
+
+impl Checker<'_> {
+    // Builtins live in the outermost scope, so user code may shadow them.
+    fn bind_builtins(&mut self) {
+        for builtin in Builtin::ALL {
+            self.env.bind_mono(&builtin.to_string(), builtin.ty());
+        }
+    }
+
+    fn fresh(&mut self) -> Type {
+        Type::Var(self.env.fresh())
+    }
+
+    // Reports a mismatch as "expected `expected`, found `found`".
+    fn expect(&mut self, expected: &Type, found: &Type) -> Result<(), TypeError> {
+        unify(expected, found, &mut self.subst)
+    }
+
+    // Checks expression `id` against `expected`, reporting a mismatch at it.
+    fn expect_expr(&mut self, expected: &Type, id: AstId) -> Result<Type, TypeError> {
+        let ty = self.check_expr(id)?;
+        self.expect(expected, &ty).map_err(|e| self.locate(e, id))?;
+        Ok(ty)
+    }
+
+    fn locate(&self, error: TypeError, id: AstId) -> TypeError {
+        match self.ast.get_span(id) {
+            Some(span) => error.at(span),
+            None => error,
+        }
+    }
+
+    fn resolve(&self, ty: &Type) -> Type {
+        ty.apply(&self.subst)
+    }
+
+    fn check_expr(&mut self, id: AstId) -> Result<Type, TypeError> {
+        let ty = self.infer_expr(id).map_err(|e| self.locate(e, id))?;
+        self.types.insert(id, ty.clone());
+        Ok(ty)
+    }
+
+    fn infer_expr(&mut self, id: AstId) -> Result<Type, TypeError> {
+        let ast = self.ast;
+        let Some(expr) = ast.get_expr(id) else {
+            return Err(TypeError::Unsupported);
+        };
+        match expr {
+            RuntimeExpr::Int(_) => Ok(int_type()),
+            RuntimeExpr::String(_) => Ok(string_type()),
+            RuntimeExpr::Bool(_) => Ok(bool_type()),
+            RuntimeExpr::Bytes(_) => Ok(list_type(int_type())),
+
+            // code values only exist while compile-time code runs
+            RuntimeExpr::Quote(_) | RuntimeExpr::Unquote(_) => Ok(self.fresh()),
+
+            RuntimeExpr::StructLiteral { type_name, fields } => {
+                let declared = self.struct_fields(type_name)?;
+                for (field, value) in fields {
+                    let ty = match declared.iter().find(|(name, _)| name == field) {
+                        Some((_, ty)) => ty.clone(),
+                        None => {
+                            return Err(TypeError::NoField {
+                                ty: named_type(type_name),
+                                field: field.clone(),
+                            })
+                        }
+                    };
+                    self.expect_expr(&ty, *value)?;
+                }
+                if let Some((missing, _)) = declared
+                    .iter()
+                    .find(|(name, _)| !fields.iter().any(|(f, _)| f == name))
+                {
+                    return Err(TypeError::MissingField {
+                        struct_name: type_name.clone(),
+                        field: missing.clone(),
+                    });
+                }
+                Ok(named_type(type_name))
+            }
+
+            RuntimeExpr::Variant {
+                enum_name,
+                variant,
+                payload,
+            } => {
+                let def = ast
+                    .decls
+                    .get_enum(enum_name)
+                    .ok_or_else(|| TypeError::UnknownType(enum_name.clone()))?;
+                let args: Vec<Type> = def.type_params.iter().map(|_| self.fresh()).collect();
+                let enum_ty = generic_type(enum_name, args.clone());
+                let decl = def.variant(variant).ok_or_else(|| TypeError::NoVariant {
+                    ty: enum_ty.clone(),
+                    variant: variant.clone(),
+                })?;
+                if !decl.payload.same_shape(payload) {
+                    return Err(TypeError::WrongPayload {
+                        enum_name: enum_name.clone(),
+                        declared: decl.clone(),
+                    });
+                }
+
+                let params = def.type_params.iter().cloned().zip(args).collect();
+                match (&decl.payload, payload) {
+                    (Payload::Tuple(types), Payload::Tuple(values)) => {
+                        for (ty, value) in types.iter().zip(values) {
+                            let ty = self.resolve_type_with(ty, &params)?;
+                            self.expect_expr(&ty, *value)?;
+                        }
+                    }
+                    (Payload::Struct(fields), Payload::Struct(values)) => {
+                        for (name, value) in values {
+                            if let Some((_, ty)) = fields.iter().find(|(field, _)| field == name) {
+                                let ty = self.resolve_type_with(ty, &params)?;
+                                self.expect_expr(&ty, *value)?;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                Ok(self.resolve(&enum_ty))
+            }
+
+            RuntimeExpr::Variable(name) => {
+                let (ty, preds) = self
+                    .env
+                    .lookup_qualified(name)
+                    .ok_or_else(|| TypeError::UnboundVar(name.clone()))?;
+                self.preds.extend(preds.into_iter().map(|pred| (pred, id)));
+                Ok(ty)
+            }
+
+            RuntimeExpr::List(elems) => {
+                let elem_ty = self.fresh();
+                for elem in elems {
+                    let ty = self.check_expr(*elem)?;
+                    self.expect(&elem_ty, &ty)?;
+                }
+                Ok(list_type(self.resolve(&elem_ty)))
+            }
+
+            RuntimeExpr::Call { callee, args } => {
+                let callee_ty = self.check_expr(*callee)?;
+                self.check_call(&callee_ty, args)
+            }
+
+            RuntimeExpr::Lambda { params, ret, body } => {
+                self.check_function(None, params, ret.as_ref(), *body, None)
+            }
+
+            RuntimeExpr::FieldAccess { target, field } => {
+                let target_ty = self.check_expr(*target)?;
+                match self.resolve(&target_ty) {
+                    Type::Module(module) => self.module_member(&module, field),
+                    ty => self.field_type(&ty, field),
+                }
+            }
+
+            RuntimeExpr::Index { target, index } => {
+                let elem_ty = self.fresh();
+                let target_ty = self.check_expr(*target)?;
+                self.expect(&list_type(elem_ty.clone()), &target_ty)?;
+                let index_ty = self.check_expr(*index)?;
+                self.expect(&int_type(), &index_ty)?;
+                Ok(self.resolve(&elem_ty))
+            }
+
+            RuntimeExpr::Slice { target, start, end } => {
+                let list_ty = list_type(self.fresh());
+                let target_ty = self.check_expr(*target)?;
+                self.expect(&list_ty, &target_ty)?;
+                for bound in [start, end].into_iter().flatten() {
+                    let bound_ty = self.check_expr(*bound)?;
+                    self.expect(&int_type(), &bound_ty)?;
+                }
+                Ok(self.resolve(&list_ty))
+            }
+
+            RuntimeExpr::MethodCall {
+                receiver,
+                method,
+                args,
+            } => {
+                let receiver_ty = self.check_expr(*receiver)?;
+                let receiver_ty = self.resolve(&receiver_ty);
+                // impl methods come first, as when the program runs
+                if let Some(method_ty) = self.trait_method(&receiver_ty, method, id)? {
+                    return self.check_call_with(&method_ty, vec![receiver_ty], args);
+                }
+                let method_ty = match &receiver_ty {
+                    Type::List(elem) => self.list_method(elem, method),
+                    Type::Primitive(PrimitiveType::String) if method == "len" => Some(Type::Func {
+                        params: vec![],
+                        ret: Box::new(int_type()),
+                    }),
+                    Type::Module(module) => Some(self.module_member(module, method)?),
+                    // a struct field holding a function
+                    Type::Named { name, .. }
+                        if self
+                            .ast
+                            .decls
+                            .get_struct(name)
+                            .is_some_and(|def| def.has_field(method)) =>
+                    {
+                        Some(self.field_type(&receiver_ty, method)?)
+                    }
+                    // the receiver's type isn't known yet
+                    Type::Var(_) => Some(self.fresh()),
+                    _ => None,
+                };
+                match method_ty {
+                    Some(method_ty) => self.check_call(&method_ty, args),
+                    None => match self.ast.decls.traits_with_method(method).first() {
+                        Some(trait_name) => Err(TypeError::MissingImpl {
+                            trait_name: trait_name.clone(),
+                            ty: receiver_ty,
+                        }),
+                        None => Err(TypeError::NoMethod {
+                            ty: receiver_ty,
+                            method: method.clone(),
+                        }),
+                    },
+                }
+            }
+
+            RuntimeExpr::Assign { target, op, value } => {
+                let target_ty = self.check_expr(*target)?;
+                let value_ty = self.check_expr(*value)?;
+                match op {
+                    AssignOp::Assign => self.expect(&target_ty, &value_ty)?,
+                    AssignOp::Add => {
+                        self.check_add(&target_ty, &value_ty)?;
+                    }
+                    AssignOp::Sub | AssignOp::Mult | AssignOp::Div => {
+                        self.expect(&int_type(), &target_ty)?;
+                        self.expect(&int_type(), &value_ty)?;
+                    }
+                }
+                Ok(self.resolve(&target_ty))
+            }
+
+            RuntimeExpr::Add(a, b) => {
+                let ta = self.check_expr(*a)?;
+                let tb = self.check_expr(*b)?;
+                self.check_add(&ta, &tb)
+            }
+            RuntimeExpr::Sub(a, b) | RuntimeExpr::Mult(a, b) | RuntimeExpr::Div(a, b) => {
+                self.check_operands(*a, *b, &int_type())?;
+                Ok(int_type())
+            }
+            RuntimeExpr::Less(a, b)
+            | RuntimeExpr::LessEqual(a, b)
+            | RuntimeExpr::Greater(a, b)
+            | RuntimeExpr::GreaterEqual(a, b) => {
+                self.check_operands(*a, *b, &int_type())?;
+                Ok(bool_type())
+            }
+            RuntimeExpr::And(a, b) | RuntimeExpr::Or(a, b) => {
+                self.check_operands(*a, *b, &bool_type())?;
+                Ok(bool_type())
+            }
+            RuntimeExpr::Equals(a, b) | RuntimeExpr::NotEquals(a, b) => {
+                let ta = self.check_expr(*a)?;
+                let tb = self.check_expr(*b)?;
+                self.expect(&ta, &tb)?;
+                Ok(bool_type())
+            }
+            RuntimeExpr::Not(a) => {
+                let ta = self.check_expr(*a)?;
+                self.expect(&bool_type(), &ta)?;
+                Ok(bool_type())
+            }
+            RuntimeExpr::Negate(a) => {
+                let ta = self.check_expr(*a)?;
+                self.expect(&int_type(), &ta)?;
+                Ok(int_type())
+            }
+        }
+    }
+
+    fn check_call(&mut self, callee_ty: &Type, args: &[AstId]) -> Result<Type, TypeError> {
+        self.check_call_with(callee_ty, Vec::with_capacity(args.len()), args)
+    }
+
+    // Like `check_call`, with the types of arguments already checked, such as
+    // a method's receiver, coming before `args`.
+    fn check_call_with(
+        &mut self,
+        callee_ty: &Type,
+        mut arg_types: Vec<Type>,
+        args: &[AstId],
+    ) -> Result<Type, TypeError> {
+        for arg in args {
+            arg_types.push(self.check_expr(*arg)?);
+        }
+        let ret = self.fresh();
+        let call_ty = Type::Func {
+            params: arg_types,
+            ret: Box::new(ret.clone()),
+        };
+        self.expect(callee_ty, &call_ty)?;
+        Ok(self.resolve(&ret))
+    }
+
+    // `+` also concatenates strings; anything else defaults to int.
+    fn check_add(&mut self, a: &Type, b: &Type) -> Result<Type, TypeError> {
+        self.expect(a, b)?;
+        let ty = self.resolve(a);
+        if ty != string_type() {
+            self.expect(&int_type(), &ty)?;
+        }
+        Ok(self.resolve(&ty))
+    }
+
+    fn check_operands(&mut self, a: AstId, b: AstId, operand: &Type) -> Result<(), TypeError> {
+        let ta = self.check_expr(a)?;
+        self.expect(operand, &ta)?;
+        let tb = self.check_expr(b)?;
+        self.expect(operand, &tb)
+    }
+
+    // Mirrors the methods in `runtime::list_methods`.
+    fn list_method(&mut self, elem: &Type, method: &str) -> Option<Type> {
+        let func = |params: Vec<Type>, ret: Type| Type::Func {
+            params,
+            ret: Box::new(ret),
+        };
+        let elem = elem.clone();
+        let ty = match method {
+            "len" => func(vec![], int_type()),
+            "push" => func(vec![elem], unit_type()),
+            "pop" => func(vec![], elem),
+            "insert" => func(vec![int_type(), elem], unit_type()),
+            "remove" => func(vec![int_type()], elem),
+            "contains" => func(vec![elem], bool_type()),
+            "map" => {
+                let out = self.fresh();
+                func(vec![func(vec![elem], out.clone())], list_type(out))
+            }
+            "filter" => func(vec![func(vec![elem.clone()], bool_type())], list_type(elem)),
+            "fold" => {
+                let acc = self.fresh();
+                func(
+                    vec![acc.clone(), func(vec![acc.clone(), elem], acc.clone())],
+                    acc,
+                )
+            }
+            _ => return None,
+        };
+        Some(ty)
+    }
+
+    // Resolves a written type such as `int`, `Point` or `List<int>`,
+    // reporting errors at the part of it that is wrong.
+    fn resolve_type(&self, ty: &TypeExpr) -> Result<Type, TypeError> {
+        self.resolve_type_with(ty, &self.type_params)
+    }
+
+    // Like `resolve_type`, inside a declaration whose type parameters stand
+    // for the types in `params`.
+    fn resolve_type_with(
+        &self,
+        ty: &TypeExpr,
+        params: &HashMap<String, Type>,
+    ) -> Result<Type, TypeError> {
+        let resolved = match ty {
+            TypeExpr::Func {
+                params: param_types,
+                ret,
+                ..
+            } => {
+                let param_types = param_types
+                    .iter()
+                    .map(|param| self.resolve_type_with(param, params))
+                    .collect::<Result<_, _>>()?;
+                Ok(Type::Func {
+                    params: param_types,
+                    ret: Box::new(self.resolve_type_with(ret, params)?),
+                })
+            }
+            TypeExpr::Named { name, args, .. } => {
+                let arity = if params.contains_key(name)
+                    || primitive_type(name).is_some()
+                    || self.ast.decls.get_struct(name).is_some()
+                {
+                    Some(0)
+                } else if name == "List" {
+                    Some(1)
+                } else {
+                    self.ast
+                        .decls
+                        .get_enum(name)
+                        .map(|def| def.type_params.len())
+                };
+                match arity {
+                    None => Err(TypeError::UnknownType(name.clone())),
+                    Some(expected) if expected != args.len() => Err(TypeError::WrongTypeArgs {
+                        name: name.clone(),
+                        expected,
+                        found: args.len(),
+                    }),
+                    Some(_) => {
+                        let mut args = args
+                            .iter()
+                            .map(|arg| self.resolve_type_with(arg, params))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        Ok(
+                            match params.get(name).cloned().or_else(|| primitive_type(name)) {
+                                Some(ty) => ty,
+                                None if name == "List" => list_type(args.remove(0)),
+                                None => generic_type(name, args),
+                            },
+                        )
+                    }
+                }
+            }
+        };
+        resolved.map_err(|e| e.at(ty.span()))
+    }
+
+    // Checks `found` against a written annotation, reporting a mismatch at
+    // the annotation.
+    fn expect_annotation(&mut self, annotation: &TypeExpr, found: &Type) -> Result<(), TypeError> {
+        let expected = self.resolve_type(annotation)?;
+        self.expect(&expected, found)
+            .map_err(|e| e.at(annotation.span()))
+    }
+
+    // The declared fields of struct `name` with their types.
+    fn struct_fields(&self, name: &str) -> Result<Vec<(String, Type)>, TypeError> {
+        let def = self
+            .ast
+            .decls
+            .get_struct(name)
+            .ok_or_else(|| TypeError::UnknownType(name.to_string()))?;
+        def.fields
+            .iter()
+            .map(|(field, ty)| Ok((field.clone(), self.resolve_type(ty)?)))
+            .collect()
+    }
+
+    // The type of `field` on a value of type `ty`. A value whose type isn't
+    // known yet is taken to be the only struct declaring the field.
+    fn field_type(&mut self, ty: &Type, field: &str) -> Result<Type, TypeError> {
+        let struct_name = match ty {
+            Type::Named { name, .. } if self.ast.decls.get_struct(name).is_some() => name.clone(),
+            Type::Var(_) => {
+                let mut structs = self.ast.decls.structs_with_field(field);
+                match structs.len() {
+                    0 => {
+                        return Err(TypeError::NoField {
+                            ty: ty.clone(),
+                            field: field.to_string(),
+                        })
+                    }
+                    1 => {
+                        let name = structs.remove(0);
+                        self.expect(&named_type(&name), ty)?;
+                        name
+                    }
+                    _ => {
+                        return Err(TypeError::AmbiguousField {
+                            field: field.to_string(),
+                            structs,
+                        })
+                    }
+                }
+            }
+            _ => {
+                return Err(TypeError::NoField {
+                    ty: ty.clone(),
+                    field: field.to_string(),
+                })
+            }
+        };
+        self.struct_fields(&struct_name)?
+            .into_iter()
+            .find(|(name, _)| name == field)
+            .map(|(_, ty)| ty)
+            .ok_or_else(|| TypeError::NoField {
+                ty: ty.clone(),
+                field: field.to_string(),
+            })
+    }
+
+    fn module_member(&mut self, module: &str, name: &str) -> Result<Type, TypeError> {
+        let scheme = self
+            .modules
+            .get(module)
+            .and_then(|members| members.get(name))
+            .cloned()
+            .ok_or_else(|| TypeError::UnboundVar(format!("{module}.{name}")))?;
+        Ok(instantiate(&scheme, &mut self.env))
+    }
+
+    // Checks a function's body and returns its (not yet generalized) type.
+    fn check_function(
+        &mut self,
+        name: Option<&str>,
+        params: &[Param],
+        ret_annotation: Option<&TypeExpr>,
+        body: AstId,
+        expected: Option<&Type>,
+    ) -> Result<Type, TypeError> {
+        let mut param_types = Vec::with_capacity(params.len());
+        for param in params {
+            let ty = match &param.ty {
+                Some(annotation) => self.resolve_type(annotation)?,
+                None => self.fresh(),
+            };
+            param_types.push(ty);
+        }
+        let ret = self.fresh();
+        let fn_ty = Type::Func {
+            params: param_types.clone(),
+            ret: Box::new(ret.clone()),
+        };
+        // an impl method starts from the trait's signature
+        if let Some(expected) = expected {
+            self.expect(expected, &fn_ty)?;
+        }
+
+        self.env.push_scope();
+        if let Some(name) = name {
+            self.env.bind_mono(name, fn_ty.clone());
+        }
+        for (param, ty) in params.iter().zip(param_types) {
+            self.env.bind_mono(&param.name, ty);
+        }
+
+        // loops around the function can't be broken out of from inside it
+        let saved = std::mem::replace(&mut self.ctx, TypeCheckCtx::new());
+        self.ctx.return_type = Some(ret.clone());
+        let body_result = self.check_stmt(body);
+        let saw_return = self.ctx.saw_return;
+        self.ctx = saved;
+        self.env.pop_scope();
+
+        body_result?;
+        if !saw_return {
+            self.expect(&unit_type(), &ret)?;
+        }
+        if let Some(annotation) = ret_annotation {
+            self.expect_annotation(annotation, &ret)?;
+        }
+        Ok(self.resolve(&fn_ty))
+    }
+
+    // Generalizes `ty`, qualified by the pending predicates on the variables
+    // it quantifies over.
+    fn generalize(&mut self, ty: Type) -> Result<TypeScheme, TypeError> {
+        match generalize_with(&self.env, &self.subst, self.resolve(&ty)) {
+            TypeScheme::PolyType { vars, ty, .. } => {
+                let preds = self.settle_preds(&vars)?;
+                Ok(TypeScheme::PolyType { vars, preds, ty })
+            }
+            mono => {
+                self.settle_preds(&[])?;
+                Ok(mono)
+            }
+        }
+    }
+
+    // Checks the pending predicates whose types are known by now and takes
+    // out the ones on `generalized` variables; the rest stay pending.
+    fn settle_preds(&mut self, generalized: &[TypeVar]) -> Result<Vec<Predicate>, TypeError> {
+        let mut qualified = Vec::new();
+        for (pred, id) in std::mem::take(&mut self.preds) {
+            let pred = pred.apply(&self.subst);
+            match &pred.ty {
+                Type::Var(var) if generalized.contains(var) => {
+                    if !qualified.contains(&pred) {
+                        qualified.push(pred);
+                    }
+                }
+                Type::Var(_) => self.preds.push((pred, id)),
+                ty => {
+                    let implemented = ty.impl_name().is_some_and(|name| {
+                        self.ast.decls.get_impl(&pred.trait_name, &name).is_some()
+                    });
+                    if !implemented {
+                        let error = TypeError::MissingImpl {
+                            trait_name: pred.trait_name.clone(),
+                            ty: ty.clone(),
+                        };
+                        return Err(self.locate(error, id));
+                    }
+                }
+            }
+        }
+        Ok(qualified)
+    }
+
+    // The type of `method` on a `receiver`, if an impl for the receiver's type
+    // provides it. When the type isn't known yet, the one trait declaring the
+    // method is taken to provide it, and the type is required to implement it.
+    fn trait_method(
+        &mut self,
+        receiver: &Type,
+        method: &str,
+        id: AstId,
+    ) -> Result<Option<Type>, TypeError> {
+        let traits = self.ast.decls.traits_with_method(method);
+        let trait_name = match receiver {
+            Type::Var(_) => match traits.as_slice() {
+                [] => return Ok(None),
+                [trait_name] => {
+                    let pred = Predicate {
+                        trait_name: trait_name.clone(),
+                        ty: receiver.clone(),
+                    };
+                    self.preds.push((pred, id));
+                    trait_name.clone()
+                }
+                _ => {
+                    return Err(TypeError::AmbiguousMethod {
+                        method: method.to_string(),
+                        traits,
+                    })
+                }
+            },
+            _ => {
+                let Some(type_name) = receiver.impl_name() else {
+                    return Ok(None);
+                };
+                let implemented = traits
+                    .into_iter()
+                    .find(|t| self.ast.decls.get_impl(t, &type_name).is_some());
+                match implemented {
+                    Some(trait_name) => trait_name,
+                    None => return Ok(None),
+                }
+            }
+        };
+        let def = self
+            .ast
+            .decls
+            .get_trait(&trait_name)
+            .ok_or_else(|| TypeError::UnknownTrait(trait_name.clone()))?;
+        match def.method(method) {
+            Some(sig) => Ok(Some(self.method_type(sig, receiver)?)),
+            None => Ok(None),
+        }
+    }
+
+    // The type of a trait method on `self_ty`, with the receiver as its first
+    // parameter.
+    fn method_type(&mut self, sig: &MethodSig, self_ty: &Type) -> Result<Type, TypeError> {
+        let scope = HashMap::from([("Self".to_string(), self_ty.clone())]);
+        let mut params = Vec::with_capacity(sig.params.len());
+        for param in &sig.params {
+            let ty = match &param.ty {
+                Some(ty) => self.resolve_type_with(ty, &scope)?,
+                None if param.name == "self" => self_ty.clone(),
+                None => self.fresh(),
+            };
+            params.push(ty);
+        }
+        let ret = match &sig.ret {
+            Some(ty) => self.resolve_type_with(ty, &scope)?,
+            None => unit_type(),
+        };
+        Ok(Type::Func {
+            params,
+            ret: Box::new(ret),
+        })
+    }
+
+    // The type `self` has in an impl for `type_name`; an impl for a generic
+    // type covers any type arguments.
+    fn impl_type(&mut self, type_name: &str) -> Result<Type, TypeError> {
+        if let Some(ty) = primitive_type(type_name) {
+            return Ok(ty);
+        }
+        if type_name == "List" {
+            return Ok(list_type(self.fresh()));
+        }
+        if self.ast.decls.get_struct(type_name).is_some() {
+            return Ok(named_type(type_name));
+        }
+        match self.ast.decls.get_enum(type_name) {
+            Some(def) => {
+                let args = def.type_params.iter().map(|_| self.fresh()).collect();
+                Ok(generic_type(type_name, args))
+            }
+            None => Err(TypeError::UnknownType(type_name.to_string())),
+        }
+    }
+
+    fn check_stmts(&mut self, stmts: &[AstId]) -> Result<(), TypeError> {
+        // functions may call functions declared later in the same block
+        for id in stmts {
+            if let Some(RuntimeStmt::FnDecl { name, .. }) = self.ast.get_stmt(*id) {
+                let ty = self.fresh();
+                self.env.bind_mono(name, ty);
+            }
+        }
+        for id in stmts {
+            self.check_stmt(*id)?;
+        }
+        Ok(())
+    }
+
+    fn check_stmt(&mut self, id: AstId) -> Result<(), TypeError> {
+        self.infer_stmt(id).map_err(|e| self.locate(e, id))
+    }
+
+    fn infer_stmt(&mut self, id: AstId) -> Result<(), TypeError> {
+        let ast = self.ast;
+        // `meta` blocks leave no statement behind
+        let Some(stmt) = ast.get_stmt(id) else {
+            return Ok(());
+        };
+        match stmt {
+            RuntimeStmt::ExprStmt(expr) | RuntimeStmt::Print(expr) => {
+                self.check_expr(*expr)?;
+            }
+
+            RuntimeStmt::VarDecl {
+                name,
+                ty: annotation,
+                expr,
+            } => {
+                let ty = self.check_expr(*expr)?;
+                if let Some(annotation) = annotation {
+                    self.expect_annotation(annotation, &ty)?;
+                }
+                // only function expressions are generalized: a variable
+                // holding a list may be mutated at any element type otherwise
+                let scheme = match ast.get_expr(*expr) {
+                    Some(RuntimeExpr::Lambda { .. }) => self.generalize(ty)?,
+                    _ => TypeScheme::MonoType(ty),
+                };
+                self.env.bind(name, scheme);
+            }
+
+            RuntimeStmt::FnDecl {
+                name,
+                type_params,
+                params,
+                ret,
+                body,
+            } => {
+                let mut scoped = self.type_params.clone();
+                let mut bounds = Vec::new();
+                for param in type_params {
+                    let ty = self.fresh();
+                    for bound in &param.bounds {
+                        if ast.decls.get_trait(bound).is_none() {
+                            return Err(TypeError::UnknownTrait(bound.clone()));
+                        }
+                        let pred = Predicate {
+                            trait_name: bound.clone(),
+                            ty: ty.clone(),
+                        };
+                        bounds.push((pred, id));
+                    }
+                    scoped.insert(param.name.clone(), ty);
+                }
+                let outer = std::mem::replace(&mut self.type_params, scoped);
+                let fn_ty = self.check_function(Some(name), params, ret.as_ref(), *body, None);
+                let scoped = std::mem::replace(&mut self.type_params, outer);
+                let fn_ty = fn_ty?;
+
+                if let Some(TypeScheme::MonoType(declared)) = self.env.get_type(name) {
+                    self.expect(&declared, &fn_ty)?;
+                }
+                // the forward declaration would keep the function monomorphic
+                self.env.unbind(name);
+                // bounds are required of callers even if the body doesn't use them
+                self.preds.extend(bounds);
+                let scheme = self.generalize(fn_ty)?;
+
+                // what the body needs of a type parameter must be declared
+                if let TypeScheme::PolyType { preds, .. } = &scheme {
+                    for pred in preds {
+                        let declared = type_params
+                            .iter()
+                            .find(|param| self.resolve(&scoped[&param.name]) == pred.ty);
+                        if let Some(param) = declared {
+                            if !param.bounds.contains(&pred.trait_name) {
+                                return Err(TypeError::MissingBound {
+                                    param: param.name.clone(),
+                                    trait_name: pred.trait_name.clone(),
+                                });
+                            }
+                        }
+                    }
+                }
+                self.env.bind(name, scheme);
+            }
+
+            RuntimeStmt::StructDecl { fields, .. } => {
+                for field in fields {
+                    self.resolve_type(&field.ty)?;
+                }
+            }
+
+            RuntimeStmt::EnumDecl {
+                type_params,
+                variants,
+                ..
+            } => {
+                let params = type_params
+                    .iter()
+                    .map(|param| (param.clone(), self.fresh()))
+                    .collect();
+                for variant in variants {
+                    let types: Vec<&TypeExpr> = match &variant.payload {
+                        Payload::Unit => vec![],
+                        Payload::Tuple(types) => types.iter().collect(),
+                        Payload::Struct(fields) => fields.iter().map(|(_, ty)| ty).collect(),
+                    };
+                    for ty in types {
+                        self.resolve_type_with(ty, &params)?;
+                    }
+                }
+            }
+
+            RuntimeStmt::TraitDecl { methods, .. } => {
+                let self_ty = self.fresh();
+                for sig in methods {
+                    self.method_type(sig, &self_ty)?;
+                }
+            }
+
+            RuntimeStmt::ImplDecl {
+                trait_name,
+                type_name,
+                methods,
+            } => {
+                let def = ast
+                    .decls
+                    .get_trait(trait_name)
+                    .ok_or_else(|| TypeError::UnknownTrait(trait_name.clone()))?;
+                let self_ty = self.impl_type(type_name)?;
+                let mut implemented = Vec::with_capacity(methods.len());
+                for method_id in methods {
+                    let Some(RuntimeStmt::FnDecl {
+                        name,
+                        params,
+                        ret,
+                        body,
+                        ..
+                    }) = ast.get_stmt(*method_id)
+                    else {
+                        continue;
+                    };
+                    let Some(sig) = def.method(name) else {
+                        let error = TypeError::NotInTrait {
+                            trait_name: trait_name.clone(),
+                            method: name.clone(),
+                        };
+                        return Err(self.locate(error, *method_id));
+                    };
+                    let expected = self.method_type(sig, &self_ty)?;
+                    self.check_function(None, params, ret.as_ref(), *body, Some(&expected))
+                        .map_err(|e| self.locate(e, *method_id))?;
+                    implemented.push(name);
+                }
+                if let Some(missing) = def.methods.iter().find(|m| !implemented.contains(&&m.name))
+                {
+                    return Err(TypeError::MissingMethod {
+                        trait_name: trait_name.clone(),
+                        method: missing.name.clone(),
+                    });
+                }
+            }
+
+            RuntimeStmt::If {
+                cond,
+                body,
+                else_branch,
+            } => {
+                self.expect_expr(&bool_type(), *cond)?;
+                self.check_stmt(*body)?;
+                if let Some(else_branch) = else_branch {
+                    self.check_stmt(*else_branch)?;
+                }
+            }
+
+            RuntimeStmt::ForEach {
+                var,
+                iterable,
+                body,
+            } => {
+                let elem_ty = self.fresh();
+                self.expect_expr(&list_type(elem_ty.clone()), *iterable)?;
+
+                self.env.push_scope();
+                self.env.bind_mono(var, elem_ty);
+                self.ctx.loop_depth += 1;
+                let body_result = self.check_stmt(*body);
+                self.ctx.loop_depth -= 1;
+                self.env.pop_scope();
+                body_result?;
+            }
+
+            RuntimeStmt::While { cond, body } => {
+                self.expect_expr(&bool_type(), *cond)?;
+                self.ctx.loop_depth += 1;
+                let body_result = self.check_stmt(*body);
+                self.ctx.loop_depth -= 1;
+                body_result?;
+            }
+
+            RuntimeStmt::Match { scrutinee, arms } => {
+                let ty = self.check_expr(*scrutinee)?;
+                for arm in arms {
+                    self.env.push_scope();
+                    let arm_result = self.check_arm(arm, &ty);
+                    self.env.pop_scope();
+                    arm_result?;
+                }
+                check_match(arms, &ast.decls)?;
+            }
+
+            RuntimeStmt::Return(_) if self.ctx.in_defer => {
+                return Err(TypeError::JumpOutOfDefer("return".to_string()))
+            }
+            RuntimeStmt::Break if self.ctx.in_defer && self.ctx.loop_depth == 0 => {
+                return Err(TypeError::JumpOutOfDefer("break".to_string()))
+            }
+            RuntimeStmt::Continue if self.ctx.in_defer && self.ctx.loop_depth == 0 => {
+                return Err(TypeError::JumpOutOfDefer("continue".to_string()))
+            }
+            RuntimeStmt::Break if self.ctx.loop_depth == 0 => {
+                return Err(TypeError::BreakOutsideLoop)
+            }
+            RuntimeStmt::Continue if self.ctx.loop_depth == 0 => {
+                return Err(TypeError::ContinueOutsideLoop)
+            }
+            RuntimeStmt::Break | RuntimeStmt::Continue => {}
+
+            RuntimeStmt::Return(expr) => {
+                let ret = self
+                    .ctx
+                    .return_type
+                    .clone()
+                    .ok_or(TypeError::InvalidReturn)?;
+                self.ctx.saw_return = true;
+                match expr {
+                    Some(expr) => {
+                        self.expect_expr(&ret, *expr)?;
+                    }
+                    None => self.expect(&ret, &unit_type())?,
+                }
+            }
+
+            RuntimeStmt::Defer(stmt) => {
+                let defer_ctx = TypeCheckCtx {
+                    in_defer: true,
+                    ..TypeCheckCtx::new()
+                };
+                let saved = std::mem::replace(&mut self.ctx, defer_ctx);
+                let result = self.check_stmt(*stmt);
+                self.ctx = saved;
+                result?;
+            }
+
+            RuntimeStmt::Block(stmts) => {
+                self.env.push_scope();
+                let result = self.check_stmts(stmts);
+                self.env.pop_scope();
+                result?;
+            }
+
+            RuntimeStmt::Import(name) => {
+                self.check_module(name)?;
+                self.env.bind_mono(name, Type::Module(name.clone()));
+            }
+
+            // generated code is checked where it is spliced in
+            RuntimeStmt::Gen(_) | RuntimeStmt::MetaBlock(_) => {}
+        }
+        Ok(())
+    }
+
+    fn check_arm(&mut self, arm: &MatchArm, ty: &Type) -> Result<(), TypeError> {
+        self.check_pattern(&arm.pattern, ty)?;
+        if let Some(guard) = arm.guard {
+            self.expect_expr(&bool_type(), guard)?;
+        }
+        self.check_stmt(arm.body)
+    }
+
+    // Checks that `pattern` can match a value of type `ty`, binding its names
+    // in the current scope.
+    fn check_pattern(&mut self, pattern: &Pattern, ty: &Type) -> Result<(), TypeError> {
+        self.infer_pattern(pattern, ty)
+            .map_err(|e| e.at(pattern.span))
+    }
+
+    fn infer_pattern(&mut self, pattern: &Pattern, ty: &Type) -> Result<(), TypeError> {
+        match &pattern.kind {
+            PatternKind::Wildcard => Ok(()),
+            PatternKind::Binding(name) => {
+                let ty = self.resolve(ty);
+                self.env.bind_mono(name, ty);
+                Ok(())
+            }
+            PatternKind::Int(_) => self.expect(ty, &int_type()),
+            PatternKind::String(_) => self.expect(ty, &string_type()),
+            PatternKind::Bool(_) => self.expect(ty, &bool_type()),
+            PatternKind::List { items, rest } => {
+                let elem_ty = self.fresh();
+                self.expect(ty, &list_type(elem_ty.clone()))?;
+                for item in items {
+                    self.check_pattern(item, &elem_ty)?;
+                }
+                if let Some(rest) = rest {
+                    self.check_pattern(rest, &list_type(elem_ty))?;
+                }
+                Ok(())
+            }
+            PatternKind::Struct { name, fields } => {
+                let declared = self.struct_fields(name)?;
+                self.expect(ty, &named_type(name))?;
+                for (field, pattern) in fields {
+                    match declared.iter().find(|(declared, _)| declared == field) {
+                        Some((_, field_ty)) => self.check_pattern(pattern, field_ty)?,
+                        None => {
+                            return Err(TypeError::NoField {
+                                ty: named_type(name),
+                                field: field.clone(),
+                            })
+                        }
+                    }
+                }
+                Ok(())
+            }
+            PatternKind::Variant {
+                enum_name,
+                variant,
+                payload,
+            } => {
+                let def = self
+                    .ast
+                    .decls
+                    .get_enum(enum_name)
+                    .ok_or_else(|| TypeError::UnknownType(enum_name.clone()))?;
+                let args: Vec<Type> = def.type_params.iter().map(|_| self.fresh()).collect();
+                let enum_ty = generic_type(enum_name, args.clone());
+                self.expect(ty, &enum_ty)?;
+                let decl = def.variant(variant).ok_or_else(|| TypeError::NoVariant {
+                    ty: self.resolve(&enum_ty),
+                    variant: variant.clone(),
+                })?;
+                let wrong_payload = || TypeError::WrongPayload {
+                    enum_name: enum_name.clone(),
+                    declared: decl.clone(),
+                };
+
+                // struct payloads may leave fields out, like struct patterns
+                let params = def.type_params.iter().cloned().zip(args).collect();
+                match (&decl.payload, payload) {
+                    (Payload::Unit, Payload::Unit) => {}
+                    (Payload::Tuple(types), Payload::Tuple(items))
+                        if types.len() == items.len() =>
+                    {
+                        for (ty, item) in types.iter().zip(items) {
+                            let ty = self.resolve_type_with(ty, &params)?;
+                            self.check_pattern(item, &ty)?;
+                        }
+                    }
+                    (Payload::Struct(declared), Payload::Struct(fields)) => {
+                        for (field, pattern) in fields {
+                            let (_, ty) = declared
+                                .iter()
+                                .find(|(declared, _)| declared == field)
+                                .ok_or_else(wrong_payload)?;
+                            let ty = self.resolve_type_with(ty, &params)?;
+                            self.check_pattern(pattern, &ty)?;
+                        }
+                    }
+                    _ => return Err(wrong_payload()),
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn check_module(&mut self, name: &str) -> Result<(), TypeError> {
+        if self.modules.contains_key(name) {
+            return Ok(());
+        }
+        let Some(roots) = self.ast.get_module(name) else {
+            return Err(TypeError::UnboundVar(name.to_string()));
+        };
+
+        // a module sees none of its importer's names
+        let module_env = self.env.detached();
+        let importer_env = std::mem::replace(&mut self.env, module_env);
+        let importer_ctx = std::mem::replace(&mut self.ctx, TypeCheckCtx::new());
+        self.bind_builtins();
+        let result = self.check_stmts(roots);
+        let module_env = std::mem::replace(&mut self.env, importer_env);
+        self.ctx = importer_ctx;
+        self.env.reattach(&module_env);
+        result?;
+
+        let members = module_env
+            .globals()
+            .iter()
+            .map(|(member, scheme)| (member.clone(), scheme.apply(&self.subst)))
+            .collect();
+        self.modules.insert(name.to_string(), members);
+        Ok(())
+    }
+}
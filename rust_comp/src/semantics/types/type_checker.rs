@@ -9,6 +9,9 @@ use super::types::*;
 pub struct TypeCheckCtx {
     pub return_type: Option<Type>,
     pub saw_return: bool,
+    pub loop_depth: usize,
+    // inside a `defer`, which can't jump out of the block it runs for
+    pub in_defer: bool,
 }
 
 impl TypeCheckCtx {
@@ -16,6 +19,8 @@ impl TypeCheckCtx {
         Self {
             return_type: None,
             saw_return: false,
+            loop_depth: 0,
+            in_defer: false,
         }
     }
 }
@@ -83,10 +88,150 @@ pub fn infer_expr(
                 },
             })
         }
+        ExpandedExpr::List(elems) => {
+            let elem_ty = Type::Var(env.fresh());
+            let mut typed_elems = Vec::with_capacity(elems.len());
+            for elem in elems {
+                let te = infer_expr(elem, env, subst)?;
+                unify(&te.ty, &elem_ty, subst)?;
+                typed_elems.push(te);
+            }
+            Ok(TypedExpr {
+                ty: list_type(elem_ty.apply(subst)),
+                kind: TypedExprKind::List(typed_elems),
+            })
+        }
+        // `+` also concatenates strings; anything else defaults to int
+        ExpandedExpr::Add(a, b) => {
+            let ta = infer_expr(a, env, subst)?;
+            let tb = infer_expr(b, env, subst)?;
+            unify(&ta.ty, &tb.ty, subst)?;
+            let ty = ta.ty.apply(subst);
+            if ty != string_type() {
+                unify(&ty, &int_type(), subst)?;
+            }
+            Ok(TypedExpr {
+                ty: ty.apply(subst),
+                kind: TypedExprKind::Add(Box::new(ta), Box::new(tb)),
+            })
+        }
+        ExpandedExpr::Sub(a, b) => {
+            let (ta, tb) = infer_operands(a, b, &int_type(), env, subst)?;
+            Ok(TypedExpr {
+                ty: int_type(),
+                kind: TypedExprKind::Sub(Box::new(ta), Box::new(tb)),
+            })
+        }
+        ExpandedExpr::Mult(a, b) => {
+            let (ta, tb) = infer_operands(a, b, &int_type(), env, subst)?;
+            Ok(TypedExpr {
+                ty: int_type(),
+                kind: TypedExprKind::Mult(Box::new(ta), Box::new(tb)),
+            })
+        }
+        ExpandedExpr::Div(a, b) => {
+            let (ta, tb) = infer_operands(a, b, &int_type(), env, subst)?;
+            Ok(TypedExpr {
+                ty: int_type(),
+                kind: TypedExprKind::Div(Box::new(ta), Box::new(tb)),
+            })
+        }
+        ExpandedExpr::Equals(a, b) => {
+            let ta = infer_expr(a, env, subst)?;
+            let tb = infer_expr(b, env, subst)?;
+            unify(&ta.ty, &tb.ty, subst)?;
+            Ok(TypedExpr {
+                ty: bool_type(),
+                kind: TypedExprKind::Equals(Box::new(ta), Box::new(tb)),
+            })
+        }
+        ExpandedExpr::NotEquals(a, b) => {
+            let ta = infer_expr(a, env, subst)?;
+            let tb = infer_expr(b, env, subst)?;
+            unify(&ta.ty, &tb.ty, subst)?;
+            Ok(TypedExpr {
+                ty: bool_type(),
+                kind: TypedExprKind::NotEquals(Box::new(ta), Box::new(tb)),
+            })
+        }
+        ExpandedExpr::Less(a, b) => {
+            let (ta, tb) = infer_operands(a, b, &int_type(), env, subst)?;
+            Ok(TypedExpr {
+                ty: bool_type(),
+                kind: TypedExprKind::Less(Box::new(ta), Box::new(tb)),
+            })
+        }
+        ExpandedExpr::LessEqual(a, b) => {
+            let (ta, tb) = infer_operands(a, b, &int_type(), env, subst)?;
+            Ok(TypedExpr {
+                ty: bool_type(),
+                kind: TypedExprKind::LessEqual(Box::new(ta), Box::new(tb)),
+            })
+        }
+        ExpandedExpr::Greater(a, b) => {
+            let (ta, tb) = infer_operands(a, b, &int_type(), env, subst)?;
+            Ok(TypedExpr {
+                ty: bool_type(),
+                kind: TypedExprKind::Greater(Box::new(ta), Box::new(tb)),
+            })
+        }
+        ExpandedExpr::GreaterEqual(a, b) => {
+            let (ta, tb) = infer_operands(a, b, &int_type(), env, subst)?;
+            Ok(TypedExpr {
+                ty: bool_type(),
+                kind: TypedExprKind::GreaterEqual(Box::new(ta), Box::new(tb)),
+            })
+        }
+        ExpandedExpr::And(a, b) => {
+            let (ta, tb) = infer_operands(a, b, &bool_type(), env, subst)?;
+            Ok(TypedExpr {
+                ty: bool_type(),
+                kind: TypedExprKind::And(Box::new(ta), Box::new(tb)),
+            })
+        }
+        ExpandedExpr::Or(a, b) => {
+            let (ta, tb) = infer_operands(a, b, &bool_type(), env, subst)?;
+            Ok(TypedExpr {
+                ty: bool_type(),
+                kind: TypedExprKind::Or(Box::new(ta), Box::new(tb)),
+            })
+        }
+        ExpandedExpr::Not(a) => {
+            let ta = infer_expr(a, env, subst)?;
+            unify(&ta.ty, &bool_type(), subst)?;
+            Ok(TypedExpr {
+                ty: bool_type(),
+                kind: TypedExprKind::Not(Box::new(ta)),
+            })
+        }
+        ExpandedExpr::Negate(a) => {
+            let ta = infer_expr(a, env, subst)?;
+            unify(&ta.ty, &int_type(), subst)?;
+            Ok(TypedExpr {
+                ty: int_type(),
+                kind: TypedExprKind::Negate(Box::new(ta)),
+            })
+        }
         _ => Err(TypeError::Unsupported),
     }
 }
 
+// Infers both operands of a binary operator that requires them to have
+// type `operand`.
+fn infer_operands(
+    a: &ExpandedExpr,
+    b: &ExpandedExpr,
+    operand: &Type,
+    env: &mut TypeEnv,
+    subst: &mut TypeSubst,
+) -> Result<(TypedExpr, TypedExpr), TypeError> {
+    let ta = infer_expr(a, env, subst)?;
+    unify(&ta.ty, operand, subst)?;
+    let tb = infer_expr(b, env, subst)?;
+    unify(&tb.ty, operand, subst)?;
+    Ok((ta, tb))
+}
+
 pub fn type_check_expr_top(expr: &ExpandedExpr, expected: &Type) -> Result<TypedExpr, TypeError> {
     type_check_expr(expr, &mut TypeEnv::new(), &mut TypeSubst::new(), expected)
 }
@@ -163,6 +308,54 @@ pub fn infer_stmt(
             Ok(typed_if)
         }
 
+        ExpandedStmt::ForEach {
+            var,
+            iterable,
+            body,
+        } => {
+            let elem_ty = Type::Var(env.fresh());
+            let typed_iter = infer_expr(iterable, env, subst)?;
+            unify(&typed_iter.ty, &list_type(elem_ty.clone()), subst)?;
+
+            env.push_scope();
+            env.bind_mono(var, elem_ty.apply(subst));
+            let saved_depth = ctx.loop_depth;
+            ctx.loop_depth += 1;
+            let typed_body = infer_stmt(body, env, subst, ctx);
+            ctx.loop_depth = saved_depth;
+            env.pop_scope();
+
+            Ok(TypedStmt::ForEach {
+                var: var.clone(),
+                iterable: Box::new(typed_iter),
+                body: Box::new(typed_body?),
+            })
+        }
+
+        ExpandedStmt::While { cond, body } => {
+            let typed_cond = type_check_expr(cond, env, subst, &bool_type())?;
+            let saved_depth = ctx.loop_depth;
+            ctx.loop_depth += 1;
+            let typed_body = infer_stmt(body, env, subst, ctx);
+            ctx.loop_depth = saved_depth;
+            Ok(TypedStmt::While {
+                cond: Box::new(typed_cond),
+                body: Box::new(typed_body?),
+            })
+        }
+        ExpandedStmt::Break => {
+            if ctx.loop_depth == 0 {
+                return Err(TypeError::BreakOutsideLoop);
+            }
+            Ok(TypedStmt::Break)
+        }
+        ExpandedStmt::Continue => {
+            if ctx.loop_depth == 0 {
+                return Err(TypeError::ContinueOutsideLoop);
+            }
+            Ok(TypedStmt::Continue)
+        }
+
         ExpandedStmt::FnDecl { name, params, body } => {
             let mut param_types = vec![];
             for _ in params {
@@ -185,6 +378,8 @@ pub fn infer_stmt(
 
             let saved_ret = ctx.return_type.take();
             let saved_saw = ctx.saw_return;
+            // loops around the declaration can't be broken out of from inside it
+            let saved_depth = std::mem::take(&mut ctx.loop_depth);
 
             ctx.return_type = Some(ret_tv.clone());
             ctx.saw_return = false;
@@ -197,6 +392,7 @@ pub fn infer_stmt(
 
             ctx.return_type = saved_ret;
             ctx.saw_return = saved_saw;
+            ctx.loop_depth = saved_depth;
 
             env.pop_scope();
 
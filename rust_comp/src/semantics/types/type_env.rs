@@ -1,5 +1,5 @@
-use super::type_utils::instantiate;
-use super::types::{Type, TypeScheme, TypeVar};
+use super::type_utils::instantiate_qualified;
+use super::types::{Predicate, Type, TypeScheme, TypeVar};
 use std::collections::HashMap;
 
 pub struct TypeEnv {
@@ -21,6 +21,25 @@ impl TypeEnv {
         return TypeVar { id };
     }
 
+    /// An empty environment that hands out type variables where this one
+    /// left off, for checking a module on its own.
+    pub fn detached(&self) -> Self {
+        Self {
+            scopes: vec![HashMap::new()],
+            next_id: self.next_id,
+        }
+    }
+
+    /// Takes back the type variables handed out by a `detached` environment.
+    pub fn reattach(&mut self, detached: &TypeEnv) {
+        self.next_id = self.next_id.max(detached.next_id);
+    }
+
+    /// The bindings of the outermost scope.
+    pub fn globals(&self) -> &HashMap<String, TypeScheme> {
+        &self.scopes[0]
+    }
+
     pub fn get_type(&self, name: &str) -> Option<TypeScheme> {
         for scope in self.scopes.iter().rev() {
             if let Some(ty) = scope.get(name) {
@@ -31,9 +50,15 @@ impl TypeEnv {
     }
 
     pub fn lookup(&mut self, name: &str) -> Option<Type> {
+        self.lookup_qualified(name).map(|(ty, _)| ty)
+    }
+
+    /// Like `lookup`, also giving the predicates the instantiated type must
+    /// satisfy.
+    pub fn lookup_qualified(&mut self, name: &str) -> Option<(Type, Vec<Predicate>)> {
         for scope in self.scopes.iter().rev() {
             if let Some(scheme) = scope.get(name).cloned() {
-                return Some(instantiate(&scheme, self));
+                return Some(instantiate_qualified(&scheme, self));
             }
         }
         None
@@ -43,6 +68,11 @@ impl TypeEnv {
         self.scopes.last_mut().unwrap().insert(name.to_string(), ty);
     }
 
+    /// Forgets `name` in the innermost scope.
+    pub fn unbind(&mut self, name: &str) {
+        self.scopes.last_mut().unwrap().remove(name);
+    }
+
     pub fn bind_mono(&mut self, name: &str, mono: Type) {
         self.bind(name, TypeScheme::MonoType(mono))
     }
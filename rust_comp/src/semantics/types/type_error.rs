@@ -1,9 +1,51 @@
 use super::types::Type;
+use crate::frontend::meta_ast::VariantDecl;
+use crate::frontend::span::Span;
 
 #[derive(Debug, Clone)]
 pub enum TypeError {
     InvalidReturn,
+    BreakOutsideLoop,
+    ContinueOutsideLoop,
     Unsupported,
     UnboundVar(String),
+    NoMethod { ty: Type, method: String },
+    UnknownType(String),
+    WrongTypeArgs { name: String, expected: usize, found: usize },
+    NoField { ty: Type, field: String },
+    MissingField { struct_name: String, field: String },
+    AmbiguousField { field: String, structs: Vec<String> },
+    NoVariant { ty: Type, variant: String },
+    WrongPayload { enum_name: String, declared: VariantDecl },
+    NonExhaustive { missing: String },
+    UnreachableArm,
+    UnknownTrait(String),
+    MissingImpl { trait_name: String, ty: Type },
+    MissingBound { param: String, trait_name: String },
+    MissingMethod { trait_name: String, method: String },
+    NotInTrait { trait_name: String, method: String },
+    AmbiguousMethod { method: String, traits: Vec<String> },
+    JumpOutOfDefer(String),
     TypeMismatch { expected: Type, found: Type },
+    Located { span: Span, error: Box<TypeError> },
+}
+
+impl TypeError {
+    /// Attaches a source location unless the error already carries one.
+    pub fn at(self, span: Span) -> TypeError {
+        match self {
+            TypeError::Located { .. } => self,
+            error => TypeError::Located {
+                span,
+                error: Box::new(error),
+            },
+        }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            TypeError::Located { span, .. } => Some(*span),
+            _ => None,
+        }
+    }
 }
@@ -1,5 +1,5 @@
 use super::type_error::TypeError;
-use super::types::{Type, TypeVar};
+use super::types::{Predicate, Type, TypeScheme, TypeVar};
 use std::collections::HashMap;
 
 pub struct TypeSubst {
@@ -21,20 +21,54 @@ pub trait ApplySubst {
 impl ApplySubst for Type {
     fn apply(&self, subst: &TypeSubst) -> Type {
         match self {
-            Type::Var(tv) => subst.map.get(tv).cloned().unwrap_or(self.clone()),
+            // a variable may be bound to a type whose variables were bound later
+            Type::Var(tv) => subst
+                .map
+                .get(tv)
+                .map_or_else(|| self.clone(), |ty| ty.apply(subst)),
             Type::Func { params, ret } => Type::Func {
                 params: params.iter().map(|t| t.apply(subst)).collect(),
                 ret: Box::new(ret.apply(subst)),
             },
+            Type::List(elem) => Type::List(Box::new(elem.apply(subst))),
+            Type::Named { name, args } => Type::Named {
+                name: name.clone(),
+                args: args.iter().map(|t| t.apply(subst)).collect(),
+            },
             _ => self.clone(),
         }
     }
 }
 
+impl ApplySubst for TypeScheme {
+    fn apply(&self, subst: &TypeSubst) -> TypeScheme {
+        match self {
+            TypeScheme::MonoType(ty) => TypeScheme::MonoType(ty.apply(subst)),
+            // quantified variables are never bound
+            TypeScheme::PolyType { vars, preds, ty } => TypeScheme::PolyType {
+                vars: vars.clone(),
+                preds: preds.iter().map(|p| p.apply(subst)).collect(),
+                ty: ty.apply(subst),
+            },
+        }
+    }
+}
+
+impl ApplySubst for Predicate {
+    fn apply(&self, subst: &TypeSubst) -> Predicate {
+        Predicate {
+            trait_name: self.trait_name.clone(),
+            ty: self.ty.apply(subst),
+        }
+    }
+}
+
 fn contains(tv: TypeVar, ty: &Type) -> bool {
     match ty {
         Type::Var(v) => *v == tv,
         Type::Func { params, ret } => params.iter().any(|p| contains(tv, p)) || contains(tv, ret),
+        Type::List(elem) => contains(tv, elem),
+        Type::Named { args, .. } => args.iter().any(|t| contains(tv, t)),
         _ => false,
     }
 }
@@ -63,6 +97,17 @@ pub fn unify(a: &Type, b: &Type, subst: &mut TypeSubst) -> Result<(), TypeError>
 
         (Type::Primitive(p1), Type::Primitive(p2)) if p1 == p2 => Ok(()),
 
+        (Type::Named { name: n1, args: a1 }, Type::Named { name: n2, args: a2 })
+            if n1 == n2 && a1.len() == a2.len() =>
+        {
+            for (x, y) in a1.iter().zip(a2.iter()) {
+                unify(x, y, subst)?;
+            }
+            Ok(())
+        }
+
+        (Type::Module(m1), Type::Module(m2)) if m1 == m2 => Ok(()),
+
         (
             Type::Func {
                 params: p1,
@@ -87,6 +132,8 @@ pub fn unify(a: &Type, b: &Type, subst: &mut TypeSubst) -> Result<(), TypeError>
             unify(r1, r2, subst)
         }
 
+        (Type::List(e1), Type::List(e2)) => unify(e1, e2, subst),
+
         _ => Err(TypeError::TypeMismatch {
             expected: a,
             found: b,
@@ -1,7 +1,7 @@
 use super::type_env::TypeEnv;
 use super::type_subst::{ApplySubst, TypeSubst};
-use super::types::{Type, TypeScheme, TypeVar};
-use std::collections::HashSet;
+use super::types::{Predicate, Type, TypeScheme, TypeVar};
+use std::collections::{HashMap, HashSet};
 
 pub trait FreeTypeVars {
     fn free_type_vars(&self) -> HashSet<TypeVar>;
@@ -19,6 +19,8 @@ impl FreeTypeVars for Type {
                 set.extend(ret.free_type_vars());
                 set
             }
+            Type::List(elem) => elem.free_type_vars(),
+            Type::Named { args, .. } => args.iter().flat_map(|t| t.free_type_vars()).collect(),
             _ => HashSet::new(),
         }
     }
@@ -39,7 +41,7 @@ impl FreeTypeVars for TypeScheme {
         match self {
             TypeScheme::MonoType(ty) => ty.free_type_vars(),
 
-            TypeScheme::PolyType { vars, ty } => {
+            TypeScheme::PolyType { vars, ty, .. } => {
                 let mut set = ty.free_type_vars();
                 for v in vars {
                     set.remove(v);
@@ -59,22 +61,75 @@ pub fn generalize(env: &TypeEnv, ty: Type) -> TypeScheme {
     if vars.is_empty() {
         TypeScheme::MonoType(ty)
     } else {
-        TypeScheme::PolyType { vars, ty }
+        TypeScheme::PolyType {
+            vars,
+            preds: vec![],
+            ty,
+        }
+    }
+}
+
+/// Like `generalize`, but looks at the environment through `subst`, so a
+/// variable the environment refers to indirectly stays monomorphic.
+pub fn generalize_with(env: &TypeEnv, subst: &TypeSubst, ty: Type) -> TypeScheme {
+    let ty_vars = ty.free_type_vars();
+    let env_vars: HashSet<TypeVar> = env
+        .all_types()
+        .flat_map(|scheme| scheme.apply(subst).free_type_vars())
+        .collect();
+
+    let vars: Vec<TypeVar> = ty_vars.difference(&env_vars).cloned().collect();
+
+    if vars.is_empty() {
+        TypeScheme::MonoType(ty)
+    } else {
+        TypeScheme::PolyType {
+            vars,
+            preds: vec![],
+            ty,
+        }
     }
 }
 
 pub fn instantiate(scheme: &TypeScheme, env: &mut TypeEnv) -> Type {
+    instantiate_qualified(scheme, env).0
+}
+
+/// Like `instantiate`, also giving the scheme's predicates on the fresh
+/// variables, which the new type must satisfy.
+pub fn instantiate_qualified(scheme: &TypeScheme, env: &mut TypeEnv) -> (Type, Vec<Predicate>) {
     match scheme {
-        TypeScheme::MonoType(ty) => ty.clone(),
+        TypeScheme::MonoType(ty) => (ty.clone(), vec![]),
 
-        TypeScheme::PolyType { vars, ty } => {
-            let mut subst = TypeSubst::new();
+        TypeScheme::PolyType { vars, preds, ty } => {
+            let fresh: HashMap<TypeVar, TypeVar> = vars.iter().map(|v| (*v, env.fresh())).collect();
 
-            for v in vars {
-                subst.map.insert(v.clone(), Type::Var(env.fresh()));
-            }
-
-            ty.apply(&subst)
+            let preds = preds
+                .iter()
+                .map(|p| Predicate {
+                    trait_name: p.trait_name.clone(),
+                    ty: rename(&p.ty, &fresh),
+                })
+                .collect();
+            (rename(ty, &fresh), preds)
         }
     }
 }
+
+// Replaces type variables one for one. Unlike applying a substitution, the
+// new variables are never looked up again, so they may reuse old ids.
+fn rename(ty: &Type, fresh: &HashMap<TypeVar, TypeVar>) -> Type {
+    match ty {
+        Type::Var(tv) => Type::Var(*fresh.get(tv).unwrap_or(tv)),
+        Type::Func { params, ret } => Type::Func {
+            params: params.iter().map(|p| rename(p, fresh)).collect(),
+            ret: Box::new(rename(ret, fresh)),
+        },
+        Type::List(elem) => Type::List(Box::new(rename(elem, fresh))),
+        Type::Named { name, args } => Type::Named {
+            name: name.clone(),
+            args: args.iter().map(|t| rename(t, fresh)).collect(),
+        },
+        _ => ty.clone(),
+    }
+}
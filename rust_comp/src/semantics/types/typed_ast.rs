@@ -36,6 +36,15 @@ pub enum TypedExprKind {
     Mult(Box<TypedExpr>, Box<TypedExpr>),
     Div(Box<TypedExpr>, Box<TypedExpr>),
     Equals(Box<TypedExpr>, Box<TypedExpr>),
+    NotEquals(Box<TypedExpr>, Box<TypedExpr>),
+    Less(Box<TypedExpr>, Box<TypedExpr>),
+    LessEqual(Box<TypedExpr>, Box<TypedExpr>),
+    Greater(Box<TypedExpr>, Box<TypedExpr>),
+    GreaterEqual(Box<TypedExpr>, Box<TypedExpr>),
+    And(Box<TypedExpr>, Box<TypedExpr>),
+    Or(Box<TypedExpr>, Box<TypedExpr>),
+    Not(Box<TypedExpr>),
+    Negate(Box<TypedExpr>),
 
     Call {
         callee: String,
@@ -66,6 +75,14 @@ pub enum TypedStmt {
         body: Box<TypedStmt>,
     },
 
+    While {
+        cond: Box<TypedExpr>,
+        body: Box<TypedStmt>,
+    },
+
+    Break,
+    Continue,
+
     Block(Vec<TypedStmt>),
 
     FnDecl {
@@ -1,12 +1,33 @@
+use std::fmt;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct TypeVar {
     pub id: usize,
 }
 
+/// A qualified type scheme: `PolyType` quantifies over `vars`, each use
+/// of it requiring `preds` of the types they are instantiated with.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum TypeScheme {
     MonoType(Type),
-    PolyType { vars: Vec<TypeVar>, ty: Type },
+    PolyType {
+        vars: Vec<TypeVar>,
+        preds: Vec<Predicate>,
+        ty: Type,
+    },
+}
+
+/// A constraint that `ty` implements trait `trait_name`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Predicate {
+    pub trait_name: String,
+    pub ty: Type,
+}
+
+impl fmt::Display for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.ty, self.trait_name)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -14,6 +35,11 @@ pub enum Type {
     Primitive(PrimitiveType),
     Var(TypeVar),
     Func { params: Vec<Type>, ret: Box<Type> },
+    List(Box<Type>),
+    // a struct or enum, by its declared name, with its type arguments
+    Named { name: String, args: Vec<Type> },
+    // the namespace an `import` binds
+    Module(String),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -24,6 +50,62 @@ pub enum PrimitiveType {
     Bool,
 }
 
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Primitive(p) => write!(f, "{p}"),
+            Type::Var(tv) => write!(f, "'t{}", tv.id),
+            Type::Func { params, ret } => {
+                write!(f, "fn(")?;
+                for (i, p) in params.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{p}")?;
+                }
+                write!(f, ") -> {ret}")
+            }
+            Type::List(elem) => write!(f, "List<{elem}>"),
+            Type::Named { name, args } if args.is_empty() => write!(f, "{name}"),
+            Type::Named { name, args } => {
+                write!(f, "{name}<")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{arg}")?;
+                }
+                write!(f, ">")
+            }
+            Type::Module(name) => write!(f, "module {name}"),
+        }
+    }
+}
+
+impl Type {
+    /// The name impls for this type are declared under: `int`, `List`,
+    /// `Point` and so on. Impls cover every instantiation of a generic type.
+    pub fn impl_name(&self) -> Option<String> {
+        match self {
+            Type::Primitive(p) => Some(p.to_string()),
+            Type::List(_) => Some("List".to_string()),
+            Type::Named { name, .. } => Some(name.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PrimitiveType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrimitiveType::Unit => write!(f, "unit"),
+            PrimitiveType::Int => write!(f, "int"),
+            PrimitiveType::String => write!(f, "string"),
+            PrimitiveType::Bool => write!(f, "bool"),
+        }
+    }
+}
+
 pub fn type_var(n: usize) -> Type {
     Type::Var(TypeVar { id: n })
 }
@@ -43,3 +125,29 @@ pub fn int_type() -> Type {
 pub fn string_type() -> Type {
     Type::Primitive(PrimitiveType::String)
 }
+
+pub fn list_type(elem: Type) -> Type {
+    Type::List(Box::new(elem))
+}
+
+pub fn named_type(name: &str) -> Type {
+    generic_type(name, vec![])
+}
+
+pub fn generic_type(name: &str, args: Vec<Type>) -> Type {
+    Type::Named {
+        name: name.to_string(),
+        args,
+    }
+}
+
+/// The builtin type a type name such as `int` stands for.
+pub fn primitive_type(name: &str) -> Option<Type> {
+    match name {
+        "unit" => Some(unit_type()),
+        "int" => Some(int_type()),
+        "string" => Some(string_type()),
+        "bool" => Some(bool_type()),
+        _ => None,
+    }
+}
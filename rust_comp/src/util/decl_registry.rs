@@ -1,4 +1,5 @@
-use crate::frontend::blueprint_ast::TypeExpr;
+use crate::frontend::id_provider::AstId;
+use crate::frontend::meta_ast::{MethodSig, TypeExpr, VariantDecl};
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt;
@@ -6,9 +7,16 @@ use std::rc::Rc;
 
 #[derive(Debug, Clone)]
 pub struct StructDef {
+    /// Field names with their declared types, in declaration order.
     pub fields: Vec<(String, TypeExpr)>,
 }
 
+impl StructDef {
+    pub fn has_field(&self, name: &str) -> bool {
+        self.fields.iter().any(|(f, _)| f == name)
+    }
+}
+
 impl fmt::Display for StructDef {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "struct {{")?;
@@ -19,11 +27,49 @@ impl fmt::Display for StructDef {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct EnumDef {
+    /// Names of the type parameters, like `T` in `Option<T>`.
+    pub type_params: Vec<String>,
+    pub variants: Vec<VariantDecl>,
+}
+
+impl EnumDef {
+    pub fn variant(&self, name: &str) -> Option<&VariantDecl> {
+        self.variants.iter().find(|v| v.name == name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TraitDef {
+    pub methods: Vec<MethodSig>,
+}
+
+impl TraitDef {
+    pub fn method(&self, name: &str) -> Option<&MethodSig> {
+        self.methods.iter().find(|m| m.name == name)
+    }
+}
+
+/// `impl Trait for Type`. An impl for a generic type such as `List` or
+/// `Option` covers every instantiation of it.
+#[derive(Debug, Clone)]
+pub struct ImplDef {
+    pub trait_name: String,
+    pub type_name: String,
+    /// Method names with the ids of their lowered `FnDecl`s.
+    pub methods: Vec<(String, AstId)>,
+}
+
 pub type DeclRegistryRef = Rc<RefCell<DeclRegistry>>;
 
-#[derive(Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct DeclRegistry {
     structs: HashMap<String, StructDef>,
+    enums: HashMap<String, EnumDef>,
+    traits: HashMap<String, TraitDef>,
+    // by type name, then trait name
+    impls: HashMap<String, HashMap<String, ImplDef>>,
     parent: Option<DeclRegistryRef>,
 }
 
@@ -31,6 +77,9 @@ impl DeclRegistry {
     pub fn new() -> DeclRegistry {
         DeclRegistry {
             structs: HashMap::new(),
+            enums: HashMap::new(),
+            traits: HashMap::new(),
+            impls: HashMap::new(),
             parent: None,
         }
     }
@@ -38,12 +87,71 @@ impl DeclRegistry {
     pub fn child(parent: DeclRegistryRef) -> DeclRegistry {
         DeclRegistry {
             structs: HashMap::new(),
+            enums: HashMap::new(),
+            traits: HashMap::new(),
+            impls: HashMap::new(),
             parent: Some(parent),
         }
     }
 
-    pub fn define_struct(&mut self, name: String, def: StructDef) {
+    /// Registers `def`, or hands it back if a struct or enum of that name
+    /// is already declared here. One declared by a parent is shadowed.
+    pub fn define_struct(&mut self, name: String, def: StructDef) -> Result<(), StructDef> {
+        if self.declares_type(&name) {
+            return Err(def);
+        }
         self.structs.insert(name, def);
+        Ok(())
+    }
+
+    /// Registers `def`, or hands it back if a struct or enum of that name
+    /// is already declared here. One declared by a parent is shadowed.
+    pub fn define_enum(&mut self, name: String, def: EnumDef) -> Result<(), EnumDef> {
+        if self.declares_type(&name) {
+            return Err(def);
+        }
+        self.enums.insert(name, def);
+        Ok(())
+    }
+
+    fn declares_type(&self, name: &str) -> bool {
+        self.structs.contains_key(name) || self.enums.contains_key(name)
+    }
+
+    pub fn define_trait(&mut self, name: String, def: TraitDef) {
+        self.traits.insert(name, def);
+    }
+
+    /// Registers `def`, or hands it back if its type already implements
+    /// the trait.
+    pub fn define_impl(&mut self, def: ImplDef) -> Result<(), ImplDef> {
+        if self.get_impl(&def.trait_name, &def.type_name).is_some() {
+            return Err(def);
+        }
+        self.impls
+            .entry(def.type_name.clone())
+            .or_default()
+            .insert(def.trait_name.clone(), def);
+        Ok(())
+    }
+
+    /// Names of the structs declaring `field`, sorted.
+    pub fn structs_with_field(&self, field: &str) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .structs
+            .iter()
+            .filter(|(_, def)| def.has_field(field))
+            .map(|(name, _)| name.clone())
+            .collect();
+        if let Some(parent) = &self.parent {
+            for name in parent.borrow().structs_with_field(field) {
+                if !names.contains(&name) && !self.structs.contains_key(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names.sort();
+        names
     }
 
     pub fn get_struct(&self, name: &str) -> Option<StructDef> {
@@ -53,4 +161,63 @@ impl DeclRegistry {
                 .and_then(|p| p.borrow().get_struct(name))
         })
     }
+
+    pub fn get_enum(&self, name: &str) -> Option<EnumDef> {
+        self.enums
+            .get(name)
+            .cloned()
+            .or_else(|| self.parent.as_ref().and_then(|p| p.borrow().get_enum(name)))
+    }
+
+    pub fn get_trait(&self, name: &str) -> Option<TraitDef> {
+        self.traits.get(name).cloned().or_else(|| {
+            self.parent
+                .as_ref()
+                .and_then(|p| p.borrow().get_trait(name))
+        })
+    }
+
+    pub fn get_impl(&self, trait_name: &str, type_name: &str) -> Option<ImplDef> {
+        self.impls
+            .get(type_name)
+            .and_then(|impls| impls.get(trait_name))
+            .cloned()
+            .or_else(|| {
+                self.parent
+                    .as_ref()
+                    .and_then(|p| p.borrow().get_impl(trait_name, type_name))
+            })
+    }
+
+    /// Names of the traits declaring `method`, sorted.
+    pub fn traits_with_method(&self, method: &str) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .traits
+            .iter()
+            .filter(|(_, def)| def.method(method).is_some())
+            .map(|(name, _)| name.clone())
+            .collect();
+        if let Some(parent) = &self.parent {
+            for name in parent.borrow().traits_with_method(method) {
+                if !names.contains(&name) && !self.traits.contains_key(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names.sort();
+        names
+    }
+
+    /// The trait and lowered `FnDecl` providing `method` for values of type
+    /// `type_name`. Methods are resolved by the type alone, so two impls for
+    /// one type giving the same method name are picked between by trait name.
+    pub fn find_method(&self, type_name: &str, method: &str) -> Option<(String, AstId)> {
+        self.traits_with_method(method)
+            .into_iter()
+            .find_map(|trait_name| {
+                let def = self.get_impl(&trait_name, type_name)?;
+                let (_, id) = def.methods.iter().find(|(name, _)| name == method)?;
+                Some((trait_name, *id))
+            })
+    }
 }
@@ -3,20 +3,26 @@ use std::path::Path;
 use crate::config::SOURCE_FILE_EXT;
 
 pub trait ExternalResolver {
-    fn read_file(&self, curr_dir: &Path, relative_path: &str) -> Option<String>;
+    fn read_file(&self, curr_dir: &Path, relative_path: &str) -> Result<String, String>;
+    fn read_bytes(&self, curr_dir: &Path, relative_path: &str) -> Result<Vec<u8>, String>;
     fn read_mod(&self, curr_dir: &Path, name: &str) -> Option<String>;
 }
 
 pub struct DefaultResolver {}
 
 impl ExternalResolver for DefaultResolver {
-    fn read_file(&self, curr_dir: &Path, relative_path: &str) -> Option<String> {
+    fn read_file(&self, curr_dir: &Path, relative_path: &str) -> Result<String, String> {
         let path = curr_dir.join(relative_path);
-        std::fs::read_to_string(&path).ok()
+        std::fs::read_to_string(&path).map_err(|e| e.to_string())
+    }
+
+    fn read_bytes(&self, curr_dir: &Path, relative_path: &str) -> Result<Vec<u8>, String> {
+        let path = curr_dir.join(relative_path);
+        std::fs::read(&path).map_err(|e| e.to_string())
     }
 
     fn read_mod(&self, curr_dir: &Path, name: &str) -> Option<String> {
-        let full = curr_dir.join(String::from(name) + SOURCE_FILE_EXT);
+        let full = curr_dir.join(format!("{name}.{SOURCE_FILE_EXT}"));
         std::fs::read_to_string(&full).ok()
     }
 
@@ -27,8 +33,15 @@ pub struct MapResolver {
 }
 
 impl ExternalResolver for MapResolver {
-    fn read_file(&self, _curr_dir: &Path, relative_path: &str) -> Option<String> {
-        self.map.get(relative_path).cloned()
+    fn read_file(&self, _curr_dir: &Path, relative_path: &str) -> Result<String, String> {
+        self.map
+            .get(relative_path)
+            .cloned()
+            .ok_or_else(|| "no such file".to_string())
+    }
+
+    fn read_bytes(&self, curr_dir: &Path, relative_path: &str) -> Result<Vec<u8>, String> {
+        self.read_file(curr_dir, relative_path).map(String::into_bytes)
     }
 
     fn read_mod(&self, _curr_dir: &Path, name: &str) -> Option<String> {
@@ -40,8 +53,12 @@ pub struct DummyResolver {}
 
 
 impl ExternalResolver for DummyResolver {
-    fn read_file(&self, _curr_dir: &Path, relative_path: &str) -> Option<String> {
-        Some(String::from("Dummy: ") + relative_path)
+    fn read_file(&self, _curr_dir: &Path, relative_path: &str) -> Result<String, String> {
+        Ok(String::from("Dummy: ") + relative_path)
+    }
+
+    fn read_bytes(&self, curr_dir: &Path, relative_path: &str) -> Result<Vec<u8>, String> {
+        self.read_file(curr_dir, relative_path).map(String::into_bytes)
     }
 
     fn read_mod(&self, _curr_dir: &Path, name: &str) -> Option<String> {
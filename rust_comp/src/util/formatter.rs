@@ -183,6 +183,35 @@ impl Formatter {
                 result
             }
 
+            ExpandedStmt::While { cond, body } => {
+                let mut result = format!(
+                    "{}while ({}) {}",
+                    self.indent(),
+                    self.format_expr(cond),
+                    if self.settings.newline_after_block_open {
+                        format!("{{{}", self.settings.line_ending)
+                    } else {
+                        "{".to_string()
+                    }
+                );
+
+                self.indent_increase();
+                result.push_str(&self.format_stmt(body));
+                self.indent_decrease();
+
+                if self.settings.newline_before_block_close {
+                    result.push_str(&self.settings.line_ending);
+                    result.push_str(&self.indent());
+                }
+                result.push('}');
+
+                result
+            }
+
+            ExpandedStmt::Break => format!("{}break;", self.indent()),
+
+            ExpandedStmt::Continue => format!("{}continue;", self.indent()),
+
             ExpandedStmt::Block(stmts) => {
                 if stmts.is_empty() {
                     return format!("{}{{}}", self.indent());
@@ -375,6 +404,88 @@ impl Formatter {
                 )
             }
 
+            ExpandedExpr::NotEquals(left, right) => {
+                let op = if self.settings.spaces_around_binary_ops {
+                    " != "
+                } else {
+                    "!="
+                };
+                format!(
+                    "{}{}{}",
+                    self.format_expr(left),
+                    op,
+                    self.format_expr(right)
+                )
+            }
+
+            ExpandedExpr::Less(left, right) => {
+                let op = if self.settings.spaces_around_binary_ops {
+                    " < "
+                } else {
+                    "<"
+                };
+                format!(
+                    "{}{}{}",
+                    self.format_expr(left),
+                    op,
+                    self.format_expr(right)
+                )
+            }
+
+            ExpandedExpr::LessEqual(left, right) => {
+                let op = if self.settings.spaces_around_binary_ops {
+                    " <= "
+                } else {
+                    "<="
+                };
+                format!(
+                    "{}{}{}",
+                    self.format_expr(left),
+                    op,
+                    self.format_expr(right)
+                )
+            }
+
+            ExpandedExpr::Greater(left, right) => {
+                let op = if self.settings.spaces_around_binary_ops {
+                    " > "
+                } else {
+                    ">"
+                };
+                format!(
+                    "{}{}{}",
+                    self.format_expr(left),
+                    op,
+                    self.format_expr(right)
+                )
+            }
+
+            ExpandedExpr::GreaterEqual(left, right) => {
+                let op = if self.settings.spaces_around_binary_ops {
+                    " >= "
+                } else {
+                    ">="
+                };
+                format!(
+                    "{}{}{}",
+                    self.format_expr(left),
+                    op,
+                    self.format_expr(right)
+                )
+            }
+
+            ExpandedExpr::And(left, right) => {
+                format!("{} and {}", self.format_expr(left), self.format_expr(right))
+            }
+
+            ExpandedExpr::Or(left, right) => {
+                format!("{} or {}", self.format_expr(left), self.format_expr(right))
+            }
+
+            ExpandedExpr::Not(operand) => format!("!{}", self.format_expr(operand)),
+
+            ExpandedExpr::Negate(operand) => format!("-{}", self.format_expr(operand)),
+
             ExpandedExpr::Call { callee, args } => {
                 let args_str = args
                     .iter()
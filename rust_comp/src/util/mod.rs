@@ -1,3 +1,4 @@
+pub mod decl_registry;
 pub mod external_resolver;
 pub mod formatter;
 pub mod formatters;
@@ -0,0 +1,94 @@
+use cronyx::frontend::lexer::*;
+use cronyx::frontend::parser::*;
+use cronyx::frontend::span::*;
+use cronyx::runtime::interpreter::*;
+
+mod common;
+use common::run;
+
+#[cfg(test)]
+mod assignment_tests {
+    use super::*;
+
+    #[test]
+    fn assignment_is_an_expression() {
+        assert_eq!(run("var x = 1; print(x = 4); print(x);").unwrap(), "4\n4\n");
+    }
+
+    #[test]
+    fn assignment_updates_enclosing_scope() {
+        let source = "var x = 1;\nif (true) { x += 1; }\nprint(x);";
+        assert_eq!(run(source).unwrap(), "2\n");
+    }
+
+    #[test]
+    fn undeclared_variable_is_an_error() {
+        match run("y = 3;") {
+            Err(EvalError::UndeclaredAssignment(name, span)) => {
+                assert_eq!(name, "y");
+                assert_eq!((span.start, span.end), (0, 1));
+            }
+            Err(other) => panic!("unexpected error {:?}", other),
+            Ok(_) => panic!("assignment to undeclared variable succeeded"),
+        }
+    }
+
+    #[test]
+    fn compound_assignment_checks_operand_types() {
+        assert!(matches!(
+            run("var x = 1; x -= \"a\";"),
+            Err(EvalError::TypeError(..))
+        ));
+    }
+
+    #[test]
+    fn list_assignment_is_bounds_checked() {
+        match run("var xs = [1, 2];\nxs[2] = 3;") {
+            Err(EvalError::IndexOutOfBounds { index, len, span }) => {
+                assert_eq!((index, len), (2, 2));
+                assert_eq!(span.line, 2);
+            }
+            Err(other) => panic!("unexpected error {:?}", other),
+            Ok(_) => panic!("out of bounds assignment succeeded"),
+        }
+    }
+
+    #[test]
+    fn unknown_field_is_an_error() {
+        let source = "struct Point { x: int }\nvar p = Point { x: 1 };\np.y = 2;";
+        assert!(matches!(run(source), Err(EvalError::NoSuchField(field, _)) if field == "y"));
+    }
+
+    #[test]
+    fn dividing_by_zero_is_an_error() {
+        assert!(matches!(
+            run("var x = 4;\nx /= 0;"),
+            Err(EvalError::DivisionByZero(_))
+        ));
+        assert!(matches!(
+            run("print(4 / 0);"),
+            Err(EvalError::DivisionByZero(_))
+        ));
+    }
+
+    #[test]
+    fn overflow_is_an_error() {
+        let source = "var x = 9223372036854775807;\nx += 1;";
+        assert!(matches!(run(source), Err(EvalError::IntegerOverflow(_))));
+        let source = "var x = 9223372036854775807;\nprint(x * 2);";
+        assert!(matches!(run(source), Err(EvalError::IntegerOverflow(_))));
+    }
+
+    #[test]
+    fn rejects_invalid_targets() {
+        let tokens = tokenize("1 + 2 = 3;", FileId(0)).unwrap();
+        let mut ctx = ParseCtx::new();
+        let errors = parse(&tokens, &mut ctx).unwrap_err();
+        match &errors[0] {
+            ParseError::InvalidAssignmentTarget(span) => {
+                assert_eq!((span.start, span.end), (0, 5));
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use cronyx::runtime::budget::*;
+use cronyx::runtime::environment::*;
+use cronyx::runtime::interpreter::*;
+use cronyx::semantics::meta::meta_policy::MetaPolicy;
+use cronyx::semantics::meta::meta_processor::*;
+
+mod common;
+use common::{lower, lower_with_resolver};
+
+#[cfg(test)]
+mod budget_tests {
+    use super::*;
+
+    fn run(source: &str, budget: Budget) -> Result<String, EvalError> {
+        let runtime_ast = lower(source);
+        let mut out = Vec::new();
+        eval_with_budget(
+            &runtime_ast,
+            &runtime_ast.sem_root_stmts,
+            Environment::new(),
+            &mut None,
+            &mut out,
+            budget,
+        )?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    // Reaching the default depth takes more stack than a test thread has.
+    fn on_big_stack<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+        std::thread::Builder::new()
+            .stack_size(Budget::STACK_SIZE)
+            .spawn(f)
+            .unwrap()
+            .join()
+            .unwrap()
+    }
+
+    fn names(call_stack: &[CallFrame]) -> Vec<Option<&str>> {
+        call_stack.iter().map(|f| f.function.as_deref()).collect()
+    }
+
+    #[test]
+    fn endless_loop_runs_out_of_fuel() {
+        let budget = Budget {
+            fuel: Some(500),
+            ..Budget::default()
+        };
+        match run("var i = 0;\nwhile (true) { i = i + 1; }", budget) {
+            Err(EvalError::OutOfFuel {
+                limit, call_stack, ..
+            }) => {
+                assert_eq!(limit, 500);
+                assert!(call_stack.is_empty());
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn out_of_fuel_carries_the_call_stack() {
+        let source = "fn spin() { while (true) { } }\nfn outer() { spin(); }\nouter();";
+        let budget = Budget {
+            fuel: Some(100),
+            ..Budget::default()
+        };
+        match run(source, budget) {
+            Err(EvalError::OutOfFuel { call_stack, .. }) => {
+                assert_eq!(names(&call_stack), vec![Some("outer"), Some("spin")]);
+                assert_eq!((call_stack[0].span.line, call_stack[0].span.col), (3, 1));
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn enough_fuel_finishes() {
+        let budget = Budget {
+            fuel: Some(1_000),
+            ..Budget::default()
+        };
+        assert_eq!(run("print(1 + 2);", budget).unwrap(), "3\n");
+    }
+
+    #[test]
+    fn unbounded_recursion_overflows() {
+        let source = "fn down(n) { return down(n - 1); }\ndown(10);";
+        match on_big_stack(move || run(source, Budget::default())) {
+            Err(EvalError::StackOverflow {
+                limit, call_stack, ..
+            }) => {
+                assert_eq!(limit, Budget::DEFAULT_MAX_DEPTH);
+                assert_eq!(call_stack.len(), Budget::DEFAULT_MAX_DEPTH);
+                assert!(names(&call_stack).iter().all(|n| *n == Some("down")));
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn max_depth_is_configurable() {
+        let source =
+            "fn count(n) { if (n == 0) { return 0; } return 1 + count(n - 1); }\nprint(count(20));";
+        let shallow = Budget {
+            max_depth: Some(10),
+            ..Budget::default()
+        };
+        assert!(matches!(
+            run(source, shallow),
+            Err(EvalError::StackOverflow { limit: 10, .. })
+        ));
+        assert_eq!(run(source, Budget::default()).unwrap(), "20\n");
+    }
+
+    #[test]
+    fn function_expressions_are_unnamed_frames() {
+        let source = "var f = fn(n) { return f(n); };\nf(1);";
+        let budget = Budget {
+            max_depth: Some(3),
+            ..Budget::default()
+        };
+        match run(source, budget) {
+            Err(EvalError::StackOverflow { call_stack, .. }) => {
+                assert_eq!(names(&call_stack), vec![None, None, None]);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn recursive_meta_fn_is_stopped() {
+        let source = "meta {\n    fn down(n) { return down(n - 1); }\n    down(1);\n}";
+        let result = on_big_stack(move || {
+            lower_with_resolver(source, &[], &MetaPolicy::default()).map(|_| ())
+        });
+        match result {
+            Err(MetaProcessError::PolicyViolation { error, block }) => {
+                assert!(matches!(*error, EvalError::StackOverflow { .. }));
+                assert_eq!(block.map(|b| (b.line, b.col)), Some((1, 1)));
+            }
+            Err(other) => panic!("unexpected error {:?}", other),
+            Ok(()) => panic!("recursion was not stopped"),
+        }
+    }
+}
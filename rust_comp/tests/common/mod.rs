@@ -0,0 +1,96 @@
+// Helpers shared by the integration tests; each test file uses some of them.
+#![allow(dead_code)]
+
+use cronyx::diagnostics::source_map::SourceMap;
+use cronyx::frontend::lexer::tokenize;
+use cronyx::frontend::parser::{parse, ParseCtx};
+use cronyx::frontend::span::FileId;
+use cronyx::runtime::environment::Environment;
+use cronyx::runtime::interpreter::{eval, EvalError};
+use cronyx::semantics::meta::meta_policy::MetaPolicy;
+use cronyx::semantics::meta::meta_processor::{process, process_with_modules, MetaProcessError};
+use cronyx::semantics::meta::module_loader::ModuleLoader;
+use cronyx::semantics::meta::runtime_ast::RuntimeAst;
+use cronyx::util::external_resolver::MapResolver;
+use std::path::{Path, PathBuf};
+
+/// Parses `source`, which must be free of syntax errors.
+pub fn parse_source(source: &str) -> ParseCtx {
+    let tokens = tokenize(source, FileId(0)).unwrap();
+    let mut ctx = ParseCtx::new();
+    parse(&tokens, &mut ctx).unwrap();
+    ctx
+}
+
+/// Lowers `source`, returning the program and what its `meta` blocks printed.
+pub fn lower_with_output(source: &str) -> Result<(RuntimeAst, String), MetaProcessError> {
+    let ctx = parse_source(source);
+    let mut out = Vec::new();
+    let runtime_ast = process(&ctx.ast, &mut out)?;
+    Ok((runtime_ast, String::from_utf8(out).unwrap()))
+}
+
+/// A program lowered with `lower_with_resolver`.
+pub struct Lowered {
+    pub ast: RuntimeAst,
+    /// What the `meta` blocks printed.
+    pub output: String,
+    /// The modules and files the program read.
+    pub dependencies: Vec<PathBuf>,
+    /// Every source diagnostics may point into, `main.cx` first.
+    pub sources: SourceMap,
+}
+
+/// Lowers `source` as `src/main.cx`, with `files` as the modules and files
+/// next to it, holding compile-time code to `policy`.
+pub fn lower_with_resolver(
+    source: &str,
+    files: &[(&str, &str)],
+    policy: &MetaPolicy,
+) -> Result<Lowered, MetaProcessError> {
+    let resolver = MapResolver {
+        map: files
+            .iter()
+            .map(|(name, src)| (name.to_string(), src.to_string()))
+            .collect(),
+    };
+    let mut sources = SourceMap::new();
+    sources.add("main.cx", source);
+    let mut modules = ModuleLoader::new(&resolver, Path::new("src"), &mut sources);
+
+    let ctx = parse_source(source);
+    let mut out = Vec::new();
+    let ast = process_with_modules(&ctx.ast, &mut out, &mut modules, policy)?;
+    let dependencies = modules.dependencies().to_vec();
+    Ok(Lowered {
+        ast,
+        output: String::from_utf8(out).unwrap(),
+        dependencies,
+        sources,
+    })
+}
+
+/// Lowers `source`, which must lower without errors.
+pub fn lower(source: &str) -> RuntimeAst {
+    lower_with_output(source).unwrap().0
+}
+
+/// Runs a lowered program, returning what it printed, up to an error if it
+/// failed.
+pub fn eval_output(runtime_ast: &RuntimeAst) -> (String, Result<(), EvalError>) {
+    let mut out = Vec::new();
+    let result = eval(
+        runtime_ast,
+        &runtime_ast.sem_root_stmts,
+        Environment::new(),
+        &mut None,
+        &mut out,
+    );
+    (String::from_utf8(out).unwrap(), result.map(|_| ()))
+}
+
+/// Lowers and runs `source`, returning what the program printed.
+pub fn run(source: &str) -> Result<String, EvalError> {
+    let (out, result) = eval_output(&lower(source));
+    result.map(|()| out)
+}
@@ -0,0 +1,54 @@
+use cronyx::semantics::meta::meta_processor::*;
+
+mod common;
+use common::lower_with_output;
+
+#[cfg(test)]
+mod compile_env_tests {
+    use super::*;
+
+    #[test]
+    fn meta_functions_are_seen_by_later_blocks() {
+        let source = "meta fn square(x) {\n    return x * x;\n}\nmeta {\n    print(square(3));\n}\nmeta {\n    print(square(4));\n}";
+        assert_eq!(lower_with_output(source).unwrap().1, "9\n16\n");
+    }
+
+    #[test]
+    fn a_block_waits_for_a_declaration_further_down() {
+        let source = "meta {\n    print(limit + 1);\n}\nmeta var limit = 9;";
+        assert_eq!(lower_with_output(source).unwrap().1, "10\n");
+    }
+
+    #[test]
+    fn names_a_block_declares_itself_are_not_waited_for() {
+        let source = "meta {\n    var n = 1;\n    print(n);\n}\nmeta var n = 2;";
+        assert_eq!(lower_with_output(source).unwrap().1, "1\n");
+    }
+
+    #[test]
+    fn an_assignment_is_seen_by_later_blocks() {
+        let source = "meta var a = 2;\nmeta { a = a + 1; print(a); }\nmeta { print(\"after\"); print(a); }";
+        assert_eq!(lower_with_output(source).unwrap().1, "3\nafter\n3\n");
+    }
+
+    #[test]
+    fn a_mutation_is_seen_by_later_blocks() {
+        let source = "meta { var a = [1]; }\nmeta { a.push(2); }\nmeta { print(a); }";
+        assert_eq!(lower_with_output(source).unwrap().1, "[1, 2]\n");
+    }
+
+    #[test]
+    fn blocks_waiting_on_each_other_are_an_error() {
+        let source = "meta {\n    var a = b;\n}\nmeta {\n    var b = a;\n}";
+        match lower_with_output(source) {
+            Err(MetaProcessError::MetaDeclarationCycle { names, span }) => {
+                assert_eq!(names, vec!["a".to_string(), "b".to_string()]);
+                assert_eq!((span.line, span.col), (1, 1));
+            }
+            other => panic!(
+                "expected a declaration cycle, got {:?}",
+                other.map(|(_, out)| out)
+            ),
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use cronyx::runtime::interpreter::*;
+
+mod common;
+use common::{eval_output, lower, lower_with_output};
+
+#[cfg(test)]
+mod defer_tests {
+    use super::*;
+
+    fn output(source: &str) -> String {
+        let (out, result) = eval_output(&lower(source));
+        result.unwrap();
+        out
+    }
+
+    #[test]
+    fn deferred_statements_run_last_first() {
+        let source = "{\n    defer print(1);\n    defer print(2);\n    print(3);\n}\nprint(4);";
+        assert_eq!(output(source), "3\n2\n1\n4\n");
+    }
+
+    #[test]
+    fn deferred_statements_see_the_block_as_it_is_left() {
+        let source = "{\n    var x = 1;\n    defer print(x);\n    x = 2;\n}";
+        assert_eq!(output(source), "2\n");
+    }
+
+    #[test]
+    fn return_and_break_run_the_deferred_statements() {
+        let source = "fn f() {\n    defer print(\"f done\");\n    while (true) {\n        defer print(\"loop done\");\n        break;\n    }\n    return 1;\n}\nprint(f());";
+        assert_eq!(output(source), "loop done\nf done\n1\n");
+    }
+
+    #[test]
+    fn errors_run_the_deferred_statements() {
+        let source = "fn f() {\n    defer print(\"cleanup\");\n    print(missing);\n}\nf();";
+        let (out, result) = eval_output(&lower(source));
+        assert!(matches!(result, Err(EvalError::UndefinedVariable(name, _)) if name == "missing"));
+        assert_eq!(out, "cleanup\n");
+    }
+
+    #[test]
+    fn the_first_error_is_reported() {
+        let source = "{\n    defer print(second);\n    print(first);\n}";
+        assert!(matches!(
+            eval_output(&lower(source)).1,
+            Err(EvalError::UndefinedVariable(name, _)) if name == "first"
+        ));
+        let source = "{\n    defer print(deferred);\n    print(1);\n}";
+        assert!(matches!(
+            eval_output(&lower(source)).1,
+            Err(EvalError::UndefinedVariable(name, _)) if name == "deferred"
+        ));
+    }
+
+    #[test]
+    fn meta_blocks_run_their_deferred_statements() {
+        let source = "meta {\n    defer print(\"closed\");\n    print(\"generating\");\n}";
+        assert_eq!(lower_with_output(source).unwrap().1, "generating\nclosed\n");
+    }
+}
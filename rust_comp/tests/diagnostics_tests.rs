@@ -0,0 +1,135 @@
+use cronyx::diagnostics::diagnostic::*;
+use cronyx::diagnostics::render::*;
+use cronyx::diagnostics::source_map::*;
+use cronyx::frontend::lexer::*;
+use cronyx::frontend::parser::*;
+use cronyx::frontend::span::*;
+use cronyx::runtime::budget::*;
+use cronyx::runtime::environment::*;
+use cronyx::runtime::interpreter::*;
+use cronyx::semantics::meta::meta_processor::*;
+use std::io;
+
+#[cfg(test)]
+mod diagnostics_tests {
+    use super::*;
+
+    fn parse_diagnostic(sources: &mut SourceMap, source: &str) -> Diagnostic {
+        let file = sources.add("main.cx", source);
+        let tokens = tokenize(source, file).unwrap();
+        let mut ctx = ParseCtx::new();
+        parse(&tokens, &mut ctx).unwrap_err()[0].to_diagnostic()
+    }
+
+    #[test]
+    fn renders_snippet_with_caret() {
+        let mut sources = SourceMap::new();
+        let diag = parse_diagnostic(&mut sources, "var x = 1;\nprint(x;\n");
+
+        let expected = "\
+error[E0101]: expected `)`, found `;`
+ --> main.cx:2:8
+  |
+2 | print(x;
+  |        ^ expected `)`
+";
+        assert_eq!(render(&diag, &sources, RenderStyle::Plain), expected);
+    }
+
+    #[test]
+    fn underlines_the_whole_span() {
+        let mut sources = SourceMap::new();
+        let file = sources.add("lib.cx", "print(name);");
+        let diag = Diagnostic::error("E9999", "bad name")
+            .with_span(Span::new(file, 6, 10, 1, 7))
+            .with_note("names are case sensitive")
+            .with_help("did you mean `Name`?");
+
+        let rendered = render(&diag, &sources, RenderStyle::Plain);
+        assert!(rendered.contains("  |       ^^^^\n"));
+        assert!(rendered.contains("  = note: names are case sensitive\n"));
+        assert!(rendered.contains("  = help: did you mean `Name`?\n"));
+    }
+
+    #[test]
+    fn renders_without_span() {
+        let diag = Diagnostic::error("E0000", "couldn't read `x.cx`");
+        let rendered = render(&diag, &SourceMap::new(), RenderStyle::Plain);
+        assert_eq!(rendered, "error[E0000]: couldn't read `x.cx`\n");
+    }
+
+    #[test]
+    fn ansi_output_is_colored() {
+        let mut sources = SourceMap::new();
+        let diag = parse_diagnostic(&mut sources, "print(1;");
+        let rendered = render(&diag, &sources, RenderStyle::Ansi);
+        assert!(rendered.starts_with("\x1b[1;31merror[E0101]"));
+        assert!(rendered.contains("\x1b[1;31m^\x1b[0m"));
+    }
+
+    #[test]
+    fn json_output_escapes_strings() {
+        let mut sources = SourceMap::new();
+        let file = sources.add("a \"b\".cx", "x");
+        let diag = Diagnostic::error("E0301", "cannot find `x`\nhere")
+            .with_span(Span::new(file, 0, 1, 1, 1))
+            .with_note("n");
+
+        assert_eq!(
+            render_json(&diag, &sources),
+            "{\"severity\":\"error\",\"code\":\"E0301\",\"message\":\"cannot find `x`\\nhere\",\
+             \"span\":{\"file\":\"a \\\"b\\\".cx\",\"line\":1,\"column\":1,\"start\":0,\"end\":1},\
+             \"label\":null,\"notes\":[\"n\"],\"help\":null}"
+        );
+    }
+
+    #[test]
+    fn runtime_errors_become_diagnostics() {
+        let mut sources = SourceMap::new();
+        let source = "print(1 + true);";
+        let file = sources.add("main.cx", source);
+        let tokens = tokenize(source, file).unwrap();
+        let mut ctx = ParseCtx::new();
+        parse(&tokens, &mut ctx).unwrap();
+        let runtime_ast = process(&ctx.ast, &mut io::sink()).unwrap();
+
+        let err = eval(
+            &runtime_ast,
+            &runtime_ast.sem_root_stmts,
+            Environment::new(),
+            &mut None,
+            &mut io::sink(),
+        )
+        .err()
+        .unwrap();
+
+        let diag = err.to_diagnostic();
+        assert_eq!(diag.code, "E0302");
+        assert_eq!(diag.span.map(|s| (s.start, s.end)), Some((6, 14)));
+    }
+
+    #[test]
+    fn recursion_shares_one_call_stack_note() {
+        let frame = |function: &str, col| CallFrame {
+            function: Some(function.to_string()),
+            span: Span::new(FileId(0), 0, 0, 1, col),
+        };
+        let mut call_stack = vec![frame("main", 1)];
+        call_stack.extend((0..5).map(|_| frame("down", 20)));
+        let err = EvalError::StackOverflow {
+            limit: 6,
+            span: Span::new(FileId(0), 0, 0, 1, 20),
+            call_stack,
+        };
+
+        let diag = err.to_diagnostic();
+        assert_eq!(diag.code, "E0323");
+        assert_eq!(
+            diag.notes,
+            vec![
+                "in `down`, called at line 1, column 20 (5 times)",
+                "in `main`, called at line 1, column 1",
+            ]
+        );
+    }
+}
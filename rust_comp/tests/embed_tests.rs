@@ -0,0 +1,79 @@
+use cronyx::frontend::lexer::*;
+use cronyx::frontend::parser::*;
+use cronyx::frontend::span::*;
+use cronyx::semantics::meta::meta_policy::MetaPolicy;
+use cronyx::semantics::meta::meta_processor::*;
+use std::path::PathBuf;
+
+mod common;
+use common::{eval_output, lower_with_resolver};
+#[cfg(test)]
+mod embed_tests {
+    use super::*;
+
+    // Runs `source`, returning its output and the files it depended on.
+    fn run(
+        source: &str,
+        files: &[(&str, &str)],
+    ) -> Result<(String, Vec<PathBuf>), MetaProcessError> {
+        let lowered = lower_with_resolver(source, files, &MetaPolicy::default())?;
+        let (out, result) = eval_output(&lowered.ast);
+        result.unwrap();
+        Ok((out, lowered.dependencies))
+    }
+
+    #[test]
+    fn embeds_text() {
+        let (out, _) = run("print(embed(\"a.txt\"));", &[("a.txt", "contents")]).unwrap();
+        assert_eq!(out, "contents\n");
+    }
+
+    #[test]
+    fn embeds_bytes() {
+        let (out, _) = run("print(embed(\"a.bin\", bytes));", &[("a.bin", "AB")]).unwrap();
+        assert_eq!(out, "[65, 66]\n");
+    }
+
+    #[test]
+    fn missing_file_is_reported() {
+        match run("var x = embed(\"nope.txt\");", &[]) {
+            Err(MetaProcessError::EmbedFailed { path, span, .. }) => {
+                assert_eq!(path, "nope.txt");
+                assert_eq!((span.line, span.col), (1, 9));
+            }
+            Err(other) => panic!("unexpected error {:?}", other),
+            Ok(_) => panic!("missing file was embedded"),
+        }
+    }
+
+    #[test]
+    fn embedded_files_are_dependencies() {
+        let files = [
+            ("util", "var text = embed(\"a.txt\");"),
+            ("a.txt", "x"),
+            ("b.txt", "y"),
+        ];
+        let source = "import util;\nprint(embed(\"b.txt\"));\nprint(embed(\"a.txt\"));";
+        let (_, mut deps) = run(source, &files).unwrap();
+        deps.sort();
+        assert_eq!(
+            deps,
+            [
+                PathBuf::from("src/a.txt"),
+                PathBuf::from("src/b.txt"),
+                PathBuf::from("src/util.cx"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_mode_is_a_parse_error() {
+        let tokens = tokenize("embed(\"a.txt\", lines);", FileId(0)).unwrap();
+        let mut ctx = ParseCtx::new();
+        let errors = parse(&tokens, &mut ctx).unwrap_err();
+        assert!(matches!(
+            &errors[0],
+            ParseError::InvalidEmbedMode { found, .. } if found == "lines"
+        ));
+    }
+}
@@ -0,0 +1,83 @@
+use cronyx::frontend::lexer::*;
+use cronyx::frontend::meta_ast::Payload;
+use cronyx::frontend::parser::*;
+use cronyx::frontend::span::*;
+use cronyx::runtime::interpreter::*;
+use cronyx::semantics::meta::meta_processor::*;
+use std::io;
+
+mod common;
+use common::run;
+
+#[cfg(test)]
+mod enum_tests {
+    use super::*;
+
+    const SHAPE: &str = "enum Shape { Circle(int), Rect { w: int, h: int }, Empty }\n";
+
+    #[test]
+    fn declarations_are_registered() {
+        let tokens = tokenize(SHAPE, FileId(0)).unwrap();
+        let mut ctx = ParseCtx::new();
+        parse(&tokens, &mut ctx).unwrap();
+        let runtime_ast = process(&ctx.ast, &mut io::sink()).unwrap();
+
+        let def = runtime_ast.decls.get_enum("Shape").unwrap();
+        let names: Vec<_> = def.variants.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, ["Circle", "Rect", "Empty"]);
+        assert!(matches!(
+            def.variant("Rect").unwrap().payload,
+            Payload::Struct(_)
+        ));
+    }
+
+    #[test]
+    fn variants_print_like_structs() {
+        let source = format!(
+            "{SHAPE}print(Shape::Circle(1));\nprint(Shape::Rect {{ h: 2, w: 3 }});\nprint(Shape::Empty);"
+        );
+        assert_eq!(
+            run(&source).unwrap(),
+            "Circle(1)\nRect {w: 3, h: 2}\nEmpty\n"
+        );
+    }
+
+    #[test]
+    fn prelude_is_always_declared() {
+        let source = "print(Option::Some(1));\nprint(none);\nprint(Result::Err(\"no\"));";
+        assert_eq!(run(source).unwrap(), "Some(1)\nNone\nErr(no)\n");
+    }
+
+    #[test]
+    fn unknown_variant_is_rejected() {
+        let source = format!("{SHAPE}print(Shape::Square);");
+        assert!(matches!(
+            run(&source),
+            Err(EvalError::UnknownVariant { variant, .. }) if variant == "Square"
+        ));
+    }
+
+    #[test]
+    fn payload_must_match_the_declaration() {
+        for constructor in [
+            "Shape::Circle",
+            "Shape::Circle(1, 2)",
+            "Shape::Rect { w: 1 }",
+            "Shape::Empty(1)",
+        ] {
+            let source = format!("{SHAPE}print({constructor});");
+            assert!(
+                matches!(run(&source), Err(EvalError::PayloadMismatch { .. })),
+                "{constructor} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn variants_compare_by_name_and_payload() {
+        let source = format!(
+            "{SHAPE}print(Shape::Circle(1) == Shape::Circle(1));\nprint(Shape::Circle(1) != Shape::Circle(2));\nprint(Shape::Empty == Shape::Rect {{ w: 1, h: 1 }});\nprint(none == none);"
+        );
+        assert_eq!(run(&source).unwrap(), "true\ntrue\nfalse\ntrue\n");
+    }
+}
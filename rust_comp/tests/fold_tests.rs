@@ -0,0 +1,108 @@
+use cronyx::runtime::interpreter::EvalError;
+use cronyx::semantics::meta::meta_processor::*;
+use cronyx::semantics::meta::runtime_ast::{RuntimeAst, RuntimeExpr, RuntimeStmt};
+
+mod common;
+use common::{lower, lower_with_output};
+
+#[cfg(test)]
+mod fold_tests {
+    use super::*;
+
+    // The initializer of the last root statement, a `var`.
+    fn initializer(ast: &RuntimeAst) -> &RuntimeExpr {
+        let last = ast.sem_root_stmts.last().unwrap();
+        match ast.get_stmt(*last) {
+            Some(RuntimeStmt::VarDecl { expr, .. }) => ast.get_expr(*expr).unwrap(),
+            other => panic!("expected a var, got {other:?}"),
+        }
+    }
+
+    fn lowering_error(source: &str) -> MetaProcessError {
+        match lower_with_output(source) {
+            Err(e) => e,
+            Ok(_) => panic!("expected lowering to fail"),
+        }
+    }
+
+    const FIB: &str = "meta fn fib(n) {\n    if (n < 2) {\n        return 1;\n    }\n    return fib(n - 1) + fib(n - 2);\n}\n";
+
+    #[test]
+    fn calls_to_meta_functions_become_literals() {
+        let ast = lower(&format!("{FIB}var x = fib(10);"));
+        assert!(matches!(initializer(&ast), RuntimeExpr::Int(89)));
+    }
+
+    #[test]
+    fn calls_before_the_declaration_are_folded_too() {
+        let ast = lower(&format!("var x = fib(5);\n{FIB}"));
+        let first = ast.sem_root_stmts[0];
+        let Some(RuntimeStmt::VarDecl { expr, .. }) = ast.get_stmt(first) else {
+            panic!("expected a var");
+        };
+        assert!(matches!(ast.get_expr(*expr), Some(RuntimeExpr::Int(8))));
+    }
+
+    #[test]
+    fn lists_and_structs_become_literals() {
+        let ast = lower("struct Point {\n    x: int;\n    y: int\n}\nmeta fn origin() {\n    return [Point { x: 0, y: 0 }];\n}\nvar o = origin();");
+        let RuntimeExpr::List(items) = initializer(&ast) else {
+            panic!("expected a list");
+        };
+        assert!(matches!(
+            ast.get_expr(items[0]),
+            Some(RuntimeExpr::StructLiteral { type_name, .. }) if type_name == "Point"
+        ));
+    }
+
+    #[test]
+    fn a_folded_division_by_zero_is_reported() {
+        let result = lower_with_output("meta fn f(n) {\n    return 10 / n;\n}\nvar a = f(0);");
+        assert!(matches!(
+            result,
+            Err(MetaProcessError::Eval(EvalError::DivisionByZero(_)))
+        ));
+    }
+
+    #[test]
+    fn calls_with_side_effects_are_rejected() {
+        let error = lowering_error(
+            "meta fn noisy(n) {\n    print(n);\n    return n;\n}\nvar x = noisy(1);",
+        );
+        assert!(
+            matches!(error, MetaProcessError::ImpureMetaCall { function, .. } if function == "noisy")
+        );
+
+        let error = lowering_error("meta fn now() {\n    return clock();\n}\nvar x = now();");
+        assert!(matches!(error, MetaProcessError::ImpureMetaCall { .. }));
+    }
+
+    #[test]
+    fn calls_mutating_compile_time_state_are_rejected() {
+        let error = lowering_error("meta var count = 0;\nmeta fn bump() {\n    count = count + 1;\n    return count;\n}\nvar x = bump();");
+        assert!(matches!(error, MetaProcessError::ImpureMetaCall { .. }));
+    }
+
+    #[test]
+    fn calls_reading_compile_time_state_are_rejected() {
+        let error = lowering_error("meta var k = 1;\nmeta fn get() {\n    return k;\n}\nmeta {\n    k = 5;\n}\nvar b = get();");
+        match error {
+            MetaProcessError::ImpureMetaCall { function, span } => {
+                assert_eq!(function, "get");
+                assert_eq!((span.line, span.col), (8, 9));
+            }
+            other => panic!("unexpected error {other:?}"),
+        }
+    }
+
+    #[test]
+    fn calls_with_runtime_arguments_are_rejected() {
+        match lowering_error(&format!("{FIB}var n = 3;\nvar x = fib(n);")) {
+            MetaProcessError::NonConstantArgument { function, span } => {
+                assert_eq!(function, "fib");
+                assert_eq!((span.line, span.col), (8, 13));
+            }
+            other => panic!("unexpected error {other:?}"),
+        }
+    }
+}
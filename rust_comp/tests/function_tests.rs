@@ -0,0 +1,66 @@
+use cronyx::runtime::interpreter::*;
+
+mod common;
+use common::run;
+
+#[cfg(test)]
+mod function_tests {
+    use super::*;
+
+    #[test]
+    fn closures_see_later_updates_to_captured_variables() {
+        let source = "var n = 1;\nvar get = fn () { return n; };\nn = 2;\nprint(get());";
+        assert_eq!(run(source).unwrap(), "2\n");
+    }
+
+    #[test]
+    fn locals_do_not_leak_into_the_caller() {
+        let source = "fn f() { var inner = 1; }\nf();\nprint(inner);";
+        assert!(matches!(
+            run(source),
+            Err(EvalError::UndefinedVariable(name, _)) if name == "inner"
+        ));
+    }
+
+    #[test]
+    fn callee_cannot_see_caller_locals() {
+        let source = "fn f() { print(secret); }\nfn g() { var secret = 1; f(); }\ng();";
+        assert!(matches!(
+            run(source),
+            Err(EvalError::UndefinedVariable(name, _)) if name == "secret"
+        ));
+    }
+
+    #[test]
+    fn calling_a_non_function_is_an_error() {
+        match run("var x = 3;\nx(1);") {
+            Err(EvalError::NonFunctionCall(span)) => {
+                assert_eq!((span.line, span.col, span.end - span.start), (2, 1, 1));
+            }
+            Err(other) => panic!("unexpected error {:?}", other),
+            Ok(_) => panic!("call on a non-function succeeded"),
+        }
+    }
+
+    #[test]
+    fn argument_count_must_match() {
+        assert!(matches!(
+            run("fn f(a, b) { return a; }\nf(1);"),
+            Err(EvalError::ArgumentMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn unit_results_are_equal() {
+        let source = "fn f() {}\nprint(f() == f());";
+        assert_eq!(run(source).unwrap(), "true\n");
+    }
+
+    #[test]
+    fn functions_cannot_be_compared() {
+        assert!(matches!(
+            run("fn f() {}\nprint(f == f);"),
+            Err(EvalError::CannotCompare { left, .. }) if left == "function"
+        ));
+    }
+}
@@ -0,0 +1,63 @@
+use cronyx::runtime::interpreter::*;
+use cronyx::semantics::meta::runtime_ast::RuntimeStmt;
+
+mod common;
+use common::{lower, run};
+
+#[cfg(test)]
+mod gen_tests {
+    use super::*;
+
+    #[test]
+    fn emitted_statements_replace_the_meta_block() {
+        let ast = lower("print(1);\nmeta {\n    gen print(2);\n    gen print(3);\n}\nprint(4);");
+        assert_eq!(ast.sem_root_stmts.len(), 4);
+        assert!(ast
+            .sem_root_stmts
+            .iter()
+            .all(|id| matches!(ast.get_stmt(*id), Some(RuntimeStmt::Print(_)))));
+        assert_eq!(
+            run("print(1);\nmeta {\n    gen print(2);\n}\nprint(3);").unwrap(),
+            "1\n2\n3\n"
+        );
+    }
+
+    #[test]
+    fn compile_time_values_become_literals() {
+        let source = "meta {\n    var xs = [1, 2];\n    var n = 3;\n    for (x in xs) {\n        gen print(x * n);\n    }\n    gen print(xs);\n}";
+        assert_eq!(run(source).unwrap(), "3\n6\n[1, 2]\n");
+    }
+
+    #[test]
+    fn string_values_rename_declarations_and_calls() {
+        let source = "meta {\n    var name = \"twice\";\n    gen fn name(x) { return x * 2; }\n    gen print(name(4));\n}";
+        assert_eq!(run(source).unwrap(), "8\n");
+    }
+
+    #[test]
+    fn names_bound_by_the_generated_code_are_kept() {
+        let source = "meta {\n    var x = 100;\n    gen fn show(x) { print(x); }\n    gen { var x = 1; x = x + 1; print(x); }\n}\nshow(5);";
+        assert_eq!(run(source).unwrap(), "2\n5\n");
+    }
+
+    #[test]
+    fn emitted_declarations_stay_in_the_enclosing_scope() {
+        let source =
+            "fn f() {\n    meta {\n        gen var y = 7;\n    }\n    return y;\n}\nprint(f());";
+        assert_eq!(run(source).unwrap(), "7\n");
+    }
+
+    #[test]
+    fn gen_in_a_nested_meta_block_emits_into_the_outer_one() {
+        let source = "meta {\n    var v = 1;\n    meta {\n        gen gen print(v);\n    }\n}";
+        assert_eq!(run(source).unwrap(), "1\n");
+    }
+
+    #[test]
+    fn gen_outside_meta_is_an_error() {
+        assert!(matches!(
+            run("gen print(1);"),
+            Err(EvalError::GenOutsideMeta(_))
+        ));
+    }
+}
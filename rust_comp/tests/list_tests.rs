@@ -0,0 +1,115 @@
+use cronyx::runtime::interpreter::*;
+use cronyx::semantics::meta::expanded_ast::{ExpandedExpr, ExpandedStmt};
+use cronyx::semantics::types::type_checker::*;
+use cronyx::semantics::types::types::*;
+
+mod common;
+use common::run;
+
+#[cfg(test)]
+mod list_tests {
+    use super::*;
+
+    #[test]
+    fn lists_are_shared_by_reference() {
+        let source = "var a = [1];\nvar b = a;\nb.push(2);\nprint(a);";
+        assert_eq!(run(source).unwrap(), "[1, 2]\n");
+    }
+
+    #[test]
+    fn index_out_of_bounds_is_reported() {
+        match run("var xs = [1, 2];\nprint(xs[2]);") {
+            Err(EvalError::IndexOutOfBounds { index, len, span }) => {
+                assert_eq!((index, len), (2, 2));
+                assert_eq!((span.line, span.col), (2, 10));
+            }
+            Err(other) => panic!("unexpected error {:?}", other),
+            Ok(_) => panic!("out of bounds index succeeded"),
+        }
+    }
+
+    #[test]
+    fn invalid_slice_is_reported() {
+        assert!(matches!(
+            run("var xs = [1, 2];\nprint(xs[1:3]);"),
+            Err(EvalError::InvalidSlice {
+                start: 1,
+                end: 3,
+                len: 2,
+                ..
+            })
+        ));
+        assert!(matches!(
+            run("var xs = [1, 2];\nprint(xs[2:1]);"),
+            Err(EvalError::InvalidSlice { .. })
+        ));
+    }
+
+    #[test]
+    fn pop_on_empty_list_fails() {
+        assert!(matches!(
+            run("var xs = [];\nxs.pop();"),
+            Err(EvalError::EmptyList(_))
+        ));
+    }
+
+    #[test]
+    fn unknown_method_and_wrong_arity() {
+        assert!(matches!(
+            run("var xs = [1];\nxs.shuffle();"),
+            Err(EvalError::UnknownMethod(name, _)) if name == "shuffle"
+        ));
+        assert!(matches!(
+            run("var xs = [1];\nxs.push();"),
+            Err(EvalError::ArgumentMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn iterating_a_non_list_is_an_error() {
+        assert!(matches!(
+            run("for (x in 3) { print(x); }"),
+            Err(EvalError::NotIterable(_))
+        ));
+    }
+
+    #[test]
+    fn list_literal_has_list_type() {
+        let expr = ExpandedExpr::List(vec![ExpandedExpr::Int(1), ExpandedExpr::Int(2)]);
+        assert_eq!(infer_expr_top(&expr).unwrap().ty, list_type(int_type()));
+    }
+
+    #[test]
+    fn mixed_list_is_rejected() {
+        let expr = ExpandedExpr::List(vec![ExpandedExpr::Int(1), ExpandedExpr::Bool(true)]);
+        assert!(infer_expr_top(&expr).is_err());
+    }
+
+    #[test]
+    fn for_requires_a_list() {
+        let stmt = ExpandedStmt::ForEach {
+            var: "x".to_string(),
+            iterable: Box::new(ExpandedExpr::Int(3)),
+            body: Box::new(ExpandedStmt::Block(vec![])),
+        };
+        assert!(infer_stmt_top(&stmt).is_err());
+    }
+
+    #[test]
+    fn lists_compare_by_their_elements() {
+        let source = "print([1] == [1]);\nprint([1, 2] == [2, 1]);\nprint([[1]] != [[2]]);";
+        assert_eq!(run(source).unwrap(), "true\nfalse\ntrue\n");
+    }
+
+    #[test]
+    fn values_of_different_types_cannot_be_compared() {
+        match run("print(1 == \"1\");") {
+            Err(EvalError::CannotCompare { left, right, span }) => {
+                assert_eq!((left.as_str(), right.as_str()), ("int", "string"));
+                assert_eq!((span.line, span.col), (1, 7));
+            }
+            Err(other) => panic!("unexpected error {:?}", other),
+            Ok(_) => panic!("an int was compared with a string"),
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use cronyx::runtime::interpreter::*;
+
+mod common;
+use common::run;
+
+#[cfg(test)]
+mod match_tests {
+    use super::*;
+
+    #[test]
+    fn first_matching_arm_runs() {
+        let source = "match 2 {\n    1 => print(\"one\");\n    2 => print(\"two\");\n    _ => print(\"many\");\n}";
+        assert_eq!(run(source).unwrap(), "two\n");
+    }
+
+    #[test]
+    fn guards_can_skip_an_arm() {
+        let source = "match 5 {\n    n if n > 10 => print(\"big\");\n    n => print(n);\n}";
+        assert_eq!(run(source).unwrap(), "5\n");
+    }
+
+    #[test]
+    fn bindings_do_not_leak_out_of_the_arm() {
+        let source = "var x = 1;\nmatch 2 {\n    x => print(x);\n}\nprint(x);";
+        assert_eq!(run(source).unwrap(), "2\n1\n");
+    }
+
+    #[test]
+    fn list_rest_binds_the_tail() {
+        let source = "match [1, 2, 3] {\n    [first, ..rest] => { print(first); print(rest); }\n}";
+        assert_eq!(run(source).unwrap(), "1\n[2, 3]\n");
+    }
+
+    #[test]
+    fn struct_payload_patterns_match_by_name() {
+        let source = "enum Shape { Rect { w: int, h: int } }\nmatch (Shape::Rect { h: 3, w: 4 }) {\n    Shape::Rect { h, w: 4 } => print(h);\n}";
+        assert_eq!(run(source).unwrap(), "3\n");
+    }
+
+    #[test]
+    fn unmatched_value_is_an_error() {
+        match run("match 3 {\n    1 => print(1);\n}") {
+            Err(EvalError::NoMatch(span)) => assert_eq!((span.line, span.col), (1, 1)),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn arms_can_break_out_of_a_loop() {
+        let source = "for (x in [1, 2, 3]) {\n    match x {\n        2 => { break; }\n        _ => print(x);\n    }\n}";
+        assert_eq!(run(source).unwrap(), "1\n");
+    }
+}
@@ -0,0 +1,123 @@
+use cronyx::frontend::span::*;
+use cronyx::runtime::budget::Budget;
+use cronyx::runtime::interpreter::*;
+use cronyx::semantics::meta::meta_policy::{Capability, MetaPolicy};
+use cronyx::semantics::meta::meta_processor::*;
+
+mod common;
+use common::{eval_output, lower_with_resolver, Lowered};
+#[cfg(test)]
+mod meta_policy_tests {
+    use super::*;
+
+    fn lower(source: &str, policy: &MetaPolicy) -> Result<Lowered, MetaProcessError> {
+        lower_with_resolver(source, &[("a.txt", "text")], policy)
+    }
+
+    fn violation(result: Result<Lowered, MetaProcessError>) -> Option<(EvalError, Option<Span>)> {
+        match result {
+            Err(MetaProcessError::PolicyViolation { error, block }) => Some((*error, block)),
+            _ => None,
+        }
+    }
+
+    fn denied(result: Result<Lowered, MetaProcessError>) -> Option<Capability> {
+        match violation(result) {
+            Some((EvalError::CapabilityDenied { capability, .. }, _)) => Some(capability),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn environment_and_clock_are_off_by_default() {
+        let policy = MetaPolicy::default();
+        assert_eq!(
+            denied(lower("meta { var home = env(\"HOME\"); }", &policy)),
+            Some(Capability::Env)
+        );
+        assert_eq!(
+            denied(lower("meta { var now = clock(); }", &policy)),
+            Some(Capability::Clock)
+        );
+    }
+
+    #[test]
+    fn allowed_capabilities_can_be_used() {
+        let source = "meta { var home = env(\"HOME\");\nvar now = clock(); }";
+        assert!(lower(source, &MetaPolicy::unrestricted()).is_ok());
+    }
+
+    #[test]
+    fn an_unset_variable_reads_as_the_empty_string() {
+        let source = "meta { print(env(\"CRONYX_SURELY_UNSET\") + \"x\"); }";
+        let lowered = lower(source, &MetaPolicy::unrestricted()).unwrap();
+        assert_eq!(lowered.output, "x\n");
+    }
+
+    #[test]
+    fn violation_names_the_meta_block() {
+        let policy = MetaPolicy {
+            print: false,
+            ..MetaPolicy::default()
+        };
+        match violation(lower("print(1);\nmeta {\n    print(2);\n}", &policy)) {
+            Some((EvalError::CapabilityDenied { capability, span }, Some(block))) => {
+                assert_eq!(capability, Capability::Print);
+                assert_eq!((span.line, span.col), (3, 5));
+                assert_eq!((block.line, block.col), (2, 1));
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn embed_needs_file_access() {
+        let policy = MetaPolicy {
+            read_files: false,
+            ..MetaPolicy::default()
+        };
+        assert_eq!(
+            denied(lower("var a = embed(\"a.txt\");", &policy)),
+            Some(Capability::ReadFiles)
+        );
+    }
+
+    #[test]
+    fn runaway_loop_runs_out_of_steps() {
+        let policy = MetaPolicy {
+            budget: Budget {
+                fuel: Some(1_000),
+                ..Budget::default()
+            },
+            ..MetaPolicy::default()
+        };
+        assert!(matches!(
+            violation(lower("meta { while (true) { } }", &policy)),
+            Some((EvalError::OutOfFuel { limit: 1_000, .. }, Some(_)))
+        ));
+    }
+
+    #[test]
+    fn growing_list_runs_out_of_memory() {
+        let policy = MetaPolicy {
+            budget: Budget::default(),
+            max_memory: Some(4_096),
+            ..MetaPolicy::default()
+        };
+        let source = "meta { var xs = [];\nwhile (true) { xs.push(1); } }";
+        assert!(matches!(
+            violation(lower(source, &policy)),
+            Some((EvalError::MemoryLimitExceeded { limit: 4_096, .. }, Some(_)))
+        ));
+    }
+
+    #[test]
+    fn runtime_code_is_not_restricted() {
+        let runtime_ast = lower("print(clock() > 0);", &MetaPolicy::default())
+            .unwrap()
+            .ast;
+        let (out, result) = eval_output(&runtime_ast);
+        result.unwrap();
+        assert_eq!(out, "true\n");
+    }
+}
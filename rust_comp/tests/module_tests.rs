@@ -0,0 +1,142 @@
+use cronyx::frontend::span::*;
+use cronyx::runtime::environment::*;
+use cronyx::runtime::interpreter::*;
+use cronyx::semantics::meta::meta_policy::MetaPolicy;
+use cronyx::semantics::meta::meta_processor::*;
+use cronyx::semantics::meta::runtime_ast::RuntimeAst;
+
+mod common;
+use common::{eval_output, lower_with_resolver};
+#[cfg(test)]
+mod module_tests {
+    use super::*;
+
+    fn lower(source: &str, files: &[(&str, &str)]) -> Result<RuntimeAst, MetaProcessError> {
+        lower_with_resolver(source, files, &MetaPolicy::default()).map(|lowered| lowered.ast)
+    }
+
+    fn run(source: &str, files: &[(&str, &str)]) -> String {
+        let (out, result) = eval_output(&lower(source, files).unwrap());
+        result.unwrap();
+        out
+    }
+
+    #[test]
+    fn module_members_are_namespaced() {
+        let util = "var name = \"util\";\nfn twice(x) { return x * 2; }";
+        let out = run(
+            "import util;\nprint(util.twice(4));\nprint(util.name);",
+            &[("util", util)],
+        );
+        assert_eq!(out, "8\nutil\n");
+    }
+
+    #[test]
+    fn module_names_do_not_leak_into_the_importer() {
+        let runtime_ast = lower(
+            "import util;\nprint(helper);",
+            &[("util", "var helper = 1;")],
+        )
+        .unwrap();
+        let result = eval(
+            &runtime_ast,
+            &runtime_ast.sem_root_stmts,
+            Environment::new(),
+            &mut None,
+            &mut Vec::new(),
+        );
+        assert!(matches!(
+            result,
+            Err(EvalError::UndefinedVariable(name, _)) if name == "helper"
+        ));
+    }
+
+    #[test]
+    fn shared_module_runs_once() {
+        let files = [
+            ("a", "import shared;"),
+            ("b", "import shared;"),
+            ("shared", "print(\"loaded\");"),
+        ];
+        assert_eq!(run("import a;\nimport b;", &files), "loaded\n");
+    }
+
+    #[test]
+    fn missing_module_is_reported() {
+        match lower("import nope;", &[]) {
+            Err(MetaProcessError::ModuleNotFound(name, span)) => {
+                assert_eq!(name, "nope");
+                assert_eq!((span.line, span.col), (1, 1));
+            }
+            Err(other) => panic!("unexpected error {:?}", other),
+            Ok(_) => panic!("missing module was loaded"),
+        }
+    }
+
+    #[test]
+    fn import_cycle_is_reported() {
+        let files = [("a", "import b;"), ("b", "import c;"), ("c", "import a;")];
+        match lower("import a;", &files) {
+            Err(MetaProcessError::ImportCycle { cycle, .. }) => {
+                assert_eq!(cycle, ["a", "b", "c", "a"]);
+            }
+            Err(other) => panic!("unexpected error {:?}", other),
+            Ok(_) => panic!("import cycle was accepted"),
+        }
+    }
+
+    #[test]
+    fn syntax_errors_point_into_the_module() {
+        match lower("import broken;", &[("broken", "var = 1;")]) {
+            Err(MetaProcessError::ModuleParse { name, errors }) => {
+                assert_eq!(name, "broken");
+                // after `main.cx` and the prelude
+                assert_eq!(errors[0].span().file, FileId(2));
+            }
+            Err(other) => panic!("unexpected error {:?}", other),
+            Ok(_) => panic!("broken module was accepted"),
+        }
+    }
+
+    #[test]
+    fn the_prelude_is_a_source_of_its_own() {
+        let lowered = lower_with_resolver("print(1);", &[], &MetaPolicy::default()).unwrap();
+        let names: Vec<&str> = (0..2)
+            .map(|i| lowered.sources.get(FileId(i)).unwrap().name.as_str())
+            .collect();
+        assert_eq!(names, ["main.cx", "<prelude>"]);
+    }
+
+    #[test]
+    fn module_types_do_not_collide_with_the_importers() {
+        let util = "struct P { a: int }\nfn foo() { var p = P { a: 1 }; return p.a; }";
+        let out = run(
+            "import util;\nstruct P { b: int }\nprint(util.foo());\nprint(P { b: 2 });",
+            &[("util", util)],
+        );
+        assert_eq!(out, "1\nP {b: 2}\n");
+    }
+
+    #[test]
+    fn a_type_declared_twice_is_reported() {
+        match lower("struct P { a: int }\nenum P { A }", &[]) {
+            Err(MetaProcessError::DuplicateType { name, span }) => {
+                assert_eq!(name, "P");
+                assert_eq!((span.line, span.col), (2, 1));
+            }
+            Err(other) => panic!("unexpected error {:?}", other),
+            Ok(_) => panic!("a second `P` was accepted"),
+        }
+        let files = [("util", "struct P { a: int }\nstruct P { b: int }")];
+        assert!(matches!(
+            lower("import util;", &files),
+            Err(MetaProcessError::DuplicateType { .. })
+        ));
+    }
+
+    #[test]
+    fn the_prelude_types_may_be_redeclared() {
+        let source = "enum Option { Some(int), Nothing }\nprint(Option::Nothing);";
+        assert_eq!(run(source, &[]), "Nothing\n");
+    }
+}
@@ -0,0 +1,106 @@
+use cronyx::frontend::lexer::*;
+use cronyx::frontend::meta_ast::*;
+use cronyx::frontend::parser::*;
+use cronyx::frontend::span::*;
+use cronyx::frontend::token::*;
+use cronyx::semantics::meta::meta_processor::*;
+use cronyx::semantics::types::runtime_checker::check_program;
+use cronyx::semantics::types::type_error::TypeError;
+use std::io;
+
+#[cfg(test)]
+mod parser_recovery_tests {
+    use super::*;
+
+    fn parse_with_errors(source: &str) -> (ParseCtx, Vec<ParseError>) {
+        let tokens = tokenize(source, FileId(0)).unwrap();
+        let mut ctx = ParseCtx::new();
+        let errors = parse(&tokens, &mut ctx).unwrap_err();
+        (ctx, errors)
+    }
+
+    fn root_kinds(ctx: &ParseCtx) -> Vec<&'static str> {
+        ctx.ast
+            .sem_root_stmts
+            .iter()
+            .map(|id| match ctx.ast.get_stmt(*id).unwrap() {
+                MetaStmt::Error => "error",
+                MetaStmt::VarDecl { .. } => "var",
+                MetaStmt::FnDecl { .. } => "fn",
+                MetaStmt::Print(_) => "print",
+                _ => "other",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reports_every_broken_statement() {
+        let (ctx, errors) = parse_with_errors("var a = ;\nprint(1;\nvar b = 2;\nvar c = );\n");
+
+        assert_eq!(errors.len(), 3);
+        let lines: Vec<usize> = errors.iter().map(|e| e.span().line).collect();
+        assert_eq!(lines, vec![1, 2, 4]);
+        assert_eq!(root_kinds(&ctx), vec!["error", "error", "var", "error"]);
+    }
+
+    #[test]
+    fn missing_operand_is_expected_expression() {
+        let (_, errors) = parse_with_errors("var a = 1 + ;");
+
+        match &errors[0] {
+            ParseError::ExpectedExpression { found, span } => {
+                assert_eq!(*found, TokenType::Semicolon);
+                assert_eq!(span.col, 13);
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn recovers_inside_blocks() {
+        let source = "fn f() {\n  var x = ;\n  print(x);\n}\nprint(1);\n";
+        let (ctx, errors) = parse_with_errors(source);
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(root_kinds(&ctx), vec!["fn", "print"]);
+    }
+
+    #[test]
+    fn skips_over_nested_braces() {
+        let source = "var x = { 1 };\nif (true) { print(1); }\nvar y = 2;\n";
+        let (ctx, errors) = parse_with_errors(source);
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(root_kinds(&ctx), vec!["error", "other", "var"]);
+    }
+
+    #[test]
+    fn skips_to_the_end_of_a_broken_braced_declaration() {
+        let source = "struct P { name: string, age: int }\nprint(1);\n";
+        let (ctx, errors) = parse_with_errors(source);
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(root_kinds(&ctx), vec!["error", "print"]);
+    }
+
+    #[test]
+    fn stops_at_end_of_file() {
+        let (ctx, errors) = parse_with_errors("fn f() {\n  print(1);\n");
+
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0], ParseError::UnexpectedEOF { .. }));
+        assert_eq!(ctx.ast.sem_root_stmts.len(), 1);
+    }
+
+    #[test]
+    fn the_recovered_program_is_still_checked() {
+        let (ctx, errors) = parse_with_errors("var = 1;\nprint(1 + \"a\");");
+        assert_eq!(errors.len(), 1);
+
+        let runtime_ast = process(&ctx.ast, &mut io::sink()).unwrap();
+        match check_program(&runtime_ast) {
+            Err(TypeError::Located { span, .. }) => assert_eq!(span.line, 2),
+            other => panic!("expected a type error on line 2, got {:?}", other),
+        }
+    }
+}
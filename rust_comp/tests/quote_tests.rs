@@ -0,0 +1,81 @@
+use cronyx::runtime::interpreter::*;
+use cronyx::semantics::meta::meta_processor::*;
+
+mod common;
+use common::{lower, lower_with_output, run};
+
+#[cfg(test)]
+mod quote_tests {
+    use super::*;
+
+    // The evaluation error a `meta` block raised while lowering.
+    fn meta_error(source: &str) -> EvalError {
+        match lower_with_output(source) {
+            Err(MetaProcessError::Eval(e)) => e,
+            Err(other) => panic!("expected an evaluation error, got {other:?}"),
+            Ok(_) => panic!("expected lowering to fail"),
+        }
+    }
+
+    #[test]
+    fn a_meta_fn_returns_code_a_gen_splices_in() {
+        let source = "meta fn square(x) {\n    return quote { $x * $x; };\n}\nmeta {\n    gen print($(square(6)));\n}";
+        assert_eq!(run(source).unwrap(), "36\n");
+    }
+
+    #[test]
+    fn statement_fragments_are_spliced_in_whole() {
+        let source = "meta {\n    var body = quote {\n        var n = 2;\n        print(n + 1);\n    };\n    gen $body;\n    gen body;\n}";
+        assert_eq!(run(source).unwrap(), "3\n3\n");
+    }
+
+    #[test]
+    fn fragments_compose() {
+        let source = "meta {\n    var a = quote { print(1); };\n    var b = quote {\n        $a;\n        print(2);\n    };\n    gen $b;\n}";
+        assert_eq!(run(source).unwrap(), "1\n2\n");
+    }
+
+    #[test]
+    fn a_quote_keeps_names_it_does_not_splice() {
+        let source = "meta {\n    var n = 5;\n    var show = quote { print(n); };\n    gen var n = 9;\n    gen $show;\n}";
+        assert_eq!(run(source).unwrap(), "9\n");
+    }
+
+    #[test]
+    fn each_quote_copies_with_fresh_ids() {
+        let ast = lower("meta {\n    var f = quote { print(1); };\n    gen $f;\n    gen $f;\n}");
+        let roots = &ast.sem_root_stmts;
+        assert_eq!(roots.len(), 2);
+        assert_ne!(roots[0], roots[1]);
+    }
+
+    #[test]
+    fn a_quote_outside_meta_code_is_an_error() {
+        let result = run("var code = quote { print(1); };");
+        assert!(matches!(result, Err(EvalError::QuoteOutsideMeta(_))));
+    }
+
+    #[test]
+    fn a_splice_outside_a_quote_is_an_error() {
+        let result = run("var x = 1;\nprint($x);");
+        assert!(matches!(result, Err(EvalError::UnquoteOutsideQuote(_))));
+    }
+
+    #[test]
+    fn statements_cannot_stand_in_for_an_expression() {
+        let error = meta_error("meta {\n    var s = quote { print(1); };\n    gen print($s);\n}");
+        assert!(matches!(error, EvalError::InvalidSplice(what, _) if what == "statements"));
+    }
+
+    #[test]
+    fn a_gen_cannot_emit_a_quote() {
+        let error = meta_error("meta {\n    gen quote { print(1); };\n}");
+        assert!(matches!(error, EvalError::QuoteInGen(span) if (span.line, span.col) == (2, 9)));
+    }
+
+    #[test]
+    fn an_emitted_meta_block_can_quote() {
+        let source = "meta {\n    gen meta {\n        var q = quote { print(1); };\n        gen $q;\n    }\n}";
+        assert_eq!(run(source).unwrap(), "1\n");
+    }
+}
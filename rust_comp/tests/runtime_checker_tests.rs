@@ -0,0 +1,473 @@
+use cronyx::diagnostics::diagnostic::ToDiagnostic;
+use cronyx::semantics::meta::meta_policy::MetaPolicy;
+use cronyx::semantics::meta::runtime_ast::{RuntimeAst, RuntimeStmt};
+use cronyx::semantics::types::runtime_checker::*;
+use cronyx::semantics::types::type_error::TypeError;
+use cronyx::semantics::types::types::*;
+
+mod common;
+use common::lower_with_resolver;
+#[cfg(test)]
+mod runtime_checker_tests {
+    use super::*;
+
+    fn lower(source: &str, files: &[(&str, &str)]) -> RuntimeAst {
+        lower_with_resolver(source, files, &MetaPolicy::default())
+            .unwrap()
+            .ast
+    }
+
+    fn check(source: &str) -> Result<TypeTable, TypeError> {
+        check_program(&lower(source, &[]))
+    }
+
+    // The type of the initializer of the variable declared by root statement `n`.
+    fn var_type(source: &str, n: usize) -> Type {
+        let ast = lower(source, &[]);
+        let types = check_program(&ast).unwrap();
+        match ast.get_stmt(ast.sem_root_stmts[n]) {
+            Some(RuntimeStmt::VarDecl { expr, .. }) => types.get(*expr).unwrap().clone(),
+            other => panic!("expected a variable declaration, got {:?}", other),
+        }
+    }
+
+    fn error_at(result: Result<TypeTable, TypeError>) -> (TypeError, (usize, usize)) {
+        match result {
+            Err(TypeError::Located { span, error }) => (*error, (span.line, span.col)),
+            Err(other) => panic!("error without a location: {:?}", other),
+            Ok(_) => panic!("program type checked"),
+        }
+    }
+
+    #[test]
+    fn records_expression_types() {
+        assert_eq!(var_type("var xs = [1, 2];", 0), list_type(int_type()));
+        assert_eq!(
+            var_type("var xs = [\"a\"];\nvar n = xs.len();", 1),
+            int_type()
+        );
+        assert_eq!(
+            var_type(
+                "var xs = [1];\nvar ys = xs.map(fn(x) { return x > 0; });",
+                1
+            ),
+            list_type(bool_type())
+        );
+    }
+
+    #[test]
+    fn mismatch_is_located_at_the_expression() {
+        let (error, at) = error_at(check("var a = 1;\nprint(a + \"b\");"));
+        assert!(matches!(error, TypeError::TypeMismatch { .. }));
+        assert_eq!(at, (2, 7));
+    }
+
+    #[test]
+    fn unbound_variable_is_reported() {
+        let (error, at) = error_at(check("print(1);\nprint(missing);"));
+        assert!(matches!(error, TypeError::UnboundVar(name) if name == "missing"));
+        assert_eq!(at, (2, 7));
+    }
+
+    #[test]
+    fn functions_are_polymorphic() {
+        let source = "fn id(x) { return x; }\nprint(id(1));\nprint(id(\"a\"));";
+        assert!(check(source).is_ok());
+    }
+
+    #[test]
+    fn functions_may_call_later_functions() {
+        let source = "fn even(n) { if (n == 0) { return true; } return odd(n - 1); }\n\
+                      fn odd(n) { if (n == 0) { return false; } return even(n - 1); }\n\
+                      print(even(4));";
+        assert!(check(source).is_ok());
+    }
+
+    #[test]
+    fn list_variables_are_not_generalized() {
+        let (error, at) = error_at(check("var xs = [];\nxs.push(1);\nxs.push(\"a\");"));
+        assert!(matches!(error, TypeError::TypeMismatch { .. }));
+        assert_eq!(at.0, 3);
+    }
+
+    #[test]
+    fn conditions_must_be_bool() {
+        let (error, at) = error_at(check("if (1) { print(1); }"));
+        assert!(matches!(
+            error,
+            TypeError::TypeMismatch { expected, found }
+                if expected == bool_type() && found == int_type()
+        ));
+        assert_eq!(at, (1, 5));
+    }
+
+    #[test]
+    fn unknown_list_method_is_reported() {
+        let (error, _) = error_at(check("var xs = [1];\nxs.shuffle();"));
+        assert!(matches!(error, TypeError::NoMethod { method, .. } if method == "shuffle"));
+    }
+
+    #[test]
+    fn break_inside_function_inside_loop_is_rejected() {
+        let source = "while (true) { fn stop() { break; } }";
+        let (error, _) = error_at(check(source));
+        assert!(matches!(error, TypeError::BreakOutsideLoop));
+    }
+
+    #[test]
+    fn module_members_are_typed() {
+        let util = "fn twice(x) { return x * 2; }";
+        let ast = lower("import util;\nprint(util.twice(\"a\"));", &[("util", util)]);
+        let (error, at) = error_at(check_program(&ast));
+        assert!(matches!(error, TypeError::TypeMismatch { .. }));
+        assert_eq!(at, (2, 7));
+
+        let ast = lower("import util;\nprint(util.twice(2));", &[("util", util)]);
+        assert!(check_program(&ast).is_ok());
+    }
+
+    #[test]
+    fn module_types_are_checked_as_the_modules() {
+        let util = "struct P { a: int }\nfn foo() { var p = P { a: 1 }; return p.a; }";
+        let source = "import util;\nstruct P { b: int }\nprint(util.foo());\nprint(P { b: 2 }.b);";
+        assert!(check_program(&lower(source, &[("util", util)])).is_ok());
+    }
+
+    #[test]
+    fn struct_literals_have_the_struct_type() {
+        let source = "struct Point { x: int; y: int }\nvar p = Point { x: 1, y: 2 };";
+        assert_eq!(var_type(source, 1), named_type("Point"));
+
+        let source = "struct Point { x: int; y: int }\nvar p = Point { x: 1, y: \"2\" };";
+        let (error, at) = error_at(check(source));
+        assert!(matches!(error, TypeError::TypeMismatch { .. }));
+        assert_eq!(at, (2, 26));
+    }
+
+    #[test]
+    fn struct_literals_must_give_every_field() {
+        let source = "struct Point { x: int; y: int }\nvar p = Point { x: 1 };";
+        let (error, _) = error_at(check(source));
+        assert!(matches!(
+            error,
+            TypeError::MissingField { struct_name, field } if struct_name == "Point" && field == "y"
+        ));
+
+        let source = "struct Point { x: int; y: int }\nvar p = Point { x: 1, y: 2, z: 3 };";
+        let (error, _) = error_at(check(source));
+        assert!(matches!(error, TypeError::NoField { field, .. } if field == "z"));
+    }
+
+    #[test]
+    fn field_types_come_from_the_declaration() {
+        let source = "struct Line { from: Point; to: Point }\n\
+                      struct Point { x: int; y: int }\n\
+                      var l = Line { from: Point { x: 1, y: 2 }, to: Point { x: 3, y: 4 } };\n\
+                      var x = l.to.x;";
+        assert_eq!(var_type(source, 3), int_type());
+
+        let source = "struct Point { x: int; y: int }\nvar p = Point { x: 1, y: 2 };\nprint(p.z);";
+        let (error, at) = error_at(check(source));
+        assert!(matches!(error, TypeError::NoField { field, .. } if field == "z"));
+        assert_eq!(at, (3, 7));
+    }
+
+    #[test]
+    fn unknown_field_types_are_rejected() {
+        let (error, _) = error_at(check("struct Point { x: float }"));
+        assert!(matches!(error, TypeError::UnknownType(name) if name == "float"));
+    }
+
+    #[test]
+    fn parameters_take_the_struct_declaring_the_field() {
+        let source = "struct Point { x: int; y: int }\n\
+                      fn getx(p) { return p.x; }\n\
+                      print(getx(1));";
+        let (error, at) = error_at(check(source));
+        assert!(matches!(
+            error,
+            TypeError::TypeMismatch { expected, .. } if expected == named_type("Point")
+        ));
+        assert_eq!(at.0, 3);
+
+        let source = "struct A { x: int }\nstruct B { x: string }\nfn getx(p) { return p.x; }";
+        let (error, _) = error_at(check(source));
+        assert!(matches!(
+            error,
+            TypeError::AmbiguousField { structs, .. } if structs == ["A", "B"]
+        ));
+    }
+
+    #[test]
+    fn annotation_mismatch_is_located_at_the_annotation() {
+        let (error, at) = error_at(check("var x: int = \"a\";"));
+        assert!(matches!(
+            error,
+            TypeError::TypeMismatch { expected, found }
+                if expected == int_type() && found == string_type()
+        ));
+        assert_eq!(at, (1, 8));
+
+        let (error, at) = error_at(check("fn show(n: int) -> string { return n; }"));
+        assert!(matches!(error, TypeError::TypeMismatch { .. }));
+        assert_eq!(at, (1, 20));
+    }
+
+    #[test]
+    fn parameter_annotations_constrain_callers() {
+        let source = "fn add(a: int, b: int) -> int { return a + b; }\nprint(add(1, \"2\"));";
+        let (error, at) = error_at(check(source));
+        assert!(matches!(error, TypeError::TypeMismatch { .. }));
+        assert_eq!(at.0, 2);
+
+        let source = "fn apply(f: fn(int) -> bool, x: int) -> bool { return f(x); }\n\
+                      var y = apply(fn(n) { return n > 0; }, 1);";
+        assert_eq!(var_type(source, 1), bool_type());
+    }
+
+    #[test]
+    fn parameter_annotations_pick_the_struct() {
+        let source = "struct A { x: int }\nstruct B { x: string }\n\
+                      fn getx(p: B) -> string { return p.x; }";
+        assert!(check(source).is_ok());
+    }
+
+    #[test]
+    fn generic_field_types_are_checked() {
+        let source = "struct Bag { items: List<int> }\nvar b = Bag { items: [\"a\"] };";
+        let (error, _) = error_at(check(source));
+        assert!(matches!(
+            error,
+            TypeError::TypeMismatch { expected, found }
+                if expected == int_type() && found == string_type()
+        ));
+
+        let (error, at) = error_at(check("struct Bag { items: List<int, int> }"));
+        assert!(matches!(
+            error,
+            TypeError::WrongTypeArgs {
+                expected: 1,
+                found: 2,
+                ..
+            }
+        ));
+        assert_eq!(at, (1, 21));
+
+        let (error, at) = error_at(check("var xs: List<Thing> = [];"));
+        assert!(matches!(error, TypeError::UnknownType(name) if name == "Thing"));
+        assert_eq!(at, (1, 14));
+    }
+
+    #[test]
+    fn variants_have_their_enum_type() {
+        let shape = "enum Shape { Circle(int), Rect { w: int, h: int }, Empty }\n";
+        assert_eq!(
+            var_type(&format!("{shape}var s = Shape::Rect {{ w: 1, h: 2 }};"), 1),
+            named_type("Shape")
+        );
+
+        let (error, at) = error_at(check(&format!("{shape}var s = Shape::Circle(\"a\");")));
+        assert!(matches!(error, TypeError::TypeMismatch { .. }));
+        assert_eq!(at, (2, 23));
+
+        let (error, _) = error_at(check(&format!("{shape}var s = Shape::Square;")));
+        assert!(matches!(error, TypeError::NoVariant { variant, .. } if variant == "Square"));
+
+        let (error, _) = error_at(check(&format!("{shape}var s = Shape::Empty(1);")));
+        assert!(
+            matches!(error, TypeError::WrongPayload { declared, .. } if declared.name == "Empty")
+        );
+    }
+
+    #[test]
+    fn generic_variants_infer_their_arguments() {
+        assert_eq!(
+            var_type("var x = Option::Some([1]);", 0),
+            generic_type("Option", vec![list_type(int_type())])
+        );
+        assert!(check("var x: Option<string> = none;\nvar y: Option<int> = none;").is_ok());
+
+        let source = "var x: Result<int, string> = Result::Err(1);";
+        let (error, _) = error_at(check(source));
+        assert!(matches!(
+            error,
+            TypeError::TypeMismatch { expected, found }
+                if expected == string_type() && found == int_type()
+        ));
+
+        let (error, _) = error_at(check("var x: Option = none;"));
+        assert!(matches!(
+            error,
+            TypeError::WrongTypeArgs {
+                expected: 1,
+                found: 0,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn enum_payload_types_must_exist() {
+        let (error, _) = error_at(check("enum Tree<T> { Leaf(T), Node(Tree<T>, Treee<T>) }"));
+        assert!(matches!(error, TypeError::UnknownType(name) if name == "Treee"));
+    }
+
+    #[test]
+    fn match_patterns_bind_typed_names() {
+        let shape = "enum Shape { Circle(int), Rect { w: int, h: int }, Empty }\n";
+        let ok = format!(
+            "{shape}match Shape::Circle(1) {{\n    Shape::Circle(r) => print(r + 1);\n    Shape::Rect {{ w }} if w > 0 => print(w);\n    _ => print(0);\n}}"
+        );
+        assert!(check(&ok).is_ok());
+
+        let (error, at) = error_at(check(
+            "match 1 {\n    \"one\" => print(1);\n    _ => print(2);\n}",
+        ));
+        assert!(matches!(error, TypeError::TypeMismatch { .. }));
+        assert_eq!(at, (2, 5));
+
+        let (error, _) = error_at(check(&format!(
+            "{shape}match Shape::Empty {{\n    Shape::Circle(r) => print(r + \"a\");\n    _ => print(0);\n}}"
+        )));
+        assert!(matches!(error, TypeError::TypeMismatch { .. }));
+    }
+
+    #[test]
+    fn missing_variants_are_reported() {
+        let shape = "enum Shape { Circle(int), Rect { w: int, h: int }, Empty }\n";
+        let (error, at) = error_at(check(&format!(
+            "{shape}match Shape::Empty {{\n    Shape::Circle(_) => print(1);\n    Shape::Empty => print(2);\n}}"
+        )));
+        assert!(
+            matches!(&error, TypeError::NonExhaustive { missing } if missing == "Shape::Rect {w: _, h: _}"),
+            "{error:?}"
+        );
+        assert_eq!(at, (2, 1));
+
+        let (error, _) = error_at(check("match true {\n    true => print(1);\n}"));
+        assert!(matches!(error, TypeError::NonExhaustive { missing } if missing == "false"));
+
+        let (error, _) = error_at(check(
+            "match [1] {\n    [] => print(0);\n    [x] => print(x);\n}",
+        ));
+        assert!(matches!(error, TypeError::NonExhaustive { missing } if missing == "_"));
+
+        // a guarded arm may fall through
+        let (error, _) = error_at(check("match 1 {\n    x if x > 0 => print(x);\n}"));
+        assert!(matches!(error, TypeError::NonExhaustive { .. }));
+    }
+
+    #[test]
+    fn covered_arms_are_unreachable() {
+        let (error, at) = error_at(check(
+            "match [1, 2] {\n    [] => print(0);\n    [_, ..] => print(1);\n    [x, y] => print(x + y);\n}",
+        ));
+        assert!(matches!(error, TypeError::UnreachableArm));
+        assert_eq!(at, (4, 5));
+
+        let (error, _) = error_at(check(
+            "match Option::Some(1) {\n    Option::Some(x) => print(x);\n    none => print(0);\n    _ => print(2);\n}",
+        ));
+        assert!(matches!(error, TypeError::UnreachableArm));
+
+        let exhaustive = "match [true] {\n    [] => print(0);\n    [true, ..] => print(1);\n    [false, ..rest] => print(rest.len());\n}";
+        assert!(check(exhaustive).is_ok());
+    }
+
+    const SHOW: &str = "trait Show { fn show(self) -> string; }\n\
+                        struct P { name: string }\n\
+                        impl Show for P { fn show(self) -> string { return self.name; } }\n";
+
+    #[test]
+    fn methods_resolve_through_impls() {
+        let source = format!("{SHOW}var s = (P {{ name: \"a\" }}).show();");
+        assert_eq!(var_type(&source, 3), Type::Primitive(PrimitiveType::String));
+
+        let (error, at) = error_at(check(&format!("{SHOW}print(true.show());")));
+        assert!(
+            matches!(&error, TypeError::MissingImpl { trait_name, .. } if trait_name == "Show"),
+            "{error:?}"
+        );
+        assert_eq!(at, (4, 7));
+    }
+
+    #[test]
+    fn inferred_bounds_are_checked_at_calls() {
+        let source = format!("{SHOW}fn twice(x) {{ return x.show() + x.show(); }}\n");
+        assert!(check(&format!("{source}print(twice(P {{ name: \"a\" }}));")).is_ok());
+
+        let (error, at) = error_at(check(&format!("{source}print(twice(1));")));
+        assert!(
+            matches!(&error, TypeError::MissingImpl { ty, .. } if *ty == Type::Primitive(PrimitiveType::Int)),
+            "{error:?}"
+        );
+        assert_eq!(at, (5, 7));
+    }
+
+    #[test]
+    fn type_parameters_need_their_bounds() {
+        let bounded = format!("{SHOW}fn show<T: Show>(x: T) -> string {{ return x.show(); }}\n");
+        assert!(check(&format!("{bounded}print(show(P {{ name: \"a\" }}));")).is_ok());
+        // a declared bound holds even when the body doesn't use it
+        let unused = format!("{SHOW}fn keep<T: Show>(x: T) -> T {{ return x; }}\nkeep(1);");
+        assert!(matches!(
+            error_at(check(&unused)).0,
+            TypeError::MissingImpl { .. }
+        ));
+
+        let unbounded = format!("{SHOW}fn show<T>(x: T) -> string {{ return x.show(); }}");
+        assert!(matches!(
+            error_at(check(&unbounded)).0,
+            TypeError::MissingBound { param, trait_name } if param == "T" && trait_name == "Show"
+        ));
+        let unknown = "fn show<T: Display>(x: T) { }";
+        assert!(
+            matches!(error_at(check(unknown)).0, TypeError::UnknownTrait(name) if name == "Display")
+        );
+    }
+
+    #[test]
+    fn impls_must_match_their_trait() {
+        let traits = "trait Show { fn show(self) -> string; fn id(self) -> int; }\n";
+        let missing = format!("{traits}impl Show for int {{ fn show(self) {{ return \"n\"; }} }}");
+        assert!(matches!(
+            error_at(check(&missing)).0,
+            TypeError::MissingMethod { method, .. } if method == "id"
+        ));
+
+        let extra = format!(
+            "{traits}impl Show for int {{\n    fn show(self) {{ return \"n\"; }}\n    fn id(self) {{ return self; }}\n    fn other(self) {{ }}\n}}"
+        );
+        let (error, at) = error_at(check(&extra));
+        assert!(matches!(error, TypeError::NotInTrait { method, .. } if method == "other"));
+        assert_eq!(at, (5, 5));
+
+        let wrong = format!("{traits}impl Show for int {{\n    fn show(self) {{ return 1; }}\n    fn id(self) {{ return self; }}\n}}");
+        assert!(matches!(
+            error_at(check(&wrong)).0,
+            TypeError::TypeMismatch { .. }
+        ));
+    }
+
+    #[test]
+    fn deferred_statements_cannot_jump_out() {
+        let (error, at) = error_at(check("fn f() {\n    defer return;\n}"));
+        assert!(matches!(error, TypeError::JumpOutOfDefer(keyword) if keyword == "return"));
+        assert_eq!(at, (2, 11));
+
+        let (error, _) = error_at(check("while (true) {\n    defer break;\n}"));
+        assert!(matches!(error, TypeError::JumpOutOfDefer(keyword) if keyword == "break"));
+
+        // loops inside the deferred statement are its own
+        let inner = "defer {\n    while (true) { break; }\n    var f = fn() { return 1; };\n}";
+        assert!(check(inner).is_ok());
+    }
+
+    #[test]
+    fn type_errors_become_located_diagnostics() {
+        let error = check("var a = -true;").unwrap_err();
+        let diag = error.to_diagnostic();
+        assert_eq!(diag.code, "E0401");
+        assert_eq!(diag.span.map(|s| (s.line, s.col)), Some((1, 9)));
+    }
+}
@@ -1,12 +1,18 @@
 use std::fs::read_to_string;
-use std::io::{self, Cursor};
+use std::io::Cursor;
 use std::path::PathBuf;
 
+use cronyx::diagnostics::source_map::SourceMap;
 use cronyx::frontend::lexer::*;
 use cronyx::frontend::parser::*;
+use cronyx::frontend::span::FileId;
 use cronyx::runtime::environment::*;
 use cronyx::runtime::interpreter::*;
+use cronyx::semantics::meta::meta_policy::MetaPolicy;
 use cronyx::semantics::meta::meta_processor::*;
+use cronyx::semantics::meta::module_loader::ModuleLoader;
+use cronyx::semantics::types::runtime_checker::check_program;
+use cronyx::util::external_resolver::DefaultResolver;
 
 pub fn run_test(root_path: &PathBuf, out_path: &PathBuf) {
     eprintln!("input : {}", root_path.display());
@@ -14,14 +20,27 @@ pub fn run_test(root_path: &PathBuf, out_path: &PathBuf) {
     let in_buf = read_to_string(root_path).unwrap();
     let expected_out = read_to_string(out_path).unwrap();
 
-    let tokens = tokenize(&in_buf).unwrap();
+    let tokens = tokenize(&in_buf, FileId(0)).unwrap();
     let mut parse_ctx = ParseCtx::new();
     let _ = parse(&tokens, &mut parse_ctx).unwrap();
     let meta_ast = &(parse_ctx.ast);
 
-    let runtime_ast = process(meta_ast, &mut io::stdout()).unwrap();
-
+    let resolver = DefaultResolver {};
+    let mut sources = SourceMap::new();
+    let mut modules = ModuleLoader::new(&resolver, root_path.parent().unwrap(), &mut sources);
+    // compile-time output comes before the program's own
     let mut eval_buf = Cursor::new(Vec::<u8>::new());
+    let runtime_ast = process_with_modules(
+        meta_ast,
+        &mut eval_buf,
+        &mut modules,
+        &MetaPolicy::default(),
+    )
+    .unwrap();
+
+    if let Err(e) = check_program(&runtime_ast) {
+        panic!("type error: {:?}", e);
+    }
 
     eval(
         &runtime_ast,
@@ -82,6 +101,8 @@ mod script_integration {
 
         // Math and binary operators
         cx_test!(math_math, "tests/01_vanilla/02_math", "math");
+        cx_test!(math_precedence, "tests/01_vanilla/02_math", "precedence");
+        cx_test!(math_logic, "tests/01_vanilla/02_math", "logic");
 
         // String functions
         cx_test!(string_concat, "tests/01_vanilla/03_string", "concat");
@@ -97,6 +118,16 @@ mod script_integration {
             "tests/01_vanilla/04_environment",
             "02_reassign"
         );
+        cx_test!(
+            environment_compound,
+            "tests/01_vanilla/04_environment",
+            "03_compound"
+        );
+        cx_test!(
+            environment_targets,
+            "tests/01_vanilla/04_environment",
+            "04_targets"
+        );
 
         // Control Flow
         cx_test!(control_if, "tests/01_vanilla/05_control", "01_if");
@@ -106,24 +137,59 @@ mod script_integration {
             "tests/01_vanilla/05_control",
             "03_if_else_chain"
         );
+        cx_test!(control_while, "tests/01_vanilla/05_control", "04_while");
+        cx_test!(
+            control_break_continue,
+            "tests/01_vanilla/05_control",
+            "05_break_continue"
+        );
 
         // Functions
         cx_test!(func_greeting, "tests/01_vanilla/06_func", "01_greeting");
         cx_test!(func_return, "tests/01_vanilla/06_func", "02_return");
         cx_test!(func_fib, "tests/01_vanilla/06_func", "03_fib");
         cx_test!(func_closure, "tests/01_vanilla/06_func", "04_closure");
+        cx_test!(func_lambda, "tests/01_vanilla/06_func", "05_lambda");
+        cx_test!(func_scope, "tests/01_vanilla/06_func", "06_scope");
+        cx_test!(
+            func_annotations,
+            "tests/01_vanilla/06_func",
+            "07_annotations"
+        );
 
         // Lists
         cx_test!(list_list, "tests/01_vanilla/07_list", "01_list");
+        cx_test!(list_index, "tests/01_vanilla/07_list", "02_index");
+        cx_test!(list_methods, "tests/01_vanilla/07_list", "03_methods");
 
         // Struct
         cx_test!(struct_struct, "tests/01_vanilla/08_struct", "01_struct");
+        cx_test!(struct_fields, "tests/01_vanilla/08_struct", "02_fields");
 
         // Imports
         cx_test!(imports_import, "tests/01_vanilla/09_imports", "main");
+        cx_test!(
+            imports_namespace,
+            "tests/01_vanilla/09_imports",
+            "namespace"
+        );
 
         // Embed
         cx_test!(embed_embed, "tests/01_vanilla/10_embed", "embed");
+        cx_test!(embed_bytes, "tests/01_vanilla/10_embed", "bytes");
+
+        // Enum
+        cx_test!(enum_enum, "tests/01_vanilla/11_enum", "01_enum");
+        cx_test!(enum_prelude, "tests/01_vanilla/11_enum", "02_prelude");
+
+        // Match
+        cx_test!(match_match, "tests/01_vanilla/12_match", "01_match");
+
+        // Trait
+        cx_test!(trait_trait, "tests/01_vanilla/13_trait", "01_trait");
+
+        // Defer
+        cx_test!(defer_defer, "tests/01_vanilla/14_defer", "01_defer");
     }
 
     #[cfg(test)]
@@ -143,6 +209,10 @@ mod script_integration {
         // fn (meta functions)
         cx_test!(meta_fn, "tests/02_meta/fn", "meta_fn");
         cx_test!(meta_fib, "tests/02_meta/fn", "fib");
+        cx_test!(meta_env, "tests/02_meta/fn", "meta_env");
+
+        // quote (code values)
+        cx_test!(quote_quote, "tests/02_meta/quote", "quote");
 
         // reflection
         cx_test!(
@@ -0,0 +1,127 @@
+use cronyx::frontend::lexer::*;
+use cronyx::frontend::meta_ast::*;
+use cronyx::frontend::parser::*;
+use cronyx::frontend::span::*;
+use cronyx::frontend::token::*;
+use cronyx::runtime::environment::*;
+use cronyx::runtime::interpreter::*;
+use cronyx::semantics::meta::meta_processor::*;
+use std::io;
+
+#[cfg(test)]
+mod span_tests {
+    use super::*;
+
+    fn parse_source(source: &str) -> (Vec<Token>, ParseCtx) {
+        let tokens = tokenize(source, FileId(3)).unwrap();
+        let mut ctx = ParseCtx::new();
+        parse(&tokens, &mut ctx).unwrap();
+        (tokens, ctx)
+    }
+
+    #[test]
+    fn tokens_carry_line_and_column() {
+        let tokens = tokenize("var x = 5;\n  print(x);", FileId(1)).unwrap();
+
+        let print = tokens
+            .iter()
+            .find(|t| t.token_type == TokenType::Print)
+            .unwrap();
+        assert_eq!(print.span, Span::new(FileId(1), 13, 18, 2, 3));
+
+        let eof = tokens.last().unwrap();
+        assert_eq!(eof.token_type, TokenType::EOF);
+        assert_eq!(eof.span.start, 22);
+    }
+
+    #[test]
+    fn spans_use_byte_offsets() {
+        let tokens = tokenize("\"héllo\" + 1", FileId(0)).unwrap();
+        assert_eq!(tokens[0].span.start, 0);
+        assert_eq!(tokens[0].span.end, 8);
+        assert_eq!(tokens[1].span.start, 9);
+        assert_eq!(tokens[1].span.col, 9);
+    }
+
+    #[test]
+    fn multiline_strings_advance_lines() {
+        let tokens = tokenize("\"a\nb\" x", FileId(0)).unwrap();
+        assert_eq!(tokens[1].span.line, 2);
+        assert_eq!(tokens[1].span.col, 4);
+    }
+
+    #[test]
+    fn scan_errors_are_located() {
+        let err = tokenize("var x = 5;\nvar y = @;", FileId(0)).unwrap_err();
+        let span = err.span();
+        assert_eq!((span.line, span.col), (2, 9));
+    }
+
+    #[test]
+    fn parse_errors_are_located() {
+        let tokens = tokenize("print(1;", FileId(0)).unwrap();
+        let mut ctx = ParseCtx::new();
+        let errs = parse(&tokens, &mut ctx).unwrap_err();
+        match &errs[0] {
+            ParseError::UnexpectedToken {
+                found,
+                expected,
+                span,
+            } => {
+                assert_eq!(*found, TokenType::Semicolon);
+                assert_eq!(*expected, TokenType::RightParen);
+                assert_eq!(span.col, 8);
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn binary_expressions_span_both_operands() {
+        let (_, ctx) = parse_source("print(1 + 22 * 3);");
+        let stmt = ctx.ast.sem_root_stmts[0];
+        let expr = match ctx.ast.get_stmt(stmt) {
+            Some(MetaStmt::Print(e)) => *e,
+            other => panic!("unexpected stmt {:?}", other),
+        };
+
+        let span = ctx.ast.get_span(expr).unwrap();
+        assert_eq!(span.file, FileId(3));
+        assert_eq!((span.start, span.end), (6, 16));
+        assert_eq!(ctx.ast.get_span(stmt).unwrap().len(), 18);
+    }
+
+    #[test]
+    fn runtime_ast_keeps_meta_spans() {
+        let (_, ctx) = parse_source("var x = 1;\nprint(x);");
+        let runtime_ast = process(&ctx.ast, &mut io::sink()).unwrap();
+
+        let print_id = runtime_ast.sem_root_stmts[1];
+        let span = runtime_ast.get_span(print_id).unwrap();
+        assert_eq!((span.line, span.col), (2, 1));
+    }
+
+    #[test]
+    fn eval_errors_point_at_the_node() {
+        let (_, ctx) = parse_source("var x = 1;\nprint(x + y);");
+        let runtime_ast = process(&ctx.ast, &mut io::sink()).unwrap();
+
+        let err = eval(
+            &runtime_ast,
+            &runtime_ast.sem_root_stmts,
+            Environment::new(),
+            &mut None,
+            &mut io::sink(),
+        )
+        .err()
+        .unwrap();
+
+        match &err {
+            EvalError::UndefinedVariable(name, span) => {
+                assert_eq!(name, "y");
+                assert_eq!((span.line, span.col), (2, 11));
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+}
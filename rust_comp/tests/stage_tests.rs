@@ -0,0 +1,72 @@
+use cronyx::semantics::meta::stage::StageEvent;
+
+mod common;
+use common::{eval_output, lower_with_output};
+
+#[cfg(test)]
+mod stage_tests {
+    use super::*;
+
+    // Compile-time output followed by the program's.
+    fn run(source: &str) -> String {
+        let (runtime_ast, mut out) = lower_with_output(source).unwrap();
+        let (runtime_out, result) = eval_output(&runtime_ast);
+        result.unwrap();
+        out.push_str(&runtime_out);
+        out
+    }
+
+    #[test]
+    fn each_level_keeps_its_environment() {
+        let source = "meta {\n    var n = 2;\n}\nmeta {\n    gen print(n * 2);\n}";
+        assert_eq!(run(source), "4\n");
+    }
+
+    #[test]
+    fn an_emitted_meta_block_runs_after_the_block_that_emitted_it() {
+        let source = "meta {\n    gen meta {\n        print(\"inner\");\n        gen print(\"runtime\");\n    }\n    print(\"outer\");\n}";
+        assert_eq!(run(source), "outer\ninner\nruntime\n");
+    }
+
+    #[test]
+    fn an_emitted_meta_block_sees_its_level_at_the_time_it_runs() {
+        let source = "meta {\n    var greeting = \"hi\";\n    gen meta {\n        gen print(greeting);\n    }\n    greeting = \"bye\";\n}";
+        assert_eq!(run(source), "bye\n");
+    }
+
+    #[test]
+    fn inner_levels_run_first() {
+        let source = "meta {\n    meta {\n        print(2);\n        gen gen print(0);\n    }\n    print(1);\n}";
+        assert_eq!(run(source), "2\n1\n0\n");
+    }
+
+    #[test]
+    fn the_trace_records_each_stage() {
+        let (ast, _) =
+            lower_with_output("meta {\n    gen meta {\n        gen print(1);\n    }\n}").unwrap();
+        let levels: Vec<(&str, usize)> = ast
+            .stages
+            .events
+            .iter()
+            .map(|event| match event {
+                StageEvent::Run { level, .. } => ("run", *level),
+                StageEvent::Emit { level, .. } => ("emit", *level),
+                StageEvent::Staged { level, .. } => ("staged", *level),
+            })
+            .collect();
+        assert_eq!(
+            levels,
+            vec![
+                ("run", 1),
+                ("emit", 1),
+                ("staged", 1),
+                ("run", 1),
+                ("emit", 1)
+            ]
+        );
+        assert_eq!(
+            ast.stages.to_string().lines().next(),
+            Some("stage 1: ran meta block at 1:1")
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use cronyx::frontend::lexer::*;
+use cronyx::frontend::parser::*;
+use cronyx::frontend::span::*;
+use cronyx::runtime::interpreter::*;
+use cronyx::semantics::meta::meta_processor::*;
+use std::io;
+
+mod common;
+use common::run;
+
+#[cfg(test)]
+mod struct_tests {
+    use super::*;
+
+    const POINT: &str = "struct Point { x: int; y: int }\n";
+
+    #[test]
+    fn declarations_are_registered() {
+        let tokens = tokenize(POINT, FileId(0)).unwrap();
+        let mut ctx = ParseCtx::new();
+        parse(&tokens, &mut ctx).unwrap();
+        let runtime_ast = process(&ctx.ast, &mut io::sink()).unwrap();
+
+        let def = runtime_ast.decls.get_struct("Point").unwrap();
+        let names: Vec<_> = def.fields.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["x", "y"]);
+    }
+
+    #[test]
+    fn unknown_struct_is_rejected() {
+        assert!(matches!(
+            run("var p = Pointt { x: 1 };"),
+            Err(EvalError::UnknownStructType(name, _)) if name == "Pointt"
+        ));
+    }
+
+    #[test]
+    fn missing_field_is_rejected() {
+        let source = format!("{POINT}var p = Point {{ x: 1 }};");
+        assert!(matches!(
+            run(&source),
+            Err(EvalError::MissingField { field, .. }) if field == "y"
+        ));
+    }
+
+    #[test]
+    fn misspelled_field_is_rejected() {
+        let source = format!("{POINT}var p = Point {{ x: 1, yy: 2 }};");
+        match run(&source) {
+            Err(EvalError::UnknownField {
+                struct_name,
+                field,
+                span,
+            }) => {
+                assert_eq!((struct_name.as_str(), field.as_str()), ("Point", "yy"));
+                assert_eq!((span.line, span.col), (2, 27));
+            }
+            Err(other) => panic!("unexpected error {:?}", other),
+            Ok(_) => panic!("misspelled field accepted"),
+        }
+    }
+
+    #[test]
+    fn duplicate_field_is_rejected() {
+        let source = format!("{POINT}var p = Point {{ x: 1, y: 2, x: 3 }};");
+        assert!(matches!(
+            run(&source),
+            Err(EvalError::DuplicateField(field, _)) if field == "x"
+        ));
+    }
+
+    #[test]
+    fn fields_are_stored_in_declaration_order() {
+        let source = format!("{POINT}print(Point {{ y: 2, x: 1 }});");
+        assert_eq!(run(&source).unwrap(), "Point {x: 1, y: 2}\n");
+    }
+
+    #[test]
+    fn structs_compare_by_their_fields() {
+        let source = format!(
+            "{POINT}print(Point {{ x: 1, y: 2 }} == Point {{ y: 2, x: 1 }});\nprint(Point {{ x: 1, y: 2 }} == Point {{ x: 2, y: 1 }});"
+        );
+        assert_eq!(run(&source).unwrap(), "true\nfalse\n");
+    }
+}
@@ -0,0 +1,68 @@
+use cronyx::semantics::meta::meta_processor::MetaProcessError;
+
+mod common;
+use common::{lower_with_output, run};
+
+#[cfg(test)]
+mod trait_tests {
+    use super::*;
+
+    const NAME: &str = "trait Name { fn name(self) -> string; }\n";
+
+    #[test]
+    fn methods_dispatch_on_the_receiver_type() {
+        let source = format!(
+            "{NAME}impl Name for int {{ fn name(self) {{ return \"int\"; }} }}\n\
+             impl Name for bool {{ fn name(self) {{ return \"bool\"; }} }}\n\
+             print(1.name());\nprint(false.name());"
+        );
+        assert_eq!(run(&source).unwrap(), "int\nbool\n");
+    }
+
+    #[test]
+    fn receiver_is_the_first_argument() {
+        let source = "trait Add { fn add(self, n: int) -> int; }\n\
+                      struct Counter { start: int }\n\
+                      impl Add for Counter { fn add(self, n) { return self.start + n; } }\n\
+                      print((Counter { start: 10 }).add(5));";
+        assert_eq!(run(source).unwrap(), "15\n");
+    }
+
+    #[test]
+    fn impls_cover_every_instance_of_a_generic_type() {
+        let source = format!(
+            "{NAME}impl Name for Option {{\n    fn name(self) {{\n        match self {{\n            Option::Some(_) => {{ return \"some\"; }}\n            Option::None => {{ return \"none\"; }}\n        }}\n    }}\n}}\n\
+             print(Option::Some(1).name());\nprint(Option::Some(\"a\").name());\nprint(Option::None.name());"
+        );
+        assert_eq!(run(&source).unwrap(), "some\nsome\nnone\n");
+    }
+
+    #[test]
+    fn impl_methods_come_before_list_methods() {
+        let source = "trait Size { fn len(self) -> int; }\n\
+                      impl Size for List { fn len(self) { return 99; } }\n\
+                      print([1, 2].len());";
+        assert_eq!(run(source).unwrap(), "99\n");
+    }
+
+    #[test]
+    fn a_second_impl_of_a_trait_for_a_type_is_rejected() {
+        let source = format!(
+            "{NAME}struct P {{ name: string }}\n\
+             impl Name for P {{ fn name(self) {{ return \"a\"; }} }}\n\
+             impl Name for P {{ fn name(self) {{ return \"b\"; }} }}"
+        );
+        match lower_with_output(&source) {
+            Err(MetaProcessError::ConflictingImpl {
+                trait_name,
+                type_name,
+                span,
+            }) => {
+                assert_eq!((trait_name.as_str(), type_name.as_str()), ("Name", "P"));
+                assert_eq!(span.line, 4);
+            }
+            Err(other) => panic!("unexpected error {:?}", other),
+            Ok(_) => panic!("the second impl was accepted"),
+        }
+    }
+}
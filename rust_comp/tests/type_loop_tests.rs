@@ -0,0 +1,55 @@
+use cronyx::semantics::meta::expanded_ast::{ExpandedExpr, ExpandedStmt};
+use cronyx::semantics::types::type_checker::*;
+use cronyx::semantics::types::type_error::TypeError;
+
+#[cfg(test)]
+mod type_loop_tests {
+    use super::*;
+
+    fn while_true(body: Vec<ExpandedStmt>) -> ExpandedStmt {
+        ExpandedStmt::While {
+            cond: Box::new(ExpandedExpr::Bool(true)),
+            body: Box::new(ExpandedStmt::Block(body)),
+        }
+    }
+
+    #[test]
+    fn break_and_continue_inside_loop() {
+        let stmt = while_true(vec![ExpandedStmt::Continue, ExpandedStmt::Break]);
+        assert!(infer_stmt_top(&stmt).is_ok());
+    }
+
+    #[test]
+    fn break_outside_loop_is_rejected() {
+        assert!(matches!(
+            infer_stmt_top(&ExpandedStmt::Break),
+            Err(TypeError::BreakOutsideLoop)
+        ));
+        assert!(matches!(
+            infer_stmt_top(&ExpandedStmt::Continue),
+            Err(TypeError::ContinueOutsideLoop)
+        ));
+    }
+
+    #[test]
+    fn loop_does_not_extend_into_nested_function() {
+        let stmt = while_true(vec![ExpandedStmt::FnDecl {
+            name: "f".to_string(),
+            params: vec![],
+            body: Box::new(ExpandedStmt::Block(vec![ExpandedStmt::Break])),
+        }]);
+        assert!(matches!(
+            infer_stmt_top(&stmt),
+            Err(TypeError::BreakOutsideLoop)
+        ));
+    }
+
+    #[test]
+    fn while_condition_must_be_bool() {
+        let stmt = ExpandedStmt::While {
+            cond: Box::new(ExpandedExpr::Int(1)),
+            body: Box::new(ExpandedStmt::Block(vec![])),
+        };
+        assert!(infer_stmt_top(&stmt).is_err());
+    }
+}
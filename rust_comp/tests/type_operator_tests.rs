@@ -0,0 +1,84 @@
+use cronyx::semantics::meta::expanded_ast::ExpandedExpr;
+use cronyx::semantics::types::type_checker::*;
+use cronyx::semantics::types::type_env::TypeEnv;
+use cronyx::semantics::types::type_error::TypeError;
+use cronyx::semantics::types::type_subst::{ApplySubst, TypeSubst};
+use cronyx::semantics::types::types::*;
+
+#[cfg(test)]
+mod type_operator_tests {
+    use super::*;
+
+    fn int(n: i64) -> Box<ExpandedExpr> {
+        Box::new(ExpandedExpr::Int(n))
+    }
+
+    fn boolean(b: bool) -> Box<ExpandedExpr> {
+        Box::new(ExpandedExpr::Bool(b))
+    }
+
+    fn string(s: &str) -> Box<ExpandedExpr> {
+        Box::new(ExpandedExpr::String(s.to_string()))
+    }
+
+    fn var(name: &str) -> Box<ExpandedExpr> {
+        Box::new(ExpandedExpr::Variable(name.to_string()))
+    }
+
+    #[test]
+    fn arithmetic_is_int() {
+        let expr = ExpandedExpr::Sub(int(1), Box::new(ExpandedExpr::Negate(int(2))));
+        assert_eq!(infer_expr_top(&expr).unwrap().ty, int_type());
+    }
+
+    #[test]
+    fn add_concatenates_strings() {
+        let expr = ExpandedExpr::Add(string("a"), string("b"));
+        assert_eq!(infer_expr_top(&expr).unwrap().ty, string_type());
+    }
+
+    #[test]
+    fn comparisons_are_bool() {
+        let expr = ExpandedExpr::LessEqual(int(1), int(2));
+        assert_eq!(infer_expr_top(&expr).unwrap().ty, bool_type());
+    }
+
+    #[test]
+    fn comparing_strings_is_an_error() {
+        let expr = ExpandedExpr::Greater(string("a"), string("b"));
+        assert!(matches!(
+            infer_expr_top(&expr),
+            Err(TypeError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn equality_requires_matching_operands() {
+        let ok = ExpandedExpr::NotEquals(string("a"), string("b"));
+        assert_eq!(infer_expr_top(&ok).unwrap().ty, bool_type());
+
+        let bad = ExpandedExpr::Equals(int(1), boolean(true));
+        assert!(infer_expr_top(&bad).is_err());
+    }
+
+    #[test]
+    fn logic_operators_require_bool() {
+        let ok = ExpandedExpr::Or(Box::new(ExpandedExpr::Not(boolean(false))), boolean(true));
+        assert_eq!(infer_expr_top(&ok).unwrap().ty, bool_type());
+
+        let bad = ExpandedExpr::And(boolean(true), int(1));
+        assert!(infer_expr_top(&bad).is_err());
+    }
+
+    #[test]
+    fn operands_constrain_type_variables() {
+        let mut env = TypeEnv::new();
+        let mut subst = TypeSubst::new();
+        let tv = Type::Var(env.fresh());
+        env.bind_mono("x", tv.clone());
+
+        let expr = ExpandedExpr::And(var("x"), boolean(true));
+        infer_expr(&expr, &mut env, &mut subst).unwrap();
+        assert_eq!(tv.apply(&subst), bool_type());
+    }
+}
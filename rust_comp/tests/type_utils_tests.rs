@@ -49,6 +49,7 @@ mod type_utils_tests {
         fn free_vars_poly_scheme_removes_quantified() {
             let scheme = TypeScheme::PolyType {
                 vars: vec![TypeVar { id: 0 }],
+                preds: vec![],
                 ty: Type::Func {
                     params: vec![type_var(0)],
                     ret: Box::new(type_var(1)),
@@ -90,6 +91,7 @@ mod type_utils_tests {
                 "id",
                 TypeScheme::PolyType {
                     vars: vec![TypeVar { id: 0 }],
+                    preds: vec![],
                     ty: Type::Func {
                         params: vec![type_var(0)],
                         ret: Box::new(type_var(0)),
@@ -109,6 +111,7 @@ mod type_utils_tests {
                 "id",
                 TypeScheme::PolyType {
                     vars: vec![TypeVar { id: 0 }],
+                    preds: vec![],
                     ty: Type::Func {
                         params: vec![type_var(0)],
                         ret: Box::new(type_var(0)),
@@ -174,7 +177,7 @@ mod type_utils_tests {
             let scheme = generalize(&env, ty.clone());
 
             match scheme {
-                TypeScheme::PolyType { vars, ty: body } => {
+                TypeScheme::PolyType { vars, ty: body, .. } => {
                     assert_eq!(vars, vec![TypeVar { id: 0 }]);
                     assert_eq!(body, ty);
                 }
@@ -226,6 +229,7 @@ mod type_utils_tests {
 
             let scheme = TypeScheme::PolyType {
                 vars: vec![TypeVar { id: 0 }],
+                preds: vec![],
                 ty: Type::Func {
                     params: vec![type_var(0)],
                     ret: Box::new(type_var(0)),